@@ -0,0 +1,244 @@
+//! Arpeggiator for live note input. Held notes are played one at a time in
+//! time with the playhead, and can be captured into the pattern when
+//! recording.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{pitch::Note, synth::{Key, KeyOrigin}};
+
+/// Order that held notes are played in.
+#[derive(Clone, Copy, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub enum ArpMode {
+    #[default]
+    Up,
+    Down,
+    UpDown,
+    /// Order the notes were pressed in.
+    Played,
+}
+
+impl ArpMode {
+    pub const VARIANTS: [ArpMode; 4] = [Self::Up, Self::Down, Self::UpDown, Self::Played];
+
+    /// Returns the UI string for this mode.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Up => "Up",
+            Self::Down => "Down",
+            Self::UpDown => "Up/down",
+            Self::Played => "As played",
+        }
+    }
+}
+
+/// User settings for the arpeggiator.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArpSettings {
+    pub enabled: bool,
+    pub mode: ArpMode,
+    pub steps_per_beat: u8,
+    /// Record generated notes instead of the held notes.
+    pub capture: bool,
+}
+
+impl Default for ArpSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            mode: ArpMode::Up,
+            steps_per_beat: 4,
+            capture: false,
+        }
+    }
+}
+
+/// Returns the key that arpeggiated notes are played on.
+pub fn key() -> Key {
+    Key {
+        origin: KeyOrigin::Arpeggiator,
+        channel: 0,
+        key: 0,
+    }
+}
+
+/// Input note held for arpeggiation.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ArpNote {
+    pub track: usize,
+    /// Input key holding the note.
+    pub key: Key,
+    /// Note as input, before any kit mapping.
+    pub note: Note,
+    /// Patch index for input mapping, or `None` for the kit.
+    pub patch_index: Option<usize>,
+    /// Sounding pitch, for ordering.
+    pub pitch: f32,
+    pub pressure: Option<f32>,
+}
+
+/// Note change generated by the arpeggiator.
+#[derive(PartialEq, Debug)]
+pub enum ArpEvent {
+    On(ArpNote),
+    Off(ArpNote),
+}
+
+/// Arpeggiator state.
+#[derive(Default)]
+pub struct Arpeggiator {
+    /// Held notes, in the order they were pressed.
+    held: Vec<ArpNote>,
+    /// Note currently sounding.
+    sounding: Option<ArpNote>,
+    /// Number of steps played since notes were first held.
+    position: usize,
+    /// Step index of the sounding note.
+    last_step: Option<i64>,
+    /// Beat of the last update.
+    beat: f64,
+}
+
+impl Arpeggiator {
+    /// Returns the beat of the last update.
+    pub fn beat(&self) -> f64 {
+        self.beat
+    }
+
+    /// Add a held note, replacing any note held by the same key.
+    pub fn press(&mut self, note: ArpNote) {
+        self.release(&note.key);
+        self.held.push(note);
+    }
+
+    /// Release the note held by `key`.
+    pub fn release(&mut self, key: &Key) {
+        self.held.retain(|n| n.key != *key);
+    }
+
+    /// Release the notes held by keys from `origin`.
+    pub fn release_origin(&mut self, origin: KeyOrigin) {
+        self.held.retain(|n| n.key.origin != origin);
+    }
+
+    /// Release all held notes, returning the note that was sounding.
+    pub fn stop(&mut self) -> Option<ArpNote> {
+        self.held.clear();
+        self.position = 0;
+        self.last_step = None;
+        self.sounding.take()
+    }
+
+    /// Advance the arpeggiator to `beat`, returning note changes. A new note
+    /// starts immediately if none is sounding, and otherwise on each step.
+    pub fn update(&mut self, beat: f64, settings: &ArpSettings) -> Vec<ArpEvent> {
+        self.beat = beat;
+        if !settings.enabled {
+            self.held.clear();
+        }
+
+        let mut events = Vec::new();
+        let step = (beat * settings.steps_per_beat.max(1) as f64).floor() as i64;
+        if self.held.is_empty() {
+            events.extend(self.sounding.take().map(ArpEvent::Off));
+            self.position = 0;
+            self.last_step = None;
+        } else if self.sounding.is_none() || self.last_step != Some(step) {
+            events.extend(self.sounding.take().map(ArpEvent::Off));
+            let note = self.next_note(settings.mode);
+            events.push(ArpEvent::On(note.clone()));
+            self.sounding = Some(note);
+            self.last_step = Some(step);
+        }
+        events
+    }
+
+    /// Returns the next note to play, and advances the position.
+    fn next_note(&mut self, mode: ArpMode) -> ArpNote {
+        let mut order: Vec<_> = self.held.iter().collect();
+        match mode {
+            ArpMode::Up | ArpMode::UpDown => order.sort_by(|a, b| a.pitch.total_cmp(&b.pitch)),
+            ArpMode::Down => order.sort_by(|a, b| b.pitch.total_cmp(&a.pitch)),
+            ArpMode::Played => (),
+        }
+
+        let n = order.len();
+        let i = match mode {
+            // don't repeat the top and bottom notes
+            ArpMode::UpDown if n > 1 => {
+                let i = self.position % (n * 2 - 2);
+                if i < n { i } else { n * 2 - 2 - i }
+            },
+            _ => self.position % n,
+        };
+        self.position += 1;
+        order[i].clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pitch::Nominal;
+
+    use super::*;
+
+    fn held(key: u8, pitch: f32) -> ArpNote {
+        ArpNote {
+            track: 1,
+            key: Key::new_from_keyboard(key),
+            note: Note::new(0, Nominal::C, 0, 4),
+            patch_index: Some(0),
+            pitch,
+            pressure: None,
+        }
+    }
+
+    /// Returns the pitches that `mode` plays over `steps` steps.
+    fn pattern(mode: ArpMode, steps: usize) -> Vec<f32> {
+        let settings = ArpSettings { enabled: true, mode, ..Default::default() };
+        let mut arp = Arpeggiator::default();
+        for (key, pitch) in [(0, 64.0), (1, 60.0), (2, 67.0)] {
+            arp.press(held(key, pitch));
+        }
+        (0..steps).filter_map(|i| {
+            arp.update(i as f64 / 4.0, &settings).into_iter().find_map(|e| match e {
+                ArpEvent::On(n) => Some(n.pitch),
+                ArpEvent::Off(_) => None,
+            })
+        }).collect()
+    }
+
+    #[test]
+    fn test_arp_modes() {
+        assert_eq!(pattern(ArpMode::Up, 4), [60.0, 64.0, 67.0, 60.0]);
+        assert_eq!(pattern(ArpMode::Down, 4), [67.0, 64.0, 60.0, 67.0]);
+        assert_eq!(pattern(ArpMode::UpDown, 6), [60.0, 64.0, 67.0, 64.0, 60.0, 64.0]);
+        assert_eq!(pattern(ArpMode::Played, 4), [64.0, 60.0, 67.0, 64.0]);
+    }
+
+    #[test]
+    fn test_arp_steps() {
+        let settings = ArpSettings { enabled: true, ..Default::default() };
+        let mut arp = Arpeggiator::default();
+        assert!(arp.update(0.0, &settings).is_empty());
+
+        // the first note starts immediately, and the next on the next step
+        arp.press(held(0, 60.0));
+        arp.press(held(1, 62.0));
+        assert_eq!(arp.update(0.1, &settings), [ArpEvent::On(held(0, 60.0))]);
+        assert!(arp.update(0.2, &settings).is_empty());
+        assert_eq!(arp.update(0.25, &settings),
+            [ArpEvent::Off(held(0, 60.0)), ArpEvent::On(held(1, 62.0))]);
+
+        // releasing all keys ends the sounding note
+        arp.release(&Key::new_from_keyboard(0));
+        arp.release(&Key::new_from_keyboard(1));
+        assert_eq!(arp.update(0.3, &settings), [ArpEvent::Off(held(1, 62.0))]);
+
+        // disabling releases held notes
+        arp.press(held(0, 60.0));
+        arp.update(0.5, &settings);
+        let off = ArpSettings { enabled: false, ..settings };
+        assert_eq!(arp.update(0.5, &off), [ArpEvent::Off(held(0, 60.0))]);
+    }
+}
@@ -0,0 +1,175 @@
+//! Queue for rendering multiple modules to WAV files in sequence.
+
+use std::{error::Error, path::PathBuf, sync::{mpsc::{Receiver, TryRecvError}, Arc}};
+
+use crate::{module::Module, playback::{self, RenderBounds, RenderUpdate}, wavmeta};
+
+/// Render state of a queued module.
+#[derive(Debug, PartialEq)]
+pub enum BatchStatus {
+    Queued,
+    /// Rendering, with progress from 0 to 1.
+    Rendering(f64),
+    Done,
+    Failed(String),
+}
+
+impl BatchStatus {
+    /// Returns the UI string for the status.
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Queued => String::from("Queued"),
+            Self::Rendering(f) => format!("Rendering: {}%", (f * 100.0).round()),
+            Self::Done => String::from("Done"),
+            Self::Failed(e) => format!("Failed: {e}"),
+        }
+    }
+}
+
+/// Module file queued for rendering.
+pub struct BatchItem {
+    pub path: PathBuf,
+    pub status: BatchStatus,
+}
+
+/// Queue of module files to render in the background, one at a time.
+#[derive(Default)]
+pub struct BatchExport {
+    pub items: Vec<BatchItem>,
+    /// Folder that WAV files are written to.
+    out_dir: PathBuf,
+    /// Index and update channel of the item being rendered.
+    current: Option<(usize, Receiver<RenderUpdate>)>,
+}
+
+impl BatchExport {
+    /// Add module files to the queue, skipping files that are already queued.
+    pub fn add(&mut self, paths: impl IntoIterator<Item = PathBuf>) {
+        for path in paths {
+            if !self.items.iter().any(|x| x.path == path) {
+                self.items.push(BatchItem { path, status: BatchStatus::Queued });
+            }
+        }
+    }
+
+    /// Remove all items except the one being rendered.
+    pub fn clear(&mut self) {
+        if let Some((i, _)) = &mut self.current {
+            self.items.drain(*i + 1..);
+            self.items.drain(..*i);
+            *i = 0;
+        } else {
+            self.items.clear();
+        }
+    }
+
+    /// Returns true if an item is being rendered.
+    pub fn is_running(&self) -> bool {
+        self.current.is_some()
+    }
+
+    /// Returns true if any items are waiting to be rendered.
+    pub fn has_queued(&self) -> bool {
+        self.items.iter().any(|x| x.status == BatchStatus::Queued)
+    }
+
+    /// Start rendering queued items to WAV files in `out_dir`.
+    pub fn start(&mut self, out_dir: PathBuf) {
+        if self.current.is_none() {
+            self.out_dir = out_dir;
+            self.start_next();
+        }
+    }
+
+    /// Start rendering the next queued item, marking items that can't be
+    /// rendered as failed.
+    fn start_next(&mut self) {
+        while let Some(i) = self.items.iter().position(|x| x.status == BatchStatus::Queued) {
+            match self.render_item(i) {
+                Ok(rx) => {
+                    self.items[i].status = BatchStatus::Rendering(0.0);
+                    self.current = Some((i, rx));
+                    return
+                }
+                Err(e) => self.items[i].status = BatchStatus::Failed(e.to_string()),
+            }
+        }
+    }
+
+    /// Load an item's module and start rendering it.
+    fn render_item(&self, i: usize) -> Result<Receiver<RenderUpdate>, Box<dyn Error>> {
+        let src = &self.items[i].path;
+        let module = Module::load(src)?;
+        if !module.ends() {
+            return Err("Module must have End event to export".into())
+        }
+        let name = src.file_stem().unwrap_or_default().to_string_lossy();
+        let path = self.out_dir.join(format!("{name}.wav"));
+        Ok(playback::render(Arc::new(module), path, None, RenderBounds::SONG))
+    }
+
+    /// Handle render updates for the current item, writing its WAV file and
+    /// moving to the next item when done. Returns a summary when the queue
+    /// finishes.
+    pub fn update(&mut self, bit_depth: Option<u8>) -> Option<String> {
+        let (i, rx) = self.current.as_ref()?;
+        let i = *i;
+        let mut result = None;
+
+        loop {
+            match rx.try_recv() {
+                Ok(RenderUpdate::Progress(f)) => self.items[i].status = BatchStatus::Rendering(f),
+                Ok(RenderUpdate::Done(wav, path, meta)) => {
+                    let write_result = wavmeta::save(&wav, &path, bit_depth, &meta);
+                    result = Some(match write_result {
+                        Ok(_) => BatchStatus::Done,
+                        Err(e) => BatchStatus::Failed(format!("Writing WAV failed: {e}")),
+                    });
+                    break
+                }
+                Ok(RenderUpdate::Frozen(..)) => (),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    result = Some(BatchStatus::Failed(String::from("Render stopped")));
+                    break
+                }
+            }
+        }
+
+        self.items[i].status = result?;
+        self.current = None;
+        self.start_next();
+
+        if self.current.is_none() {
+            let failed = self.items.iter()
+                .filter(|x| matches!(x.status, BatchStatus::Failed(_)))
+                .count();
+            Some(format!("Batch export finished with {failed} failures."))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_queue() {
+        let mut batch = BatchExport::default();
+        batch.add([PathBuf::from("a.osctet"), PathBuf::from("b.osctet")]);
+        batch.add([PathBuf::from("a.osctet")]);
+        assert_eq!(batch.items.len(), 2);
+        assert!(batch.has_queued());
+
+        // missing files fail without stopping the queue
+        batch.start(PathBuf::from("."));
+        assert!(!batch.is_running());
+        assert!(!batch.has_queued());
+        assert!(batch.items.iter().all(|x| matches!(x.status, BatchStatus::Failed(_))));
+
+        batch.clear();
+        assert!(batch.items.is_empty());
+    }
+}
@@ -0,0 +1,28 @@
+//! Renders the test modules and compares them against the stored baseline.
+//! Pass `--update` to replace the baseline with the new renders.
+
+use std::{env, path::Path, process::ExitCode};
+
+use osctet::regression::{self, TESTDATA_DIR};
+
+fn main() -> ExitCode {
+    let update = env::args().skip(1).any(|arg| arg == "--update");
+    match regression::run(Path::new(TESTDATA_DIR), update) {
+        Ok(results) => {
+            for result in &results {
+                println!("{}: {}", result.name, result.outcome.describe());
+            }
+            let failures = results.iter().filter(|x| x.outcome.is_failure()).count();
+            println!("{} modules, {failures} failures", results.len());
+            if failures == 0 || update {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,115 @@
+//! Library of named pattern selections ("clips") that can be pasted into any
+//! module. Clips are stored as pattern text along with the tuning they were
+//! written in, so that notes can be remapped to the tuning they're pasted
+//! into.
+
+use std::{error::Error, fs, path::{Path, PathBuf}};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{exe_relative_path, module::EventData, patterntext::{self, TextEvent}, pitch::{Note, Tuning}};
+
+/// Folder that clips are saved in.
+const CLIP_DIR: &str = "clips";
+
+/// File extension of saved clips.
+const CLIP_EXT: &str = "toml";
+
+/// Returns the path of the clip library folder.
+pub fn clip_dir() -> PathBuf {
+    exe_relative_path(CLIP_DIR)
+}
+
+/// Returns the names of the clips in `dir`, sorted.
+pub fn list(dir: &Path) -> Vec<String> {
+    let mut names: Vec<_> = fs::read_dir(dir).into_iter().flatten()
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == CLIP_EXT))
+        .filter_map(|p| p.file_stem().map(|s| s.to_string_lossy().into_owned()))
+        .collect();
+    names.sort();
+    names
+}
+
+/// Returns the path of the clip named `name` in `dir`.
+fn clip_path(dir: &Path, name: &str) -> PathBuf {
+    let name: String = name.chars()
+        .map(|c| if c.is_control() || "/\\:*?\"<>|".contains(c) { '_' } else { c })
+        .collect();
+    dir.join(format!("{name}.{CLIP_EXT}"))
+}
+
+/// Saved pattern selection.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Clip {
+    /// Events as pattern text.
+    pub text: String,
+    /// Tuning in effect at the start of the selection.
+    pub tuning: Tuning,
+}
+
+impl Clip {
+    /// Load the clip named `name` from `dir`.
+    pub fn load(dir: &Path, name: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(toml::from_str(&fs::read_to_string(clip_path(dir, name))?)?)
+    }
+
+    /// Save the clip as `name` in `dir`, replacing any clip with that name.
+    pub fn save(&self, dir: &Path, name: &str) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(dir)?;
+        fs::write(clip_path(dir, name), toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Delete the clip named `name` from `dir`.
+    pub fn delete(dir: &Path, name: &str) -> Result<(), Box<dyn Error>> {
+        Ok(fs::remove_file(clip_path(dir, name))?)
+    }
+
+    /// Returns the clip's events. `tuning` returns the tuning that notes in
+    /// the given channel offset should be remapped to.
+    pub fn events<'a>(&self, tuning: impl Fn(usize) -> &'a Tuning
+    ) -> Result<Vec<TextEvent>, String> {
+        let mut events = patterntext::parse_events(&self.text)?;
+        for e in &mut events {
+            if let EventData::Pitch(note) = &mut e.data {
+                *note = self.remap(note, tuning(e.channel_offset));
+            }
+        }
+        Ok(events)
+    }
+
+    /// Returns the note in `tuning` nearest in pitch to `note` in the clip's
+    /// tuning. Notes are unchanged if the tunings are the same.
+    fn remap(&self, note: &Note, tuning: &Tuning) -> Note {
+        if *tuning == self.tuning {
+            return *note
+        }
+        tuning.nearest_note(self.tuning.midi_pitch(note)).unwrap_or(*note)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pitch::Nominal;
+
+    use super::*;
+
+    #[test]
+    fn test_clip_events() {
+        let clip = Clip {
+            text: String::from("0 0 C4\n1/2 1 vC5\n1 0 off\n"),
+            tuning: Tuning::divide(2.0, 31, 1).unwrap(),
+        };
+        let same = clip.events(|_| &clip.tuning).unwrap();
+        assert_eq!(same, patterntext::parse_events(&clip.text).unwrap());
+
+        let edo12 = Tuning::divide(2.0, 12, 1).unwrap();
+        let events = clip.events(|_| &edo12).unwrap();
+        assert_eq!(events[0].data, EventData::Pitch(Note::new(0, Nominal::C, 0, 4)));
+        assert_eq!(events[1].data, EventData::Pitch(Note::new(0, Nominal::C, 0, 5)));
+        assert_eq!(events[2].data, EventData::NoteOff);
+
+        assert_eq!(clip_path(Path::new("clips"), "a/b"), Path::new("clips/a_b.toml"));
+    }
+}
@@ -3,27 +3,105 @@ use std::{collections::HashSet, error::Error, path::{Path, PathBuf}};
 use macroquad::input::KeyCode;
 use serde::{Deserialize, Serialize};
 
-use crate::{exe_relative_path, input::{self, Action, Hotkey, Modifiers}, pitch::Note, ui::theme::Theme};
+use crate::{arp::ArpSettings, exe_relative_path, input::{self, Action, CcMode, Hotkey, Modifiers}, melody::MelodySettings, module::{BendRamp, GlideCurve, LfoRate, PatternLfo}, pitch::{Note, PitchDisplay}, timespan::Timespan, ui::{pattern::{PatternView, RecordMode, ValueScaling}, theme::Theme}};
 
 const CONFIG_FILENAME: &str = "config.toml";
+const PATCH_TEMPLATE_FILENAME: &str = "template.oscins";
+const MODULE_TEMPLATE_FILENAME: &str = "template.osctet";
+const MAX_RECENT_MODULES: usize = 10;
 
 fn config_path() -> PathBuf {
     exe_relative_path(CONFIG_FILENAME)
 }
 
+/// Returns the path of the patch used as the "Init" template.
+pub fn patch_template_path() -> PathBuf {
+    exe_relative_path(PATCH_TEMPLATE_FILENAME)
+}
+
+/// Returns the path of the module used as the new-module template.
+pub fn module_template_path() -> PathBuf {
+    exe_relative_path(MODULE_TEMPLATE_FILENAME)
+}
+
 // these are functions instead of constants to make serde happy
 
 fn default_font_size() -> usize { 1 }
 
 fn default_midi_send_velocity() -> bool { true }
 
+fn default_latency_compensation() -> bool { true }
+
+fn default_param_smooth_time() -> f32 { 0.005 }
+
+fn default_control_smooth_time() -> f32 { 0.005 }
+
+fn default_show_minimap() -> bool { true }
+
+fn default_restore_session() -> bool { true }
+
+fn default_error_alerts() -> bool { true }
+
+fn default_quick_export_name() -> String { String::from("{title}_{date}") }
+
+fn default_vibrato() -> PatternLfo { PatternLfo { depth: 4, rate: LfoRate::Hz(5.0) } }
+
+fn default_tremolo() -> PatternLfo { PatternLfo { depth: 8, rate: LfoRate::Sync(2, 1) } }
+
+fn default_tempo_ratio() -> (u8, u8) { (3, 2) }
+
+fn default_bar_beats() -> u8 { 4 }
+
+fn default_pad_bars() -> u8 { 1 }
+
+fn default_macro_repeats() -> u8 { 1 }
+
+fn default_step_record_rows() -> u8 { 1 }
+
+fn default_retrigger_hits() -> u8 { 4 }
+
+fn default_note_timing() -> Timespan { Timespan::new(1, 2) }
+
+fn default_bend_ramp() -> BendRamp {
+    BendRamp { cents: 200, duration: Timespan::new(1, 2), curve: GlideCurve::Linear }
+}
+
 /// Stores local configuration.
 #[derive(Serialize, Deserialize)]
 pub struct Config {
     pub default_midi_input: Option<String>,
+    pub default_midi_output: Option<String>,
+    /// Send the module tuning to the MIDI output whenever it changes.
+    #[serde(default)]
+    pub midi_send_tuning: bool,
     pub midi_send_pressure: Option<bool>,
     #[serde(default = "default_midi_send_velocity")]
     pub midi_send_velocity: bool,
+    /// Response time for smoothing the modulation controller, in seconds.
+    #[serde(default = "default_control_smooth_time")]
+    pub mod_smooth_time: f32,
+    /// Response time for smoothing pressure, in seconds.
+    #[serde(default = "default_control_smooth_time")]
+    pub pressure_smooth_time: f32,
+    /// Track played by each MIDI input channel. Zero plays the keyjazz
+    /// track.
+    #[serde(default)]
+    pub midi_channel_tracks: [usize; 16],
+    /// Split point of each MIDI input channel. Keys below the split point
+    /// play the channel's split track instead. Zero disables the split.
+    #[serde(default)]
+    pub midi_split_keys: [u8; 16],
+    /// Track played by keys below the split point of each MIDI input
+    /// channel. Zero plays the keyjazz track.
+    #[serde(default)]
+    pub midi_split_tracks: [usize; 16],
+    /// Program changes remap the track they're routed to, instead of
+    /// selecting a patch in the instruments tab.
+    #[serde(default)]
+    pub midi_program_remaps_track: bool,
+    /// Value mode of each macro controller, from CC 41 to 48.
+    #[serde(default)]
+    pub midi_macro_cc_modes: [CcMode; 8],
     pub theme: Option<Theme>,
     pub module_folder: Option<String>,
     pub patch_folder: Option<String>,
@@ -31,6 +109,7 @@ pub struct Config {
     pub scale_folder: Option<String>,
     pub sample_folder: Option<String>,
     pub theme_folder: Option<String>,
+    pub script_folder: Option<String>,
     #[serde(default = "default_keys")]
     keys: Vec<(Hotkey, Action)>,
     #[serde(default = "input::default_note_keys")]
@@ -42,6 +121,118 @@ pub struct Config {
     pub display_info: bool,
     pub desired_sample_rate: u32,
     pub render_bit_depth: Option<u8>,
+    /// Audio buffer size to request, in frames. `None` uses the device default.
+    pub audio_buffer_size: Option<u32>,
+    #[serde(default = "default_latency_compensation")]
+    pub latency_compensation: bool,
+    #[serde(default)]
+    pub show_profiler: bool,
+    /// Response time for smoothing patch parameter edits, in seconds.
+    #[serde(default = "default_param_smooth_time")]
+    pub param_smooth_time: f32,
+    /// Audition pitch events when typed or moved onto in the pattern editor.
+    #[serde(default)]
+    pub preview_notes: bool,
+    /// Keep keyjazz notes on until their key is pressed again.
+    #[serde(default)]
+    pub keyjazz_latch: bool,
+    /// Mute audition-only tracks when playback starts.
+    #[serde(default)]
+    pub mute_audition_tracks: bool,
+    /// Bars of metronome before recording starts.
+    #[serde(default)]
+    pub record_count_in: u8,
+    /// Start recording playback a bar before the cursor.
+    #[serde(default)]
+    pub record_pre_roll: bool,
+    /// Whether recording merges with or replaces existing events.
+    #[serde(default)]
+    pub record_mode: RecordMode,
+    /// Limit recording to the selected rows, if the selection spans rows.
+    #[serde(default)]
+    pub record_punch: bool,
+    #[serde(default = "default_show_minimap")]
+    pub show_minimap: bool,
+    #[serde(default)]
+    pub show_tempo_lane: bool,
+    #[serde(default)]
+    pub show_piano_roll: bool,
+    /// Master output gain in dB. Not applied to renders.
+    #[serde(default)]
+    pub output_gain: f32,
+    #[serde(default)]
+    pub melody: MelodySettings,
+    #[serde(default)]
+    pub arp: ArpSettings,
+    #[serde(default)]
+    pub value_scaling: ValueScaling,
+    /// Settings for inserted vibrato events.
+    #[serde(default = "default_vibrato")]
+    pub vibrato: PatternLfo,
+    /// Settings for inserted tremolo events.
+    #[serde(default = "default_tremolo")]
+    pub tremolo: PatternLfo,
+    /// Ratio for inserted rational tempo events.
+    #[serde(default = "default_tempo_ratio")]
+    pub tempo_ratio: (u8, u8),
+    /// Beats in a bar, for padding the song.
+    #[serde(default = "default_bar_beats")]
+    pub bar_beats: u8,
+    /// Bars to add when padding the song.
+    #[serde(default = "default_pad_bars")]
+    pub pad_bars: u8,
+    /// Number of times the play macro command repeats the macro.
+    #[serde(default = "default_macro_repeats")]
+    pub macro_repeats: u8,
+    /// Rows the cursor advances after each step recorded note or chord.
+    #[serde(default = "default_step_record_rows")]
+    pub step_record_rows: u8,
+    /// Settings for inserted bend ramp events.
+    #[serde(default = "default_bend_ramp")]
+    pub bend_ramp: BendRamp,
+    /// Number of hits in inserted retrigger events.
+    #[serde(default = "default_retrigger_hits")]
+    pub retrigger_hits: u8,
+    /// Final pressure digit of inserted retrigger events, if ramping.
+    #[serde(default)]
+    pub retrigger_pressure: Option<u8>,
+    /// Offset of inserted note delays and cuts, as a fraction of a row.
+    #[serde(default = "default_note_timing")]
+    pub note_timing: Timespan,
+    #[serde(default)]
+    pub pitch_display: PitchDisplay,
+    /// Reopen the last module and window state on startup.
+    #[serde(default = "default_restore_session")]
+    pub restore_session: bool,
+    /// Write status messages to a file, for screen readers.
+    #[serde(default)]
+    pub announce_status: bool,
+    /// Show reported errors in dialogs instead of notifications.
+    #[serde(default = "default_error_alerts")]
+    pub error_alerts: bool,
+    /// Filename template for quick export, without extension.
+    #[serde(default = "default_quick_export_name")]
+    pub quick_export_name: String,
+    /// Paths of recently opened modules, most recent first.
+    #[serde(default)]
+    pub recent_modules: Vec<String>,
+    #[serde(default)]
+    pub session: Session,
+}
+
+/// Window and editor state saved on quit.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    /// Path of the open module, if it had been saved.
+    pub module_path: Option<String>,
+    /// Window size, if not fullscreen.
+    pub window_size: Option<(u32, u32)>,
+    pub fullscreen: bool,
+    pub tab: usize,
+    pub pattern: PatternView,
+    /// Scroll offsets of the general, instruments, and settings tabs.
+    pub tab_scroll: [f32; 3],
 }
 
 impl Config {
@@ -67,6 +258,9 @@ impl Config {
             scale_folder: self.scale_folder.take(),
             sample_folder: self.sample_folder.take(),
             theme_folder: self.theme_folder.take(),
+            script_folder: self.script_folder.take(),
+            recent_modules: std::mem::take(&mut self.recent_modules),
+            session: std::mem::take(&mut self.session),
             ..Default::default()
         };
     }
@@ -85,6 +279,11 @@ impl Config {
         self.keys.iter_mut()
     }
 
+    /// Returns the keymap entries.
+    pub fn keymap(&self) -> &[(Hotkey, Action)] {
+        &self.keys
+    }
+
     /// Returns the action associated with the given hotkey.
     pub fn hotkey_action(&self, hotkey: &Hotkey) -> Option<&Action> {
         self.keys.iter()
@@ -92,11 +291,45 @@ impl Config {
             .map(|(_, a)| a)
     }
 
+    /// Returns true if `key` is bound to the action, with any modifiers.
+    pub fn action_uses_key(&self, action: Action, key: KeyCode) -> bool {
+        self.keys.iter().any(|(k, a)| *a == action && k.key == key)
+    }
+
     /// Returns true if the action's associated hotkey is down.
     pub fn action_is_down(&self, action: Action) -> bool {
         self.keys.iter().any(|(k, a)| *a == action && k.is_down())
     }
 
+    /// Returns the track that MIDI input on `channel` is routed to, if it
+    /// isn't routed to the keyjazz track.
+    pub fn midi_channel_track(&self, channel: u8, num_tracks: usize) -> Option<usize> {
+        self.midi_channel_tracks.get(channel as usize).copied()
+            .filter(|t| *t != 0 && *t < num_tracks)
+    }
+
+    /// Returns the track that MIDI `key` on `channel` is routed to, if it
+    /// isn't routed to the keyjazz track. Keys below the channel's split
+    /// point use its split track.
+    pub fn midi_key_track(&self, channel: u8, key: u8, num_tracks: usize
+    ) -> Option<usize> {
+        let c = channel as usize;
+        if self.midi_split_keys.get(c).is_some_and(|k| key < *k) {
+            Some(self.midi_split_tracks[c]).filter(|t| *t != 0 && *t < num_tracks)
+        } else {
+            self.midi_channel_track(channel, num_tracks)
+        }
+    }
+
+    /// Move a module path to the front of the recent modules list.
+    pub fn add_recent_module(&mut self, path: &Path) {
+        if let Some(s) = path.to_str() {
+            self.recent_modules.retain(|p| p != s);
+            self.recent_modules.insert(0, s.to_owned());
+            self.recent_modules.truncate(MAX_RECENT_MODULES);
+        }
+    }
+
     /// Return a string in the format "(hotkey) - (action)".
     pub fn hotkey_string(&self, action: Action) -> String {
         let key_string = self.keys.iter()
@@ -112,8 +345,17 @@ impl Default for Config {
         let keys = default_keys();
         Self {
             default_midi_input: None,
+            default_midi_output: None,
+            midi_send_tuning: false,
             midi_send_pressure: Some(true),
             midi_send_velocity: default_midi_send_velocity(),
+            mod_smooth_time: default_control_smooth_time(),
+            pressure_smooth_time: default_control_smooth_time(),
+            midi_channel_tracks: Default::default(),
+            midi_split_keys: Default::default(),
+            midi_split_tracks: Default::default(),
+            midi_program_remaps_track: false,
+            midi_macro_cc_modes: Default::default(),
             theme: None,
             module_folder: None,
             patch_folder: None,
@@ -121,6 +363,7 @@ impl Default for Config {
             scale_folder: None,
             sample_folder: None,
             theme_folder: None,
+            script_folder: None,
             keys,
             note_keys: input::default_note_keys(),
             font_size: default_font_size(),
@@ -128,6 +371,42 @@ impl Default for Config {
             display_info: true,
             desired_sample_rate: 48000,
             render_bit_depth: Some(16),
+            audio_buffer_size: None,
+            latency_compensation: default_latency_compensation(),
+            show_profiler: false,
+            param_smooth_time: default_param_smooth_time(),
+            preview_notes: false,
+            keyjazz_latch: false,
+            mute_audition_tracks: false,
+            record_count_in: 0,
+            record_pre_roll: false,
+            record_mode: RecordMode::Overdub,
+            record_punch: false,
+            show_minimap: default_show_minimap(),
+            show_tempo_lane: false,
+            show_piano_roll: false,
+            output_gain: 0.0,
+            melody: Default::default(),
+            arp: Default::default(),
+            value_scaling: Default::default(),
+            vibrato: default_vibrato(),
+            tremolo: default_tremolo(),
+            tempo_ratio: default_tempo_ratio(),
+            bar_beats: default_bar_beats(),
+            pad_bars: default_pad_bars(),
+            macro_repeats: default_macro_repeats(),
+            step_record_rows: default_step_record_rows(),
+            bend_ramp: default_bend_ramp(),
+            retrigger_hits: default_retrigger_hits(),
+            retrigger_pressure: None,
+            note_timing: default_note_timing(),
+            pitch_display: Default::default(),
+            restore_session: default_restore_session(),
+            announce_status: false,
+            error_alerts: default_error_alerts(),
+            quick_export_name: default_quick_export_name(),
+            recent_modules: Vec::new(),
+            session: Default::default(),
         }
     }
 }
@@ -146,10 +425,18 @@ fn default_keys() -> Vec<(Hotkey, Action)> {
         (Hotkey::new(Modifiers::Ctrl, KeyCode::O), Action::OpenSong),
         (Hotkey::new(Modifiers::Ctrl, KeyCode::S), Action::SaveSong),
         (Hotkey::new(Modifiers::CtrlShift, KeyCode::S), Action::SaveSongAs),
+        (Hotkey::new(Modifiers::Ctrl, KeyCode::I), Action::ImportModule),
         (Hotkey::new(Modifiers::Ctrl, KeyCode::E), Action::RenderSong),
         (Hotkey::new(Modifiers::CtrlShift, KeyCode::E), Action::RenderTracks),
+        (Hotkey::new(Modifiers::CtrlAlt, KeyCode::E), Action::RenderSelection),
+        (Hotkey::new(Modifiers::CtrlAltShift, KeyCode::E), Action::RenderLoop),
+        (Hotkey::new(Modifiers::Ctrl, KeyCode::F5), Action::QuickExport),
         (Hotkey::new(Modifiers::CtrlShift, KeyCode::Tab), Action::PrevTab),
         (Hotkey::new(Modifiers::Ctrl, KeyCode::Tab), Action::NextTab),
+        (Hotkey::new(Modifiers::CtrlShift, KeyCode::P), Action::CommandPalette),
+        (Hotkey::new(Modifiers::Ctrl, KeyCode::T), Action::NewTab),
+        (Hotkey::new(Modifiers::Ctrl, KeyCode::W), Action::CloseTab),
+        (Hotkey::new(Modifiers::Ctrl, KeyCode::M), Action::ModulateParameter),
         (Hotkey::new(Modifiers::Ctrl, KeyCode::Z), Action::Undo),
         (Hotkey::new(Modifiers::Ctrl, KeyCode::Y), Action::Redo),
 
@@ -158,6 +445,8 @@ fn default_keys() -> Vec<(Hotkey, Action)> {
         (Hotkey::new(Modifiers::Ctrl, KeyCode::Equal), Action::IncrementDivision),
         (Hotkey::new(Modifiers::Alt, KeyCode::Minus), Action::HalveDivision),
         (Hotkey::new(Modifiers::Alt, KeyCode::Equal), Action::DoubleDivision),
+        (Hotkey::new(Modifiers::CtrlShift, KeyCode::Minus), Action::ZoomOutRows),
+        (Hotkey::new(Modifiers::CtrlShift, KeyCode::Equal), Action::ZoomInRows),
         (Hotkey::new(Modifiers::Shift, KeyCode::Key9), Action::DecrementOctave),
         (Hotkey::new(Modifiers::Shift, KeyCode::Key0), Action::IncrementOctave),
         (Hotkey::new(Modifiers::Ctrl, KeyCode::D), Action::FocusDivision),
@@ -177,14 +466,31 @@ fn default_keys() -> Vec<(Hotkey, Action)> {
         (Hotkey::new(Modifiers::None, KeyCode::End), Action::PatternEnd),
         (Hotkey::new(Modifiers::Ctrl, KeyCode::A), Action::SelectAllChannels),
         (Hotkey::new(Modifiers::Ctrl, KeyCode::L), Action::SelectAllRows),
+        (Hotkey::new(Modifiers::Alt, KeyCode::C), Action::CollapseTrack),
 
         // events
         (Hotkey::new(Modifiers::None, KeyCode::Space), Action::UseLastNote),
         (Hotkey::new(Modifiers::None, KeyCode::Key1), Action::NoteOff),
         (Hotkey::new(Modifiers::None, KeyCode::T), Action::TapTempo),
         (Hotkey::new(Modifiers::None, KeyCode::L), Action::Loop),
+        (Hotkey::new(Modifiers::Shift, KeyCode::L), Action::LoopSelection),
+        (Hotkey::new(Modifiers::None, KeyCode::F7), Action::CheckControlEvents),
+        (Hotkey::new(Modifiers::None, KeyCode::F8), Action::RecordMacro),
+        (Hotkey::new(Modifiers::Shift, KeyCode::F8), Action::PlayMacro),
         (Hotkey::new(Modifiers::None, KeyCode::E), Action::End),
+        (Hotkey::new(Modifiers::Shift, KeyCode::E), Action::MoveEnd),
+        (Hotkey::new(Modifiers::Alt, KeyCode::E), Action::TrimToEnd),
+        (Hotkey::new(Modifiers::Alt, KeyCode::B), Action::PadSong),
+        (Hotkey::new(Modifiers::Alt, KeyCode::T), Action::TuningChange),
+        (Hotkey::new(Modifiers::Alt, KeyCode::R), Action::RationalTempo),
         (Hotkey::new(Modifiers::None, KeyCode::GraveAccent), Action::Interpolate),
+        (Hotkey::new(Modifiers::Shift, KeyCode::GraveAccent), Action::CycleGlideCurve),
+        (Hotkey::new(Modifiers::CtrlShift, KeyCode::I), Action::InsertVibrato),
+        (Hotkey::new(Modifiers::CtrlShift, KeyCode::T), Action::InsertTremolo),
+        (Hotkey::new(Modifiers::CtrlShift, KeyCode::B), Action::InsertBendRamp),
+        (Hotkey::new(Modifiers::Alt, KeyCode::G), Action::InsertRetrigger),
+        (Hotkey::new(Modifiers::Alt, KeyCode::D), Action::InsertNoteDelay),
+        (Hotkey::new(Modifiers::Alt, KeyCode::X), Action::InsertNoteCut),
 
         // pitch & notation
         (Hotkey::new(Modifiers::None, KeyCode::F1), Action::DecrementValues),
@@ -205,22 +511,38 @@ fn default_keys() -> Vec<(Hotkey, Action)> {
         (Hotkey::new(Modifiers::CtrlShift, KeyCode::V), Action::MixPaste),
         (Hotkey::new(Modifiers::CtrlAlt, KeyCode::V), Action::InsertPaste),
         (Hotkey::new(Modifiers::Ctrl, KeyCode::H), Action::StretchPaste),
+        (Hotkey::new(Modifiers::AltShift, KeyCode::C), Action::CopyPatternText),
+        (Hotkey::new(Modifiers::AltShift, KeyCode::V), Action::PastePatternText),
+        (Hotkey::new(Modifiers::CtrlAlt, KeyCode::S), Action::ExportPatternText),
+        (Hotkey::new(Modifiers::CtrlAlt, KeyCode::O), Action::ImportPatternText),
+        (Hotkey::new(Modifiers::CtrlShift, KeyCode::O), Action::ImportMidi),
+        (Hotkey::new(Modifiers::Ctrl, KeyCode::R), Action::RunScript),
+        (Hotkey::new(Modifiers::CtrlShift, KeyCode::R), Action::RerunScript),
 
         // playback
         (Hotkey::new(Modifiers::None, KeyCode::Enter), Action::PlayFromScreen),
         (Hotkey::new(Modifiers::Shift, KeyCode::Enter), Action::PlayFromCursor),
         (Hotkey::new(Modifiers::Ctrl, KeyCode::Enter), Action::PlayFromStart),
+        (Hotkey::new(Modifiers::CtrlShift, KeyCode::Enter), Action::ToggleRecord),
         (Hotkey::new(Modifiers::None, KeyCode::ScrollLock), Action::ToggleFollow),
+        (Hotkey::new(Modifiers::CtrlShift, KeyCode::L), Action::SetLoopRange),
+        (Hotkey::new(Modifiers::Alt, KeyCode::L), Action::ToggleLoopRange),
         (Hotkey::new(Modifiers::None, KeyCode::F9), Action::MuteTrack),
         (Hotkey::new(Modifiers::None, KeyCode::F10), Action::SoloTrack),
         (Hotkey::new(Modifiers::None, KeyCode::F11), Action::UnmuteAllTracks),
+        (Hotkey::new(Modifiers::Ctrl, KeyCode::F), Action::FreezeTrack),
         (Hotkey::new(Modifiers::None, KeyCode::F12), Action::Panic),
+        (Hotkey::new(Modifiers::None, KeyCode::F5), Action::HoldNotes),
+        (Hotkey::new(Modifiers::None, KeyCode::F6), Action::ToggleLatch),
+        (Hotkey::new(Modifiers::Alt, KeyCode::Enter), Action::ToggleFullscreen),
 
         // misc. pattern
         (Hotkey::new(Modifiers::None, KeyCode::Delete), Action::Delete),
         (Hotkey::new(Modifiers::None, KeyCode::Insert), Action::InsertRows),
         (Hotkey::new(Modifiers::None, KeyCode::Backspace), Action::DeleteRows),
         (Hotkey::new(Modifiers::Ctrl, KeyCode::P), Action::PlaceEvenly),
+        (Hotkey::new(Modifiers::Ctrl, KeyCode::G), Action::GenerateMelody),
+        (Hotkey::new(Modifiers::Alt, KeyCode::V), Action::ScaleValues),
     ];
 
     if cfg!(target_os = "macos") {
@@ -1,8 +1,11 @@
 //! Custom FunDSP audio nodes.
 
 use std::marker::PhantomData;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
 
 use fundsp::prelude::*;
+use fundsp::wave::Wave;
 
 /// Slightly different implementation of adsr_live. Inputs are 1) gate and 2) scale.
 pub fn adsr_scalable(
@@ -39,6 +42,19 @@ pub fn adsr_scalable(
     })
 }
 
+/// Returns the level of an ADSR envelope at `time`, if the gate is released
+/// at `release_start`. Matches the output of `adsr_scalable` at unit speed.
+pub fn adsr_level(attack: f32, decay: f32, sustain: f32, release: f32,
+    release_start: f32, time: f32
+) -> f32 {
+    let ads_value = ads(attack, decay, sustain, time, false);
+    if time < release_start {
+        ads_value
+    } else {
+        ads_value * clamp01(delerp(release, 0.0, time - release_start))
+    }
+}
+
 /// ADS envelope. Helper for ADSR.
 fn ads(attack: f32, decay: f32, sustain: f32, time: f32, sqrt_attack: bool) -> f32 {
     if time < attack {
@@ -59,9 +75,13 @@ fn ads(attack: f32, decay: f32, sustain: f32, time: f32, sqrt_attack: bool) -> f
 }
 
 /// Stereo compressor. Slope is 0.0..=1.0, equivalent to (ratio - 1) / ratio.
-pub fn compressor(threshold: f32, slope: f32, attack: f32, release: f32
+/// The level detector is high-passed at `sidechain_hz`, or not filtered if
+/// it's zero. Gain reduction in dB is written to `reduction`.
+pub fn compressor(threshold: f32, slope: f32, attack: f32, release: f32,
+    sidechain_hz: f32, reduction: &Shared
 ) -> An<Compressor<U2>> {
-    An(Compressor::new(DEFAULT_SR, threshold, slope, attack, release))
+    An(Compressor::new(DEFAULT_SR, threshold, slope, attack, release,
+        sidechain_hz, reduction.clone()))
 }
 
 #[derive(Clone)]
@@ -74,27 +94,57 @@ where
     threshold_db: f32,
     slope: f32,
     follower: AFollow<f32>,
+    sidechain_hz: f32,
+    /// One-pole high-pass coefficient for the sidechain.
+    sidechain_coeff: f32,
+    /// Previous sidechain input and output samples.
+    sidechain_state: (Frame<f32, N>, Frame<f32, N>),
+    reduction: Shared,
 }
 
 impl<N> Compressor<N>
 where
     N: Size<f32>,
 {
-    fn new(sample_rate: f64, threshold: f32, slope: f32, attack: f32, release: f32
+    fn new(sample_rate: f64, threshold: f32, slope: f32, attack: f32, release: f32,
+        sidechain_hz: f32, reduction: Shared
     ) -> Self {
         // attack/release scaling copied from fundsp's limiter
         // follower tracks dB of gain reduction
         let mut follower = AFollow::new(attack * 0.4, release * 0.4);
         follower.set_sample_rate(sample_rate);
         follower.set_value(0.0);
+        reduction.set(0.0);
 
-        Self {
+        let mut comp = Self {
             _marker: PhantomData,
             sample_rate,
             threshold_db: amp_db(threshold),
             slope,
             follower,
+            sidechain_hz,
+            sidechain_coeff: 1.0,
+            sidechain_state: Default::default(),
+            reduction,
+        };
+        comp.set_sample_rate(sample_rate);
+        comp
+    }
+
+    /// Returns the peak level of the sidechain signal.
+    fn sidechain_peak(&mut self, input: &Frame<f32, N>) -> f32 {
+        if self.sidechain_hz <= 0.0 {
+            return input.iter().fold(0.0, |amp, &x| max(amp, abs(x)))
+        }
+        let (prev_in, prev_out) = &mut self.sidechain_state;
+        let mut amp = 0.0;
+        for i in 0..N::USIZE {
+            let y = self.sidechain_coeff * (prev_out[i] + input[i] - prev_in[i]);
+            prev_in[i] = input[i];
+            prev_out[i] = y;
+            amp = max(amp, abs(y));
         }
+        amp
     }
 }
 
@@ -113,13 +163,16 @@ where
     fn set_sample_rate(&mut self, sample_rate: f64) {
         self.sample_rate = sample_rate;
         self.follower.set_sample_rate(sample_rate);
+        let rc = 1.0 / (std::f32::consts::TAU * self.sidechain_hz.max(f32::EPSILON));
+        self.sidechain_coeff = rc / (rc + 1.0 / sample_rate as f32);
     }
 
     #[inline]
     fn tick(&mut self, input: &Frame<f32, Self::Inputs>) -> Frame<f32, Self::Outputs> {
-        let amp = input.iter().fold(0.0, |amp, &x| max(amp, abs(x)));
+        let amp = self.sidechain_peak(input);
         let resp = self.follower.filter_mono(
             (amp_db(amp) - self.threshold_db).max(0.0) * self.slope);
+        self.reduction.set(resp);
         input.clone() * Frame::splat(db_amp(-resp))
     }
 
@@ -188,23 +241,67 @@ impl AudioNode for PowShaper {
 
 /// Parameter smoother. Cheaper than `follow()`.
 pub fn smooth() -> An<Smooth> {
-    An(Smooth::new())
+    An(Smooth::new(Smooth::RESPONSE_TIME))
+}
+
+/// Response time of `param_smooth()`, as f32 bits. Defaults to 0.005.
+static PARAM_SMOOTH_TIME: AtomicU32 = AtomicU32::new(0x3ba3d70a);
+
+/// Sets the response time of subsequently built `param_smooth()` nodes.
+pub fn set_param_smooth_time(t: f32) {
+    PARAM_SMOOTH_TIME.store(t.max(0.0).to_bits(), Ordering::Relaxed);
+}
+
+/// Smoother for user-edited parameters. Uses the response time set by
+/// `set_param_smooth_time()`.
+pub fn param_smooth() -> An<Smooth> {
+    An(Smooth::new(f32::from_bits(PARAM_SMOOTH_TIME.load(Ordering::Relaxed))))
+}
+
+/// Response time of `mod_smooth()`, as f32 bits. Defaults to 0.005.
+static MOD_SMOOTH_TIME: AtomicU32 = AtomicU32::new(0x3ba3d70a);
+
+/// Response time of `pressure_smooth()`, as f32 bits. Defaults to 0.005.
+static PRESSURE_SMOOTH_TIME: AtomicU32 = AtomicU32::new(0x3ba3d70a);
+
+/// Sets the response time of subsequently built `mod_smooth()` nodes.
+pub fn set_mod_smooth_time(t: f32) {
+    MOD_SMOOTH_TIME.store(t.max(0.0).to_bits(), Ordering::Relaxed);
+}
+
+/// Sets the response time of subsequently built `pressure_smooth()` nodes.
+pub fn set_pressure_smooth_time(t: f32) {
+    PRESSURE_SMOOTH_TIME.store(t.max(0.0).to_bits(), Ordering::Relaxed);
+}
+
+/// Smoother for the modulation controller. Uses the response time set by
+/// `set_mod_smooth_time()`.
+pub fn mod_smooth() -> An<Smooth> {
+    An(Smooth::new(f32::from_bits(MOD_SMOOTH_TIME.load(Ordering::Relaxed))))
+}
+
+/// Smoother for pressure. Uses the response time set by
+/// `set_pressure_smooth_time()`.
+pub fn pressure_smooth() -> An<Smooth> {
+    An(Smooth::new(f32::from_bits(PRESSURE_SMOOTH_TIME.load(Ordering::Relaxed))))
 }
 
 #[derive(Clone)]
 pub struct Smooth {
     value: Option<f32>,
+    response_time: f32,
     prev_coeff: f32,
     next_coeff: f32,
 }
 
 impl Smooth {
-    /// Halfway response time in seconds.
+    /// Default halfway response time in seconds.
     const RESPONSE_TIME: f32 = 0.005;
 
-    fn new() -> Self {
+    fn new(response_time: f32) -> Self {
         let mut node = Self {
             value: None,
+            response_time,
             prev_coeff: 0.0,
             next_coeff: 0.0,
         };
@@ -224,7 +321,8 @@ impl AudioNode for Smooth {
     }
 
     fn set_sample_rate(&mut self, sample_rate: f64) {
-        let response_samples = Self::RESPONSE_TIME * sample_rate as f32;
+        // a response time under ~one sample means no smoothing
+        let response_samples = (self.response_time * sample_rate as f32).max(0.6912);
         self.next_coeff = 0.6912 / response_samples;
         self.prev_coeff = 1.0 - self.next_coeff;
     }
@@ -251,4 +349,464 @@ impl AudioNode for Smooth {
         output.set(0, input.at(0));
         output
     }
-}
\ No newline at end of file
+}
+
+/// Samples per frame in a standard wavetable file.
+pub const WAVETABLE_FRAME_LEN: usize = 2048;
+
+/// Wavetable oscillator reading consecutive single-cycle frames from the
+/// first channel of `wave`. Inputs are 1) frequency and 2) table position in
+/// 0..1, which crossfades between adjacent frames.
+pub fn wavetable_osc(wave: Arc<Wave>) -> An<WavetableOsc> {
+    An(WavetableOsc::new(wave))
+}
+
+#[derive(Clone)]
+pub struct WavetableOsc {
+    wave: Arc<Wave>,
+    frame_len: usize,
+    frames: usize,
+    phase: f32,
+    sample_duration: f32,
+}
+
+impl WavetableOsc {
+    fn new(wave: Arc<Wave>) -> Self {
+        // treat a file shorter than a standard frame as a single cycle
+        let frame_len = WAVETABLE_FRAME_LEN.min(wave.len()).max(1);
+        let frames = (wave.len() / frame_len).max(1);
+        let mut node = Self { wave, frame_len, frames, phase: 0.0, sample_duration: 0.0 };
+        node.set_sample_rate(DEFAULT_SR);
+        node
+    }
+
+    /// Returns the linearly interpolated value at `phase` in a frame.
+    fn frame_value(&self, frame: usize, phase: f32) -> f32 {
+        let x = phase * self.frame_len as f32;
+        let i = x as usize % self.frame_len;
+        let start = frame * self.frame_len;
+        let a = self.sample(start + i);
+        let b = self.sample(start + (i + 1) % self.frame_len);
+        lerp(a, b, x.fract())
+    }
+
+    fn sample(&self, index: usize) -> f32 {
+        if index < self.wave.len() {
+            self.wave.at(0, index)
+        } else {
+            0.0
+        }
+    }
+}
+
+impl AudioNode for WavetableOsc {
+    const ID: u64 = 203;
+    type Inputs = U2;
+    type Outputs = U1;
+
+    fn reset(&mut self) {
+        self.phase = 0.0;
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_duration = (1.0 / sample_rate) as f32;
+    }
+
+    #[inline]
+    fn tick(&mut self, input: &Frame<f32, Self::Inputs>) -> Frame<f32, Self::Outputs> {
+        let pos = clamp01(input[1]) * (self.frames - 1) as f32;
+        let frame = (pos as usize).min(self.frames - 1);
+        let mut value = self.frame_value(frame, self.phase);
+        if frame + 1 < self.frames {
+            value = lerp(value, self.frame_value(frame + 1, self.phase), pos.fract());
+        }
+        self.phase = (self.phase + input[0] * self.sample_duration).rem_euclid(1.0);
+        [value].into()
+    }
+}
+
+/// Additive oscillator summing sine partials. Input is fundamental frequency.
+/// Partials with zero amplitude or above the Nyquist frequency are skipped.
+/// Output is scaled down if the amplitudes sum to more than 1.
+pub fn additive_osc(amps: &[f32], ratios: &[f32]) -> An<AdditiveOsc> {
+    An(AdditiveOsc::new(amps, ratios))
+}
+
+#[derive(Clone)]
+pub struct AdditiveOsc {
+    /// Frequency ratio and amplitude of each partial.
+    partials: Vec<(f32, f32)>,
+    phases: Vec<f32>,
+    sample_duration: f32,
+    nyquist: f32,
+}
+
+impl AdditiveOsc {
+    fn new(amps: &[f32], ratios: &[f32]) -> Self {
+        let scale = amps.iter().sum::<f32>().max(1.0).recip();
+        let partials: Vec<_> = ratios.iter().zip(amps)
+            .filter(|(_, amp)| **amp > 0.0)
+            .map(|(ratio, amp)| (*ratio, amp * scale))
+            .collect();
+        let mut node = Self {
+            phases: vec![0.0; partials.len()],
+            partials,
+            sample_duration: 0.0,
+            nyquist: 0.0,
+        };
+        node.set_sample_rate(DEFAULT_SR);
+        node
+    }
+}
+
+impl AudioNode for AdditiveOsc {
+    const ID: u64 = 204;
+    type Inputs = U1;
+    type Outputs = U1;
+
+    fn reset(&mut self) {
+        self.phases.fill(0.0);
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_duration = (1.0 / sample_rate) as f32;
+        self.nyquist = (sample_rate * 0.5) as f32;
+    }
+
+    #[inline]
+    fn tick(&mut self, input: &Frame<f32, Self::Inputs>) -> Frame<f32, Self::Outputs> {
+        let mut value = 0.0;
+        for ((ratio, amp), phase) in self.partials.iter().zip(self.phases.iter_mut()) {
+            let freq = input[0] * ratio;
+            if freq < self.nyquist {
+                value += amp * (*phase * std::f32::consts::TAU).sin();
+            }
+            *phase = (*phase + freq * self.sample_duration).rem_euclid(1.0);
+        }
+        [value].into()
+    }
+}
+
+/// Lowest frequency a plucked string can be tuned to.
+const PLUCK_MIN_FREQ: f32 = 10.0;
+/// Gain applied on each pass through the string.
+const PLUCK_FEEDBACK: f32 = 0.999;
+
+/// Karplus-Strong plucked string, excited by one period of white noise when
+/// the node starts. Inputs are 1) frequency and 2) brightness in 0..1; lower
+/// brightness damps high frequencies more quickly.
+pub fn pluck_string(seed: u64) -> An<PluckString> {
+    An(PluckString::new(seed))
+}
+
+#[derive(Clone)]
+pub struct PluckString {
+    buffer: Vec<f32>,
+    pos: usize,
+    /// Previous output of the delay line, for the damping filter.
+    prev: f32,
+    /// Remaining samples of excitation. None if the string hasn't started.
+    burst: Option<usize>,
+    seed: u64,
+    state: u64,
+    sample_rate: f32,
+}
+
+impl PluckString {
+    fn new(seed: u64) -> Self {
+        // xorshift can't start from zero
+        let seed = seed | 1;
+        let mut node = Self {
+            buffer: Vec::new(),
+            pos: 0,
+            prev: 0.0,
+            burst: None,
+            seed,
+            state: seed,
+            sample_rate: 0.0,
+        };
+        node.set_sample_rate(DEFAULT_SR);
+        node
+    }
+
+    /// Returns white noise in -1..1.
+    fn noise(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+    }
+}
+
+impl AudioNode for PluckString {
+    const ID: u64 = 205;
+    type Inputs = U2;
+    type Outputs = U1;
+
+    fn reset(&mut self) {
+        self.buffer.fill(0.0);
+        self.pos = 0;
+        self.prev = 0.0;
+        self.burst = None;
+        self.state = self.seed;
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate as f32;
+        self.buffer = vec![0.0; (self.sample_rate / PLUCK_MIN_FREQ) as usize + 2];
+        self.pos = 0;
+    }
+
+    #[inline]
+    fn tick(&mut self, input: &Frame<f32, Self::Inputs>) -> Frame<f32, Self::Outputs> {
+        let len = self.buffer.len();
+        let period = (self.sample_rate / input[0].max(PLUCK_MIN_FREQ))
+            .min((len - 2) as f32);
+        let burst = *self.burst.get_or_insert(period as usize);
+        let excitation = if burst > 0 {
+            self.burst = Some(burst - 1);
+            self.noise()
+        } else {
+            0.0
+        };
+
+        // the damping filter delays by up to half a sample, so compensate
+        let damping = 0.5 * (1.0 - clamp01(input[1]));
+        let delay = (period - damping).max(1.0);
+        let read = (self.pos as f32 - delay).rem_euclid(len as f32);
+        let i = read as usize % len;
+        let delayed = lerp(self.buffer[i], self.buffer[(i + 1) % len], read.fract());
+        let filtered = lerp(delayed, self.prev, damping);
+        self.prev = delayed;
+
+        let value = excitation + filtered * PLUCK_FEEDBACK;
+        self.buffer[self.pos] = value;
+        self.pos = (self.pos + 1) % len;
+        [value].into()
+    }
+}
+
+/// First three formant frequencies of the vowels A, E, I, O, and U.
+const VOWEL_FORMANTS: [[f32; 3]; 5] = [
+    [800.0, 1150.0, 2900.0],
+    [400.0, 2000.0, 2700.0],
+    [270.0, 2140.0, 2950.0],
+    [450.0, 800.0, 2830.0],
+    [325.0, 700.0, 2530.0],
+];
+
+/// Relative gain of each formant.
+const FORMANT_GAINS: [f32; 3] = [1.0, 0.5, 0.25];
+
+/// Vowel formant filter made of parallel bandpass filters. Inputs are 1)
+/// audio, 2) vowel position in 0..1, morphing through A, E, I, O, and U, and
+/// 3) resonance in 0..1.
+pub fn formant_filter() -> An<FormantFilter> {
+    An(FormantFilter::new())
+}
+
+#[derive(Clone)]
+pub struct FormantFilter {
+    /// Integrator states of each state-variable filter.
+    state: [(f32, f32); 3],
+    sample_rate: f32,
+}
+
+impl FormantFilter {
+    fn new() -> Self {
+        let mut node = Self { state: [(0.0, 0.0); 3], sample_rate: 0.0 };
+        node.set_sample_rate(DEFAULT_SR);
+        node
+    }
+}
+
+impl AudioNode for FormantFilter {
+    const ID: u64 = 206;
+    type Inputs = U3;
+    type Outputs = U1;
+
+    fn reset(&mut self) {
+        self.state = [(0.0, 0.0); 3];
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate as f32;
+    }
+
+    #[inline]
+    fn tick(&mut self, input: &Frame<f32, Self::Inputs>) -> Frame<f32, Self::Outputs> {
+        let pos = clamp01(input[1]) * (VOWEL_FORMANTS.len() - 1) as f32;
+        let vowel = (pos as usize).min(VOWEL_FORMANTS.len() - 2);
+        let k = (2.0 + clamp01(input[2]) * 18.0).recip();
+        let mut value = 0.0;
+
+        for (i, (ic1eq, ic2eq)) in self.state.iter_mut().enumerate() {
+            let freq = lerp(VOWEL_FORMANTS[vowel][i], VOWEL_FORMANTS[vowel + 1][i],
+                pos - vowel as f32).min(self.sample_rate * 0.45);
+            let g = (std::f32::consts::PI * freq / self.sample_rate).tan();
+            let a1 = (1.0 + g * (g + k)).recip();
+            let a2 = g * a1;
+            let v3 = input[0] - *ic2eq;
+            let v1 = a1 * *ic1eq + a2 * v3;
+            let v2 = *ic2eq + a2 * *ic1eq + g * a2 * v3;
+            *ic1eq = 2.0 * v1 - *ic1eq;
+            *ic2eq = 2.0 * v2 - *ic2eq;
+            // scale bandpass output for unity gain at the peak
+            value += v1 * k * FORMANT_GAINS[i];
+        }
+
+        [value].into()
+    }
+}
+
+/// Sample player for the first channel of `wave`, looping from `loop_point` to
+/// the end if set. Inputs are 1) playback speed, in source frames per output
+/// frame, and 2) start position in 0..1, which is read on the first sample.
+pub fn pcm_player(wave: Arc<Wave>, loop_point: Option<usize>) -> An<PcmPlayer> {
+    An(PcmPlayer { wave, loop_point, pos: 0.0, started: false })
+}
+
+#[derive(Clone)]
+pub struct PcmPlayer {
+    wave: Arc<Wave>,
+    loop_point: Option<usize>,
+    pos: f64,
+    started: bool,
+}
+
+impl PcmPlayer {
+    /// Returns the frame at `index`, following the loop past the end.
+    fn frame(&self, index: usize) -> f32 {
+        let len = self.wave.len();
+        if index < len {
+            self.wave.at(0, index)
+        } else if let Some(pt) = self.loop_point.filter(|&pt| pt < len) {
+            self.wave.at(0, pt + (index - len) % (len - pt))
+        } else {
+            0.0
+        }
+    }
+}
+
+impl AudioNode for PcmPlayer {
+    const ID: u64 = 207;
+    type Inputs = U2;
+    type Outputs = U1;
+
+    fn reset(&mut self) {
+        self.pos = 0.0;
+        self.started = false;
+    }
+
+    #[inline]
+    fn tick(&mut self, input: &Frame<f32, Self::Inputs>) -> Frame<f32, Self::Outputs> {
+        let len = self.wave.len();
+        if !self.started {
+            self.started = true;
+            self.pos = (clamp01(input[1]) * len.saturating_sub(1) as f32) as f64;
+        }
+
+        if let Some(pt) = self.loop_point.filter(|&pt| pt < len) {
+            if self.pos >= len as f64 {
+                self.pos = pt as f64 + (self.pos - len as f64) % (len - pt) as f64;
+            }
+        }
+
+        // cubic interpolation between the two nearest frames
+        let i = self.pos as usize;
+        let t = self.pos.fract() as f32;
+        let y0 = if i > 0 { self.frame(i - 1) } else { 0.0 };
+        let (y1, y2, y3) = (self.frame(i), self.frame(i + 1), self.frame(i + 2));
+        let value = y1 + 0.5 * t * (y2 - y0
+            + t * (2.0 * y0 - 5.0 * y1 + 4.0 * y2 - y3
+            + t * (3.0 * (y1 - y2) + y3 - y0)));
+
+        self.pos += input[0].max(0.0) as f64;
+        [value].into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wavetable_crossfade() {
+        let mut samples = vec![0.25; WAVETABLE_FRAME_LEN];
+        samples.extend([-0.5; WAVETABLE_FRAME_LEN]);
+        let mut osc = WavetableOsc::new(Arc::new(Wave::from_samples(44100.0, &samples)));
+
+        // zero frequency holds the phase at the start of each frame
+        assert_eq!(osc.tick(&[0.0, 0.0].into())[0], 0.25);
+        assert_eq!(osc.tick(&[0.0, 1.0].into())[0], -0.5);
+        assert_eq!(osc.tick(&[0.0, 0.5].into())[0], -0.125);
+        // position is clamped
+        assert_eq!(osc.tick(&[0.0, 2.0].into())[0], -0.5);
+    }
+
+    #[test]
+    fn test_additive_nyquist() {
+        // the second partial is above the Nyquist frequency at 441 Hz
+        let mut osc = AdditiveOsc::new(&[0.5, 0.5], &[1.0, 60.5]);
+        let mut fundamental = AdditiveOsc::new(&[0.5], &[1.0]);
+        let mut peak: f32 = 0.0;
+        for _ in 0..1000 {
+            let value = osc.tick(&[441.0].into())[0];
+            assert_eq!(value, fundamental.tick(&[441.0].into())[0]);
+            peak = peak.max(value.abs());
+        }
+        assert!(peak > 0.4);
+    }
+
+    #[test]
+    fn test_pluck_decay() {
+        // 441 Hz is a period of 100 samples at 44.1 kHz
+        const PERIOD: usize = 100;
+        let mut string = PluckString::new(1);
+        let output: Vec<f32> = (0..PERIOD * 8001)
+            .map(|_| string.tick(&[441.0, 1.0].into())[0])
+            .collect();
+
+        // without damping, each period repeats the last at reduced gain
+        for (prev, next) in output[..PERIOD * 9].iter().zip(&output[PERIOD..PERIOD * 10]) {
+            assert_eq!(*next, prev * PLUCK_FEEDBACK);
+        }
+        assert!(output[..PERIOD].iter().any(|x| x.abs() > 0.1));
+        assert!(output[PERIOD * 8000..].iter().all(|x| x.abs() < 1e-3));
+    }
+
+    #[test]
+    fn test_formant_resonance() {
+        for sample_rate in [44100.0, 4000.0] {
+            let mut filter = FormantFilter::new();
+            filter.set_sample_rate(sample_rate);
+            let mut peak: f32 = 0.0;
+            for i in 0..sample_rate as usize {
+                let x = (std::f64::consts::TAU * 800.0 * i as f64 / sample_rate).sin();
+                let value = filter.tick(&[x as f32, 0.0, 1.0].into())[0];
+                assert!(value.is_finite());
+                peak = peak.max(value.abs());
+            }
+            // formants above the Nyquist frequency are lowered, not unstable
+            assert!(peak < 2.0, "peak {peak} at {sample_rate} Hz");
+        }
+    }
+
+    #[test]
+    fn test_pcm_player() {
+        let samples: Vec<f32> = (0..9).map(|x| x as f32).collect();
+        let wave = Arc::new(Wave::from_samples(44100.0, &samples));
+        let play = |loop_point, start: f32| {
+            let mut player = PcmPlayer { wave: wave.clone(), loop_point, pos: 0.0,
+                started: false };
+            (0..12).map(|_| player.tick(&[1.0, start].into())[0]).collect::<Vec<_>>()
+        };
+
+        assert_eq!(play(Some(5), 0.0),
+            [0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 5.0, 6.0, 7.0]);
+        assert_eq!(play(Some(5), 0.5),
+            [4.0, 5.0, 6.0, 7.0, 8.0, 5.0, 6.0, 7.0, 8.0, 5.0, 6.0, 7.0]);
+        assert_eq!(play(None, 0.5),
+            [4.0, 5.0, 6.0, 7.0, 8.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+    }
+}
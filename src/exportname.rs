@@ -0,0 +1,59 @@
+//! Filename templates for quick export.
+
+/// Expand placeholders in a filename template. `{title}` is the song title,
+/// or `file` if the title is empty; `{file}` is the module filename without
+/// extension; `{date}` and `{time}` are the UTC date and time at `unix_secs`.
+/// Characters that aren't valid in filenames are replaced.
+pub fn expand(template: &str, title: &str, file: &str, unix_secs: u64) -> String {
+    let days = unix_secs / 86400;
+    let secs = unix_secs % 86400;
+    let (y, m, d) = civil_date(days as i64);
+    let title = if title.is_empty() { file } else { title };
+
+    let s = template
+        .replace("{title}", title)
+        .replace("{file}", file)
+        .replace("{date}", &format!("{y:04}-{m:02}-{d:02}"))
+        .replace("{time}",
+            &format!("{:02}-{:02}-{:02}", secs / 3600, secs / 60 % 60, secs % 60));
+    sanitize(&s)
+}
+
+/// Returns the (year, month, day) of a count of days since 1970-01-01.
+fn civil_date(days: i64) -> (i64, u32, u32) {
+    // algorithm from Howard Hinnant's date library
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    (y, m, d)
+}
+
+/// Replace path separators and other reserved characters with underscores.
+fn sanitize(s: &str) -> String {
+    s.chars()
+        .map(|c| if c.is_control() || "/\\:*?\"<>|".contains(c) { '_' } else { c })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        assert_eq!(civil_date(0), (1970, 1, 1));
+        assert_eq!(civil_date(11016), (2000, 2, 29));
+
+        // 2024-03-05 06:07:08 UTC
+        let t = 1709618828;
+        assert_eq!(expand("{title}_{date}", "Song", "song", t), "Song_2024-03-05");
+        assert_eq!(expand("{title} {time}", "", "song", t), "song 06-07-08");
+        assert_eq!(expand("{title}", "A/B: C", "x", t), "A_B_ C");
+    }
+}
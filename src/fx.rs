@@ -1,40 +1,101 @@
 //! Global FX.
 
 use fundsp::hacker32::*;
-use realseq::SequencerBackend;
 use serde::{Deserialize, Serialize};
 
 use crate::dsp::compressor;
 
+/// Maximum master stereo width.
+pub const MAX_STEREO_WIDTH: f32 = 2.0;
+
+/// Range of EQ band frequencies, in Hz.
+pub const MIN_EQ_FREQ: f32 = 20.0;
+pub const MAX_EQ_FREQ: f32 = 20000.0;
+
+/// Maximum EQ band boost or cut, in dB.
+pub const MAX_EQ_GAIN: f32 = 18.0;
+
+/// Range of EQ band Q values.
+pub const MIN_EQ_Q: f32 = 0.1;
+pub const MAX_EQ_Q: f32 = 10.0;
+
+/// Maximum compressor sidechain high-pass cutoff, in Hz.
+pub const MAX_SIDECHAIN_HZ: f32 = 500.0;
+
 // Serializable FX settings, to be stored in save files.
-#[derive(Clone, Default, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct FXSettings {
     pub spatial: SpatialFx,
     pub comp: Compression,
+    /// Master stereo width. 0 is mono and 1 is unchanged.
+    #[serde(default = "default_stereo_width")]
+    pub width: f32,
+    #[serde(default)]
+    pub eq: Equalizer,
+}
+
+impl Default for FXSettings {
+    fn default() -> Self {
+        Self {
+            spatial: Default::default(),
+            comp: Default::default(),
+            width: default_stereo_width(),
+            eq: Default::default(),
+        }
+    }
 }
 
+fn default_stereo_width() -> f32 { 1.0 }
+
 /// Handles updates of global FX.
 pub struct GlobalFX {
     pub net: Net,
     spatial_id: NodeId,
     comp_id: NodeId,
+    eq_id: NodeId,
+    /// Compressor gain reduction, in dB.
+    comp_reduction: Shared,
+    width: Shared,
+    /// Nonzero if output is summed to mono for monitoring.
+    mono: Shared,
 }
 
 impl GlobalFX {
     const FADE_TIME: f32 = 0.1;
 
-    pub fn new(backend: SequencerBackend, settings: &FXSettings) -> Self {
+    /// Creates global FX that process the output of `input`, which should
+    /// have dry stereo and FX send stereo outputs.
+    pub fn new(input: Box<dyn AudioUnit>, settings: &FXSettings) -> Self {
         let (spatial, spatial_id) = Net::wrap_id(settings.spatial.make_node());
-        let (comp, comp_id) = Net::wrap_id(settings.comp.make_node());
+        let comp_reduction = shared(0.0);
+        let (comp, comp_id) = Net::wrap_id(settings.comp.make_node(&comp_reduction));
+        let (eq, eq_id) = Net::wrap_id(settings.eq.make_node());
+        let width = shared(settings.width);
+        let mono = shared(0.0);
+
+        // mid/side width, with the side signal removed for mono monitoring
+        let width_node = (pass() | pass()
+            | (var(&width) >> follow(Self::FADE_TIME)) | var(&mono))
+            >> map(|i: &Frame<f32, U4>| {
+                let mid = (i[0] + i[1]) * 0.5;
+                let side = if i[3] > 0.0 { 0.0 } else { (i[0] - i[1]) * 0.5 * i[2] };
+                Frame::<f32, U2>::from([mid + side, mid - side])
+            });
 
         Self {
-            net: Net::wrap(Box::new(backend))
+            net: Net::wrap(input)
                 >> (multipass::<U2>()
                     + (multipass::<U2>() >> spatial))
                 >> (dcblock() | dcblock())
-                >> comp,
+                >> eq
+                >> comp
+                >> Net::wrap(Box::new(width_node)),
             spatial_id,
             comp_id,
+            eq_id,
+            comp_reduction,
+            width,
+            mono,
         }
     }
 
@@ -43,8 +104,31 @@ impl GlobalFX {
         self.net.crossfade(self.spatial_id, Fade::Smooth, Self::FADE_TIME,
             settings.spatial.make_node());
         self.net.crossfade(self.comp_id, Fade::Smooth, Self::FADE_TIME,
-            settings.comp.make_node());
+            settings.comp.make_node(&self.comp_reduction));
+        self.net.crossfade(self.eq_id, Fade::Smooth, Self::FADE_TIME,
+            settings.eq.make_node());
         self.net.commit();
+        self.set_width(settings.width);
+    }
+
+    /// Update EQ FX.
+    pub fn commit_eq(&mut self, eq: &Equalizer) {
+        self.crossfade(self.eq_id, eq.make_node());
+    }
+
+    /// Update master stereo width.
+    pub fn set_width(&mut self, width: f32) {
+        self.width.set(width);
+    }
+
+    /// Returns true if output is summed to mono for monitoring.
+    pub fn mono_monitor(&self) -> bool {
+        self.mono.value() > 0.0
+    }
+
+    /// Sum output to mono, for checking mono compatibility. Not saved.
+    pub fn set_mono_monitor(&mut self, mono: bool) {
+        self.mono.set(if mono { 1.0 } else { 0.0 });
     }
 
     /// Update spatial FX.
@@ -54,7 +138,12 @@ impl GlobalFX {
 
     /// Update compression FX.
     pub fn commit_comp(&mut self, comp: &Compression) {
-        self.crossfade(self.comp_id, comp.make_node());
+        self.crossfade(self.comp_id, comp.make_node(&self.comp_reduction));
+    }
+
+    /// Returns the current compressor gain reduction, in dB.
+    pub fn comp_reduction(&self) -> f32 {
+        self.comp_reduction.value()
     }
 
     fn crossfade(&mut self, id: NodeId, unit: Box<dyn AudioUnit>) {
@@ -71,13 +160,28 @@ pub struct Compression {
     pub slope: f32,
     pub attack: f32,
     pub release: f32,
+    /// Gain applied after compression.
+    #[serde(default = "default_makeup")]
+    pub makeup: f32,
+    /// Cutoff of the level detector's high-pass filter, in Hz. 0 is off.
+    #[serde(default)]
+    pub sidechain_hz: f32,
 }
 
+fn default_makeup() -> f32 { 1.0 }
+
 impl Compression {
-    fn make_node(&self) -> Box<dyn AudioUnit> {
-        if self.threshold < 1.0 && self.slope > 0.0 {
-            let comp = compressor(self.threshold, self.slope, self.attack, self.release);
-            Box::new((mul(self.gain) | mul(self.gain)) >> comp)
+    /// Returns true if the settings result in any compression.
+    pub fn is_active(&self) -> bool {
+        self.threshold < 1.0 && self.slope > 0.0
+    }
+
+    fn make_node(&self, reduction: &Shared) -> Box<dyn AudioUnit> {
+        if self.is_active() {
+            let comp = compressor(self.threshold, self.slope, self.attack, self.release,
+                self.sidechain_hz, reduction);
+            Box::new((mul(self.gain) | mul(self.gain)) >> comp
+                >> (mul(self.makeup) | mul(self.makeup)))
         } else {
             Box::new(pass() | pass())
         }
@@ -92,6 +196,48 @@ impl Default for Compression {
             slope: 0.75,
             attack: 0.001,
             release: 0.05,
+            makeup: default_makeup(),
+            sidechain_hz: 0.0,
+        }
+    }
+}
+
+/// Band of the master EQ.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EqBand {
+    /// Center or corner frequency, in Hz.
+    pub freq: f32,
+    /// Gain in dB.
+    pub gain: f32,
+    pub q: f32,
+}
+
+/// Three-band EQ settings: low shelf, mid peak, and high shelf.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Equalizer {
+    pub low: EqBand,
+    pub mid: EqBand,
+    pub high: EqBand,
+}
+
+impl Equalizer {
+    fn make_node(&self) -> Box<dyn AudioUnit> {
+        if [&self.low, &self.mid, &self.high].iter().all(|b| b.gain == 0.0) {
+            return Box::new(pass() | pass())
+        }
+        let channel = || lowshelf_hz(self.low.freq, self.low.q, db_amp(self.low.gain))
+            >> bell_hz(self.mid.freq, self.mid.q, db_amp(self.mid.gain))
+            >> highshelf_hz(self.high.freq, self.high.q, db_amp(self.high.gain));
+        Box::new(channel() | channel())
+    }
+}
+
+impl Default for Equalizer {
+    fn default() -> Self {
+        Self {
+            low: EqBand { freq: 200.0, gain: 0.0, q: 0.7 },
+            mid: EqBand { freq: 1000.0, gain: 0.0, q: 0.7 },
+            high: EqBand { freq: 5000.0, gain: 0.0, q: 0.7 },
         }
     }
 }
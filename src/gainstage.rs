@@ -0,0 +1,76 @@
+//! Level analysis for giving patches consistent gains.
+
+use fundsp::math::{amp_db, db_amp};
+
+use crate::synth::{Patch, MAX_PATCH_GAIN, REF_PITCH};
+
+const SAMPLE_RATE: f32 = 44100.0;
+
+/// Length of the reference note, in seconds.
+const NOTE_TIME: f32 = 1.0;
+
+/// RMS level that trims aim for, in dB.
+const TARGET_RMS_DB: f32 = -18.0;
+
+/// Highest peak level that trims may produce, in dB.
+const PEAK_CEILING_DB: f32 = -1.0;
+
+/// Measured levels of a patch's reference note.
+pub struct LevelReport {
+    pub patch_index: usize,
+    pub peak_db: f32,
+    pub rms_db: f32,
+    /// Suggested patch gain, or None if the patch is silent.
+    pub trim: Option<f32>,
+}
+
+/// Play a reference note with each of the patches at `indices` and suggest
+/// gain trims.
+pub fn analyze(patches: &[Patch], indices: &[usize]) -> Vec<LevelReport> {
+    indices.iter().filter_map(|&i| {
+        let patch = patches.get(i)?;
+        let (peak, rms) = patch.measure_note(REF_PITCH as f32, NOTE_TIME, SAMPLE_RATE);
+        Some(LevelReport {
+            patch_index: i,
+            peak_db: amp_db(peak),
+            rms_db: amp_db(rms),
+            trim: suggest_gain(patch.gain.0.value(), peak, rms),
+        })
+    }).collect()
+}
+
+/// Returns the gain that would bring a note measured at `gain` to the target
+/// RMS level, lowered if needed to keep peaks under the ceiling.
+fn suggest_gain(gain: f32, peak: f32, rms: f32) -> Option<f32> {
+    if rms < db_amp(-120.0) {
+        return None
+    }
+    let factor = (db_amp(TARGET_RMS_DB) / rms).min(db_amp(PEAK_CEILING_DB) / peak);
+    Some((gain * factor).clamp(0.0, MAX_PATCH_GAIN))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suggest_gain() {
+        assert_eq!(suggest_gain(1.0, 0.0, 0.0), None);
+
+        // quiet patches are raised to the target level
+        let gain = suggest_gain(0.5, db_amp(-30.0), db_amp(-36.0)).unwrap();
+        assert!((amp_db(gain / 0.5) - 18.0).abs() < 0.01);
+
+        // spiky patches are limited by the peak ceiling
+        let gain = suggest_gain(1.0, db_amp(-2.0), db_amp(-30.0)).unwrap();
+        assert!((amp_db(gain) - 1.0).abs() < 0.01);
+
+        // gains stay in range
+        assert_eq!(suggest_gain(1.0, 0.001, 0.001), Some(MAX_PATCH_GAIN));
+
+        let patches = [Patch::new(String::from("Init"))];
+        let reports = analyze(&patches, &[0, 1]);
+        assert_eq!(reports.len(), 1);
+        assert!(reports[0].rms_db > -60.0 && reports[0].peak_db < 0.0);
+    }
+}
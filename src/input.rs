@@ -8,14 +8,20 @@ use serde::{Deserialize, Serialize};
 use crate::{config::Config, pitch::{Nominal, Note, Tuning}};
 
 pub const CC_MODULATION: u8 = 1;
+pub const CC_MODULATION_LSB: u8 = 33;
 pub const CC_MACRO_MIN: u8 = 41;
 pub const CC_MACRO_MAX: u8 = 48;
+pub const CC_SUSTAIN: u8 = 64;
+pub const CC_SOSTENUTO: u8 = 66;
 pub const CC_RPN_MSB: u8 = 101;
 pub const CC_RPN_LSB: u8 = 100;
 pub const CC_DATA_ENTRY_MSB: u8 = 6;
 pub const CC_DATA_ENTRY_LSB: u8 = 38;
 pub const RPN_PITCH_BEND_SENSITIVITY: (u8, u8) = (0, 0);
 
+/// Pedal controller values at or above this are "down".
+pub const PEDAL_THRESHOLD: u8 = 64;
+
 /// Returns the last byte of a keycode name. This is used as the equivalent of
 /// a MIDI key number for tracking held notes.
 pub fn u8_from_key(k: KeyCode) -> u8 {
@@ -32,23 +38,57 @@ fn use_sharps(t: &Tuning) -> bool {
 }
 
 /// Translates a key combination into a note.
-pub fn note_from_key(key: Hotkey, t: &Tuning, equave: i8, cfg: &Config) -> Option<Note> {
-    cfg.note_keys.iter()
-        .find(|(k, _)| *k == key)
-        .map(|(_, n)| {
-            let n = if use_sharps(t) { *n } else {
-                Note {
-                    sharps: 0,
-                    arrows: n.sharps,
-                    ..*n
-                }
-            };
-            let n = adjust_note_for_modifier_keys(n, cfg, t);
+pub fn note_from_key(key: Hotkey, t: &Tuning, map: &KeyMap, equave: i8, cfg: &Config
+) -> Option<Note> {
+    let i = cfg.note_keys.iter().position(|(k, _)| *k == key)?;
+    let n = if map.is_active() {
+        map.note(i, t)?
+    } else {
+        let n = cfg.note_keys[i].1;
+        if use_sharps(t) { n } else {
             Note {
-                equave: n.equave + equave,
+                sharps: 0,
+                arrows: n.sharps,
                 ..n
             }
-        })
+        }
+    };
+    let n = adjust_note_for_modifier_keys(n, cfg, t);
+    Some(Note {
+        equave: n.equave + equave,
+        ..n
+    })
+}
+
+/// Assignment of scale degrees to note keys, for tunings with more notes
+/// than there are keys.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct KeyMap {
+    /// Scale degrees played by consecutive note keys, in the order the keys
+    /// are listed in settings. Empty uses the standard key layout.
+    pub degrees: Vec<usize>,
+    /// Number of degrees to skip before the first key. Rotating a subset
+    /// plays its modes.
+    pub rotation: usize,
+}
+
+impl KeyMap {
+    pub fn is_active(&self) -> bool {
+        !self.degrees.is_empty()
+    }
+
+    /// Returns the note for the note key at `index`, relative to equave 0.
+    fn note(&self, index: usize, t: &Tuning) -> Option<Note> {
+        let degrees: Vec<_> = self.degrees.iter()
+            .filter(|d| **d < t.size() as usize)
+            .collect();
+        if degrees.is_empty() {
+            return None
+        }
+        let i = index + self.rotation;
+        let degree = *degrees[i % degrees.len()];
+        t.notation(degree, (i / degrees.len()) as i8).first().copied()
+    }
 }
 
 /// Returns the default key-to-note mapping.
@@ -193,8 +233,44 @@ fn enharmonic_alternative(note: Note, tuning: &Tuning) -> Note {
     }
 }
 
-/// Decodes MIDI events. Program change is omitted since this project has no
-/// use for it.
+/// Interpretation of controller values. Relative modes are used by endless
+/// encoders, which send increments instead of positions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum CcMode {
+    #[default]
+    Absolute,
+    /// Increments of 1 to 63 are sent as-is, and decrements as 128 minus the
+    /// amount.
+    TwosComplement,
+    /// Increments and decrements are offset from 64.
+    OffsetBinary,
+}
+
+impl CcMode {
+    pub const VARIANTS: [Self; 3] = [Self::Absolute, Self::TwosComplement, Self::OffsetBinary];
+
+    /// Returns the UI string for the mode.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Absolute => "Absolute",
+            Self::TwosComplement => "Relative (2's complement)",
+            Self::OffsetBinary => "Relative (offset binary)",
+        }
+    }
+
+    /// Returns the controller position after receiving `value`, given the
+    /// `current` position.
+    pub fn apply(&self, current: u8, value: u8) -> u8 {
+        let delta = match self {
+            Self::Absolute => return value.min(127),
+            Self::TwosComplement => if value < 64 { value as i16 } else { value as i16 - 128 },
+            Self::OffsetBinary => value as i16 - 64,
+        };
+        (current as i16 + delta).clamp(0, 127) as u8
+    }
+}
+
+/// Decodes MIDI events.
 pub enum MidiEvent {
     NoteOff {
         channel: u8,
@@ -216,6 +292,10 @@ pub enum MidiEvent {
         controller: u8,
         value: u8,
     },
+    ProgramChange {
+        channel: u8,
+        program: u8,
+    },
     ChannelPressure {
         channel: u8,
         pressure: u8,
@@ -245,6 +325,7 @@ impl MidiEvent {
                 channel, key: data[1], pressure: *data.get(2)? }),
             0xb0 => Some(Self::Controller {
                 channel, controller: data[1], value: *data.get(2)? }),
+            0xc0 => Some(Self::ProgramChange { channel, program: data[1] }),
             0xd0 => Some(Self::ChannelPressure { channel, pressure: data[1] }),
             0xe0 => Some(Self::Pitch { channel, bend: {
                 // weird 14-bit integer format
@@ -254,6 +335,19 @@ impl MidiEvent {
             _ => None,
         }
     }
+
+    /// Returns the channel the event was sent on.
+    pub fn channel(&self) -> u8 {
+        match *self {
+            Self::NoteOff { channel, .. }
+            | Self::NoteOn { channel, .. }
+            | Self::PolyPressure { channel, .. }
+            | Self::Controller { channel, .. }
+            | Self::ProgramChange { channel, .. }
+            | Self::ChannelPressure { channel, .. }
+            | Self::Pitch { channel, .. } => channel,
+        }
+    }
 }
 
 /// Redefinition of macroquad's KeyCode for serde.
@@ -622,6 +716,7 @@ pub enum Action {
     NudgeOctaveDown,
     NudgeEnharmonic,
     ToggleFollow,
+    ToggleRecord,
     NextTab,
     PrevTab,
     SelectAllChannels,
@@ -643,6 +738,51 @@ pub enum Action {
     Panic,
     UseLastNote,
     Quit,
+    ImportModule,
+    RenderSelection,
+    SetLoopRange,
+    ToggleLoopRange,
+    FreezeTrack,
+    CollapseTrack,
+    CopyPatternText,
+    PastePatternText,
+    ExportPatternText,
+    ImportPatternText,
+    RunScript,
+    RerunScript,
+    GenerateMelody,
+    SendTuning,
+    TuningChange,
+    ToggleFullscreen,
+    OpenRecent,
+    ZoomInRows,
+    ZoomOutRows,
+    ScaleValues,
+    CycleGlideCurve,
+    InsertVibrato,
+    InsertTremolo,
+    ImportMidi,
+    HoldNotes,
+    ToggleLatch,
+    MoveEnd,
+    TrimToEnd,
+    PadSong,
+    LoopSelection,
+    CheckControlEvents,
+    RecordMacro,
+    PlayMacro,
+    InsertBendRamp,
+    InsertRetrigger,
+    InsertNoteDelay,
+    InsertNoteCut,
+    RenderLoop,
+    QuickExport,
+    RecoverSong,
+    ToggleStepRecord,
+    CommandPalette,
+    NewTab,
+    CloseTab,
+    ModulateParameter,
 }
 
 impl Action {
@@ -696,6 +836,7 @@ impl Action {
             Self::NudgeOctaveDown => "Transpose octave down",
             Self::NudgeEnharmonic => "Enharmonic swap",
             Self::ToggleFollow => "Toggle pattern follow",
+            Self::ToggleRecord => "Toggle record",
             Self::NextTab => "Next tab",
             Self::PrevTab => "Previous tab",
             Self::SelectAllChannels => "Select all channels",
@@ -717,6 +858,51 @@ impl Action {
             Self::Panic => "Panic",
             Self::UseLastNote => "Use last note",
             Self::Quit => "Quit",
+            Self::ImportModule => "Import from module",
+            Self::RenderSelection => "Render selection",
+            Self::SetLoopRange => "Set loop range",
+            Self::ToggleLoopRange => "Toggle loop range",
+            Self::FreezeTrack => "Toggle track freeze",
+            Self::CollapseTrack => "Toggle track columns",
+            Self::CopyPatternText => "Copy as text",
+            Self::PastePatternText => "Paste text",
+            Self::ExportPatternText => "Export pattern text",
+            Self::ImportPatternText => "Import pattern text",
+            Self::RunScript => "Run script",
+            Self::RerunScript => "Rerun last script",
+            Self::GenerateMelody => "Generate melody",
+            Self::SendTuning => "Send tuning via MIDI",
+            Self::TuningChange => "Insert tuning change",
+            Self::ToggleFullscreen => "Toggle fullscreen",
+            Self::OpenRecent => "Open recent song",
+            Self::ZoomInRows => "Zoom in rows",
+            Self::ZoomOutRows => "Zoom out rows",
+            Self::ScaleValues => "Scale values",
+            Self::CycleGlideCurve => "Cycle glide curve",
+            Self::InsertVibrato => "Insert vibrato",
+            Self::InsertTremolo => "Insert tremolo",
+            Self::ImportMidi => "Import MIDI file",
+            Self::HoldNotes => "Hold keyjazz notes",
+            Self::ToggleLatch => "Toggle keyjazz latch",
+            Self::MoveEnd => "Move end to cursor",
+            Self::TrimToEnd => "Trim after end",
+            Self::PadSong => "Pad song",
+            Self::LoopSelection => "Loop selection",
+            Self::CheckControlEvents => "Check control events",
+            Self::RecordMacro => "Record macro",
+            Self::PlayMacro => "Play macro",
+            Self::InsertBendRamp => "Insert bend ramp",
+            Self::InsertRetrigger => "Insert retrigger",
+            Self::InsertNoteDelay => "Insert note delay",
+            Self::InsertNoteCut => "Insert note cut",
+            Self::RenderLoop => "Render seamless loop",
+            Self::QuickExport => "Quick export",
+            Self::RecoverSong => "Recover damaged song",
+            Self::ToggleStepRecord => "Toggle step record",
+            Self::CommandPalette => "Command palette",
+            Self::NewTab => "New song tab",
+            Self::CloseTab => "Close song tab",
+            Self::ModulateParameter => "Modulate parameter",
         }
     }
 }
@@ -733,4 +919,47 @@ mod tests {
         assert!(!use_sharps(&Tuning::divide(2.0, 10, 1).unwrap()));
         assert!(!use_sharps(&Tuning::divide(2.0, 14, 1).unwrap()));
     }
+
+    #[test]
+    fn test_midi_event_channel() {
+        assert_eq!(MidiEvent::parse(&[0x93, 60, 100]).unwrap().channel(), 3);
+        assert_eq!(MidiEvent::parse(&[0xef, 0, 0x40]).unwrap().channel(), 15);
+        assert_eq!(MidiEvent::parse(&[0xc2, 1]).unwrap().channel(), 2);
+        assert!(MidiEvent::parse(&[0xf8]).is_none());
+    }
+
+    #[test]
+    fn test_cc_mode() {
+        assert_eq!(CcMode::Absolute.apply(10, 100), 100);
+        assert_eq!(CcMode::TwosComplement.apply(10, 3), 13);
+        assert_eq!(CcMode::TwosComplement.apply(10, 127), 9);
+        assert_eq!(CcMode::TwosComplement.apply(10, 64), 0);
+        assert_eq!(CcMode::OffsetBinary.apply(10, 66), 12);
+        assert_eq!(CcMode::OffsetBinary.apply(10, 60), 6);
+        assert_eq!(CcMode::OffsetBinary.apply(126, 70), 127);
+    }
+
+    #[test]
+    fn test_key_map() {
+        let t = Tuning::divide(2.0, 31, 1).unwrap();
+        let map = KeyMap {
+            degrees: vec![0, 5, 10, 13, 18, 23, 28],
+            rotation: 1,
+        };
+        assert_eq!(t.scale_index(&map.note(0, &t).unwrap()), (5, 0));
+        assert_eq!(t.scale_index(&map.note(5, &t).unwrap()), (28, 0));
+        assert_eq!(t.scale_index(&map.note(6, &t).unwrap()), (0, 1));
+
+        let all = KeyMap {
+            degrees: (0..31).collect(),
+            rotation: 0,
+        };
+        for i in 0..40 {
+            assert_eq!(t.scale_index(&all.note(i, &t).unwrap()),
+                (i % 31, (i / 31) as i8));
+        }
+
+        let invalid = KeyMap { degrees: vec![40], rotation: 0 };
+        assert!(invalid.note(0, &t).is_none());
+    }
 }
\ No newline at end of file
@@ -1,48 +1,87 @@
 use std::env;
 use std::error::Error;
-use std::path::PathBuf;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::{channel, Sender, Receiver};
 use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
+use arp::{ArpEvent, ArpNote, Arpeggiator};
 use config::Config;
 use cpal::SampleRate;
 use fx::{FXSettings, GlobalFX};
-use midir::{InitError, MidiInput, MidiInputConnection, MidiInputPort};
+use mixer::Mixer;
+use midir::{InitError, MidiInput, MidiInputConnection, MidiInputPort, MidiOutput, MidiOutputConnection, MidiOutputPort};
 use fundsp::hacker32::*;
-use cpal::{traits::{DeviceTrait, HostTrait, StreamTrait}, StreamConfig};
-use module::{EventData, Module, TrackTarget};
-use playback::{Player, RenderUpdate};
+use cpal::{traits::{DeviceTrait, HostTrait, StreamTrait}, BufferSize, StreamConfig, SupportedBufferSize};
+use module::{Edit, EditorState, EventData, Module, TrackTarget};
+use pitch::Tuning;
+use playback::{Player, RenderBounds, RenderUpdate};
 use rfd::FileDialog;
-use synth::{Key, KeyOrigin};
+use synth::{Key, KeyOrigin, PlayMode};
 use macroquad::prelude::*;
+use macroquad::miniquad::window::{clipboard_get, clipboard_set};
 
+mod arp;
 mod pitch;
 mod input;
 mod config;
 mod synth;
 mod fx;
+mod mixer;
 mod ui;
 pub mod module;
 pub mod playback;
 mod dsp;
 mod timespan;
+mod patterntext;
+mod script;
+mod melody;
+mod mts;
+mod midifile;
+mod batch;
+mod cliplib;
+mod wavmeta;
+mod exportname;
+mod songpos;
+mod gainstage;
+#[cfg(feature = "regression")]
+pub mod regression;
 
 use input::{Action, Hotkey, MidiEvent, Modifiers};
 use timespan::Timespan;
 use ui::developer::DevState;
 use ui::general::GeneralState;
 use ui::info::Info;
+use ui::palette::{self, PaletteCommand};
 use ui::instruments::{fix_patch_index, InstrumentsState};
 use ui::settings::SettingsState;
-use ui::{is_alt_down, is_ctrl_down};
+use ui::{is_alt_down, is_ctrl_down, is_shift_down};
 use ui::pattern::PatternEditor;
 
 /// Application name, for window title, etc.
 pub const APP_NAME: &str = "Osctet";
 const MODULE_FILETYPE_NAME: &str = "Osctet module";
 const MODULE_EXT: &str = "osctet";
+const PATTERN_TEXT_EXT: &str = "txt";
+const SCRIPT_EXT: &str = "rhai";
+/// Filename to write status messages to, for screen readers.
+const STATUS_FILENAME: &str = "status.txt";
+const MIDI_EXTS: [&str; 2] = ["mid", "midi"];
 const PKG_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Range of the master volume control, in dB.
+const MIN_OUTPUT_GAIN: f32 = -40.0;
+const MAX_OUTPUT_GAIN: f32 = 6.0;
+
+/// Range of the playback speed multiplier.
+const MIN_PLAYBACK_SPEED: f32 = 0.5;
+const MAX_PLAYBACK_SPEED: f32 = 2.0;
+
+/// Level of an empty output meter, in dB.
+const METER_FLOOR: f32 = -48.0;
+
 /// Returns a path in the same directory as the executable. If no executable
 /// path is available, returns the plain filename as a path.
 pub fn exe_relative_path(filename: &str) -> PathBuf {
@@ -59,6 +98,14 @@ pub fn exe_relative_path(filename: &str) -> PathBuf {
     }
 }
 
+/// Returns the window size and fullscreen state saved by the last session.
+pub fn saved_window_state() -> (Option<(u32, u32)>, bool) {
+    Config::load().ok()
+        .filter(|c| c.restore_session)
+        .map(|c| (c.session.window_size, c.session.fullscreen))
+        .unwrap_or_default()
+}
+
 type MidiConn = MidiInputConnection<Sender<Vec<u8>>>;
 
 /// Handles MIDI connection and state.
@@ -73,6 +120,18 @@ pub struct Midi {
     input_id: u16,
     rpn: (u8, u8),
     bend_range: f32,
+    /// Most recent modulation MSB on each channel, for 14-bit values.
+    mod_msb: [u8; 16],
+    /// Current value of each macro controller on each channel, for relative
+    /// CC modes.
+    macro_values: [[u8; 8]; 16],
+    output: Option<MidiOutput>,
+    output_name: Option<String>,
+    output_selection: Option<String>,
+    output_conn: Option<MidiOutputConnection>,
+    output_id: u16,
+    /// Tuning most recently sent to the output.
+    sent_tuning: Option<Tuning>,
 }
 
 impl Midi {
@@ -86,8 +145,17 @@ impl Midi {
             input_id: 0,
             rpn: (0, 0),
             bend_range: 2.0,
+            mod_msb: [0; 16],
+            macro_values: [[0; 8]; 16],
+            output: None,
+            output_name: None,
+            output_selection: None,
+            output_conn: None,
+            output_id: 0,
+            sent_tuning: None,
         };
         m.input = m.new_input().ok();
+        m.output = m.new_output().ok();
         m
     }
 
@@ -105,6 +173,31 @@ impl Midi {
             .find(|p| input.port_name(p).is_ok_and(|s| s == *selection))
             .ok_or("Selected MIDI device not found")
     }
+
+    /// Create a new MIDI output for the application.
+    fn new_output(&mut self) -> Result<MidiOutput, InitError> {
+        self.output_id += 1;
+        MidiOutput::new(&format!("{} output #{}", APP_NAME, self.output_id))
+    }
+
+    /// Returns the currently selected output port.
+    fn selected_output_port(&self) -> Result<MidiOutputPort, &'static str> {
+        let selection = self.output_selection.as_ref().ok_or("No MIDI output selected")?;
+        let output = self.output.as_ref().ok_or("Could not open MIDI")?;
+        output.ports().into_iter()
+            .find(|p| output.port_name(p).is_ok_and(|s| s == *selection))
+            .ok_or("Selected MIDI output not found")
+    }
+
+    /// Send `tuning` to the output as an MTS bulk tuning dump.
+    fn send_tuning(&mut self, tuning: &Tuning) -> Result<(), Box<dyn Error>> {
+        let conn = self.output_conn.as_mut().ok_or("No MIDI output selected")?;
+        // record the tuning even on failure, so that errors aren't repeated
+        // every frame
+        self.sent_tuning = Some(tuning.clone());
+        conn.send(&mts::bulk_dump(tuning, 0, APP_NAME))?;
+        Ok(())
+    }
 }
 
 const MAIN_TAB_ID: &str = "main";
@@ -120,6 +213,29 @@ const TABS: [&str; 4] = ["General", "Pattern", "Instruments", "Settings"];
 #[cfg(debug_assertions)]
 const TABS: [&str; 5] = ["General", "Pattern", "Instruments", "Settings", "Developer"];
 
+const SONG_TAB_ID: &str = "songs";
+
+/// Module open in a song tab, with its editor state.
+struct ModuleSession {
+    module: Module,
+    save_path: Option<PathBuf>,
+    pattern_editor: PatternEditor,
+    patch_index: Option<usize>,
+}
+
+impl ModuleSession {
+    /// Returns a session with a new module, used to fill the slot of the
+    /// current session.
+    fn placeholder() -> Self {
+        Self {
+            module: Module::new(Default::default()),
+            save_path: None,
+            pattern_editor: PatternEditor::default(),
+            patch_index: None,
+        }
+    }
+}
+
 /// Top-level store of application state.
 struct App {
     octave: i8,
@@ -135,6 +251,19 @@ struct App {
     save_path: Option<PathBuf>,
     render_channel: Option<Receiver<RenderUpdate>>,
     version: String,
+    last_script: Option<PathBuf>,
+    fullscreen: bool,
+    /// Module waiting on confirmation to be opened.
+    pending_open: Option<PathBuf>,
+    /// Keyjazz notes kept on by latch mode, with their tracks.
+    latched_keys: Vec<(usize, Key)>,
+    arp: Arpeggiator,
+    /// Open song tabs. The current session's module and editor state are
+    /// in use elsewhere, and its slot holds a placeholder.
+    sessions: Vec<ModuleSession>,
+    session_index: usize,
+    /// Open while status messages are enabled.
+    status_file: Option<File>,
 }
 
 impl App {
@@ -143,6 +272,11 @@ impl App {
     ) -> Self {
         let mut midi = Midi::new();
         midi.port_selection = config.default_midi_input.clone();
+        midi.output_selection = config.default_midi_output.clone();
+        dsp::set_param_smooth_time(config.param_smooth_time);
+        dsp::set_mod_smooth_time(config.mod_smooth_time);
+        dsp::set_pressure_smooth_time(config.pressure_smooth_time);
+        let fullscreen = config.restore_session && config.session.fullscreen;
         App {
             octave: 3,
             midi,
@@ -157,6 +291,14 @@ impl App {
             save_path: None,
             render_channel: None,
             version: format!("v{PKG_VERSION}"),
+            last_script: None,
+            fullscreen,
+            pending_open: None,
+            latched_keys: Vec::new(),
+            arp: Default::default(),
+            sessions: vec![ModuleSession::placeholder()],
+            session_index: 0,
+            status_file: None,
         }
     }
 
@@ -174,15 +316,198 @@ impl App {
         }
     }
 
+    /// Returns the track that input on a MIDI channel plays.
+    fn midi_track(&self, channel: u8, module: &Module) -> usize {
+        self.config.midi_channel_track(channel, module.tracks.len())
+            .unwrap_or_else(|| self.keyjazz_track())
+    }
+
+    /// Returns the track that a key on a MIDI channel plays.
+    fn midi_key_track(&self, channel: u8, key: u8, module: &Module) -> usize {
+        self.config.midi_key_track(channel, key, module.tracks.len())
+            .unwrap_or_else(|| self.keyjazz_track())
+    }
+
     /// Returns the current patch index to use for keyjazzing.
     fn keyjazz_patch_index(&self, module: &Module) -> Option<usize> {
-        match module.tracks[self.keyjazz_track()].target {
+        self.track_patch_index(self.keyjazz_track(), module)
+    }
+
+    /// Returns the patch index to use for live input on `track`.
+    fn track_patch_index(&self, track: usize, module: &Module) -> Option<usize> {
+        match module.tracks[track].target {
             TrackTarget::Global | TrackTarget::None => self.instruments_state.patch_index,
             TrackTarget::Kit => None,
             TrackTarget::Patch(i) => Some(i),
         }
     }
 
+    /// Perform a key command.
+    fn handle_action(&mut self, action: Action, module: &mut Module, player: &mut Player) {
+        match action {
+            Action::IncrementDivision => self.pattern_editor.inc_division(),
+            Action::DecrementDivision => self.pattern_editor.dec_division(),
+            Action::DoubleDivision => self.pattern_editor.double_division(),
+            Action::HalveDivision => self.pattern_editor.halve_division(),
+            Action::ZoomInRows => self.pattern_editor.zoom_rows(1),
+            Action::ZoomOutRows => self.pattern_editor.zoom_rows(-1),
+            Action::FocusDivision => self.ui.focus("Division"),
+            Action::IncrementOctave =>
+                self.octave = self.octave.saturating_add(1),
+            Action::DecrementOctave =>
+                self.octave = self.octave.saturating_sub(1),
+            Action::PlayFromStart =>
+                player.toggle_play_from(Timespan::ZERO, module),
+            Action::PlayFromScreen => {
+                let tick = self.pattern_editor.screen_beat_tick();
+                player.toggle_play_from(tick, module)
+            }
+            Action::PlayFromCursor =>
+                player.toggle_play_from(self.pattern_editor.cursor_tick(), module),
+            Action::StopPlayback => player.stop(),
+            Action::SetLoopRange => {
+                let (start, end) = self.pattern_editor.selection_ticks();
+                player.set_loop_range(start, end);
+            }
+            Action::ToggleLoopRange => if !player.toggle_loop() {
+                self.ui.report("No loop range set");
+            },
+            Action::NewSong => if module.has_unsaved_changes {
+                self.ui.confirm("Discard unsaved changes?", Action::NewSong);
+            } else {
+                self.new_module(module, player)
+            },
+            Action::OpenSong=> if module.has_unsaved_changes {
+                self.ui.confirm("Discard unsaved changes?", Action::OpenSong);
+            } else {
+                self.open_module(module, player)
+            },
+            Action::SaveSong => self.save_module(module, player),
+            Action::SaveSongAs => self.save_module_as(module, player),
+            Action::ImportModule => self.import_module(player),
+            Action::RenderSong => self.render_and_save(module, player, false),
+            Action::RenderTracks => self.render_and_save(module, player, true),
+            Action::RenderSelection => self.render_selection(module, player),
+            Action::RenderLoop => self.render_loop(module, player),
+            Action::QuickExport => self.quick_export(module),
+            Action::FreezeTrack => self.toggle_freeze(module, player),
+            Action::CopyPatternText => {
+                clipboard_set(&self.pattern_editor.selection_text(module));
+                self.ui.notify(String::from("Copied pattern text."));
+            }
+            Action::PastePatternText => match clipboard_get() {
+                Some(text) => self.paste_pattern_text(module, &text),
+                None => self.ui.report("Clipboard is empty"),
+            },
+            Action::ExportPatternText => self.export_pattern_text(module, player),
+            Action::ImportPatternText => self.import_pattern_text(module, player),
+            Action::ImportMidi => self.import_midi(module, player),
+            Action::RunScript => self.pick_script(module, player),
+            Action::RerunScript => match self.last_script.clone() {
+                Some(path) => self.run_script(module, path),
+                None => self.ui.report("No script has been run"),
+            },
+            Action::SendTuning => match self.midi.send_tuning(&module.tuning) {
+                Ok(()) => self.ui.notify(String::from("Sent tuning.")),
+                Err(e) => self.ui.report(format!("Error sending tuning: {e}")),
+            },
+            Action::ToggleFullscreen => {
+                self.fullscreen = !self.fullscreen;
+                set_fullscreen(self.fullscreen);
+            }
+            Action::MoveEnd => module.push_edit(
+                module.move_end(self.pattern_editor.cursor_tick())),
+            Action::TrimToEnd => match module.trim_to_end() {
+                Some(edit) => module.push_edit(edit),
+                None => self.ui.report("No events after End"),
+            },
+            Action::LoopSelection => {
+                let (start, end) = self.pattern_editor.selection_ticks();
+                if start < end {
+                    module.push_edit(module.set_loop_events(start, end));
+                } else {
+                    self.ui.report("Selection is empty");
+                }
+            },
+            Action::CheckControlEvents => {
+                let warnings = module.control_warnings();
+                if warnings.is_empty() {
+                    self.ui.notify(String::from("No problems found."));
+                } else {
+                    self.ui.report(warnings.join("\n"));
+                }
+            },
+            Action::PadSong => {
+                let beats = self.config.pad_bars as i32 * self.config.bar_beats as i32;
+                module.push_edit(module.pad_end(Timespan::new(beats, 1)));
+            },
+            Action::GenerateMelody => if let Err(e) =
+                self.pattern_editor.generate_melody(module, &self.config.melody) {
+                self.ui.report(e);
+            },
+            Action::ScaleValues => if let Err(e) =
+                self.pattern_editor.scale_values(module, &self.config.value_scaling) {
+                self.ui.report(e);
+            },
+            Action::CycleGlideCurve =>
+                match self.pattern_editor.cycle_glide_curve(module) {
+                    Ok(curve) =>
+                        self.ui.notify(format!("Glide curve: {}", curve.name())),
+                    Err(e) => self.ui.report(e),
+                },
+            Action::Undo => if module.undo() {
+                player.update_synths(module.drain_track_history(), module);
+                fix_patch_index(&mut self.instruments_state.patch_index,
+                    module.patches.len());
+            } else {
+                self.ui.report("Nothing to undo");
+            },
+            Action::Redo => if module.redo() {
+                player.update_synths(module.drain_track_history(), module);
+                fix_patch_index(&mut self.instruments_state.patch_index,
+                    module.patches.len());
+            } else {
+                self.ui.report("Nothing to redo");
+            },
+            Action::NextTab => self.ui.next_tab(MAIN_TAB_ID, TABS.len()),
+            Action::PrevTab => self.ui.prev_tab(MAIN_TAB_ID, TABS.len()),
+            Action::Panic => {
+                self.latched_keys.clear();
+                self.arp.stop();
+                player.panic();
+            },
+            Action::RecordMacro => match self.pattern_editor.toggle_macro_recording() {
+                Some(n) => self.ui.notify(format!("Recorded macro of {n} steps.")),
+                None => self.ui.notify(String::from("Recording macro.")),
+            },
+            Action::ToggleStepRecord => if self.pattern_editor.toggle_step_record() {
+                self.ui.notify(String::from("Step record on."));
+            } else {
+                self.ui.notify(String::from("Step record off."));
+            },
+            Action::PlayMacro => if let Err(e) = self.pattern_editor.play_macro(
+                module, &self.config, player) {
+                self.ui.report(e);
+            },
+            Action::CommandPalette => self.ui.open_palette(),
+            Action::ModulateParameter => self.ui.request_modulation(),
+            Action::NewTab => self.new_session(module, player),
+            Action::CloseTab => if self.sessions.len() < 2 {
+                self.ui.notify(String::from("No other songs are open."));
+            } else if module.has_unsaved_changes {
+                self.ui.confirm("Discard unsaved changes?", Action::CloseTab);
+            } else {
+                self.close_session(module, player);
+            },
+            Action::HoldNotes => player.keyboard_hold(true),
+            Action::ToggleLatch =>
+                self.config.keyjazz_latch = !self.config.keyjazz_latch,
+            _ => if self.ui.get_tab(MAIN_TAB_ID) == Some(TAB_PATTERN) {
+                self.pattern_editor.action(action, module, &self.config, player);
+            },
+        }
+    }
+
     /// Handle keyboard input.
     fn handle_keys(&mut self, module: &mut Module, player: &mut Player) {
         let (pressed, released) = (get_keys_pressed(), get_keys_released());
@@ -190,73 +515,33 @@ impl App {
 
         // translate released keys into note-offs
         for key in released {
+            // modifiers may have changed since the press, so ignore them
+            if self.config.action_uses_key(Action::HoldNotes, key) {
+                player.keyboard_hold(false);
+            }
+
             let hk = Hotkey::new(mods, key);
-            let note = input::note_from_key(hk, &module.tuning, self.octave, &self.config);
+            let note = input::note_from_key(hk, &module.tuning, &module.key_map,
+                self.octave, &self.config);
             if note.is_some() {
                 let key = Key::new_from_keyboard(input::u8_from_key(key));
                 self.ui.note_queue.push((key.clone(), EventData::NoteOff));
-                player.note_off(self.keyjazz_track(), key);
+                if self.config.arp.enabled {
+                    self.arp.release(&key);
+                } else if !self.config.keyjazz_latch {
+                    player.note_off(self.keyjazz_track(), key);
+                }
             }
         }
 
         // translate pressed keys into key commands
         for key in pressed {
             let hk = Hotkey::new(mods, key);
-            if let Some(action) = self.config.hotkey_action(&hk) {
-                match action {
-                    Action::IncrementDivision => self.pattern_editor.inc_division(),
-                    Action::DecrementDivision => self.pattern_editor.dec_division(),
-                    Action::DoubleDivision => self.pattern_editor.double_division(),
-                    Action::HalveDivision => self.pattern_editor.halve_division(),
-                    Action::FocusDivision => self.ui.focus("Division"),
-                    Action::IncrementOctave =>
-                        self.octave = self.octave.saturating_add(1),
-                    Action::DecrementOctave =>
-                        self.octave = self.octave.saturating_sub(1),
-                    Action::PlayFromStart =>
-                        player.toggle_play_from(Timespan::ZERO, module),
-                    Action::PlayFromScreen => {
-                        let tick = self.pattern_editor.screen_beat_tick();
-                        player.toggle_play_from(tick, module)
-                    }
-                    Action::PlayFromCursor =>
-                        player.toggle_play_from(self.pattern_editor.cursor_tick(), module),
-                    Action::StopPlayback => player.stop(),
-                    Action::NewSong => if module.has_unsaved_changes {
-                        self.ui.confirm("Discard unsaved changes?", Action::NewSong);
-                    } else {
-                        self.new_module(module, player)
-                    },
-                    Action::OpenSong=> if module.has_unsaved_changes {
-                        self.ui.confirm("Discard unsaved changes?", Action::OpenSong);
-                    } else {
-                        self.open_module(module, player)
-                    },
-                    Action::SaveSong => self.save_module(module, player),
-                    Action::SaveSongAs => self.save_module_as(module, player),
-                    Action::RenderSong => self.render_and_save(module, player, false),
-                    Action::RenderTracks => self.render_and_save(module, player, true),
-                    Action::Undo => if module.undo() {
-                        player.update_synths(module.drain_track_history());
-                        fix_patch_index(&mut self.instruments_state.patch_index,
-                            module.patches.len());
-                    } else {
-                        self.ui.report("Nothing to undo");
-                    },
-                    Action::Redo => if module.redo() {
-                        player.update_synths(module.drain_track_history());
-                        fix_patch_index(&mut self.instruments_state.patch_index,
-                            module.patches.len());
-                    } else {
-                        self.ui.report("Nothing to redo");
-                    },
-                    Action::NextTab => self.ui.next_tab(MAIN_TAB_ID, TABS.len()),
-                    Action::PrevTab => self.ui.prev_tab(MAIN_TAB_ID, TABS.len()),
-                    Action::Panic => player.panic(),
-                    _ => if self.ui.get_tab(MAIN_TAB_ID) == Some(TAB_PATTERN) {
-                        self.pattern_editor.action(*action, module, &self.config, player);
-                    },
-                }
+            if self.ui.consumes_key(&hk) {
+                continue
+            }
+            if let Some(action) = self.config.hotkey_action(&hk).copied() {
+                self.handle_action(action, module, player);
             } else if let Some(action) = self.config.hotkey_action(&hk.without_shift()) {
                 // these actions have some special behavior when used with shift
                 match action {
@@ -273,24 +558,95 @@ impl App {
             }
 
             // translate pressed keys into note-ons
-            let note = input::note_from_key(hk, &module.tuning, self.octave, &self.config);
+            let note = input::note_from_key(hk, &module.tuning, &module.key_map,
+                self.octave, &self.config);
             if let Some(note) = note {
                 let key = Key::new_from_keyboard(input::u8_from_key(key));
                 self.ui.note_queue.push((key.clone(), EventData::Pitch(note)));
+                // typed pitches are previewed by the pattern editor instead
+                let previewing = self.config.preview_notes && !player.is_playing()
+                    && self.pattern_editor.in_note_column(&self.ui);
                 if !(self.ui.accepting_note_input()
                     || self.pattern_editor.in_digit_column(&self.ui)
                     || self.pattern_editor.in_global_track(&self.ui)
+                    || previewing
                 ) {
-                    if let Some((patch, note)) =
-                        module.map_input(self.keyjazz_patch_index(module), note) {
-                        let pitch = module.tuning.midi_pitch(&note);
-                        player.note_on(self.keyjazz_track(), key, pitch, None, patch);
+                    let index = self.keyjazz_patch_index(module);
+                    if let Some((patch, mapped_note)) = module.map_input(index, note) {
+                        let track = self.keyjazz_track();
+                        let pitch = module.play_pitch(&module.tuning, &mapped_note,
+                            index.is_none());
+                        if self.config.arp.enabled {
+                            self.arp.press(ArpNote {
+                                track,
+                                key,
+                                note,
+                                patch_index: index,
+                                pitch,
+                                pressure: patch.keyjazz_pressure,
+                            });
+                            continue
+                        }
+                        if self.config.keyjazz_latch {
+                            // pressing a latched key again releases it
+                            if let Some(i) = self.latched_keys.iter()
+                                .position(|(t, k)| *t == track && *k == key) {
+                                self.latched_keys.remove(i);
+                                player.note_off(track, key);
+                                continue
+                            }
+                            // mono patches replace the latched note
+                            if patch.play_mode != PlayMode::Poly {
+                                self.latched_keys.retain(|(t, _)| *t != track);
+                            }
+                            self.latched_keys.push((track, key.clone()));
+                        }
+                        player.note_on(track, key, pitch, patch.keyjazz_pressure, patch);
                     }
                 }
             }
         }
     }
 
+    /// Advance the arpeggiator, playing its notes and capturing them in the
+    /// pattern when recording.
+    fn update_arpeggiator(&mut self, module: &mut Module, player: &mut Player) {
+        // follow the playhead when playing, so that steps land on rows
+        let beat = if player.is_playing() {
+            player.beat()
+        } else {
+            self.arp.beat() + player.interval_beats(get_frame_time() as f64)
+        };
+        let capture = self.config.arp.capture && player.recording_armed();
+        for event in self.arp.update(beat, &self.config.arp) {
+            match event {
+                ArpEvent::On(note) => {
+                    if let Some((patch, _)) = module.map_input(note.patch_index, note.note) {
+                        player.note_on(note.track, arp::key(), note.pitch, note.pressure, patch);
+                    }
+                    if capture {
+                        self.pattern_editor.record_generated(EventData::Pitch(note.note),
+                            note.track, player.get_tick(), module);
+                    }
+                },
+                ArpEvent::Off(note) => {
+                    player.note_off(note.track, arp::key());
+                    if capture {
+                        self.pattern_editor.record_generated(EventData::NoteOff,
+                            note.track, player.get_tick(), module);
+                    }
+                },
+            }
+        }
+    }
+
+    /// Release all notes held by keyjazz latch mode.
+    fn release_latched_keys(&mut self, player: &mut Player) {
+        for (track, key) in self.latched_keys.drain(..) {
+            player.note_off(track, key);
+        }
+    }
+
     /// Attempt to connect to the selected MIDI port.
     fn midi_connect(&mut self) -> Result<MidiConn, Box<dyn Error>> {
         let port = self.midi.selected_port()?;
@@ -314,7 +670,7 @@ impl App {
     }
 
     /// Handle incoming MIDI messages.
-    fn handle_midi(&mut self, module: &Module, player: &mut Player) {
+    fn handle_midi(&mut self, module: &mut Module, player: &mut Player) {
         for evt in self.get_midi_events() {
             self.handle_midi_event(evt, module, player);
         }
@@ -336,14 +692,20 @@ impl App {
     }
 
     /// Handle an incoming MIDI message.
-    fn handle_midi_event(&mut self, evt: MidiEvent, module: &Module, player: &mut Player) {
+    fn handle_midi_event(&mut self, evt: MidiEvent, module: &mut Module,
+        player: &mut Player
+    ) {
+        let track = self.midi_track(evt.channel(), module);
         match evt {
             MidiEvent::NoteOff { channel, key, .. } => {
+                let track = self.midi_key_track(channel, key, module);
                 let key = Key::new_from_midi(channel, key);
-                player.note_off(self.keyjazz_track(), key.clone());
+                self.arp.release(&key);
+                player.note_off(track, key.clone());
                 self.ui.note_queue.push((key, EventData::NoteOff));
             },
             MidiEvent::NoteOn { channel, key, velocity } => {
+                let track = self.midi_key_track(channel, key, module);
                 let key = Key::new_from_midi(channel, key);
                 if velocity != 0 {
                     let note = input::note_from_midi(key.key, &module.tuning, &self.config);
@@ -353,29 +715,41 @@ impl App {
                         self.ui.note_queue.push((key.clone(), EventData::Pressure(v)));
                     }
 
-                    let index = self.keyjazz_patch_index(module);
+                    let index = self.track_patch_index(track, module);
                     if let Some((patch, mapped_note)) = module.map_input(index, note) {
                         if !self.ui.accepting_note_input() {
-                            let pitch = module.tuning.midi_pitch(&mapped_note);
+                            let pitch = module.play_pitch(&module.tuning, &mapped_note,
+                                index.is_none());
                             let pressure = if self.config.midi_send_velocity {
                                 Some(velocity as f32 / 127.0)
                             } else {
                                 None
                             };
-                            player.note_on(self.keyjazz_track(),
-                                key.clone(), pitch, pressure, patch);
+                            if self.config.arp.enabled {
+                                self.arp.press(ArpNote {
+                                    track,
+                                    key: key.clone(),
+                                    note,
+                                    patch_index: index,
+                                    pitch,
+                                    pressure,
+                                });
+                            } else {
+                                player.note_on(track, key.clone(), pitch, pressure, patch);
+                            }
                         }
                     }
                 } else {
-                    player.note_off(self.keyjazz_track(), key.clone());
+                    self.arp.release(&key);
+                    player.note_off(track, key.clone());
                     self.ui.note_queue.push((key, EventData::NoteOff));
                 }
             },
             MidiEvent::PolyPressure { channel, key, pressure } => {
                 if self.config.midi_send_pressure == Some(true) {
+                    let track = self.midi_key_track(channel, key, module);
                     let key = Key::new_from_midi(channel, key);
-                    player.poly_pressure(self.keyjazz_track(), key.clone(),
-                        pressure as f32 / 127.0);
+                    player.poly_pressure(track, key.clone(), pressure as f32 / 127.0);
                     let v = EventData::digit_from_midi(pressure);
                     self.ui.note_queue.push((key, EventData::Pressure(v)));
                 }
@@ -383,9 +757,28 @@ impl App {
             MidiEvent::Controller { channel, controller, value } => {
                 let norm_value = value as f32 / 127.0;
                 match controller {
-                    input::CC_MODULATION | input::CC_MACRO_MIN..=input::CC_MACRO_MAX => {
-                        player.modulate(self.keyjazz_track(), channel, norm_value);
+                    input::CC_MODULATION => {
+                        self.midi.mod_msb[channel as usize] = value;
+                        player.modulate(track, channel, norm_value);
+                        self.queue_midi_modulation(channel, value);
+                    },
+                    input::CC_MODULATION_LSB => {
+                        let msb = self.midi.mod_msb[channel as usize] as u16;
+                        let value = (msb << 7 | value as u16) as f32 / 16383.0;
+                        player.modulate(track, channel, value);
                     },
+                    input::CC_MACRO_MIN..=input::CC_MACRO_MAX => {
+                        let i = (controller - input::CC_MACRO_MIN) as usize;
+                        let v = &mut self.midi.macro_values[channel as usize][i];
+                        *v = self.config.midi_macro_cc_modes[i].apply(*v, value);
+                        let v = *v;
+                        player.modulate(track, channel, v as f32 / 127.0);
+                        self.queue_midi_modulation(channel, v);
+                    },
+                    input::CC_SUSTAIN =>
+                        player.sustain(track, channel, value >= input::PEDAL_THRESHOLD),
+                    input::CC_SOSTENUTO =>
+                        player.sostenuto(track, channel, value >= input::PEDAL_THRESHOLD),
                     input::CC_RPN_MSB => self.midi.rpn.0 = value,
                     input::CC_RPN_LSB => self.midi.rpn.1 = value,
                     input::CC_DATA_ENTRY_MSB =>
@@ -403,10 +796,11 @@ impl App {
                     _ => (),
                 }
             },
+            MidiEvent::ProgramChange { program, .. } =>
+                self.program_change(program as usize, track, module),
             MidiEvent::ChannelPressure { channel, pressure } => {
                 if self.config.midi_send_pressure == Some(true) {
-                    player.channel_pressure(self.keyjazz_track(),
-                        channel, pressure as f32 / 127.0);
+                    player.channel_aftertouch(track, channel, pressure as f32 / 127.0);
                     let key = Key::new_from_midi(channel, 0);
                     let v = EventData::digit_from_midi(pressure);
                     self.ui.note_queue.push((key, EventData::Pressure(v)));
@@ -414,7 +808,7 @@ impl App {
             },
             MidiEvent::Pitch { channel, bend } => {
                 let semitones = bend * self.midi.bend_range;
-                player.pitch_bend(self.keyjazz_track(), channel, semitones);
+                player.pitch_bend(track, channel, semitones);
                 let key = Key::new_from_midi(channel, 0);
                 let data = EventData::Bend((semitones * 100.0).round() as i16);
                 self.ui.note_queue.push((key, data));
@@ -422,6 +816,37 @@ impl App {
         }
     }
 
+    /// Queue a modulation controller value as pattern input, for recording.
+    fn queue_midi_modulation(&mut self, channel: u8, value: u8) {
+        let key = Key::new_from_midi(channel, 0);
+        let data = EventData::Modulation(EventData::digit_from_midi(value));
+        self.ui.note_queue.push((key, data));
+    }
+
+    /// Select a patch from a MIDI program change. Program 0 selects the kit,
+    /// or no patch if remapping a track.
+    fn program_change(&mut self, program: usize, track: usize, module: &mut Module) {
+        let patch = program.checked_sub(1);
+        if patch.is_some_and(|i| i >= module.patches.len()) {
+            return
+        }
+
+        let current = match module.tracks[track].target {
+            TrackTarget::Patch(i) => Some(Some(i)),
+            TrackTarget::None => Some(None),
+            TrackTarget::Global | TrackTarget::Kit => None,
+        };
+        match current {
+            Some(current) if self.config.midi_program_remaps_track => {
+                if current != patch {
+                    let target = patch.map(TrackTarget::Patch).unwrap_or(TrackTarget::None);
+                    module.push_edit(Edit::RemapTrack(track, target));
+                }
+            }
+            _ => self.instruments_state.patch_index = patch,
+        }
+    }
+
     /// Reconnect if MIDI connection settings have changed.
     fn check_midi_reconnect(&mut self) {
         if self.midi.port_selection.is_some()
@@ -449,6 +874,40 @@ impl App {
         }
     }
 
+    /// Reconnect if MIDI output settings have changed.
+    fn check_midi_output_reconnect(&mut self) {
+        if self.midi.output_selection.is_some()
+            && self.midi.output_selection != self.midi.output_name {
+            let conn = self.midi.selected_output_port()
+                .map_err(|e| e.to_string())
+                .and_then(|port| {
+                    let output = self.midi.new_output().map_err(|e| e.to_string())?;
+                    output.connect(&port, APP_NAME).map_err(|e| e.to_string())
+                });
+            match conn {
+                Ok(conn) => {
+                    if let Some(c) = self.midi.output_conn.replace(conn) {
+                        c.close();
+                    }
+                    self.midi.output_name = self.midi.output_selection.clone();
+                    self.midi.sent_tuning = None;
+                    self.config.default_midi_output = self.midi.output_name.clone();
+                },
+                Err(e) => {
+                    self.midi.output_selection = None;
+                    self.config.default_midi_output = None;
+                    self.ui.report(format!("MIDI output connection failed: {e}"));
+                },
+            }
+        } else if self.midi.output_selection.is_none() && self.midi.output_name.is_some() {
+            if let Some(c) = self.midi.output_conn.take() {
+                c.close();
+            }
+            self.midi.output_name = None;
+            self.config.default_midi_output = None;
+        }
+    }
+
     /// Do 1 frame. Returns false if it's quitting time.
     fn frame(&mut self, module: &Arc<Mutex<Module>>, player: &Arc<Mutex<Player>>) -> bool {
         if self.dev_state.only_draw_on_input && !mouse_kb_input() {
@@ -461,27 +920,39 @@ impl App {
             let mut player = player.lock().unwrap();
 
             if is_quit_requested() {
-                if module.has_unsaved_changes {
+                if self.any_unsaved_changes(&module) {
                     self.ui.confirm("Discard unsaved changes?", Action::Quit);
                 } else {
+                    self.save_session();
                     self.save_config();
                     return false
                 }
             }
 
             if self.ui.accepting_keyboard_input() {
+                self.latched_keys.clear();
+                self.arp.release_origin(KeyOrigin::Keyboard);
                 player.clear_notes_with_origin(KeyOrigin::Keyboard);
             } else {
                 self.handle_keys(&mut module, &mut player);
             }
 
+            // latch may have been turned off from the settings tab
+            if !self.config.keyjazz_latch && !self.latched_keys.is_empty() {
+                self.release_latched_keys(&mut player);
+            }
+
             if self.ui.accepting_note_input() {
+                self.arp.release_origin(KeyOrigin::Midi);
                 player.clear_notes_with_origin(KeyOrigin::Midi);
             }
 
-            // ctrl+scroll. this is here instead of in pattern code because
-            // division can always be changed
-            if is_ctrl_down() && mouse_wheel().1 != 0.0 {
+            // ctrl+shift+scroll zooms rows. ctrl+scroll changes division,
+            // which is here instead of in pattern code because division can
+            // always be changed
+            if is_ctrl_down() && is_shift_down() && mouse_wheel().1 != 0.0 {
+                self.pattern_editor.zoom_rows(mouse_wheel().1.signum() as i32);
+            } else if is_ctrl_down() && mouse_wheel().1 != 0.0 {
                 let pe = &mut self.pattern_editor;
                 let d = mouse_wheel().1.signum() as i8;
                 pe.set_division(if !is_alt_down() {
@@ -501,14 +972,121 @@ impl App {
                 }
             }
 
-            self.handle_midi(&module, &mut player);
+            self.handle_midi(&mut module, &mut player);
+            self.update_arpeggiator(&mut module, &mut player);
+            if self.config.midi_send_tuning && self.midi.output_conn.is_some()
+                && self.midi.sent_tuning.as_ref() != Some(&module.tuning) {
+                if let Err(e) = self.midi.send_tuning(&module.tuning) {
+                    self.ui.report(format!("Error sending tuning: {e}"));
+                }
+            }
+            self.handle_render_updates(&mut player);
+            if let Some(s) = self.general_state.batch.update(self.config.render_bit_depth) {
+                self.ui.notify(s);
+            }
+            self.handle_dropped_files(&mut module, &mut player);
+
+            // unfreeze tracks that were edited
+            player.update_synths(module.drain_track_history(), &module);
+            player.fill_voice_pools(&module);
         }
 
-        self.handle_render_updates();
         self.check_midi_reconnect();
+        self.check_midi_output_reconnect();
         self.process_ui(module, player)
     }
 
+    /// Record window and editor state in the config for the next startup.
+    fn save_session(&mut self) {
+        let session = &mut self.config.session;
+        session.module_path = self.save_path.as_ref()
+            .and_then(|p| p.to_str())
+            .map(String::from);
+        session.fullscreen = self.fullscreen;
+        if !self.fullscreen {
+            session.window_size = Some((screen_width() as u32, screen_height() as u32));
+        }
+        session.tab = self.ui.get_tab(MAIN_TAB_ID).unwrap_or_default();
+        session.pattern = self.pattern_editor.view();
+        session.tab_scroll = [
+            self.general_state.scroll,
+            self.instruments_state.scroll,
+            self.settings_state.scroll,
+        ];
+    }
+
+    /// Restore editor state from the last session, reopening its module if
+    /// no other module was loaded.
+    fn restore_session(&mut self, module: &mut Module, player: &mut Player) {
+        let session = self.config.session.clone();
+        self.pattern_editor.set_row_zoom(session.pattern.row_zoom);
+        if session.tab < TABS.len() {
+            self.ui.set_tab(MAIN_TAB_ID, session.tab);
+        }
+        [
+            self.general_state.scroll,
+            self.instruments_state.scroll,
+            self.settings_state.scroll,
+        ] = session.tab_scroll;
+
+        if self.save_path.is_none() {
+            if let Some(path) = session.module_path {
+                self.open_path(module, player, path.into());
+                if self.save_path.is_some() {
+                    self.pattern_editor.restore_view(&session.pattern, module);
+                }
+            }
+        }
+    }
+
+    /// Open modules, import MIDI files, and load samples dropped onto the window.
+    fn handle_dropped_files(&mut self, module: &mut Module, player: &mut Player) {
+        let paths: Vec<PathBuf> = get_dropped_files().into_iter()
+            .filter_map(|f| f.path)
+            .collect();
+        if paths.is_empty() {
+            return
+        }
+
+        let module_path = paths.iter().find(|p| p.extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case(MODULE_EXT)));
+        if let Some(path) = module_path {
+            self.confirm_open_path(module, player, path.clone());
+            return
+        }
+
+        let midi_paths: Vec<_> = paths.iter()
+            .filter(|p| p.extension().and_then(|ext| ext.to_str())
+                .is_some_and(|ext| MIDI_EXTS.iter().any(|x| ext.eq_ignore_ascii_case(x))))
+            .collect();
+        if !midi_paths.is_empty() {
+            for path in midi_paths {
+                self.load_midi_file(module, path);
+            }
+            return
+        }
+
+        let patch_paths: Vec<_> = paths.iter()
+            .filter(|p| ui::instruments::is_patch_file(p))
+            .collect();
+        if patch_paths.is_empty() {
+            self.ui.report("Unsupported file type");
+        } else if self.ui.get_tab(MAIN_TAB_ID) != Some(TAB_INSTRUMENTS) {
+            self.ui.report("Drop instruments and samples on the instruments tab");
+        } else {
+            for path in patch_paths {
+                match ui::instruments::load_patch_file(path) {
+                    Ok(patch) => {
+                        let index = module.patches.len();
+                        module.push_edit(Edit::InsertPatch(index, patch));
+                        self.instruments_state.patch_index = Some(index);
+                    }
+                    Err(e) => self.ui.report(format!("Error loading patch: {e}")),
+                }
+            }
+        }
+    }
+
     /// Save config to disk, logging errors.
     fn save_config(&mut self) {
         if let Err(e) = self.config.save(self.ui.style.theme.clone()) {
@@ -517,24 +1095,24 @@ impl App {
     }
 
     /// Handle incoming render status updates.
-    fn handle_render_updates(&mut self) {
+    fn handle_render_updates(&mut self, player: &mut Player) {
         if let Some(rx) = &self.render_channel {
             while let Ok(update) = rx.try_recv() {
                 match update {
                     RenderUpdate::Progress(f) =>
-                        self.ui.notify(format!("Rendering: {}%", (f * 100.0).round())),
-                    RenderUpdate::Done(wav, path) => {
-                        let write_result = if self.config.render_bit_depth == Some(32) {
-                            wav.save_wav32(path)
-                        } else {
-                            wav.save_wav16(path)
-                        };
+                        self.ui.status(format!("Rendering: {}%", (f * 100.0).round())),
+                    RenderUpdate::Done(wav, path, meta) => {
+                        let write_result = wavmeta::save(&wav, &path,
+                            self.config.render_bit_depth, &meta);
 
                         match write_result {
                             Ok(_) => self.ui.notify(String::from("Wrote WAV.")),
                             Err(e) => self.ui.report(format!("Writing WAV failed: {e}")),
                         }
                     }
+                    RenderUpdate::Frozen(id, data) => if player.finish_freeze(id, data) {
+                        self.ui.notify(String::from("Froze track."));
+                    }
                 }
             }
         }
@@ -552,7 +1130,15 @@ impl App {
                 match action {
                     Action::NewSong => self.new_module(&mut module, &mut player),
                     Action::OpenSong => self.open_module(&mut module, &mut player),
+                    Action::OpenRecent => if let Some(path) = self.pending_open.take() {
+                        self.open_path(&mut module, &mut player, path);
+                    },
+                    Action::RecoverSong => if let Some(path) = self.pending_open.take() {
+                        self.recover_path(&mut module, &mut player, path);
+                    },
+                    Action::CloseTab => self.close_session(&mut module, &mut player),
                     Action::Quit => {
+                        self.save_session();
                         self.save_config();
                         return false
                     }
@@ -560,11 +1146,25 @@ impl App {
                 }
             }
 
-            self.bottom_panel(&mut player);
+            self.command_palette(&mut module, &mut player);
+            self.bottom_panel(&module, &mut player);
+
+            if self.sessions.len() > 1 {
+                let labels = self.session_labels(&module);
+                let labels: Vec<_> = labels.iter().map(|s| s.as_str()).collect();
+                let i = self.ui.tab_menu(SONG_TAB_ID, &labels, "");
+                self.switch_session(&mut module, &mut player, i);
+            }
 
             match self.ui.tab_menu(MAIN_TAB_ID, &TABS, &self.version) {
-                TAB_GENERAL => ui::general::draw(&mut self.ui, &mut module,
-                    &mut self.fx, &mut self.config, &mut player, &mut self.general_state),
+                TAB_GENERAL => {
+                    ui::general::draw(&mut self.ui, &mut module, &mut self.fx,
+                        &mut self.config, &mut player, &mut self.general_state,
+                        self.save_path.as_ref(), &self.pattern_editor);
+                    if let Some(path) = self.general_state.recent_selection.take() {
+                        self.confirm_open_path(&mut module, &mut player, path);
+                    }
+                }
                 TAB_PATTERN => ui::pattern::draw(&mut self.ui, &mut module,
                     &mut player, &mut self.pattern_editor, &self.config),
                 TAB_INSTRUMENTS => ui::instruments::draw(&mut self.ui, &mut module,
@@ -579,11 +1179,68 @@ impl App {
 
         let tab_nav = self.ui.get_tab(MAIN_TAB_ID).is_none_or(|i| i != TAB_PATTERN);
         self.ui.end_frame(tab_nav);
+        let announcements = self.ui.take_announcements();
+        if self.config.announce_status {
+            self.write_status(&announcements);
+        } else {
+            self.status_file = None;
+        }
         true
     }
 
+    /// Append status messages to the status file, creating it if it isn't
+    /// open. Messages are disabled if the file can't be written.
+    fn write_status(&mut self, messages: &[String]) {
+        if messages.is_empty() {
+            return
+        }
+        if self.status_file.is_none() {
+            match File::create(exe_relative_path(STATUS_FILENAME)) {
+                Ok(f) => self.status_file = Some(f),
+                Err(e) => {
+                    self.config.announce_status = false;
+                    self.ui.report(format!("Error creating status file: {e}"));
+                    return
+                }
+            }
+        }
+        if let Some(f) = &mut self.status_file {
+            if let Err(e) = messages.iter().try_for_each(|m| writeln!(f, "{m}")) {
+                self.status_file = None;
+                self.config.announce_status = false;
+                self.ui.report(format!("Error writing status file: {e}"));
+            }
+        }
+    }
+
+    /// Draw the command palette, if open, and run the chosen command.
+    fn command_palette(&mut self, module: &mut Module, player: &mut Player) {
+        let mut items = Vec::new();
+        let choice = self.ui.palette(|query| {
+            items = palette::items(query, module, &self.config,
+                self.pattern_editor.beat_division);
+            items.iter().map(|x| x.label.clone()).collect()
+        });
+        let Some(item) = choice.and_then(|i| items.into_iter().nth(i)) else {
+            return
+        };
+
+        match item.command {
+            PaletteCommand::Action(action) => self.handle_action(action, module, player),
+            PaletteCommand::GoTo(tick) => {
+                self.ui.set_tab(MAIN_TAB_ID, TAB_PATTERN);
+                self.pattern_editor.go_to(tick);
+            }
+            PaletteCommand::Track(i) => {
+                self.ui.set_tab(MAIN_TAB_ID, TAB_PATTERN);
+                self.pattern_editor.go_to_track(i);
+            }
+            PaletteCommand::Open(path) => self.confirm_open_path(module, player, path),
+        }
+    }
+
     /// Draw the status panel at the bottom of the screen.
-    fn bottom_panel(&mut self, player: &mut Player) {
+    fn bottom_panel(&mut self, module: &Module, player: &mut Player) {
         self.ui.start_bottom_panel();
 
         if let Some(n) = self.ui.edit_box("Division", 3,
@@ -607,26 +1264,79 @@ impl App {
         self.ui.shared_slider("stereo_width", "Stereo width",
             &player.stereo_width, -1.0..=1.0, None, 1, true, Info::StereoWidth);
 
+        self.ui.formatted_slider("playback_speed", "Speed", &mut player.speed,
+            MIN_PLAYBACK_SPEED..=MAX_PLAYBACK_SPEED, 1, true, Info::PlaybackSpeed,
+            |f| format!("{:.0}%", f * 100.0), |f| f / 100.0);
+
+        self.ui.slider("output_gain", "Volume", &mut self.config.output_gain,
+            MIN_OUTPUT_GAIN..=MAX_OUTPUT_GAIN, Some("dB"), 1, true, Info::OutputGain);
+        player.output_gain = db_amp(self.config.output_gain);
+        player.mute_audition = self.config.mute_audition_tracks;
+        self.ui.checkbox("Mute", &mut player.output_muted, true, Info::OutputMute);
+        self.ui.meter((amp_db(player.output_peak) - METER_FLOOR) / -METER_FLOOR,
+            Info::OutputMeter);
+
+        if self.ui.get_tab(MAIN_TAB_ID) == Some(TAB_PATTERN) {
+            self.position_controls(module, player);
+            let readout = self.pattern_editor.cursor_note(module).and_then(|note|
+                module.tuning.pitch_readout(&note, self.config.pitch_display)
+                    .map(|s| format!("{note}: {s}")));
+            if let Some(s) = readout {
+                self.ui.offset_label(&s, Info::PitchReadout);
+            }
+        }
+
+        if self.config.show_profiler {
+            self.ui.offset_label(&format!("DSP load: {}% (peak {}%)",
+                (player.dsp_load * 100.0).round(), (player.peak_dsp_load * 100.0).round()),
+                Info::DspLoad);
+        }
+
         self.ui.end_bottom_panel();
     }
 
+    /// Draw the cursor position in musical and clock time, which can be
+    /// edited to move the cursor, and the playhead position if playing.
+    fn position_controls(&mut self, module: &Module, player: &Player) {
+        let (bar_beats, division) = (self.config.bar_beats, self.pattern_editor.beat_division);
+        let cursor = self.pattern_editor.cursor_tick();
+
+        if let Some(s) = self.ui.edit_box("Position", 9,
+            songpos::format_bbt(cursor, bar_beats, division), Info::SongPosition
+        ) {
+            match songpos::parse_bbt(&s, bar_beats, division) {
+                Some(tick) => self.pattern_editor.go_to(tick),
+                None => self.ui.report(format!("Invalid position: {s}")),
+            }
+        }
+
+        if let Some(s) = self.ui.edit_box("Time", 9,
+            songpos::format_clock(module.time_at(cursor)), Info::SongPosition
+        ) {
+            match songpos::parse_clock(&s) {
+                Some(secs) => self.pattern_editor.go_to(
+                    Timespan::approximate(module.beat_at_time(secs))),
+                None => self.ui.report(format!("Invalid time: {s}")),
+            }
+        }
+
+        if player.is_playing() {
+            let tick = player.get_tick();
+            self.ui.offset_label(&format!("Playhead: {} / {}",
+                songpos::format_bbt(tick, bar_beats, division),
+                songpos::format_clock(module.time_at(tick))), Info::SongPosition);
+        }
+    }
+
     /// Browse for and start rendering a WAV file.
     fn render_and_save(&mut self, module: &Module, player: &mut Player, tracks: bool) {
         if module.ends() {
-            let dialog = ui::new_file_dialog(player)
-                .add_filter("WAV file", &["wav"])
-                .set_directory(self.config.render_folder.clone()
-                    .unwrap_or(String::from(".")))
-                .set_file_name(module.title.clone());
-
-            if let Some(mut path) = dialog.save_file() {
-                path.set_extension("wav");
-                self.config.render_folder = config::dir_as_string(&path);
+            if let Some(path) = self.render_dialog(module, player) {
                 let module = Arc::new(module.clone());
                 self.render_channel = Some(if tracks {
                     playback::render_tracks(module, path)
                 } else {
-                    playback::render(module, path, None)
+                    playback::render(module, path, None, RenderBounds::SONG)
                 });
             }
         } else {
@@ -634,15 +1344,111 @@ impl App {
         }
     }
 
-    /// Handle the "new song" key command.
+    /// Handle the "render selection" key command. Renders the pattern
+    /// selection if it spans multiple rows, otherwise the song loop.
+    fn render_selection(&mut self, module: &Module, player: &mut Player) {
+        let (start, end) = self.pattern_editor.selection_ticks();
+        let range = if start != end {
+            Some((start, end))
+        } else {
+            module.loop_range()
+        };
+
+        if let Some((start, end)) = range {
+            if let Some(path) = self.render_dialog(module, player) {
+                let module = Arc::new(module.clone());
+                self.render_channel = Some(playback::render(module, path, None,
+                    RenderBounds::range(start, end)));
+            }
+        } else {
+            self.ui.report("Select a timespan or mark a loop to render a range")
+        }
+    }
+
+    /// Handle the "render seamless loop" key command.
+    fn render_loop(&mut self, module: &Module, player: &mut Player) {
+        if let Some((start, end)) = module.loop_range() {
+            if let Some(path) = self.render_dialog(module, player) {
+                let module = Arc::new(module.clone());
+                self.render_channel = Some(playback::render(module, path, None,
+                    RenderBounds::seamless_loop(&module, start, end)));
+            }
+        } else {
+            self.ui.report("Module must have Loop and End events to export a loop")
+        }
+    }
+
+    /// Handle the "toggle track freeze" key command.
+    fn toggle_freeze(&mut self, module: &Module, player: &mut Player) {
+        let track = self.pattern_editor.cursor_track();
+        if track == 0 {
+            self.ui.report("Global track cannot be frozen");
+        } else if player.track_frozen(track) {
+            player.unfreeze(module, track);
+            self.ui.notify(String::from("Unfroze track."));
+        } else {
+            self.render_channel = Some(player.freeze(Arc::new(module.clone()), track));
+        }
+    }
+
+    /// Browse for a WAV file path to render to.
+    /// Handle the "quick export" key command. Renders the song next to the
+    /// module file, named using the configured template.
+    fn quick_export(&mut self, module: &Module) {
+        let Some(save_path) = &self.save_path else {
+            self.ui.report("Module must be saved before quick export");
+            return
+        };
+        if !module.ends() {
+            self.ui.report("Module must have End event to export");
+            return
+        }
+
+        let file = save_path.file_stem().unwrap_or_default().to_string_lossy();
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let name = exportname::expand(&self.config.quick_export_name,
+            &module.title, &file, secs);
+        let path = save_path.with_file_name(format!("{name}.wav"));
+        self.render_channel = Some(playback::render(Arc::new(module.clone()), path, None,
+            RenderBounds::SONG));
+    }
+
+    fn render_dialog(&mut self, module: &Module, player: &mut Player) -> Option<PathBuf> {
+        let dialog = ui::new_file_dialog(player)
+            .add_filter("WAV file", &["wav"])
+            .set_directory(self.config.render_folder.clone()
+                .unwrap_or(String::from(".")))
+            .set_file_name(module.title.clone());
+
+        dialog.save_file().map(|mut path| {
+            path.set_extension("wav");
+            self.config.render_folder = config::dir_as_string(&path);
+            path
+        })
+    }
+
+    /// Handle the "new song" key command. Uses the module template if one
+    /// has been saved.
     fn new_module(&mut self, module: &mut Module, player: &mut Player) {
-        self.load_module(module, Module::new(Default::default()), player);
+        let path = config::module_template_path();
+        let new_mod = if path.exists() {
+            Module::load(&path).unwrap_or_else(|e| {
+                self.ui.report(format!("Error loading module template: {e}"));
+                Module::new(Default::default())
+            })
+        } else {
+            Module::new(Default::default())
+        };
+        self.load_module(module, new_mod, player);
         self.save_path = None;
     }
 
     /// Handle the "save song" key command.
     fn save_module(&mut self, module: &mut Module, player: &mut Player) {
         if let Some(path) = &self.save_path {
+            module.editor = Some(self.editor_state());
             if let Err(e) = module.save(self.pattern_editor.beat_division, path) {
                 self.ui.report(format!("Error saving module: {e}"));
             } else {
@@ -660,29 +1466,202 @@ impl App {
         if let Some(mut path) = dialog.save_file() {
             path.set_extension(MODULE_EXT);
             self.config.module_folder = config::dir_as_string(&path);
+            module.editor = Some(self.editor_state());
             if let Err(e) = module.save(self.pattern_editor.beat_division, &path) {
                 self.ui.report(format!("Error saving module: {e}"));
             } else {
+                self.config.add_recent_module(&path);
                 self.save_path = Some(path);
                 self.ui.notify(String::from("Saved module."));
             }
         }
     }
 
+    /// Returns the editor state to save with the module.
+    fn editor_state(&self) -> EditorState {
+        EditorState {
+            pattern: self.pattern_editor.view(),
+            patch_index: self.instruments_state.patch_index,
+        }
+    }
+
     /// Handle the "open song" key command.
     fn open_module(&mut self, module: &mut Module, player: &mut Player) {
+        if let Some(path) = self.module_dialog(player).pick_file() {
+            self.config.module_folder = config::dir_as_string(&path);
+            self.open_path(module, player, path);
+        }
+    }
+
+    /// Open a module at `path`, confirming if there are unsaved changes.
+    fn confirm_open_path(&mut self, module: &mut Module, player: &mut Player, path: PathBuf) {
+        if module.has_unsaved_changes {
+            self.pending_open = Some(path);
+            self.ui.confirm("Discard unsaved changes?", Action::OpenRecent);
+        } else {
+            self.open_path(module, player, path);
+        }
+    }
+
+    /// Load the module at `path` and add it to the recent modules list.
+    fn open_path(&mut self, module: &mut Module, player: &mut Player, path: PathBuf) {
+        match Module::load(&path) {
+            Ok(new_module) => {
+                self.report_unknown_fields(&new_module);
+                self.load_module(module, new_module, player);
+                self.config.add_recent_module(&path);
+                self.save_path = Some(path);
+            },
+            Err(e) => {
+                self.config.recent_modules.retain(|p| path.to_str() != Some(p.as_str()));
+                if path.exists() {
+                    self.ui.confirm(&format!("Error loading module: {e}. Attempt recovery?"),
+                        Action::RecoverSong);
+                    self.pending_open = Some(path);
+                } else {
+                    self.ui.report(format!("Error loading module: {e}"));
+                }
+            }
+        }
+    }
+
+    /// Load what can be read from a damaged module at `path`. The module
+    /// isn't associated with the path, so the damaged file isn't overwritten
+    /// by accident.
+    fn recover_path(&mut self, module: &mut Module, player: &mut Player, path: PathBuf) {
+        match Module::recover(&path) {
+            Ok((new_module, dropped)) => {
+                self.report_unknown_fields(&new_module);
+                self.load_module(module, new_module, player);
+                self.save_path = None;
+                if dropped.is_empty() {
+                    self.ui.notify(String::from("Recovered module."));
+                } else {
+                    self.ui.report(format!("Recovered module without: {}", dropped.join(", ")));
+                }
+            }
+            Err(e) => self.ui.report(format!("Error recovering module: {e}")),
+        }
+    }
+
+    /// Report fields of a loaded module that this version can't read.
+    fn report_unknown_fields(&mut self, module: &Module) {
+        if module.unknown_fields > 0 {
+            self.ui.report(format!("Module has {} fields from a newer version of \
+                {APP_NAME}. They will be lost if the module is saved.",
+                module.unknown_fields));
+        }
+    }
+
+    /// Handle the "import from module" key command.
+    fn import_module(&mut self, player: &mut Player) {
         if let Some(path) = self.module_dialog(player).pick_file() {
             self.config.module_folder = config::dir_as_string(&path);
             match Module::load(&path) {
-                Ok(new_module) => {
-                    self.load_module(module, new_module, player);
-                    self.save_path = Some(path);
-                },
+                Ok(other) => {
+                    self.general_state.start_import(other);
+                    self.ui.set_tab(MAIN_TAB_ID, TAB_GENERAL);
+                }
                 Err(e) => self.ui.report(format!("Error loading module: {e}")),
             }
         }
     }
 
+    /// Handle the "export pattern text" key command.
+    fn export_pattern_text(&mut self, module: &Module, player: &mut Player) {
+        let dialog = self.pattern_text_dialog(player).set_file_name(module.title.clone());
+
+        if let Some(mut path) = dialog.save_file() {
+            path.set_extension(PATTERN_TEXT_EXT);
+            self.config.module_folder = config::dir_as_string(&path);
+            let text = self.pattern_editor.selection_text(module);
+            match std::fs::write(&path, text) {
+                Ok(()) => self.ui.notify(String::from("Exported pattern text.")),
+                Err(e) => self.ui.report(format!("Error exporting pattern text: {e}")),
+            }
+        }
+    }
+
+    /// Handle the "import pattern text" key command.
+    fn import_pattern_text(&mut self, module: &mut Module, player: &mut Player) {
+        if let Some(path) = self.pattern_text_dialog(player).pick_file() {
+            self.config.module_folder = config::dir_as_string(&path);
+            match std::fs::read_to_string(&path) {
+                Ok(text) => self.paste_pattern_text(module, &text),
+                Err(e) => self.ui.report(format!("Error importing pattern text: {e}")),
+            }
+        }
+    }
+
+    /// Handle the "import MIDI file" key command.
+    fn import_midi(&mut self, module: &mut Module, player: &mut Player) {
+        let dir = self.config.module_folder.clone().unwrap_or(String::from("."));
+        let dialog = ui::new_file_dialog(player)
+            .add_filter("MIDI file", &MIDI_EXTS)
+            .set_directory(dir);
+
+        if let Some(path) = dialog.pick_file() {
+            self.config.module_folder = config::dir_as_string(&path);
+            self.load_midi_file(module, &path);
+        }
+    }
+
+    /// Append tracks from the MIDI file at `path`.
+    fn load_midi_file(&mut self, module: &mut Module, path: &Path) {
+        let result: Result<Edit, Box<dyn Error>> = std::fs::read(path)
+            .map_err(Into::into)
+            .and_then(|data| midifile::import(&data, module));
+        match result {
+            Ok(edit) => {
+                module.push_edit(edit);
+                self.ui.notify(String::from("Imported MIDI file."));
+            }
+            Err(e) => self.ui.report(format!("Error importing MIDI file: {e}")),
+        }
+    }
+
+    /// Insert pattern text at the pattern cursor.
+    fn paste_pattern_text(&mut self, module: &mut Module, text: &str) {
+        if let Err(e) = self.pattern_editor.paste_text(module, text) {
+            self.ui.report(format!("Error pasting pattern text: {e}"));
+        }
+    }
+
+    /// Handle the "run script" key command.
+    fn pick_script(&mut self, module: &mut Module, player: &mut Player) {
+        let dir = self.config.script_folder.clone().unwrap_or(String::from("."));
+        let dialog = ui::new_file_dialog(player)
+            .add_filter("Rhai script", &[SCRIPT_EXT])
+            .set_directory(dir);
+
+        if let Some(path) = dialog.pick_file() {
+            self.config.script_folder = config::dir_as_string(&path);
+            self.run_script(module, path);
+        }
+    }
+
+    /// Run the script at `path` over the pattern selection.
+    fn run_script(&mut self, module: &mut Module, path: PathBuf) {
+        let result = match std::fs::read_to_string(&path) {
+            Ok(src) => self.pattern_editor.run_script(module, &src),
+            Err(e) => Err(e.into()),
+        };
+        match result {
+            Ok(messages) => if !messages.is_empty() {
+                self.ui.notify(messages.join("\n"));
+            },
+            Err(e) => self.ui.report(format!("Error running script: {e}")),
+        }
+        self.last_script = Some(path);
+    }
+
+    fn pattern_text_dialog(&self, player: &mut Player) -> FileDialog {
+        let dir = self.config.module_folder.clone().unwrap_or(String::from("."));
+        ui::new_file_dialog(player)
+            .add_filter("Pattern text", &[PATTERN_TEXT_EXT])
+            .set_directory(dir)
+    }
+
     fn module_dialog(&self, player: &mut Player) -> FileDialog {
         let dir = self.config.module_folder.clone().unwrap_or(String::from("."));
         ui::new_file_dialog(player)
@@ -694,18 +1673,108 @@ impl App {
     /// needed.
     fn load_module(&mut self, module: &mut Module, new_mod: Module, player: &mut Player) {
         *module = new_mod;
-        let follow = self.pattern_editor.follow;
-        self.pattern_editor = PatternEditor::default();
+        let (follow, row_zoom) = (self.pattern_editor.follow, self.pattern_editor.row_zoom);
+        let mut prev_editor = std::mem::take(&mut self.pattern_editor);
+        self.pattern_editor.take_clipboard(&mut prev_editor);
         self.pattern_editor.beat_division = module.division;
         self.pattern_editor.follow = follow;
+        self.pattern_editor.row_zoom = row_zoom;
         self.instruments_state.patch_index = if module.patches.is_empty() {
             None
         } else {
             Some(0)
         };
+        if let Some(state) = module.editor {
+            self.pattern_editor.restore_view(&state.pattern, module);
+            if state.patch_index.is_none_or(|i| i < module.patches.len()) {
+                self.instruments_state.patch_index = state.patch_index;
+            }
+        }
         player.reinit(module.tracks.len());
         self.fx.reinit(&module.fx);
     }
+
+    /// Swap the current module and editor state with those in session slot
+    /// `i`.
+    fn swap_session(&mut self, module: &mut Module, i: usize) {
+        let session = &mut self.sessions[i];
+        std::mem::swap(module, &mut session.module);
+        std::mem::swap(&mut self.save_path, &mut session.save_path);
+        std::mem::swap(&mut self.pattern_editor, &mut session.pattern_editor);
+        std::mem::swap(&mut self.instruments_state.patch_index, &mut session.patch_index);
+    }
+
+    /// Switch to the song tab at index `i`. Playback stops, since only the
+    /// current song can play.
+    fn switch_session(&mut self, module: &mut Module, player: &mut Player, i: usize) {
+        let prev = self.session_index;
+        if i == prev || i >= self.sessions.len() {
+            return
+        }
+
+        self.release_latched_keys(player);
+        if let Some(note) = self.arp.stop() {
+            player.note_off(note.track, arp::key());
+        }
+        self.swap_session(module, prev);
+        self.swap_session(module, i);
+        self.session_index = i;
+        self.ui.set_tab(SONG_TAB_ID, i);
+
+        // the pattern clipboard is shared between songs
+        self.pattern_editor.take_clipboard(&mut self.sessions[prev].pattern_editor);
+
+        player.reinit(module.tracks.len());
+        self.fx.reinit(&module.fx);
+    }
+
+    /// Open a new song tab with a new module.
+    fn new_session(&mut self, module: &mut Module, player: &mut Player) {
+        self.sessions.push(ModuleSession::placeholder());
+        self.switch_session(module, player, self.sessions.len() - 1);
+        self.new_module(module, player);
+    }
+
+    /// Close the current song tab, if there are others.
+    fn close_session(&mut self, module: &mut Module, player: &mut Player) {
+        let i = self.session_index;
+        if self.sessions.len() < 2 {
+            return
+        }
+        let next = if i + 1 < self.sessions.len() { i + 1 } else { i - 1 };
+        self.switch_session(module, player, next);
+        self.sessions.remove(i);
+        if next > i {
+            self.session_index -= 1;
+            self.ui.set_tab(SONG_TAB_ID, self.session_index);
+        }
+    }
+
+    /// Returns true if any song, open or in the background, has unsaved
+    /// changes.
+    fn any_unsaved_changes(&self, module: &Module) -> bool {
+        module.has_unsaved_changes
+            || self.sessions.iter().any(|s| s.module.has_unsaved_changes)
+    }
+
+    /// Returns the labels of the song tabs.
+    fn session_labels(&self, module: &Module) -> Vec<String> {
+        self.sessions.iter().enumerate().map(|(i, session)| {
+            let (path, unsaved) = if i == self.session_index {
+                (&self.save_path, module.has_unsaved_changes)
+            } else {
+                (&session.save_path, session.module.has_unsaved_changes)
+            };
+            let name = path.as_ref()
+                .and_then(|p| p.file_stem())
+                .map_or(String::from("Untitled"), |s| s.to_string_lossy().into_owned());
+            if unsaved {
+                format!("{name}*")
+            } else {
+                name
+            }
+        }).collect()
+    }
 }
 
 /// Returns JACK if available, otherwise ALSA.
@@ -723,7 +1792,8 @@ fn get_audio_device() -> Option<cpal::Device> {
 }
 
 /// Returns the best available audio output stream config.
-fn preferred_config(device: &cpal::Device, desired_sr: SampleRate
+fn preferred_config(device: &cpal::Device, desired_sr: SampleRate,
+    desired_buffer_size: Option<u32>
 ) -> Result<StreamConfig, Box<dyn Error>> {
     device.supported_output_configs()?
         .filter(|conf| conf.channels() == 2)
@@ -734,7 +1804,15 @@ fn preferred_config(device: &cpal::Device, desired_sr: SampleRate
             conf.sample_format() == cpal::SampleFormat::F32
         )).map(|conf| {
             let sr = desired_sr.clamp(conf.min_sample_rate(), conf.max_sample_rate());
-            conf.with_sample_rate(sr).into()
+            let buffer_size = match (desired_buffer_size, conf.buffer_size()) {
+                (Some(n), SupportedBufferSize::Range { min, max }) =>
+                    BufferSize::Fixed(n.clamp(*min, *max)),
+                _ => BufferSize::Default,
+            };
+            StreamConfig {
+                buffer_size,
+                ..conf.with_sample_rate(sr).into()
+            }
         }).ok_or("no supported audio config".into())
 }
 
@@ -745,24 +1823,23 @@ pub async fn run(arg: Option<String>) -> Result<(), Box<dyn Error>> {
 
     let audio_conf: Result<StreamConfig, Box<dyn Error>> = device.as_ref()
         .ok_or("no audio output device".into())
-        .and_then(|device| preferred_config(device, SampleRate(conf.desired_sample_rate)));
+        .and_then(|device| preferred_config(device,
+            SampleRate(conf.desired_sample_rate), conf.audio_buffer_size));
     let sample_rate = audio_conf.as_ref()
         .map(|config| config.sample_rate.0)
         .unwrap_or(44100);
     let cloned_conf = audio_conf.as_ref().cloned().ok();
 
-    let mut seq = Sequencer::new(false, 4);
-    seq.set_sample_rate(sample_rate as f64);
-
-    // the sequencer backend is probably not necessary anymore due to mutexing,
-    // but it's still convenient for ownership reasons.
+    // the net backends are probably not necessary anymore due to mutexing,
+    // but they're still convenient for ownership reasons.
     let fx_settings: FXSettings = Default::default();
-    let mut global_fx = GlobalFX::new(seq.backend(), &fx_settings);
+    let module = Module::new(fx_settings.clone());
+    let mut mixer = Mixer::new(module.tracks.len(), sample_rate as f64);
+    let mut global_fx = GlobalFX::new(mixer.backend(), &fx_settings);
     global_fx.net.set_sample_rate(sample_rate as f64);
     let mut backend = BlockRateAdapter::new(Box::new(global_fx.net.backend()));
 
-    let module = Module::new(fx_settings);
-    let player = Player::new(seq, module.tracks.len(), sample_rate as f32);
+    let player = Player::new(mixer, sample_rate as f32);
     let module = Arc::new(Mutex::new(module));
     let player = Arc::new(Mutex::new(player));
 
@@ -770,29 +1847,67 @@ pub async fn run(arg: Option<String>) -> Result<(), Box<dyn Error>> {
     let update_interval: f64 = UPDATE_FRAMES as f64 / sample_rate as f64;
     let mut frames_until_update = UPDATE_FRAMES;
 
+    // output gain starts at zero and is slew-limited, so that launching or
+    // changing the volume never jumps straight to full scale
+    const GAIN_RAMP_TIME: f32 = 0.05;
+    const STARTUP_RAMP_TIME: f32 = 1.0;
+    let gain_step = 1.0 / (GAIN_RAMP_TIME * sample_rate as f32);
+    let startup_step = 1.0 / (STARTUP_RAMP_TIME * sample_rate as f32);
+    let (mut output_gain, mut target_gain, mut startup_gain) = (0.0f32, 0.0f32, 0.0f32);
+
     let stream_module = module.clone();
     let stream_player = player.clone();
 
+    // DSP time, peak, correlation sums, and duration of the last buffer,
+    // recorded at the next player update so that the player isn't locked
+    // again at the end of the callback
+    let mut last_buffer: Option<(f64, f32, (f32, f32, f32), f64)> = None;
+
     // audio callback
     let stream = audio_conf.and_then(|config| {
         Ok(device.expect("device should be present if config is").build_output_stream(
-            &config, move |data: &mut[f32], _: &cpal::OutputCallbackInfo| {
+            &config, move |data: &mut[f32], info: &cpal::OutputCallbackInfo| {
+                let start_time = Instant::now();
+                let mut peak: f32 = 0.0;
+                let (mut lr, mut ll, mut rr) = (0.0, 0.0, 0.0);
                 let mut i = 0;
                 let len = data.len();
+                let timestamp = info.timestamp();
+                let output_latency = timestamp.playback.duration_since(&timestamp.callback)
+                    .map(|d| d.as_secs_f64())
+                    .unwrap_or((len / 2) as f64 / sample_rate as f64);
                 while i < len {
                     if frames_until_update == 0 {
                         let module = stream_module.lock().unwrap();
                         let mut player = stream_player.lock().unwrap();
+                        if let Some((dsp_time, peak, (lr, ll, rr), buffer_time))
+                            = last_buffer.take() {
+                            player.record_dsp_time(dsp_time, buffer_time);
+                            player.record_output_peak(peak, buffer_time);
+                            player.record_output_correlation(lr, ll, rr, buffer_time);
+                        }
                         player.buffer_size = data.len() / 2;
+                        player.output_latency = output_latency;
                         player.frame(&module, update_interval);
+                        target_gain = player.effective_output_gain();
                         frames_until_update = UPDATE_FRAMES;
                     }
+                    output_gain += (target_gain - output_gain).clamp(-gain_step, gain_step);
+                    startup_gain = (startup_gain + startup_step).min(1.0);
+                    let gain = output_gain * startup_gain;
                     let (l, r) = backend.get_stereo();
-                    data[i] = l;
-                    data[i+1] = r;
+                    data[i] = l * gain;
+                    data[i+1] = r * gain;
+                    peak = peak.max(data[i].abs()).max(data[i+1].abs());
+                    lr += l * r;
+                    ll += l * l;
+                    rr += r * r;
                     i += 2;
                     frames_until_update -= 1;
                 }
+                let buffer_time = (len / 2) as f64 / sample_rate as f64;
+                last_buffer = Some((start_time.elapsed().as_secs_f64(), peak,
+                    (lr, ll, rr), buffer_time));
             },
             |err| eprintln!("stream error: {err}"),
             None
@@ -809,11 +1924,14 @@ pub async fn run(arg: Option<String>) -> Result<(), Box<dyn Error>> {
         Err(e) => app.ui.report(format!("Could not initialize audio: {e}"))
     };
 
-    if let Some(arg) = arg {
-        match Module::load(&arg.into()) {
-            Ok(m) => app.load_module(
-                &mut module.lock().unwrap(), m, &mut player.lock().unwrap()),
-            Err(e) => app.ui.report(format!("Error loading module: {e}")),
+    {
+        let mut module = module.lock().unwrap();
+        let mut player = player.lock().unwrap();
+        if let Some(arg) = arg {
+            app.open_path(&mut module, &mut player, arg.into());
+        }
+        if app.config.restore_session {
+            app.restore_session(&mut module, &mut player);
         }
     }
 
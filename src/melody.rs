@@ -0,0 +1,154 @@
+//! Random melody generation constrained to a subset of the module tuning.
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{module::{Edit, Event, EventData, LocatedEvent, Module, Position, NOTE_COLUMN}, pitch::{Nominal, Note}, timespan::Timespan};
+
+/// Parameters for the melody generator.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MelodySettings {
+    /// Scale steps to draw notes from. Empty uses every step of the tuning.
+    pub steps: Vec<usize>,
+    /// Chance of placing a note on each row, from 0 to 1.
+    pub density: f32,
+    /// Lowest note to generate.
+    pub low: Note,
+    /// Highest note to generate.
+    pub high: Note,
+    pub seed: u32,
+}
+
+impl Default for MelodySettings {
+    fn default() -> Self {
+        Self {
+            steps: Vec::new(),
+            density: 0.5,
+            low: Note::new(0, Nominal::C, 0, 4),
+            high: Note::new(0, Nominal::C, 0, 5),
+            seed: 0,
+        }
+    }
+}
+
+impl MelodySettings {
+    /// Returns the notes in the generator's range that belong to the scale
+    /// subset, sorted by pitch.
+    fn candidates(&self, module: &Module) -> Vec<Note> {
+        let tuning = &module.tuning;
+        let (low, high) = (tuning.midi_pitch(&self.low), tuning.midi_pitch(&self.high));
+        let steps: Vec<_> = if self.steps.is_empty() {
+            (0..tuning.size() as usize).collect()
+        } else {
+            self.steps.iter().copied().filter(|s| *s < tuning.size() as usize).collect()
+        };
+
+        let mut notes: Vec<_> = (self.low.equave - 1..=self.high.equave + 1)
+            .flat_map(|equave| steps.iter()
+                .filter_map(move |step| tuning.notation(*step, equave).first().copied()))
+            .map(|note| (tuning.midi_pitch(&note), note))
+            .filter(|(pitch, _)| *pitch >= low - 0.001 && *pitch <= high + 0.001)
+            .collect();
+        notes.sort_by(|a, b| a.0.total_cmp(&b.0));
+        notes.dedup_by(|a, b| (a.0 - b.0).abs() < 0.001);
+        notes.into_iter().map(|(_, note)| note).collect()
+    }
+}
+
+/// Generates random notes on each row of the note channels between `start`
+/// and `end`, replacing existing notes and note offs. The edit is returned
+/// rather than applied, so that it can be pushed as a single undo step.
+pub fn generate(module: &Module, start: Position, end: Position, row: Timespan,
+    settings: &MelodySettings
+) -> Result<Edit, &'static str> {
+    let notes = settings.candidates(module);
+    if notes.is_empty() {
+        return Err("No scale notes in range")
+    }
+
+    let (start_tuple, end_tuple) = (start.x_tuple(), end.x_tuple());
+    let channels: Vec<_> = module.tracks.iter().enumerate().skip(1)
+        .flat_map(|(t, track)| (0..track.channels.len()).map(move |c| (t, c)))
+        .filter(|(t, c)| (*t, *c, NOTE_COLUMN) >= start_tuple
+            && (*t, *c, NOTE_COLUMN) <= end_tuple)
+        .collect();
+    if channels.is_empty() {
+        return Err("No note columns selected")
+    }
+
+    let mut rng = StdRng::seed_from_u64(settings.seed as u64);
+    let mut add = Vec::new();
+    for &(track, channel) in &channels {
+        let mut tick = start.tick;
+        while tick < end.tick {
+            if rng.gen::<f32>() < settings.density {
+                add.push(LocatedEvent {
+                    track,
+                    channel,
+                    event: Event {
+                        tick,
+                        data: EventData::Pitch(notes[rng.gen_range(0..notes.len())]),
+                    },
+                });
+            }
+            tick += row;
+        }
+    }
+
+    let mut remove: Vec<_> = module.scan_events(start, end).into_iter()
+        .filter(|e| matches!(e.event.data, EventData::Pitch(_) | EventData::NoteOff)
+            && channels.contains(&(e.track, e.channel)))
+        .map(|e| e.position())
+        .collect();
+    remove.extend(add.iter().map(|e| e.position()));
+
+    Ok(Edit::PatternData { remove, add })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate() {
+        let module = Module::new(Default::default());
+        let start = Position::new(Timespan::ZERO, 1, 0, 0);
+        let end = Position::new(Timespan::new(4, 1), 1, 0, 2);
+        let row = Timespan::new(1, 4);
+        let settings = MelodySettings {
+            steps: vec![0, 4, 7],
+            density: 0.75,
+            ..Default::default()
+        };
+
+        let Ok(Edit::PatternData { add, .. }) =
+            generate(&module, start, end, row, &settings) else {
+            panic!("expected pattern data edit")
+        };
+        assert!(!add.is_empty() && add.len() <= 16);
+        let tuning = &module.tuning;
+        for e in &add {
+            let EventData::Pitch(note) = e.event.data else {
+                panic!("expected pitch event")
+            };
+            assert!(settings.steps.contains(&tuning.scale_index(&note).0));
+            let pitch = tuning.midi_pitch(&note);
+            assert!(pitch >= tuning.midi_pitch(&settings.low) - 0.001);
+            assert!(pitch <= tuning.midi_pitch(&settings.high) + 0.001);
+        }
+
+        // same seed, same melody
+        let Ok(Edit::PatternData { add: again, .. }) =
+            generate(&module, start, end, row, &settings) else {
+            panic!("expected pattern data edit")
+        };
+        assert_eq!(add.len(), again.len());
+        assert!(add.iter().zip(&again)
+            .all(|(a, b)| a.event.tick == b.event.tick && a.event.data == b.event.data));
+
+        let empty = MelodySettings { steps: vec![100], ..Default::default() };
+        assert!(generate(&module, start, end, row, &empty).is_err());
+    }
+
+}
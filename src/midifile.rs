@@ -0,0 +1,448 @@
+//! Standard MIDI file import.
+//!
+//! Each MIDI track/channel pair with notes becomes a pattern track, with as
+//! many channels as its polyphony requires. Channel 10 is imported as a kit
+//! track. Pitch bends assume a range of 2 semitones.
+
+use std::error::Error;
+
+use crate::{module::{Channel, Edit, Event, EventData, LocatedEvent, Module, Track, TrackTarget, NOTE_COLUMN}, pitch::{Note, Tuning}, playback::DEFAULT_TEMPO, timespan::Timespan};
+
+/// Imported events are quantized to this fraction of a beat.
+const IMPORT_DIVISION: u8 = 96;
+
+/// Pitch bend range, in cents.
+const BEND_RANGE: f32 = 200.0;
+
+/// Zero-indexed MIDI channel conventionally used for drums.
+const DRUM_CHANNEL: u8 = 9;
+
+/// Timing of MIDI ticks.
+enum Division {
+    /// Ticks per quarter note.
+    Metrical(u16),
+    /// Ticks per second.
+    Timecode(f64),
+}
+
+enum Message {
+    NoteOff(u8),
+    NoteOn(u8, u8),
+    /// 14-bit value, centered at 0x2000.
+    PitchBend(u16),
+    /// Microseconds per quarter note.
+    Tempo(u32),
+}
+
+/// Message with absolute tick and channel. Channel is unused for meta events.
+struct TimedMessage {
+    tick: u32,
+    channel: u8,
+    message: Message,
+}
+
+/// Reads big-endian integers and variable-length quantities from a byte slice.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], &'static str> {
+        let bytes = self.data.get(self.pos..self.pos + n).ok_or("Unexpected end of file")?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, &'static str> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, &'static str> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, &'static str> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn vlq(&mut self) -> Result<u32, &'static str> {
+        let mut value = 0;
+        for _ in 0..4 {
+            let b = self.u8()?;
+            value = (value << 7) | (b & 0x7f) as u32;
+            if b & 0x80 == 0 {
+                return Ok(value)
+            }
+        }
+        Err("Invalid variable-length quantity")
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+}
+
+/// Parses the header and tracks of a MIDI file.
+fn parse(data: &[u8]) -> Result<(Division, Vec<Vec<TimedMessage>>), &'static str> {
+    let mut r = Reader { data, pos: 0 };
+    if r.bytes(4).ok() != Some(b"MThd".as_slice()) {
+        return Err("Not a MIDI file")
+    }
+    let header_len = r.u32()? as usize;
+    let header = r.bytes(header_len)?;
+    let mut h = Reader { data: header, pos: 0 };
+    let _format = h.u16()?;
+    let num_tracks = h.u16()?;
+    let division = match h.u16()? {
+        0 => return Err("Invalid time division"),
+        d if d & 0x8000 == 0 => Division::Metrical(d),
+        d => {
+            let fps = -((d >> 8) as u8 as i8 as i16) as f64;
+            let fps = if fps == 29.0 { 29.97 } else { fps };
+            Division::Timecode(fps * (d & 0xff) as f64)
+        }
+    };
+
+    let mut tracks = Vec::new();
+    while tracks.len() < num_tracks as usize && !r.at_end() {
+        let id = r.bytes(4)?;
+        let len = r.u32()? as usize;
+        let chunk = r.bytes(len)?;
+        if id == b"MTrk" {
+            tracks.push(parse_track(chunk)?);
+        }
+    }
+
+    Ok((division, tracks))
+}
+
+/// Parses the events of a track chunk.
+fn parse_track(data: &[u8]) -> Result<Vec<TimedMessage>, &'static str> {
+    let mut r = Reader { data, pos: 0 };
+    let mut messages = Vec::new();
+    let mut tick: u32 = 0;
+    let mut running_status = None;
+
+    while !r.at_end() {
+        tick = tick.saturating_add(r.vlq()?);
+        let mut status = r.u8()?;
+
+        match status {
+            0xff => {
+                running_status = None;
+                let kind = r.u8()?;
+                let len = r.vlq()? as usize;
+                let data = r.bytes(len)?;
+                match (kind, data) {
+                    (0x2f, _) => break, // end of track
+                    (0x51, [a, b, c]) => messages.push(TimedMessage {
+                        tick,
+                        channel: 0,
+                        message: Message::Tempo(u32::from_be_bytes([0, *a, *b, *c])),
+                    }),
+                    _ => (),
+                }
+                continue
+            }
+            0xf0 | 0xf7 => {
+                running_status = None;
+                let len = r.vlq()? as usize;
+                r.bytes(len)?;
+                continue
+            }
+            0xf1..=0xfe => return Err("Unexpected system message"),
+            _ => (),
+        }
+
+        // channel message, possibly with running status
+        let first_data = if status < 0x80 {
+            let data = status;
+            status = running_status.ok_or("Data byte without status")?;
+            data
+        } else {
+            running_status = Some(status);
+            r.u8()?
+        };
+        let channel = status & 0xf;
+        let message = match status & 0xf0 {
+            0x80 => {
+                r.u8()?;
+                Some(Message::NoteOff(first_data))
+            }
+            0x90 => match r.u8()? {
+                0 => Some(Message::NoteOff(first_data)),
+                velocity => Some(Message::NoteOn(first_data, velocity)),
+            },
+            0xa0 | 0xb0 => {
+                r.u8()?;
+                None
+            }
+            0xc0 | 0xd0 => None,
+            _ => {
+                let msb = r.u8()?;
+                Some(Message::PitchBend((first_data as u16) | ((msb as u16) << 7)))
+            }
+        };
+        if let Some(message) = message {
+            messages.push(TimedMessage { tick, channel, message });
+        }
+    }
+
+    Ok(messages)
+}
+
+/// Returns the nearest note in `tuning` to each MIDI key.
+fn key_map(tuning: &Tuning) -> Vec<Note> {
+    let candidates: Vec<_> = (-2..=12)
+        .flat_map(|equave| (0..tuning.size() as usize)
+            .filter_map(move |step| tuning.notation(step, equave).first().copied()))
+        .map(|note| (tuning.midi_pitch(&note), note))
+        .collect();
+
+    (0..=127).map(|key| {
+        candidates.iter()
+            .min_by(|a, b| (a.0 - key as f32).abs().total_cmp(&(b.0 - key as f32).abs()))
+            .map(|(_, note)| *note)
+            .expect("tuning should have notes")
+    }).collect()
+}
+
+/// Converts a MIDI tick to a quantized beat position.
+fn tick_beats(tick: u32, division: &Division) -> Timespan {
+    let beats = match division {
+        Division::Metrical(ppq) => tick as f64 / *ppq as f64,
+        Division::Timecode(tps) => tick as f64 / tps * DEFAULT_TEMPO as f64 / 60.0,
+    };
+    Timespan::new((beats * IMPORT_DIVISION as f64).round() as i32, IMPORT_DIVISION)
+}
+
+/// Builds a pattern track from the messages of one MIDI track/channel pair.
+/// Returns `None` if there are no notes.
+fn make_track(messages: &[&TimedMessage], division: &Division, keys: &[Note],
+    target: TrackTarget
+) -> Option<Track> {
+    // key held in each channel, and the tick of the channel's last note
+    let mut held: Vec<(Option<u8>, Timespan)> = Vec::new();
+    let mut channels: Vec<Channel> = Vec::new();
+    let mut last_pressure: Vec<Option<u8>> = Vec::new();
+    let mut bends = Vec::new();
+
+    for msg in messages {
+        let tick = tick_beats(msg.tick, division);
+        match msg.message {
+            Message::NoteOn(key, velocity) => {
+                let i = held.iter().position(|(k, _)| k.is_none()).unwrap_or_else(|| {
+                    held.push((None, Timespan::ZERO));
+                    channels.push(Channel::default());
+                    last_pressure.push(None);
+                    held.len() - 1
+                });
+                let events = &mut channels[i].events;
+                // the new note replaces an off at the same tick
+                events.retain(|e| e.tick != tick || e.data != EventData::NoteOff);
+                events.push(Event {
+                    tick,
+                    data: EventData::Pitch(keys[key as usize & 0x7f]),
+                });
+                let pressure = EventData::digit_from_midi(velocity);
+                if last_pressure[i] != Some(pressure) {
+                    events.push(Event { tick, data: EventData::Pressure(pressure) });
+                    last_pressure[i] = Some(pressure);
+                }
+                held[i] = (Some(key), tick);
+            }
+            Message::NoteOff(key) => {
+                if let Some(i) = held.iter().position(|(k, _)| *k == Some(key)) {
+                    let start = held[i].1;
+                    let min_end = start + Timespan::new(1, IMPORT_DIVISION);
+                    channels[i].events.push(Event {
+                        tick: tick.max(min_end),
+                        data: EventData::NoteOff,
+                    });
+                    held[i].0 = None;
+                }
+            }
+            Message::PitchBend(value) => {
+                let cents = ((value as f32 - 8192.0) / 8192.0 * BEND_RANGE).round() as i16;
+                if bends.last().is_none_or(|(_, c)| *c != cents) {
+                    bends.push((tick, cents));
+                }
+            }
+            Message::Tempo(_) => (),
+        }
+    }
+
+    if channels.is_empty() {
+        return None
+    }
+
+    // bends apply to every channel, except where a note event is in the way
+    for channel in &mut channels {
+        for &(tick, cents) in &bends {
+            if !channel.events.iter()
+                .any(|e| e.tick == tick && e.data.logical_column() == NOTE_COLUMN) {
+                channel.events.push(Event { tick, data: EventData::Bend(cents) });
+            }
+        }
+        channel.sort_events();
+    }
+
+    Some(Track { channels, ..Track::new(target) })
+}
+
+/// Returns an edit appending tracks imported from MIDI file data, and tempo
+/// events from the file's tempo map. The edit is returned rather than
+/// applied, so that it can be pushed as a single undo step.
+pub fn import(data: &[u8], module: &Module) -> Result<Edit, Box<dyn Error>> {
+    let (division, midi_tracks) = parse(data)?;
+    let keys = key_map(&module.tuning);
+    let patch_target = if module.patches.is_empty() {
+        TrackTarget::None
+    } else {
+        TrackTarget::Patch(0)
+    };
+
+    let mut edits = Vec::new();
+    for messages in &midi_tracks {
+        let mut channels: Vec<_> = messages.iter()
+            .filter(|m| !matches!(m.message, Message::Tempo(_)))
+            .map(|m| m.channel)
+            .collect();
+        channels.sort();
+        channels.dedup();
+
+        for channel in channels {
+            let mut messages: Vec<_> = messages.iter()
+                .filter(|m| m.channel == channel && !matches!(m.message, Message::Tempo(_)))
+                .collect();
+            // handle note offs before note ons at the same tick, so that
+            // legato lines stay in one channel
+            messages.sort_by_key(|m| (m.tick, matches!(m.message, Message::NoteOn(..))));
+
+            let target = if channel == DRUM_CHANNEL { TrackTarget::Kit } else { patch_target };
+            if let Some(track) = make_track(&messages, &division, &keys, target) {
+                edits.push(Edit::InsertTrack(module.tracks.len() + edits.len(), track));
+            }
+        }
+    }
+    if edits.is_empty() {
+        return Err("No notes found".into())
+    }
+
+    let tempos: Vec<_> = match division {
+        Division::Metrical(_) => midi_tracks.iter().flatten()
+            .filter_map(|m| match m.message {
+                Message::Tempo(us) if us > 0 => Some(LocatedEvent {
+                    track: 0,
+                    channel: 0,
+                    event: Event {
+                        tick: tick_beats(m.tick, &division),
+                        data: EventData::Tempo(60_000_000.0 / us as f32),
+                    },
+                }),
+                _ => None,
+            })
+            .collect(),
+        // timecode ticks were converted at the default tempo
+        Division::Timecode(_) => vec![LocatedEvent {
+            track: 0,
+            channel: 0,
+            event: Event {
+                tick: Timespan::ZERO,
+                data: EventData::Tempo(DEFAULT_TEMPO),
+            },
+        }],
+    };
+    if !tempos.is_empty() {
+        edits.push(Edit::PatternData {
+            remove: tempos.iter().map(|e| e.position()).collect(),
+            add: tempos,
+        });
+    }
+
+    Ok(Edit::Group(edits))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pitch::Nominal;
+
+    use super::*;
+
+    /// Wraps track data in a format 1 MIDI file with 96 ticks per quarter.
+    fn midi_file(tracks: &[&[u8]]) -> Vec<u8> {
+        let mut data = b"MThd".to_vec();
+        data.extend(6u32.to_be_bytes());
+        data.extend(1u16.to_be_bytes());
+        data.extend((tracks.len() as u16).to_be_bytes());
+        data.extend(96u16.to_be_bytes());
+        for track in tracks {
+            data.extend(b"MTrk");
+            data.extend((track.len() as u32).to_be_bytes());
+            data.extend(*track);
+        }
+        data
+    }
+
+    #[test]
+    fn test_import() {
+        let module = Module::new(Default::default());
+        let data = midi_file(&[
+            // tempo 100 bpm
+            &[0, 0xff, 0x51, 3, 0x09, 0x27, 0xc0, 0, 0xff, 0x2f, 0],
+            // C4 and E4 chord, then G4 with running status, and a bend
+            &[
+                0, 0x90, 60, 127,
+                0, 64, 64,
+                0x60, 0x80, 60, 0,
+                0, 0x90, 64, 0,
+                0, 67, 100,
+                0x30, 0xe0, 0, 0x60,
+                0x30, 0x80, 67, 0,
+                0, 0xff, 0x2f, 0,
+            ],
+            // drums
+            &[0, 0x99, 36, 100, 0x60, 0x89, 36, 0, 0, 0xff, 0x2f, 0],
+        ]);
+
+        let Edit::Group(edits) = import(&data, &module).unwrap() else {
+            panic!("expected group edit")
+        };
+        assert_eq!(edits.len(), 3);
+
+        let Edit::InsertTrack(index, track) = &edits[0] else {
+            panic!("expected track insertion")
+        };
+        assert_eq!(*index, module.tracks.len());
+        assert!(matches!(track.target, TrackTarget::Patch(0)));
+        assert_eq!(track.channels.len(), 2);
+        let events = &track.channels[0].events;
+        assert_eq!(events[0].data, EventData::Pitch(Note::new(0, Nominal::C, 0, 4)));
+        assert_eq!(events[1].data, EventData::Pressure(EventData::DIGIT_MAX));
+        assert!(events.iter().any(|e| e.tick == Timespan::new(1, 1)
+            && matches!(e.data, EventData::Pitch(_))));
+        assert!(events.iter().any(|e| e.tick == Timespan::new(3, 2)
+            && e.data == EventData::Bend(100)));
+        assert!(events.iter().any(|e| e.tick == Timespan::new(2, 1)
+            && e.data == EventData::NoteOff));
+
+        let Edit::InsertTrack(_, drums) = &edits[1] else {
+            panic!("expected track insertion")
+        };
+        assert!(matches!(drums.target, TrackTarget::Kit));
+
+        let Edit::PatternData { add, .. } = &edits[2] else {
+            panic!("expected pattern data edit")
+        };
+        assert_eq!(add.len(), 1);
+        let EventData::Tempo(t) = add[0].event.data else {
+            panic!("expected tempo event")
+        };
+        assert!((t - 100.0).abs() < 0.01);
+
+        assert!(import(b"RIFF", &module).is_err());
+    }
+}
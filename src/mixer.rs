@@ -0,0 +1,245 @@
+//! Per-track audio outputs and the master bus that sums them.
+
+use fundsp::hacker32::*;
+
+/// Channels in a track output: dry stereo, then FX send stereo.
+pub const TRACK_CHANNELS: usize = 4;
+
+/// Response time for smoothing track output parameter changes, in seconds.
+const PARAM_SMOOTH_TIME: f32 = 0.02;
+
+/// Lowest track high-pass and low-pass cutoff, in Hz.
+pub const MIN_TONE_HZ: f32 = 20.0;
+
+/// Highest track high-pass and low-pass cutoff, in Hz.
+pub const MAX_TONE_HZ: f32 = 20000.0;
+
+/// Q of track tone filters.
+const TONE_Q: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+/// Shared parameters of a track's output stage.
+struct OutputParams {
+    pan: Shared,
+    highpass_hz: Shared,
+    lowpass_hz: Shared,
+    /// 1 if either tone filter is enabled, 0 to bypass them.
+    filtered: Shared,
+}
+
+impl OutputParams {
+    fn new() -> Self {
+        Self {
+            pan: shared(0.0),
+            highpass_hz: shared(MIN_TONE_HZ),
+            lowpass_hz: shared(MAX_TONE_HZ),
+            filtered: shared(0.0),
+        }
+    }
+
+    /// Returns a mono high-pass and low-pass filter using these parameters.
+    fn tone_filter(&self) -> Net {
+        let smooth = |x: &Shared| var(x) >> follow(PARAM_SMOOTH_TIME);
+        let filter = ((pass() | smooth(&self.highpass_hz) | dc(TONE_Q)) >> highpass()
+            | smooth(&self.lowpass_hz) | dc(TONE_Q)) >> lowpass();
+        Net::wrap(Box::new(((pass() ^ filter) | smooth(&self.filtered))
+            >> map(|i: &Frame<f32, U3>| i[0] + (i[1] - i[0]) * i[2])))
+    }
+}
+
+/// Owns one sequencer per track. Each sequencer feeds a node in the bus net,
+/// which passes the sum of the previous tracks through and adds its own
+/// output, so that tracks can be added and removed without interrupting the
+/// others.
+pub struct Mixer {
+    /// One per track.
+    tracks: Vec<Sequencer>,
+    /// Bus node IDs, one per track.
+    ids: Vec<NodeId>,
+    /// Output stage parameters, one per track.
+    params: Vec<OutputParams>,
+    bus: Net,
+    sample_rate: f64,
+}
+
+impl Mixer {
+    pub fn new(num_tracks: usize, sample_rate: f64) -> Self {
+        let mut bus = Net::new(0, TRACK_CHANNELS);
+        bus.set_sample_rate(sample_rate);
+        let mut mixer = Self {
+            tracks: Vec::new(),
+            ids: Vec::new(),
+            params: Vec::new(),
+            bus,
+            sample_rate,
+        };
+        for i in 0..num_tracks {
+            mixer.insert_track(i);
+        }
+        mixer
+    }
+
+    /// Returns the bus output, for use as the input to global FX. Can only be
+    /// called once.
+    pub fn backend(&mut self) -> Box<dyn AudioUnit> {
+        Box::new(self.bus.backend())
+    }
+
+    pub fn track_count(&self) -> usize {
+        self.tracks.len()
+    }
+
+    /// Returns the sequencer for a track.
+    pub fn track(&mut self, index: usize) -> &mut Sequencer {
+        &mut self.tracks[index]
+    }
+
+    /// Set the pan position of a track's output, from -1 to 1.
+    pub fn set_pan(&mut self, index: usize, pan: f32) {
+        self.params[index].pan.set(pan);
+    }
+
+    /// Set the cutoffs of a track's high-pass and low-pass filters, in Hz.
+    /// `None` disables a filter.
+    pub fn set_tone(&mut self, index: usize, highpass_hz: Option<f32>, lowpass_hz: Option<f32>) {
+        let params = &self.params[index];
+        params.highpass_hz.set(highpass_hz.map_or(MIN_TONE_HZ,
+            |f| f.clamp(MIN_TONE_HZ, MAX_TONE_HZ)));
+        params.lowpass_hz.set(lowpass_hz.map_or(MAX_TONE_HZ,
+            |f| f.clamp(MIN_TONE_HZ, MAX_TONE_HZ)));
+        params.filtered.set(if highpass_hz.is_some() || lowpass_hz.is_some() {
+            1.0
+        } else {
+            0.0
+        });
+    }
+
+    /// Returns the number of nodes in the bus net.
+    pub fn node_count(&self) -> usize {
+        self.bus.size()
+    }
+
+    /// Add a track output at `index`.
+    pub fn insert_track(&mut self, index: usize) {
+        let mut seq = Sequencer::new(false, TRACK_CHANNELS);
+        seq.set_sample_rate(self.sample_rate);
+        let params = OutputParams::new();
+        let panner = map(|i: &Frame<f32, U5>| {
+            let (l, r) = ((1.0 - i[4]).min(1.0), (1.0 + i[4]).min(1.0));
+            Frame::<f32, U4>::from([i[0] * l, i[1] * r, i[2] * l, i[3] * r])
+        });
+        let tone = params.tone_filter() | params.tone_filter()
+            | params.tone_filter() | params.tone_filter();
+        let mut node = ((Net::wrap(Box::new(seq.backend()))
+            | Net::wrap(Box::new(var(&params.pan) >> follow(PARAM_SMOOTH_TIME))))
+            >> Net::wrap(Box::new(panner))
+            >> tone)
+            + Net::wrap(Box::new(multipass::<U4>()));
+        node.set_sample_rate(self.sample_rate);
+        let id = self.bus.push(Box::new(node));
+
+        if let Some(prev) = index.checked_sub(1).map(|i| self.ids[i]) {
+            self.bus.pipe_all(prev, id);
+        }
+        match self.ids.get(index) {
+            Some(next) => self.bus.pipe_all(id, *next),
+            None => self.bus.pipe_output(id),
+        }
+
+        self.tracks.insert(index, seq);
+        self.ids.insert(index, id);
+        self.params.insert(index, params);
+        self.commit();
+    }
+
+    /// Remove the track output at `index`. Sound from the track stops
+    /// immediately.
+    pub fn remove_track(&mut self, index: usize) {
+        self.tracks.remove(index);
+        self.params.remove(index);
+        self.bus.remove_link(self.ids.remove(index));
+        self.commit();
+    }
+
+    /// Add or remove tracks from the end to reach `num_tracks`.
+    pub fn resize(&mut self, num_tracks: usize) {
+        while self.track_count() > num_tracks {
+            self.remove_track(self.track_count() - 1);
+        }
+        while self.track_count() < num_tracks {
+            self.insert_track(self.track_count());
+        }
+    }
+
+    fn commit(&mut self) {
+        if self.bus.has_backend() {
+            self.bus.commit();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_track_order() {
+        let mut mixer = Mixer::new(2, 44100.0);
+        let mut backend = mixer.backend();
+        mixer.insert_track(1);
+        mixer.remove_track(0);
+        assert_eq!(mixer.track_count(), 2);
+
+        // only the track pushed to should sound
+        mixer.track(1).push_relative(0.0, f64::INFINITY, Fade::Smooth, 0.0, 0.0,
+            Box::new(dc(1.0) | zero() | zero() | zero()));
+        let mut frame = [0.0; TRACK_CHANNELS];
+        for _ in 0..64 {
+            backend.tick(&[], &mut frame);
+        }
+        assert_eq!(frame, [1.0, 0.0, 0.0, 0.0]);
+
+        mixer.resize(1);
+        for _ in 0..64 {
+            backend.tick(&[], &mut frame);
+        }
+        assert_eq!(frame, [0.0; TRACK_CHANNELS]);
+    }
+
+    #[test]
+    fn test_track_pan() {
+        let mut mixer = Mixer::new(1, 44100.0);
+        let mut backend = mixer.backend();
+        mixer.track(0).push_relative(0.0, f64::INFINITY, Fade::Smooth, 0.0, 0.0,
+            Box::new(dc(1.0) | dc(1.0) | dc(0.5) | dc(0.5)));
+        mixer.set_pan(0, 0.5);
+        let mut frame = [0.0; TRACK_CHANNELS];
+        for _ in 0..44100 {
+            backend.tick(&[], &mut frame);
+        }
+        let expected = [0.5, 1.0, 0.25, 0.5];
+        assert!(frame.iter().zip(expected).all(|(x, y)| (x - y).abs() < 1e-3));
+    }
+
+    #[test]
+    fn test_track_tone() {
+        let mut mixer = Mixer::new(1, 44100.0);
+        let mut backend = mixer.backend();
+        mixer.track(0).push_relative(0.0, f64::INFINITY, Fade::Smooth, 0.0, 0.0,
+            Box::new(dc(1.0) | dc(1.0) | dc(1.0) | dc(1.0)));
+        let mut frame = [0.0; TRACK_CHANNELS];
+
+        // high-pass blocks DC
+        mixer.set_tone(0, Some(1000.0), None);
+        for _ in 0..44100 {
+            backend.tick(&[], &mut frame);
+        }
+        assert!(frame.iter().all(|x| x.abs() < 1e-3));
+
+        // disabled filters are bypassed
+        mixer.set_tone(0, None, None);
+        for _ in 0..44100 {
+            backend.tick(&[], &mut frame);
+        }
+        assert!(frame.iter().all(|x| (x - 1.0).abs() < 1e-3));
+    }
+}
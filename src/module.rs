@@ -1,17 +1,127 @@
 //! Definitions for most stored module data.
 
-use std::{collections::HashSet, error::Error, fs::File, io::{BufReader, Read, Write}, path::PathBuf};
+use std::{collections::HashSet, error::Error, fmt::Display, fs::File, io::{BufReader, Read, Write}, path::{Path, PathBuf}, sync::atomic::{AtomicU64, Ordering}};
 
 use flate2::{bufread::GzDecoder, write::GzEncoder};
-use serde::{Deserialize, Serialize};
+use serde::{de::{DeserializeOwned, IgnoredAny}, Deserialize, Serialize};
 
-use crate::{fx::FXSettings, pitch::{Note, Tuning}, playback::{tick_interval, DEFAULT_TEMPO}, synth::Patch, timespan::Timespan};
+use crate::{fx::FXSettings, input::KeyMap, pitch::{Note, Tuning}, playback::{tick_interval, DEFAULT_TEMPO}, synth::Patch, timespan::Timespan, ui::pattern::PatternView};
 
 pub const GLOBAL_COLUMN: u8 = 0;
 pub const NOTE_COLUMN: u8 = 0;
 pub const VEL_COLUMN: u8 = 1;
 pub const MOD_COLUMN: u8 = 2;
 
+/// Base of the exponential glide curve. Higher values bend the curve more.
+const EXP_CURVE_BASE: f32 = 16.0;
+
+/// Number of steps in a stepped glide between two events.
+const STEPPED_CURVE_STEPS: f32 = 8.0;
+
+/// Version of the module file format. Increment this when a change to saved
+/// data can't be handled by serde defaults, and add a migration.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Migrations from each format version to the next, indexed by the older
+/// version.
+const MIGRATIONS: [fn(&mut Module); FORMAT_VERSION as usize] = [
+    // 0 -> 1: files gained a version header; module data is unchanged
+    |_| (),
+];
+
+/// Section names of serialized module fields, in order.
+const MODULE_FIELDS: [&str; 16] = ["title", "author", "tuning", "effects", "kit",
+    "patches", "patterns", "beat division", "adaptive JI", "JI tolerance",
+    "extra tunings", "key map", "editor state", "transpose", "pitch shift",
+    "render seed"];
+
+/// One serialized field of a damaged module.
+struct FieldRecovery<'a> {
+    fields: &'a [&'a [u8]],
+    /// Field count declared by the data.
+    len: usize,
+    i: usize,
+    /// Section name reported if the field can't be read.
+    name: &'static str,
+}
+
+impl FieldRecovery<'_> {
+    /// Deserialize the field into `value`. If the field was declared but
+    /// can't be read, `value` is unchanged and the section is marked dropped.
+    fn read<T: DeserializeOwned>(&self, value: &mut T, dropped: &mut Vec<&'static str>) {
+        match self.fields.get(self.i).map(|b| rmp_serde::from_slice(b)) {
+            Some(Ok(x)) => *value = x,
+            _ if self.i >= self.len => (),
+            _ => if !dropped.contains(&self.name) {
+                dropped.push(self.name);
+            }
+        }
+    }
+}
+
+/// Returns the declared length of a MessagePack array and the size of its
+/// header.
+fn msgpack_array_header(input: &[u8]) -> Option<(usize, usize)> {
+    match *input.first()? {
+        b @ 0x90..=0x9f => Some(((b & 0x0f) as usize, 1)),
+        0xdc => Some((u16::from_be_bytes(input.get(1..3)?.try_into().ok()?) as usize, 3)),
+        0xdd => Some((u32::from_be_bytes(input.get(1..5)?.try_into().ok()?) as usize, 5)),
+        _ => None,
+    }
+}
+
+/// Encode already-encoded elements as a MessagePack array.
+fn join_msgpack_array(elements: &[&[u8]]) -> Vec<u8> {
+    let mut output = Vec::new();
+    match elements.len() {
+        len @ 0..=15 => output.push(0x90 | len as u8),
+        len @ 16..=0xffff => {
+            output.push(0xdc);
+            output.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            output.push(0xdd);
+            output.extend_from_slice(&(len as u32).to_be_bytes());
+        }
+    }
+    for element in elements {
+        output.extend_from_slice(element);
+    }
+    output
+}
+
+/// Returns the module data that follows the version in versioned file data.
+fn versioned_module_data(input: &[u8]) -> Option<&[u8]> {
+    let (_, header) = msgpack_array_header(input)?;
+    let mut data = &input[header..];
+    u32::deserialize(&mut rmp_serde::Deserializer::new(&mut data)).ok()?;
+    Some(data)
+}
+
+/// Split a MessagePack array into its declared length and the encoded
+/// elements that could be delimited. If an element is damaged, it extends
+/// to the end of the input and no further elements are returned.
+fn split_msgpack_array(input: &[u8]) -> Option<(usize, Vec<&[u8]>)> {
+    let (len, mut pos) = msgpack_array_header(input)?;
+
+    let mut elements = Vec::new();
+    while elements.len() < len && pos < input.len() {
+        let rest = &input[pos..];
+        let mut reader = rest;
+        let complete = IgnoredAny::deserialize(
+            &mut rmp_serde::Deserializer::new(&mut reader)).is_ok();
+        if complete {
+            let n = rest.len() - reader.len();
+            elements.push(&rest[..n]);
+            pos += n;
+        } else {
+            elements.push(rest);
+            break
+        }
+    }
+    Some((len, elements))
+}
+
 /// Stores all saved song data and undo state.
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Module {
@@ -25,6 +135,34 @@ pub struct Module {
     /// This field is just for save/load. See `PatternEditor` for actual usage.
     #[serde(default = "default_division")]
     pub division: u8,
+    /// Retune pattern notes toward simple JI ratios above the lowest
+    /// sounding note.
+    #[serde(default)]
+    pub adaptive_ji: bool,
+    /// Largest adjustment adaptive JI will make, in cents.
+    #[serde(default = "default_ji_tolerance")]
+    pub ji_tolerance: f32,
+    /// Tunings selectable by tuning change events, after `tuning`.
+    #[serde(default)]
+    pub extra_tunings: Vec<Tuning>,
+    /// Scale degrees played by the note keys, for large tunings.
+    #[serde(default)]
+    pub key_map: KeyMap,
+    /// This field is just for save/load. Editor state is restored from it
+    /// when the module is opened.
+    #[serde(default)]
+    pub editor: Option<EditorState>,
+    /// Scale steps that played notes are transposed by. Kit notes aren't
+    /// transposed.
+    #[serde(default)]
+    pub transpose: i16,
+    /// Offset added to all played pitches, in cents.
+    #[serde(default)]
+    pub pitch_shift: f32,
+    /// Seed for random voice values in renders, so that exports of the same
+    /// module are identical. Live playback stays random.
+    #[serde(default)]
+    pub render_seed: u32,
 
     #[serde(skip)]
     undo_stack: Vec<Edit>,
@@ -34,11 +172,39 @@ pub struct Module {
     track_history: Vec<TrackEdit>,
     #[serde(skip)]
     pub has_unsaved_changes: bool,
+    /// Number of fields in the loaded file that this version doesn't know.
+    /// They are dropped on save.
+    #[serde(skip)]
+    pub unknown_fields: usize,
+    /// Changes whenever the module is edited. Unique across modules.
+    #[serde(skip, default = "next_version")]
+    version: u64,
+}
+
+/// Editor state saved with a module, so that reopening it returns to where
+/// editing left off.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct EditorState {
+    pub pattern: PatternView,
+    /// Selected patch, or None for the kit.
+    pub patch_index: Option<usize>,
 }
 
 /// Default beat division for serde.
 fn default_division() -> u8 { 4 }
 
+/// Default adaptive JI tolerance for serde.
+fn default_ji_tolerance() -> f32 { 20.0 }
+
+/// Default track pressure and modulation scale for serde.
+fn default_track_scale() -> f32 { 1.0 }
+
+/// Returns a new, globally unique module version.
+fn next_version() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    COUNTER.fetch_add(1, Ordering::Relaxed)
+}
+
 impl Module {
     pub fn new(fx: FXSettings) -> Module {
         Self {
@@ -58,24 +224,151 @@ impl Module {
             redo_stack: Vec::new(),
             track_history: Vec::new(),
             has_unsaved_changes: false,
+            unknown_fields: 0,
             division: default_division(),
+            adaptive_ji: false,
+            ji_tolerance: default_ji_tolerance(),
+            extra_tunings: Vec::new(),
+            key_map: KeyMap::default(),
+            editor: None,
+            transpose: 0,
+            pitch_shift: 0.0,
+            render_seed: 0,
+            version: next_version(),
         }
     }
 
+    /// Returns a copy of the module without pattern data, title, or undo
+    /// history, for use as a new-module template.
+    pub fn template(&self) -> Module {
+        let mut template = Self {
+            title: String::new(),
+            editor: None,
+            tracks: self.tracks.iter().map(|track| Track {
+                channels: vec![Channel::default(); track.channels.len()],
+                ..track.clone()
+            }).collect(),
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            track_history: Vec::new(),
+            has_unsaved_changes: false,
+            unknown_fields: 0,
+            version: next_version(),
+            ..self.clone()
+        };
+        // the template path is unrelated to sample paths
+        template.embed_samples();
+        template
+    }
+
     /// Load a module from `path`.
     pub fn load(path: &PathBuf) -> Result<Self, Box<dyn Error>> {
         let file = File::open(path)?;
         let mut input = Vec::new();
         GzDecoder::new(BufReader::new(file)).read_to_end(&mut input)?;
-        let mut module = rmp_serde::from_slice::<Self>(&input)?;
-        module.init_patches();
+        let mut module = Self::from_bytes(&input)?;
+        module.init_patches(path.parent());
+        Ok(module)
+    }
+
+    /// Deserialize and migrate a module from uncompressed file data.
+    fn from_bytes(input: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let invalid = |e: rmp_serde::decode::Error|
+            format!("Module data is invalid or incompatible: {e}");
+
+        // files without a version header are version 0
+        let (version, data) = match rmp_serde::from_slice::<(u32, IgnoredAny)>(input) {
+            Ok((version, _)) if version > FORMAT_VERSION => return Err(format!(
+                "Module was saved by a newer version of {} (format version {version}, \
+                this version supports up to {FORMAT_VERSION})", crate::APP_NAME).into()),
+            Ok((version, _)) => (version, versioned_module_data(input)
+                .ok_or("Module data is invalid or incompatible")?),
+            Err(_) => (0, input),
+        };
+
+        // fields added by a newer version without a format change are
+        // dropped, and counted so that they can be reported
+        let len = msgpack_array_header(data).map_or(0, |(len, _)| len);
+        let mut module = if len > MODULE_FIELDS.len() {
+            let fields = split_msgpack_array(data).map(|(_, x)| x).unwrap_or_default();
+            let known = join_msgpack_array(&fields[..MODULE_FIELDS.len().min(fields.len())]);
+            rmp_serde::from_slice::<Self>(&known)
+        } else {
+            rmp_serde::from_slice::<Self>(data)
+        }.map_err(invalid)?;
+        module.unknown_fields = len.saturating_sub(MODULE_FIELDS.len());
+
+        for migrate in &MIGRATIONS[version as usize..] {
+            migrate(&mut module);
+        }
         Ok(module)
     }
 
+    /// Load as much of a damaged module file as possible, using defaults for
+    /// sections that can't be read. Returns the module and the names of the
+    /// dropped sections.
+    pub fn recover(path: &PathBuf) -> Result<(Self, Vec<&'static str>), Box<dyn Error>> {
+        let file = File::open(path)?;
+        let mut input = Vec::new();
+        // keep whatever was decompressed before any error
+        let _ = GzDecoder::new(BufReader::new(file)).read_to_end(&mut input);
+        let (mut module, dropped) = Self::recover_from_bytes(&input)?;
+        module.init_patches(path.parent());
+        Ok((module, dropped))
+    }
+
+    /// Recover module fields from uncompressed file data, one at a time.
+    fn recover_from_bytes(input: &[u8]) -> Result<(Self, Vec<&'static str>), Box<dyn Error>> {
+        const UNREADABLE: &str = "No module data could be recovered";
+        let (mut len, mut fields) = split_msgpack_array(input).ok_or(UNREADABLE)?;
+
+        // unwrap the version header, if present
+        if len == 2 && fields.first().is_some_and(|b| rmp_serde::from_slice::<u32>(b).is_ok()) {
+            (len, fields) = fields.get(1).copied().and_then(split_msgpack_array)
+                .ok_or(UNREADABLE)?;
+        }
+        if fields.is_empty() {
+            return Err(UNREADABLE.into())
+        }
+
+        let mut m = Self::new(Default::default());
+        let mut dropped = Vec::new();
+        let f = |i| FieldRecovery { fields: &fields, len, i, name: MODULE_FIELDS[i] };
+        f(0).read(&mut m.title, &mut dropped);
+        f(1).read(&mut m.author, &mut dropped);
+        f(2).read(&mut m.tuning, &mut dropped);
+        f(3).read(&mut m.fx, &mut dropped);
+        f(4).read(&mut m.kit, &mut dropped);
+        f(5).read(&mut m.patches, &mut dropped);
+        f(6).read(&mut m.tracks, &mut dropped);
+        f(7).read(&mut m.division, &mut dropped);
+        f(8).read(&mut m.adaptive_ji, &mut dropped);
+        f(9).read(&mut m.ji_tolerance, &mut dropped);
+        f(10).read(&mut m.extra_tunings, &mut dropped);
+        f(11).read(&mut m.key_map, &mut dropped);
+        f(12).read(&mut m.editor, &mut dropped);
+        f(13).read(&mut m.transpose, &mut dropped);
+        f(14).read(&mut m.pitch_shift, &mut dropped);
+        f(15).read(&mut m.render_seed, &mut dropped);
+
+        // remove references to patches that weren't recovered
+        let n = m.patches.len();
+        m.kit.retain(|entry| entry.patch_index < n);
+        for track in &mut m.tracks {
+            if matches!(track.target, TrackTarget::Patch(i) if i >= n) {
+                track.target = TrackTarget::None;
+            }
+        }
+
+        m.has_unsaved_changes = true;
+        m.unknown_fields = len.saturating_sub(MODULE_FIELDS.len());
+        Ok((m, dropped))
+    }
+
     /// Initialize deserialized patches.
-    fn init_patches(&mut self) {
+    fn init_patches(&mut self, base_dir: Option<&Path>) {
         for patch in &mut self.patches {
-            patch.init();
+            patch.init(base_dir);
         }
     }
 
@@ -83,13 +376,68 @@ impl Module {
     /// editor stores the working beat division, not the module.
     pub fn save(&mut self, division: u8, path: &PathBuf) -> Result<(), Box<dyn Error>> {
         self.division = division;
-        let contents = rmp_serde::to_vec(self)?;
+
+        // linked samples are saved as references, not data
+        let base_dir = path.parent();
+        let stripped: Vec<_> = self.patches.iter_mut()
+            .flat_map(|p| p.pcm_data_mut())
+            .map(|data| data.strip_data(base_dir))
+            .collect();
+        let contents = rmp_serde::to_vec(&(FORMAT_VERSION, &*self));
+        for (data, stripped) in self.patches.iter_mut()
+            .flat_map(|p| p.pcm_data_mut())
+            .zip(stripped) {
+            if let Some(stripped) = stripped {
+                data.restore_data(stripped);
+            }
+        }
+
+        let contents = contents?;
         let file = File::create(path)?;
         GzEncoder::new(file, Default::default()).write_all(&contents)?;
         self.has_unsaved_changes = false;
         Ok(())
     }
 
+    /// Copy linked samples into the sample directory next to the module at
+    /// `path`. Returns the number of samples collected.
+    pub fn collect_samples(&mut self, path: &Path) -> Result<usize, Box<dyn Error>> {
+        let base_dir = path.parent().ok_or("invalid module path")?;
+        let mut n = 0;
+        let mut result = Ok(());
+        for data in self.patches.iter_mut().flat_map(|p| p.pcm_data_mut()) {
+            match data.collect(base_dir) {
+                Ok(true) => n += 1,
+                Ok(false) => (),
+                Err(e) => {
+                    result = Err(e);
+                    break
+                }
+            }
+        }
+        // samples collected before an error still reference their copies
+        if n > 0 {
+            self.has_unsaved_changes = true;
+        }
+        result.map(|_| n)
+    }
+
+    /// Embed all linked samples in the module. Returns the number of samples
+    /// embedded.
+    pub fn embed_samples(&mut self) -> usize {
+        let mut n = 0;
+        for data in self.patches.iter_mut().flat_map(|p| p.pcm_data_mut()) {
+            if data.is_linked() {
+                data.embed();
+                n += 1;
+            }
+        }
+        if n > 0 {
+            self.has_unsaved_changes = true;
+        }
+        n
+    }
+
     /// Map a patch index and note to a patch and note, accounting for kit
     /// mappings.
     pub fn map_input(&self,
@@ -109,6 +457,57 @@ impl Module {
             .and_then(|x| self.patches.get(x.patch_index).map(|p| (p, x.patch_note)))
     }
 
+    /// Returns the name of the kit mapping that `note` activates, if it's
+    /// named.
+    pub fn kit_name(&self, note: Note) -> Option<&str> {
+        self.kit.iter()
+            .find(|x| x.input_note == note)
+            .map(|x| x.name.as_str())
+            .filter(|s| !s.is_empty())
+    }
+
+    /// Replace the kit with mappings for `patches`, with input notes rising
+    /// by one step of the tuning from `start`.
+    pub fn fill_kit(&mut self, patches: &[usize], start: Note) {
+        self.kit = patches.iter().enumerate().map(|(i, &patch_index)| KitEntry {
+            input_note: start.step_shift(i as isize, &self.tuning),
+            patch_index,
+            ..Default::default()
+        }).collect();
+        self.has_unsaved_changes = true;
+    }
+
+    /// Set the input note of the first kit mapping for `patch_index`, adding
+    /// a mapping if there is none. Other mappings for the note are removed.
+    pub fn learn_kit_note(&mut self, patch_index: usize, note: Note) {
+        self.kit.retain(|x| x.input_note != note || x.patch_index == patch_index);
+        if let Some(entry) = self.kit.iter_mut().find(|x| x.patch_index == patch_index) {
+            entry.input_note = note;
+        } else {
+            self.kit.push(KitEntry { input_note: note, patch_index, ..Default::default() });
+        }
+        self.has_unsaved_changes = true;
+    }
+
+    /// Insert a patch, shifting kit and track references to later patches.
+    fn insert_patch(&mut self, index: usize, patch: Patch) {
+        self.patches.insert(index, patch);
+
+        for entry in self.kit.iter_mut() {
+            if entry.patch_index >= index {
+                entry.patch_index += 1;
+            }
+        }
+
+        for track in self.tracks.iter_mut() {
+            if let TrackTarget::Patch(i) = &mut track.target {
+                if *i >= index {
+                    *i += 1;
+                }
+            }
+        }
+    }
+
     /// Remove the patch at `index`.
     fn remove_patch(&mut self, index: usize) -> Patch {
         let patch = self.patches.remove(index);
@@ -133,6 +532,33 @@ impl Module {
         patch
     }
 
+    /// Move a patch from index `from` to index `to`, updating kit and track
+    /// references to the patches in between.
+    fn move_patch(&mut self, from: usize, to: usize) {
+        let patch = self.patches.remove(from);
+        self.patches.insert(to, patch);
+
+        let remap = |i: usize| if i == from {
+            to
+        } else if from < to && i > from && i <= to {
+            i - 1
+        } else if to < from && i >= to && i < from {
+            i + 1
+        } else {
+            i
+        };
+
+        for entry in self.kit.iter_mut() {
+            entry.patch_index = remap(entry.patch_index);
+        }
+
+        for track in self.tracks.iter_mut() {
+            if let TrackTarget::Patch(i) = &mut track.target {
+                *i = remap(*i);
+            }
+        }
+    }
+
     /// Return copies of pattern events between two positions.
     /// The end tick is exclusive unless start and end ticks are equal.
     pub fn scan_events(&self, start: Position, end: Position) -> Vec<LocatedEvent> {
@@ -202,6 +628,22 @@ impl Module {
         })
     }
 
+    /// Returns the MIDI pitch that `note` plays at in `tuning`, after the
+    /// song transpose and pitch shift. Kit notes are only pitch shifted.
+    pub fn play_pitch(&self, tuning: &Tuning, note: &Note, kit: bool) -> f32 {
+        let pitch = if kit || self.transpose == 0 {
+            tuning.midi_pitch(note)
+        } else {
+            tuning.midi_pitch(&note.step_shift(self.transpose as isize, tuning))
+        };
+        pitch + self.pitch_shift / 100.0
+    }
+
+    /// Returns true if `track` plays kit notes.
+    pub fn is_kit_track(&self, track: usize) -> bool {
+        matches!(self.tracks.get(track).map(|t| t.target), Some(TrackTarget::Kit))
+    }
+
     /// Push an edit appending a new track.
     pub fn add_track(&mut self) {
         let index = self.tracks.len();
@@ -257,6 +699,10 @@ impl Module {
     /// Performs an edit operation and returns its inverse.
     fn flip_edit(&mut self, edit: Edit) -> Edit {
         self.has_unsaved_changes = true;
+        self.version = next_version();
+        for i in self.modified_tracks(&edit) {
+            self.track_history.push(TrackEdit::Modify(i));
+        }
         match edit {
             Edit::InsertTrack(index, track) => {
                 self.tracks.insert(index, track);
@@ -304,12 +750,27 @@ impl Module {
                 Edit::PatternData { remove: flip_remove, add: flip_add }
             }
             Edit::InsertPatch(index, patch) => {
-                self.patches.insert(index, patch);
+                self.insert_patch(index, patch);
                 Edit::RemovePatch(index)
             }
             Edit::RemovePatch(index) => {
+                // kit entries and tracks that use the patch need restoring too
+                let mut restore: Vec<_> = self.kit.iter().enumerate()
+                    .filter(|(_, x)| x.patch_index == index)
+                    .map(|(i, x)| Edit::InsertKitEntry(i, x.clone()))
+                    .collect();
+                restore.extend(self.tracks.iter().enumerate()
+                    .filter(|(_, t)|
+                        matches!(t.target, TrackTarget::Patch(i) if i == index))
+                    .map(|(i, _)| Edit::RemapTrack(i, TrackTarget::Patch(index))));
+
                 let patch = self.remove_patch(index);
-                Edit::InsertPatch(index, patch)
+                if restore.is_empty() {
+                    Edit::InsertPatch(index, patch)
+                } else {
+                    restore.insert(0, Edit::InsertPatch(index, patch));
+                    Edit::Group(restore)
+                }
             }
             Edit::ShiftEvents { channels, start, distance, insert } => {
                 // shift/delete events starting at selection
@@ -343,6 +804,33 @@ impl Module {
                     insert: deleted,
                 }
             },
+            Edit::InsertKitEntry(index, entry) => {
+                self.kit.insert(index, entry);
+                Edit::RemoveKitEntry(index)
+            }
+            Edit::RemoveKitEntry(index) => {
+                let entry = self.kit.remove(index);
+                Edit::InsertKitEntry(index, entry)
+            }
+            Edit::MovePatch(from, to) => {
+                self.move_patch(from, to);
+                Edit::MovePatch(to, from)
+            }
+            Edit::PatchGains(gains) => {
+                Edit::PatchGains(gains.into_iter().map(|(i, gain)| {
+                    let param = &self.patches[i].gain.0;
+                    let old = param.value();
+                    param.set(gain);
+                    (i, old)
+                }).collect())
+            }
+            Edit::Group(edits) => {
+                let mut flipped: Vec<_> = edits.into_iter()
+                    .map(|edit| self.flip_edit(edit))
+                    .collect();
+                flipped.reverse();
+                Edit::Group(flipped)
+            }
             Edit::ReplaceEvents(events) => {
                 Edit::ReplaceEvents(events.into_iter().map(|event| {
                     self.replace_event(event)
@@ -351,6 +839,59 @@ impl Module {
         }
     }
 
+    /// Returns the indices of tracks whose output could be changed by an edit.
+    /// Track insertion/removal and groups are not included.
+    fn modified_tracks(&self, edit: &Edit) -> Vec<usize> {
+        let mut tracks: Vec<usize> = match edit {
+            Edit::InsertTrack(..) | Edit::RemoveTrack(_) | Edit::Group(_) => Vec::new(),
+            Edit::RemapTrack(i, _) | Edit::AddChannel(i, _) | Edit::RemoveChannel(i)
+                => vec![*i],
+            Edit::PatternData { remove, add } => remove.iter().map(|p| p.track)
+                .chain(add.iter().map(|e| e.track))
+                .collect(),
+            Edit::ShiftEvents { channels, insert, .. } => channels.iter()
+                .map(|c| c.track as usize)
+                .chain(insert.iter().map(|e| e.track))
+                .collect(),
+            Edit::ReplaceEvents(events) => events.iter().map(|e| e.track).collect(),
+            Edit::InsertKitEntry(..) | Edit::RemoveKitEntry(_) =>
+                self.tracks.iter().enumerate()
+                    .filter(|(_, t)| matches!(t.target, TrackTarget::Kit))
+                    .map(|(i, _)| i)
+                    .collect(),
+            Edit::PatchGains(gains) =>
+                self.tracks.iter().enumerate()
+                    .filter(|(_, t)| match t.target {
+                        TrackTarget::Patch(i) => gains.iter().any(|(j, _)| *j == i),
+                        TrackTarget::Kit => true,
+                        _ => false,
+                    })
+                    .map(|(i, _)| i)
+                    .collect(),
+            Edit::MovePatch(from, to) =>
+                self.tracks.iter().enumerate()
+                    .filter(|(_, t)| match t.target {
+                        TrackTarget::Patch(i) => (*from.min(to)..=*from.max(to)).contains(&i),
+                        TrackTarget::Kit => true,
+                        _ => false,
+                    })
+                    .map(|(i, _)| i)
+                    .collect(),
+            Edit::InsertPatch(index, _) | Edit::RemovePatch(index) =>
+                self.tracks.iter().enumerate()
+                    .filter(|(_, t)| match t.target {
+                        TrackTarget::Patch(i) => i >= *index,
+                        TrackTarget::Kit => matches!(edit, Edit::RemovePatch(_)),
+                        _ => false,
+                    })
+                    .map(|(i, _)| i)
+                    .collect(),
+        };
+        tracks.sort();
+        tracks.dedup();
+        tracks
+    }
+
     /// Replace an event in-place, returning the old value.
     pub fn replace_event(&mut self, new_evt: LocatedEvent) -> LocatedEvent {
         if let Some(old_evt) = self.event_at(&new_evt.position()) {
@@ -365,6 +906,71 @@ impl Module {
         }
     }
 
+    /// Push an edit that merges patches, kit entries, and tracks from another
+    /// module. Patches used by imported kit entries and tracks are imported
+    /// even if not selected. The global track is never imported.
+    pub fn import(&mut self, other: &Module, patches: &[usize], kit: bool,
+        tracks: &[usize]
+    ) {
+        // collect patches to import, in order
+        let mut patch_indices: Vec<usize> = patches.to_vec();
+        if kit {
+            patch_indices.extend(other.kit.iter().map(|e| e.patch_index));
+        }
+        for &i in tracks {
+            if let Some(TrackTarget::Patch(j)) = other.tracks.get(i).map(|t| t.target) {
+                patch_indices.push(j);
+            }
+        }
+        patch_indices.retain(|&i| i < other.patches.len());
+        patch_indices.sort();
+        patch_indices.dedup();
+
+        let remap = |i: usize| patch_indices.iter().position(|&j| j == i)
+            .map(|j| self.patches.len() + j);
+        let mut edits = Vec::new();
+
+        for (j, &i) in patch_indices.iter().enumerate() {
+            edits.push(Edit::InsertPatch(self.patches.len() + j,
+                other.patches[i].clone()));
+        }
+
+        if kit {
+            let mut kit_index = self.kit.len();
+            for entry in &other.kit {
+                if let Some(patch_index) = remap(entry.patch_index) {
+                    edits.push(Edit::InsertKitEntry(kit_index, KitEntry {
+                        patch_index,
+                        ..entry.clone()
+                    }));
+                    kit_index += 1;
+                }
+            }
+        }
+
+        let mut track_index = self.tracks.len();
+        for &i in tracks {
+            if let Some(track) = other.tracks.get(i) {
+                let target = match track.target {
+                    TrackTarget::Global => continue,
+                    TrackTarget::Patch(j) => remap(j)
+                        .map(TrackTarget::Patch)
+                        .unwrap_or(TrackTarget::None),
+                    target => target,
+                };
+                edits.push(Edit::InsertTrack(track_index, Track {
+                    target,
+                    ..track.clone()
+                }));
+                track_index += 1;
+            }
+        }
+
+        if !edits.is_empty() {
+            self.push_edit(Edit::Group(edits));
+        }
+    }
+
     /// Returns true if there was something to undo.
     pub fn undo(&mut self) -> bool {
         if let Some(edit) = self.undo_stack.pop() {
@@ -387,11 +993,42 @@ impl Module {
         }
     }
 
-    /// Returns track insertions & removals made since the last call.
+    /// Returns a value that changes whenever the module is edited.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Returns track insertions, removals, and modifications made since the
+    /// last call.
     pub fn drain_track_history(&mut self) -> Vec<TrackEdit> {
         self.track_history.drain(..).collect()
     }
 
+    /// Record a change to the patch at `index` made outside of `push_edit`,
+    /// marking tracks that play the patch as modified.
+    pub fn patch_edited(&mut self, index: usize) {
+        self.has_unsaved_changes = true;
+        self.version = next_version();
+        for (i, track) in self.tracks.iter().enumerate() {
+            let plays_patch = match track.target {
+                TrackTarget::Patch(j) => j == index,
+                TrackTarget::Kit => self.kit.iter().any(|x| x.patch_index == index),
+                _ => false,
+            };
+            if plays_patch {
+                self.track_history.push(TrackEdit::Modify(i));
+            }
+        }
+    }
+
+    /// Record a change to settings that affect every track, such as tuning,
+    /// made outside of `push_edit`.
+    pub fn global_edited(&mut self) {
+        self.has_unsaved_changes = true;
+        self.version = next_version();
+        self.track_history.push(TrackEdit::Modify(0));
+    }
+
     /// Returns the last loop event before beat count `before_time`.
     pub fn find_loop_start(&self, before_time: f64) -> Option<Timespan> {
         self.tracks[0].channels.iter().flat_map(|c| {
@@ -409,7 +1046,7 @@ impl Module {
     }
 
     /// Return all events in the global channel, in sorted order.
-    fn ctrl_events(&self) -> Vec<&Event> {
+    pub fn ctrl_events(&self) -> Vec<&Event> {
         let mut events: Vec<_> = self.tracks[0].channels.iter()
             .flat_map(|c| c.events.iter())
             .collect();
@@ -417,6 +1054,115 @@ impl Module {
         events
     }
 
+    /// Returns the value of the last global modulation event before `tick`.
+    pub fn global_modulation_before(&self, tick: Timespan) -> Option<u8> {
+        self.ctrl_events().into_iter()
+            .filter(|e| e.tick < tick)
+            .filter_map(|e| match e.data {
+                EventData::GlobalModulation(v) => Some(v),
+                _ => None,
+            })
+            .last()
+    }
+
+    /// Returns the ticks of the loop start and the first End event, if the
+    /// module loops.
+    pub fn loop_range(&self) -> Option<(Timespan, Timespan)> {
+        let end = self.end_tick()?;
+        self.find_loop_start(end.as_f64()).map(|start| (start, end))
+    }
+
+    /// Returns the tick of the first End event, if any.
+    pub fn end_tick(&self) -> Option<Timespan> {
+        self.ctrl_events().into_iter()
+            .find(|e| e.data == EventData::End)
+            .map(|e| e.tick)
+    }
+
+    /// Returns an edit that replaces all End events with one at `tick`.
+    pub fn move_end(&self, tick: Timespan) -> Edit {
+        let end = LocatedEvent::from_position(
+            Position::new(tick, 0, 0, EventData::End.logical_column()), EventData::End);
+        let mut remove: Vec<_> = self.tracks[0].channels.iter().enumerate()
+            .flat_map(|(i, c)| c.events.iter()
+                .filter(|e| e.data == EventData::End)
+                .map(move |e| Position::new(e.tick, 0, i, e.data.logical_column())))
+            .collect();
+        remove.push(end.position());
+        Edit::PatternData { remove, add: vec![end] }
+    }
+
+    /// Returns an edit that deletes all events after the first End event, or
+    /// None if there are no such events.
+    pub fn trim_to_end(&self) -> Option<Edit> {
+        let end = self.end_tick()?;
+        let remove: Vec<_> = self.tracks.iter().enumerate()
+            .flat_map(|(t, track)| track.channels.iter().enumerate()
+                .flat_map(move |(c, channel)| channel.events.iter()
+                    .filter(move |e| e.tick > end)
+                    .map(move |e| Position::new(e.tick, t, c, e.data.logical_column()))))
+            .collect();
+        (!remove.is_empty()).then_some(Edit::PatternData { remove, add: Vec::new() })
+    }
+
+    /// Returns an edit that replaces all Loop and End events with a Loop
+    /// event at `start` and an End event at `end`.
+    pub fn set_loop_events(&self, start: Timespan, end: Timespan) -> Edit {
+        let add = vec![
+            LocatedEvent::from_position(
+                Position::new(start, 0, 0, EventData::Loop.logical_column()), EventData::Loop),
+            LocatedEvent::from_position(
+                Position::new(end, 0, 0, EventData::End.logical_column()), EventData::End),
+        ];
+        let mut remove: Vec<_> = self.tracks[0].channels.iter().enumerate()
+            .flat_map(|(i, c)| c.events.iter()
+                .filter(|e| matches!(e.data, EventData::Loop | EventData::End))
+                .map(move |e| Position::new(e.tick, 0, i, e.data.logical_column())))
+            .collect();
+        remove.extend(add.iter().map(|e| e.position()));
+        Edit::PatternData { remove, add }
+    }
+
+    /// Returns descriptions of problems with Loop and End events.
+    pub fn control_warnings(&self) -> Vec<&'static str> {
+        let events = self.ctrl_events();
+        let ends: Vec<_> = events.iter()
+            .filter(|e| e.data == EventData::End)
+            .map(|e| e.tick)
+            .collect();
+        let loops: Vec<_> = events.iter()
+            .filter(|e| e.data == EventData::Loop)
+            .map(|e| e.tick)
+            .collect();
+        let mut warnings = Vec::new();
+
+        if ends.len() > 1 {
+            warnings.push("Multiple End events; only the first is used");
+        }
+        if let Some(end) = ends.first() {
+            if loops.iter().any(|t| t >= end) {
+                warnings.push("Loop event at or after End");
+            }
+        } else if !loops.is_empty() {
+            warnings.push("Loop event without End");
+        }
+        if loops.first() == Some(&Timespan::ZERO) {
+            warnings.push("Loop at tick 0 has nothing before it");
+        }
+
+        warnings
+    }
+
+    /// Returns an edit that moves the end of the song later by `distance`.
+    /// If there is no End event, one is placed `distance` after the last
+    /// event.
+    pub fn pad_end(&self, distance: Timespan) -> Edit {
+        let end = self.end_tick()
+            .or_else(|| self.last_event_tick())
+            .unwrap_or_default();
+        self.move_end(end + distance)
+    }
+
     /// Returns true if the module loops.
     pub fn loops(&self) -> bool {
         for event in self.ctrl_events() {
@@ -457,6 +1203,55 @@ impl Module {
         }).max()
     }
 
+    /// Returns the number of tunings in the module.
+    pub fn tuning_count(&self) -> usize {
+        self.extra_tunings.len() + 1
+    }
+
+    /// Returns the tuning at an index, where 0 is the main tuning. Invalid
+    /// indices fall back to the main tuning.
+    pub fn tuning_at(&self, index: usize) -> &Tuning {
+        index.checked_sub(1)
+            .and_then(|i| self.extra_tunings.get(i))
+            .unwrap_or(&self.tuning)
+    }
+
+    /// Mutable version of `tuning_at`.
+    pub fn tuning_at_mut(&mut self, index: usize) -> &mut Tuning {
+        match index.checked_sub(1) {
+            Some(i) if i < self.extra_tunings.len() => &mut self.extra_tunings[i],
+            _ => &mut self.tuning,
+        }
+    }
+
+    /// Returns the global tuning index and per-track tuning overrides in
+    /// effect after all tuning changes before `tick`. A global tuning change
+    /// clears track overrides.
+    pub fn tunings_before(&self, tick: Timespan) -> (usize, Vec<Option<usize>>) {
+        let mut changes: Vec<_> = self.tracks.iter().enumerate()
+            .flat_map(|(i, track)| track.channels.iter()
+                .flat_map(move |c| c.events.iter().map(move |e| (i, e))))
+            .filter_map(|(i, e)| match e.data {
+                EventData::TuningChange(n) if e.tick < tick => Some((e.tick, i, n)),
+                _ => None,
+            })
+            .collect();
+        changes.sort_by_key(|(tick, track, _)| (*tick, *track));
+
+        let mut global = 0;
+        let mut tracks = vec![None; self.tracks.len()];
+        for (_, track, n) in changes {
+            if track == 0 {
+                global = n as usize;
+                tracks.fill(None);
+            } else {
+                tracks[track] = Some(n as usize);
+            }
+        }
+
+        (global, tracks)
+    }
+
     /// Return the tempo at a given tick.
     pub fn tempo_at(&self, tick: Timespan) -> f32 {
         let mut result = DEFAULT_TEMPO;
@@ -472,6 +1267,83 @@ impl Module {
         result
     }
 
+    /// Returns the tempo from each tempo change onward, and whether it glides
+    /// toward the next change.
+    pub fn tempo_map(&self) -> Vec<(Timespan, f32, bool)> {
+        let mut points = vec![(Timespan::ZERO, DEFAULT_TEMPO, false)];
+        let mut gliding = false;
+
+        for evt in self.ctrl_events() {
+            let last = points.last_mut().expect("tempo map should not be empty");
+            let tempo = match evt.data {
+                EventData::Tempo(t) => t,
+                EventData::RationalTempo(n, d) => last.1 * n as f32 / d as f32,
+                EventData::StartGlide(GLOBAL_COLUMN) => {
+                    gliding = true;
+                    last.2 = true;
+                    continue
+                }
+                EventData::EndGlide(GLOBAL_COLUMN) => {
+                    gliding = false;
+                    continue
+                }
+                _ => continue,
+            };
+            if last.0 == evt.tick {
+                last.1 = tempo;
+            } else {
+                points.push((evt.tick, tempo, gliding));
+            }
+        }
+
+        points
+    }
+
+    /// Returns the playback time of `tick` in seconds.
+    pub fn time_at(&self, tick: Timespan) -> f64 {
+        let mut prev_tick = Timespan::ZERO;
+        let mut time = 0.0;
+        let mut tempo = DEFAULT_TEMPO;
+
+        for evt in self.ctrl_events().into_iter().take_while(|e| e.tick < tick) {
+            let new_tempo = match evt.data {
+                EventData::Tempo(t) => t,
+                EventData::RationalTempo(n, d) => tempo * n as f32 / d as f32,
+                _ => continue,
+            };
+            time += tick_interval(evt.tick - prev_tick, tempo);
+            prev_tick = evt.tick;
+            tempo = new_tempo;
+        }
+
+        time + tick_interval(tick - prev_tick, tempo)
+    }
+
+    /// Returns the beat reached after `time` seconds of playback. Inverse of
+    /// `time_at`.
+    pub fn beat_at_time(&self, time: f64) -> f64 {
+        let mut prev_tick = Timespan::ZERO;
+        let mut elapsed = 0.0;
+        let mut tempo = DEFAULT_TEMPO;
+
+        for evt in self.ctrl_events() {
+            let new_tempo = match evt.data {
+                EventData::Tempo(t) => t,
+                EventData::RationalTempo(n, d) => tempo * n as f32 / d as f32,
+                _ => continue,
+            };
+            let dt = tick_interval(evt.tick - prev_tick, tempo);
+            if elapsed + dt > time {
+                break
+            }
+            elapsed += dt;
+            prev_tick = evt.tick;
+            tempo = new_tempo;
+        }
+
+        prev_tick.as_f64() + (time - elapsed) * tempo as f64 / 60.0
+    }
+
     /// Returns the total playtime of the module in seconds.
     pub fn playtime(&self) -> f64 {
         let mut tick = Timespan::ZERO;
@@ -511,12 +1383,42 @@ pub struct KitEntry {
     pub input_note: Note,
     pub patch_index: usize,
     pub patch_note: Note,
+    /// User-assigned name, shown in kit tracks. Empty if unnamed.
+    #[serde(default)]
+    pub name: String,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Track {
     pub target: TrackTarget,
     pub channels: Vec<Channel>,
+    /// User-assigned name. Empty if unnamed.
+    #[serde(default)]
+    pub name: String,
+    /// Index of the user-assigned label color, if any.
+    #[serde(default)]
+    pub color: Option<u8>,
+    /// If true, the track is left out of renders.
+    #[serde(default)]
+    pub audition_only: bool,
+    /// Multiplier for pressure events in playback.
+    #[serde(default = "default_track_scale")]
+    pub pressure_scale: f32,
+    /// Multiplier for modulation events in playback.
+    #[serde(default = "default_track_scale")]
+    pub mod_scale: f32,
+    /// Playback offset in milliseconds. Negative values play early.
+    #[serde(default)]
+    pub offset: f32,
+    /// Tempo-synced panning of the track output, if enabled.
+    #[serde(default)]
+    pub auto_pan: Option<AutoPan>,
+    /// High-pass filter cutoff of the track output in Hz, if enabled.
+    #[serde(default)]
+    pub highpass: Option<f32>,
+    /// Low-pass filter cutoff of the track output in Hz, if enabled.
+    #[serde(default)]
+    pub lowpass: Option<f32>,
 }
 
 impl Track {
@@ -524,8 +1426,56 @@ impl Track {
         Self {
             target,
             channels: vec![Channel::default()],
+            name: String::new(),
+            color: None,
+            audition_only: false,
+            pressure_scale: default_track_scale(),
+            mod_scale: default_track_scale(),
+            offset: 0.0,
+            auto_pan: None,
+            highpass: None,
+            lowpass: None,
         }
     }
+
+    /// Returns a pressure level from 0 to 1 after track scaling.
+    pub fn scale_pressure(&self, level: f32) -> f32 {
+        (level * self.pressure_scale).min(1.0)
+    }
+
+    /// Returns a modulation level from 0 to 1 after track scaling.
+    pub fn scale_modulation(&self, level: f32) -> f32 {
+        (level * self.mod_scale).min(1.0)
+    }
+}
+
+/// Tempo-synced sine panning of a track's output.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AutoPan {
+    /// Length of a cycle, in beats.
+    pub period: f32,
+    /// Sweep width, from 0 to 1.
+    pub depth: f32,
+    /// Phase offset, from 0 to 1 cycle.
+    pub phase: f32,
+}
+
+impl Default for AutoPan {
+    fn default() -> Self {
+        Self {
+            period: 4.0,
+            depth: 1.0,
+            phase: 0.0,
+        }
+    }
+}
+
+impl AutoPan {
+    /// Returns the pan position at `beat`, from -1 to 1.
+    pub fn pan_at(&self, beat: f64) -> f32 {
+        let t = beat / self.period.max(f32::EPSILON) as f64 + self.phase as f64;
+        self.depth * (t * std::f64::consts::TAU).sin() as f32
+    }
 }
 
 /// Track "output" mapping.
@@ -600,6 +1550,30 @@ impl Channel {
         glide
     }
 
+    /// Returns the curve of the glide starting at `tick` in a (spatial) column.
+    pub fn glide_curve(&self, col: u8, tick: Timespan) -> GlideCurve {
+        self.events.iter().find_map(|e| match e.data {
+            EventData::GlideCurve(i, curve) if i == col && e.tick == tick => Some(curve),
+            _ => None,
+        }).unwrap_or_default()
+    }
+
+    /// Returns the note delay at `tick`, if any.
+    pub fn note_delay(&self, tick: Timespan) -> Option<Timespan> {
+        self.events.iter().find_map(|e| match e.data {
+            EventData::NoteDelay(t) if e.tick == tick => Some(t),
+            _ => None,
+        })
+    }
+
+    /// Returns the note cut at `tick`, if any.
+    pub fn note_cut(&self, tick: Timespan) -> Option<Timespan> {
+        self.events.iter().find_map(|e| match e.data {
+            EventData::NoteCut(t) if e.tick == tick => Some(t),
+            _ => None,
+        })
+    }
+
     /// Returns the last event before `tick` in `column`.
     pub fn prev_event(&self, column: u8, tick: Timespan) -> Option<&Event> {
         self.events.iter()
@@ -636,6 +1610,167 @@ pub enum EventData {
     Bend(i16),
     /// Section marker. No effect on playback.
     Section,
+    /// Switch to a module tuning by index. Applies to all tracks when in the
+    /// global track.
+    TuningChange(u8),
+    /// Interpolation curve of the glide starting at the same tick. Linear if
+    /// absent.
+    GlideCurve(u8, GlideCurve),
+    /// Pitch LFO for notes in the channel.
+    Vibrato(PatternLfo),
+    /// Volume LFO for notes in the channel.
+    Tremolo(PatternLfo),
+    /// Pitch bend that moves smoothly from the current bend to a target.
+    BendRamp(BendRamp),
+    /// Repeated hits of the channel's last note.
+    Retrigger(Retrigger),
+    /// Delays the note or note off at the same tick by a number of beats.
+    NoteDelay(Timespan),
+    /// Releases the channel's note a number of beats after the event.
+    NoteCut(Timespan),
+    /// Modulation for every channel of every track.
+    GlobalModulation(u8),
+}
+
+/// Number, spacing, and pressure of retriggered hits.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Retrigger {
+    /// Number of hits, evenly spaced starting at the event.
+    pub hits: u8,
+    /// Length of time the hits are spread over, in beats.
+    pub length: Timespan,
+    /// Pressure digit of the last hit. Pressure ramps to it from the current
+    /// pressure if set.
+    pub end_pressure: Option<u8>,
+}
+
+impl Retrigger {
+    /// Returns the offset in beats and the pressure ramp position, from 0 to
+    /// 1, of each hit.
+    pub fn hits(&self) -> impl Iterator<Item = (f64, f32)> + '_ {
+        let n = self.hits.max(1);
+        (0..n).map(move |i| (
+            self.length.as_f64() * i as f64 / n as f64,
+            if n > 1 { i as f32 / (n - 1) as f32 } else { 1.0 },
+        ))
+    }
+}
+
+/// Target, length, and shape of a pattern bend ramp.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BendRamp {
+    /// Cent offset from the starting note.
+    pub cents: i16,
+    /// Length of the ramp in beats.
+    pub duration: Timespan,
+    pub curve: GlideCurve,
+}
+
+/// Depth and rate of a pattern vibrato or tremolo.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PatternLfo {
+    /// Digit value. Zero turns the effect off.
+    pub depth: u8,
+    pub rate: LfoRate,
+}
+
+impl PatternLfo {
+    pub const OFF: Self = Self {
+        depth: 0,
+        rate: LfoRate::Hz(0.0),
+    };
+}
+
+/// Rate of a pattern vibrato or tremolo.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LfoRate {
+    /// Cycles per second.
+    Hz(f32),
+    /// Cycles per number of beats, following the tempo.
+    Sync(u8, u8),
+}
+
+impl LfoRate {
+    /// Returns the rate in cycles per second at `tempo`.
+    pub fn hz(&self, tempo: f32) -> f32 {
+        match *self {
+            Self::Hz(f) => f,
+            Self::Sync(n, d) => n as f32 / d as f32 * tempo / 60.0,
+        }
+    }
+
+    /// Parses a rate written as `<hz>hz`, `<cycles>`, or `<cycles>/<beats>`.
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim().to_lowercase();
+        if let Some(f) = s.strip_suffix("hz") {
+            f.trim().parse().ok()
+                .filter(|f: &f32| *f >= 0.0 && f.is_finite())
+                .map(Self::Hz)
+        } else {
+            let (n, d) = s.split_once('/').unwrap_or((s.as_str(), "1"));
+            let (n, d) = (n.parse().ok()?, d.parse().ok()?);
+            (d > 0).then_some(Self::Sync(n, d))
+        }
+    }
+}
+
+impl Display for LfoRate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Hz(hz) => write!(f, "{hz}hz"),
+            Self::Sync(n, 1) => write!(f, "{n}"),
+            Self::Sync(n, d) => write!(f, "{n}/{d}"),
+        }
+    }
+}
+
+/// Shape of interpolation between events in a glide.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum GlideCurve {
+    #[default]
+    Linear,
+    /// Starts slow and speeds up.
+    Exponential,
+    /// Starts and ends slow.
+    SCurve,
+    /// Moves in discrete steps.
+    Stepped,
+}
+
+impl GlideCurve {
+    pub const VARIANTS: [Self; 4] =
+        [Self::Linear, Self::Exponential, Self::SCurve, Self::Stepped];
+
+    /// Returns the UI string for the curve.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Linear => "Linear",
+            Self::Exponential => "Exponential",
+            Self::SCurve => "S-curve",
+            Self::Stepped => "Stepped",
+        }
+    }
+
+    /// Returns the next curve type, for cycling.
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Linear => Self::Exponential,
+            Self::Exponential => Self::SCurve,
+            Self::SCurve => Self::Stepped,
+            Self::Stepped => Self::Linear,
+        }
+    }
+
+    /// Maps linear progress between two events, from 0 to 1, through the
+    /// curve.
+    pub fn apply(&self, t: f32) -> f32 {
+        match self {
+            Self::Linear => t,
+            Self::Exponential => (EXP_CURVE_BASE.powf(t) - 1.0) / (EXP_CURVE_BASE - 1.0),
+            Self::SCurve => t * t * (3.0 - 2.0 * t),
+            Self::Stepped => (t * STEPPED_CURVE_STEPS).floor() / STEPPED_CURVE_STEPS,
+        }
+    }
 }
 
 impl EventData {
@@ -645,6 +1780,15 @@ impl EventData {
     /// Binary or'ed with "spatial column" value.
     pub const INTERP_COL_FLAG: u8 = 0x80;
 
+    /// Binary or'ed with interpolation column value for glide curves.
+    pub const CURVE_COL_FLAG: u8 = 0x40;
+
+    /// Binary or'ed with note column value for note delays.
+    pub const DELAY_COL_FLAG: u8 = 0x20;
+
+    /// Binary or'ed with note column value for note cuts.
+    pub const CUT_COL_FLAG: u8 = 0x10;
+
     /// Convert a 7-bit MIDI value to a digit value.
     pub fn digit_from_midi(midi_value: u8) -> u8 {
         (midi_value as f32 * Self::DIGIT_MAX as f32 / 127.0).round() as u8
@@ -652,7 +1796,8 @@ impl EventData {
 
     /// Returns the column where the event should be drawn.
     pub fn spatial_column(&self) -> u8 {
-        self.logical_column() & !Self::INTERP_COL_FLAG
+        self.logical_column() & !(Self::INTERP_COL_FLAG | Self::CURVE_COL_FLAG
+            | Self::DELAY_COL_FLAG | Self::CUT_COL_FLAG)
     }
 
     /// Returns a logical column value. Used to distinguish
@@ -663,6 +1808,10 @@ impl EventData {
             Self::Modulation(_) => MOD_COLUMN,
             Self::StartGlide(col) | Self::EndGlide(col) | Self::TickGlide(col)
                 => col | Self::INTERP_COL_FLAG,
+            Self::GlideCurve(col, _)
+                => col | Self::INTERP_COL_FLAG | Self::CURVE_COL_FLAG,
+            Self::NoteDelay(_) => NOTE_COLUMN | Self::DELAY_COL_FLAG,
+            Self::NoteCut(_) => NOTE_COLUMN | Self::CUT_COL_FLAG,
             _ => NOTE_COLUMN,
         }
     }
@@ -671,11 +1820,16 @@ impl EventData {
     pub fn goes_in_track(&self, track: usize) -> bool {
         match self {
             Self::Bend(_) | Self::Pressure(_) | Self::Modulation(_)
-                | Self::NoteOff | Self::Pitch(_) => track != 0,
+                | Self::NoteOff | Self::Pitch(_) | Self::Vibrato(_)
+                | Self::Tremolo(_) | Self::BendRamp(_)
+                | Self::Retrigger(_) | Self::NoteDelay(_)
+                | Self::NoteCut(_) => track != 0,
             Self::Tempo(_) | Self::RationalTempo(_, _)
-                | Self::End | Self::Loop | Self::Section => track == 0,
+                | Self::End | Self::Loop | Self::Section
+                | Self::GlobalModulation(_) => track == 0,
+            Self::TuningChange(_) => true,
             Self::StartGlide(col) | Self::EndGlide(col) | Self::TickGlide(col)
-                => track != 0 || *col == GLOBAL_COLUMN,
+                | Self::GlideCurve(col, _) => track != 0 || *col == GLOBAL_COLUMN,
             Self::InterpolatedModulation(_) | Self::InterpolatedPitch(_)
                 | Self::InterpolatedPressure(_) => false, // never in pattern
         }
@@ -753,6 +1907,14 @@ pub enum Edit {
         insert: Vec<LocatedEvent>,
     },
     ReplaceEvents(Vec<LocatedEvent>),
+    InsertKitEntry(usize, KitEntry),
+    RemoveKitEntry(usize),
+    /// Set the gains of patches, by index.
+    PatchGains(Vec<(usize, f32)>),
+    /// Move a patch from one index to another.
+    MovePatch(usize, usize),
+    /// Multiple edits, applied in order.
+    Group(Vec<Edit>),
 }
 
 /// Position of a channel.
@@ -762,11 +1924,13 @@ pub struct ChannelCoords {
     channel: u8,
 }
 
-/// Used to track added/removed Tracks for synchronizing Player with Module.
-#[derive(Clone)]
+/// Used to track added/removed/modified Tracks for synchronizing Player with
+/// Module.
+#[derive(Clone, Debug, PartialEq)]
 pub enum TrackEdit {
     Insert(usize),
     Remove(usize),
+    Modify(usize),
 }
 
 /// Event with global location data, for the undo stack.
@@ -804,6 +1968,191 @@ impl LocatedEvent {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_recover() {
+        let mut module = Module::new(Default::default());
+        module.title = String::from("Song");
+        module.patches.push(Patch::new(String::from("Lead")));
+        module.tracks[2].target = TrackTarget::Patch(1);
+
+        let bytes = rmp_serde::to_vec(&(FORMAT_VERSION, &module)).unwrap();
+        let (m, dropped) = Module::recover_from_bytes(&bytes).unwrap();
+        assert_eq!(m.patches.len(), 2);
+        assert!(dropped.is_empty());
+
+        // truncate partway through the tracks
+        let (_, outer) = split_msgpack_array(&bytes).unwrap();
+        let (_, fields) = split_msgpack_array(outer[1]).unwrap();
+        let tracks_start = fields[6].as_ptr() as usize - bytes.as_ptr() as usize;
+        let (m, dropped) = Module::recover_from_bytes(&bytes[..tracks_start + 2]).unwrap();
+        assert_eq!(m.title, "Song");
+        assert_eq!(m.patches.len(), 2);
+        assert!(dropped.contains(&"patterns"));
+        assert!(!dropped.contains(&"patches"));
+
+        // unreadable patches leave tracks without a target
+        let patches_start = fields[5].as_ptr() as usize - bytes.as_ptr() as usize;
+        let mut damaged = bytes[..patches_start].to_vec();
+        damaged.push(0x05); // an integer instead of an array
+        damaged.extend(&bytes[tracks_start..]);
+        let (m, dropped) = Module::recover_from_bytes(&damaged).unwrap();
+        assert_eq!(dropped, ["patches"]);
+        assert!(matches!(m.tracks[2].target, TrackTarget::None));
+
+        assert!(Module::recover_from_bytes(&[0xc1]).is_err());
+    }
+
+    #[test]
+    fn test_kit_fill_and_learn() {
+        let mut module = Module::new(Default::default());
+        module.patches.push(Patch::new(String::from("B")));
+        module.patches.push(Patch::new(String::from("C")));
+        let c4 = Note::default();
+        let [cs4, d4, e4] = [1, 2, 4].map(|n| c4.step_shift(n, &module.tuning));
+        let patch_name = |module: &Module, note| module.get_kit_patch(note)
+            .map(|x| x.0.name.clone());
+
+        module.fill_kit(&[0, 1, 2], c4);
+        assert_eq!(module.kit.iter().map(|x| x.patch_index).collect::<Vec<_>>(), [0, 1, 2]);
+        assert_eq!(module.kit[2].input_note, d4);
+        assert_eq!(patch_name(&module, cs4).as_deref(), Some("B"));
+
+        // learning a note takes it from the patch that had it
+        module.learn_kit_note(0, d4);
+        assert_eq!(module.kit.len(), 2);
+        assert_eq!(patch_name(&module, d4).as_deref(), Some("Init"));
+        module.learn_kit_note(2, e4);
+        assert_eq!(module.kit.len(), 3);
+        assert_eq!(patch_name(&module, e4).as_deref(), Some("C"));
+
+        assert_eq!(module.kit_name(e4), None);
+        module.kit[2].name = String::from("Snare");
+        assert_eq!(module.kit_name(e4), Some("Snare"));
+    }
+
+    #[test]
+    fn test_remove_patches() {
+        let mut module = Module::new(Default::default());
+        module.patches.push(Patch::new(String::from("B")));
+        module.patches.push(Patch::new(String::from("C")));
+        module.tracks.push(Track::new(TrackTarget::Patch(2)));
+        module.kit.push(KitEntry { patch_index: 1, ..Default::default() });
+        module.kit.push(KitEntry { patch_index: 2, ..Default::default() });
+
+        // remove the first two patches in one undo step
+        module.push_edit(Edit::Group(vec![Edit::RemovePatch(1), Edit::RemovePatch(0)]));
+        assert_eq!(module.patches.len(), 1);
+        assert!(matches!(module.tracks[2].target, TrackTarget::None));
+        assert!(matches!(module.tracks[3].target, TrackTarget::Patch(0)));
+        assert_eq!(module.kit.len(), 1);
+        assert_eq!(module.kit[0].patch_index, 0);
+
+        // undo restores references to the removed patches
+        assert!(module.undo());
+        assert_eq!(module.patches[1].name, "B");
+        assert!(matches!(module.tracks[2].target, TrackTarget::Patch(0)));
+        assert!(matches!(module.tracks[3].target, TrackTarget::Patch(2)));
+        assert_eq!(module.kit.iter().map(|x| x.patch_index).collect::<Vec<_>>(), [1, 2]);
+
+        assert!(module.redo());
+        assert_eq!(module.patches.len(), 1);
+        assert_eq!(module.kit.len(), 1);
+    }
+
+    #[test]
+    fn test_recovery_fields() {
+        let mut module = Module::new(Default::default());
+        module.title = String::from("Song");
+        module.ji_tolerance = 0.5;
+        module.transpose = -3;
+        module.pitch_shift = 0.25;
+        module.render_seed = 7;
+
+        // every serialized field needs an entry in the recovery table
+        let bytes = rmp_serde::to_vec(&module).unwrap();
+        let (len, fields) = split_msgpack_array(&bytes).unwrap();
+        assert_eq!(len, MODULE_FIELDS.len());
+        assert_eq!(fields.len(), len);
+
+        let (m, dropped) = Module::recover_from_bytes(&bytes).unwrap();
+        assert!(dropped.is_empty());
+        assert_eq!(m.title, "Song");
+        assert_eq!(m.ji_tolerance, 0.5);
+        assert_eq!(m.transpose, -3);
+        assert_eq!(m.pitch_shift, 0.25);
+        assert_eq!(m.render_seed, 7);
+    }
+
+    #[test]
+    fn test_format_version() {
+        let mut module = Module::new(Default::default());
+        module.title = String::from("Song");
+
+        let legacy = rmp_serde::to_vec(&module).unwrap();
+        assert_eq!(Module::from_bytes(&legacy).unwrap().title, "Song");
+
+        let current = rmp_serde::to_vec(&(FORMAT_VERSION, &module)).unwrap();
+        assert_eq!(Module::from_bytes(&current).unwrap().title, "Song");
+
+        let newer = rmp_serde::to_vec(&(FORMAT_VERSION + 1, &module)).unwrap();
+        let e = Module::from_bytes(&newer).err().unwrap();
+        assert!(e.to_string().contains("newer version"));
+
+        // a field appended without a format change is dropped and counted
+        let data = rmp_serde::to_vec(&module).unwrap();
+        let (_, mut fields) = split_msgpack_array(&data).unwrap();
+        let extra = rmp_serde::to_vec(&1u8).unwrap();
+        fields.push(&extra);
+        let version = rmp_serde::to_vec(&FORMAT_VERSION).unwrap();
+        let extended = join_msgpack_array(&[&version, &join_msgpack_array(&fields)]);
+        let m = Module::from_bytes(&extended).unwrap();
+        assert_eq!(m.title, "Song");
+        assert_eq!(m.unknown_fields, 1);
+        assert_eq!(Module::from_bytes(&current).unwrap().unknown_fields, 0);
+    }
+
+    #[test]
+    fn test_template() {
+        let mut module = Module::new(Default::default());
+        module.title = String::from("Song");
+        module.tracks[2].channels.push(Channel::default());
+        module.insert_event(2, 1, Event {
+            tick: Timespan::ZERO,
+            data: EventData::NoteOff,
+        });
+        module.division = 3;
+
+        let template = module.template();
+        assert!(template.title.is_empty());
+        assert_eq!(template.division, 3);
+        assert_eq!(template.tracks.len(), module.tracks.len());
+        assert_eq!(template.tracks[2].channels.len(), 2);
+        assert!(template.tracks.iter()
+            .all(|t| t.channels.iter().all(|c| c.events.is_empty())));
+        assert!(!template.has_unsaved_changes);
+    }
+
+    #[test]
+    fn test_retrigger_hits() {
+        let retrig = Retrigger { hits: 4, length: Timespan::new(1, 2), end_pressure: None };
+        let hits: Vec<_> = retrig.hits().collect();
+        assert_eq!(hits, [(0.0, 0.0), (0.125, 1.0 / 3.0), (0.25, 2.0 / 3.0), (0.375, 1.0)]);
+        let single = Retrigger { hits: 1, ..retrig };
+        assert_eq!(single.hits().collect::<Vec<_>>(), [(0.0, 1.0)]);
+    }
+
+    #[test]
+    fn test_note_timing_columns() {
+        let off = EventData::NoteOff;
+        let delay = EventData::NoteDelay(Timespan::new(1, 8));
+        let cut = EventData::NoteCut(Timespan::new(1, 4));
+        for data in [&delay, &cut] {
+            assert_eq!(data.spatial_column(), NOTE_COLUMN);
+            assert_ne!(data.logical_column(), off.logical_column());
+        }
+        assert_ne!(delay.logical_column(), cut.logical_column());
+    }
+
     #[test]
     fn test_digit_from_midi() {
         assert_eq!(EventData::digit_from_midi(0x00), 0x0);
@@ -811,4 +2160,260 @@ mod tests {
         assert_eq!(EventData::digit_from_midi(0x3f), 0x7);
         assert_eq!(EventData::digit_from_midi(0x40), 0x8);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_glide_curve() {
+        for curve in [GlideCurve::Linear, GlideCurve::Exponential,
+            GlideCurve::SCurve, GlideCurve::Stepped] {
+            assert_eq!(curve.apply(0.0), 0.0);
+            assert!((curve.apply(1.0) - 1.0).abs() < 0.001);
+        }
+        assert!(GlideCurve::Exponential.apply(0.5) < 0.5);
+        assert!(GlideCurve::SCurve.apply(0.25) < 0.25);
+        assert!(GlideCurve::SCurve.apply(0.75) > 0.75);
+        assert_eq!(GlideCurve::Stepped.apply(0.1), 0.0);
+        assert_eq!(GlideCurve::Stepped.apply(0.5), 0.5);
+
+        let mut channel = Channel::default();
+        channel.events.push(Event {
+            tick: Timespan::new(1, 1),
+            data: EventData::GlideCurve(VEL_COLUMN, GlideCurve::SCurve),
+        });
+        assert_eq!(channel.glide_curve(VEL_COLUMN, Timespan::new(1, 1)), GlideCurve::SCurve);
+        assert_eq!(channel.glide_curve(MOD_COLUMN, Timespan::new(1, 1)), GlideCurve::Linear);
+        assert_eq!(channel.glide_curve(VEL_COLUMN, Timespan::ZERO), GlideCurve::Linear);
+        assert!(!channel.is_interpolated(VEL_COLUMN, Timespan::new(1, 1)));
+    }
+
+    #[test]
+    fn test_lfo_rate() {
+        assert_eq!(LfoRate::parse("5.5hz"), Some(LfoRate::Hz(5.5)));
+        assert_eq!(LfoRate::parse("6 Hz"), Some(LfoRate::Hz(6.0)));
+        assert_eq!(LfoRate::parse("2"), Some(LfoRate::Sync(2, 1)));
+        assert_eq!(LfoRate::parse("3/4"), Some(LfoRate::Sync(3, 4)));
+        assert_eq!(LfoRate::parse("1/0"), None);
+        assert_eq!(LfoRate::parse("-1hz"), None);
+        assert_eq!(LfoRate::parse("fast"), None);
+        for rate in [LfoRate::Hz(5.5), LfoRate::Sync(2, 1), LfoRate::Sync(3, 4)] {
+            assert_eq!(LfoRate::parse(&rate.to_string()), Some(rate));
+        }
+        assert_eq!(LfoRate::Sync(1, 2).hz(120.0), 1.0);
+        assert_eq!(LfoRate::Hz(3.0).hz(120.0), 3.0);
+    }
+
+    #[test]
+    fn test_import() {
+        let mut module = Module::new(Default::default());
+        let mut other = Module::new(Default::default());
+        other.patches.push(Patch::new(String::from("Other")));
+        other.tracks[2].target = TrackTarget::Patch(1);
+        other.kit.push(KitEntry {
+            patch_index: 1,
+            ..Default::default()
+        });
+
+        module.import(&other, &[], true, &[0, 2]);
+        assert_eq!(module.patches.len(), 2);
+        assert_eq!(module.patches[1].name, "Other");
+        assert_eq!(module.kit.len(), 1);
+        assert_eq!(module.kit[0].patch_index, 1);
+        assert_eq!(module.tracks.len(), 4);
+        assert!(matches!(module.tracks[3].target, TrackTarget::Patch(1)));
+
+        assert!(module.undo());
+        assert_eq!(module.patches.len(), 1);
+        assert!(module.kit.is_empty());
+        assert_eq!(module.tracks.len(), 3);
+    }
+
+    #[test]
+    fn test_import_invalid_kit_entry() {
+        let mut module = Module::new(Default::default());
+        let mut other = Module::new(Default::default());
+        other.patches.push(Patch::new(String::from("Other")));
+        other.kit.push(KitEntry {
+            patch_index: 5,
+            ..Default::default()
+        });
+        other.kit.push(KitEntry {
+            patch_index: 1,
+            name: String::from("Valid"),
+            ..Default::default()
+        });
+
+        // entries with invalid patches are skipped without leaving gaps
+        module.import(&other, &[], true, &[]);
+        assert_eq!(module.kit.len(), 1);
+        assert_eq!(module.kit[0].name, "Valid");
+        assert_eq!(module.kit[0].patch_index, 1);
+    }
+
+    #[test]
+    fn test_move_patch() {
+        let mut module = Module::new(Default::default());
+        module.patches[0].name = String::from("A");
+        module.patches.push(Patch::new(String::from("B")));
+        module.patches.push(Patch::new(String::from("C")));
+        module.tracks.push(Track::new(TrackTarget::Patch(2)));
+        module.kit.push(KitEntry { patch_index: 1, ..Default::default() });
+
+        module.push_edit(Edit::MovePatch(2, 0));
+        let names: Vec<_> = module.patches.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["C", "A", "B"]);
+        assert!(matches!(module.tracks[2].target, TrackTarget::Patch(1)));
+        assert!(matches!(module.tracks[3].target, TrackTarget::Patch(0)));
+        assert_eq!(module.kit[0].patch_index, 2);
+
+        assert!(module.undo());
+        let names: Vec<_> = module.patches.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["A", "B", "C"]);
+        assert!(matches!(module.tracks[2].target, TrackTarget::Patch(0)));
+        assert!(matches!(module.tracks[3].target, TrackTarget::Patch(2)));
+        assert_eq!(module.kit[0].patch_index, 1);
+    }
+
+    #[test]
+    fn test_tunings_before() {
+        let mut module = Module::new(Default::default());
+        module.extra_tunings.push(Tuning::divide(2.0, 19, 1).unwrap());
+        let change = |n, d, i| Event {
+            tick: Timespan::new(n, d),
+            data: EventData::TuningChange(i),
+        };
+        module.insert_event(2, 0, change(1, 1, 1));
+        module.insert_event(0, 0, change(2, 1, 1));
+        module.insert_event(2, 0, change(3, 1, 0));
+
+        let t = |n| Timespan::new(n, 1);
+        assert_eq!(module.tunings_before(t(1)), (0, vec![None; 3]));
+        assert_eq!(module.tunings_before(t(2)), (0, vec![None, None, Some(1)]));
+        assert_eq!(module.tunings_before(t(3)), (1, vec![None; 3]));
+        assert_eq!(module.tunings_before(t(4)), (1, vec![None, None, Some(0)]));
+        assert_eq!(module.tuning_at(1).size(), 19);
+        assert_eq!(module.tuning_at(5).size(), 12);
+    }
+
+    #[test]
+    fn test_track_scale() {
+        let mut track = Track::new(TrackTarget::None);
+        assert_eq!(track.scale_pressure(0.5), 0.5);
+        track.pressure_scale = 1.5;
+        track.mod_scale = 0.5;
+        assert_eq!(track.scale_pressure(0.5), 0.75);
+        assert_eq!(track.scale_pressure(1.0), 1.0);
+        assert_eq!(track.scale_modulation(1.0), 0.5);
+    }
+
+    #[test]
+    fn test_play_pitch() {
+        let mut module = Module::new(Default::default());
+        let a4 = Note::new(0, crate::pitch::Nominal::A, 0, 4);
+        let tuning = module.tuning.clone();
+        assert_eq!(module.play_pitch(&tuning, &a4, false), 69.0);
+
+        module.transpose = -2;
+        module.pitch_shift = 50.0;
+        assert_eq!(module.play_pitch(&tuning, &a4, false), 67.5);
+        assert_eq!(module.play_pitch(&tuning, &a4, true), 69.5);
+        assert!(module.is_kit_track(1));
+        assert!(!module.is_kit_track(2));
+    }
+
+    #[test]
+    fn test_global_modulation_before() {
+        let mut module = Module::new(Default::default());
+        let t = |n| Timespan::new(n, 1);
+        module.insert_event(0, 0, Event { tick: t(1), data: EventData::GlobalModulation(3) });
+        module.insert_event(0, 0, Event { tick: t(2), data: EventData::Tempo(90.0) });
+        module.insert_event(0, 0, Event { tick: t(3), data: EventData::GlobalModulation(9) });
+        assert_eq!(module.global_modulation_before(t(1)), None);
+        assert_eq!(module.global_modulation_before(t(3)), Some(3));
+        assert_eq!(module.global_modulation_before(t(4)), Some(9));
+        assert!(EventData::GlobalModulation(0).goes_in_track(0));
+        assert!(!EventData::GlobalModulation(0).goes_in_track(1));
+    }
+
+    #[test]
+    fn test_tempo_map() {
+        let mut module = Module::new(Default::default());
+        let event = |n, data| Event { tick: Timespan::new(n, 1), data };
+        module.insert_event(0, 0, event(0, EventData::Tempo(100.0)));
+        module.insert_event(0, 0, event(0, EventData::StartGlide(GLOBAL_COLUMN)));
+        module.insert_event(0, 0, event(4, EventData::Tempo(150.0)));
+        module.insert_event(0, 0, event(4, EventData::EndGlide(GLOBAL_COLUMN)));
+        module.insert_event(0, 0, event(8, EventData::RationalTempo(1, 2)));
+
+        let t = |n| Timespan::new(n, 1);
+        assert_eq!(module.tempo_map(), vec![
+            (t(0), 100.0, true),
+            (t(4), 150.0, false),
+            (t(8), 75.0, false),
+        ]);
+    }
+
+    #[test]
+    fn test_time_at() {
+        let mut module = Module::new(Default::default());
+        let event = |n, data| Event { tick: Timespan::new(n, 1), data };
+        module.insert_event(0, 0, event(0, EventData::Tempo(60.0)));
+        module.insert_event(0, 0, event(4, EventData::RationalTempo(2, 1)));
+
+        assert_eq!(module.time_at(Timespan::ZERO), 0.0);
+        assert_eq!(module.time_at(Timespan::new(4, 1)), 4.0);
+        assert_eq!(module.time_at(Timespan::new(6, 1)), 5.0);
+
+        assert_eq!(module.beat_at_time(2.0), 2.0);
+        assert_eq!(module.beat_at_time(5.0), 6.0);
+    }
+
+    #[test]
+    fn test_end_edits() {
+        let mut module = Module::new(Default::default());
+        let event = |n, data| Event { tick: Timespan::new(n, 1), data };
+        let t = |n| Timespan::new(n, 1);
+        module.insert_event(1, 0, event(6, EventData::NoteOff));
+        assert_eq!(module.end_tick(), None);
+        assert!(module.trim_to_end().is_none());
+
+        module.push_edit(module.pad_end(t(2)));
+        assert_eq!(module.end_tick(), Some(t(8)));
+
+        module.insert_event(0, 0, event(12, EventData::End));
+        module.push_edit(module.move_end(t(4)));
+        assert_eq!(module.end_tick(), Some(t(4)));
+        assert_eq!(module.tracks[0].channels[0].events.iter()
+            .filter(|e| e.data == EventData::End).count(), 1);
+
+        module.push_edit(module.trim_to_end().unwrap());
+        assert_eq!(module.last_event_tick(), Some(t(4)));
+
+        module.push_edit(module.pad_end(t(4)));
+        assert_eq!(module.end_tick(), Some(t(8)));
+        assert!(module.undo());
+        assert_eq!(module.end_tick(), Some(t(4)));
+    }
+
+    #[test]
+    fn test_loop_events() {
+        let mut module = Module::new(Default::default());
+        let event = |n, data| Event { tick: Timespan::new(n, 1), data };
+        let t = |n| Timespan::new(n, 1);
+        module.insert_event(0, 0, event(0, EventData::Loop));
+        module.insert_event(0, 0, event(2, EventData::End));
+        module.insert_event(0, 0, event(4, EventData::End));
+        module.insert_event(0, 0, event(6, EventData::Loop));
+        assert_eq!(module.control_warnings().len(), 3);
+
+        module.push_edit(module.set_loop_events(t(1), t(3)));
+        assert_eq!(module.loop_range(), Some((t(1), t(3))));
+        assert!(module.control_warnings().is_empty());
+    }
+
+    #[test]
+    fn test_auto_pan() {
+        let pan = AutoPan { period: 2.0, depth: 0.5, phase: 0.25 };
+        assert!((pan.pan_at(0.0) - 0.5).abs() < 1e-6);
+        assert!((pan.pan_at(1.0) + 0.5).abs() < 1e-6);
+        assert!(pan.pan_at(0.5).abs() < 1e-6);
+    }
+}
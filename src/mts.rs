@@ -0,0 +1,109 @@
+//! MIDI Tuning Standard (MTS) messages, for sharing the module tuning with
+//! external synthesizers.
+//!
+//! Keys are mapped so that key 60 plays C4 and each key up or down moves by
+//! one step of the tuning.
+
+use crate::pitch::{Nominal, Note, Tuning};
+
+const SYSEX_START: u8 = 0xf0;
+const SYSEX_END: u8 = 0xf7;
+const NON_REAL_TIME: u8 = 0x7e;
+const ALL_DEVICES: u8 = 0x7f;
+const MTS_SUB_ID: u8 = 0x08;
+const BULK_DUMP_REPLY: u8 = 0x01;
+const NAME_LENGTH: usize = 16;
+const MIDDLE_C: u8 = 60;
+
+/// Returns a bulk tuning dump SysEx message for `tuning`. `name` is
+/// truncated or padded to 16 ASCII characters.
+pub fn bulk_dump(tuning: &Tuning, program: u8, name: &str) -> Vec<u8> {
+    let mut msg = vec![
+        SYSEX_START, NON_REAL_TIME, ALL_DEVICES, MTS_SUB_ID, BULK_DUMP_REPLY,
+        program & 0x7f,
+    ];
+
+    let name: Vec<_> = name.chars()
+        .map(|c| if c.is_ascii() && !c.is_ascii_control() { c as u8 } else { b'?' })
+        .chain(std::iter::repeat(b' '))
+        .take(NAME_LENGTH)
+        .collect();
+    msg.extend(name);
+
+    for key in 0..=127 {
+        msg.extend(encode_pitch(key_pitch(tuning, key)));
+    }
+
+    // checksum is the XOR of everything between the start byte and itself
+    let checksum = msg[1..].iter().fold(0, |acc, b| acc ^ b) & 0x7f;
+    msg.push(checksum);
+    msg.push(SYSEX_END);
+    msg
+}
+
+/// Returns the MIDI pitch of a keyboard key in `tuning`.
+fn key_pitch(tuning: &Tuning, key: u8) -> f32 {
+    let c4 = Note::new(0, Nominal::C, 0, 4);
+    let (index, equave) = tuning.scale_index(&c4);
+    let root_pitch = tuning.midi_pitch(&Note { equave, ..tuning.root });
+    let n = tuning.size() as i32;
+    let steps = index as i32 + key as i32 - MIDDLE_C as i32;
+    let equave_cents = tuning.scale.last().expect("scale cannot be empty");
+    let step_cents = match steps.rem_euclid(n) {
+        0 => 0.0,
+        i => tuning.scale[i as usize - 1],
+    };
+
+    root_pitch + (equave_cents * steps.div_euclid(n) as f32 + step_cents) / 100.0
+}
+
+/// Encodes a MIDI pitch as a semitone and a 14-bit fraction of a semitone.
+fn encode_pitch(pitch: f32) -> [u8; 3] {
+    const FRACTION_MAX: i32 = 0x3fff;
+
+    let pitch = pitch.clamp(0.0, 127.0 + FRACTION_MAX as f32 / 16384.0);
+    let mut semitone = pitch.floor() as i32;
+    let mut fraction = ((pitch - semitone as f32) * 16384.0).round() as i32;
+    if fraction > FRACTION_MAX {
+        semitone += 1;
+        fraction = 0;
+    }
+    if semitone > 127 {
+        (semitone, fraction) = (127, FRACTION_MAX);
+    }
+
+    [semitone as u8, (fraction >> 7) as u8, (fraction & 0x7f) as u8]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_pitch() {
+        assert_eq!(encode_pitch(60.0), [60, 0, 0]);
+        assert_eq!(encode_pitch(60.5), [60, 0x40, 0]);
+        assert_eq!(encode_pitch(-1.0), [0, 0, 0]);
+        assert_eq!(encode_pitch(200.0), [127, 0x7f, 0x7f]);
+    }
+
+    #[test]
+    fn test_bulk_dump() {
+        let edo12 = Tuning::divide(2.0, 12, 1).unwrap();
+        let msg = bulk_dump(&edo12, 0, "osctet");
+        assert_eq!(msg.len(), 408);
+        assert_eq!(&msg[6..22], b"osctet          ");
+        assert_eq!(*msg.last().unwrap(), SYSEX_END);
+        assert!(msg[1..msg.len() - 1].iter().all(|b| *b <= 0x7f));
+        for key in 0..=127 {
+            let i = 22 + key as usize * 3;
+            assert_eq!(msg[i..i + 3], [key, 0, 0]);
+        }
+
+        let edo19 = Tuning::divide(2.0, 19, 1).unwrap();
+        let c4 = edo19.midi_pitch(&Note::new(0, Nominal::C, 0, 4));
+        assert!((key_pitch(&edo19, MIDDLE_C) - c4).abs() < 0.001);
+        assert!((key_pitch(&edo19, MIDDLE_C + 19) - (c4 + 12.0)).abs() < 0.001);
+        assert!((key_pitch(&edo19, MIDDLE_C - 1) - (c4 - 12.0 / 19.0)).abs() < 0.001);
+    }
+}
@@ -0,0 +1,364 @@
+//! Plain-text representation of pattern data, for exchanging patterns with
+//! text editors, version control, and scripts.
+//!
+//! Each non-empty line not starting with `#` is one event, in the form
+//! `<beat> <channel> <event>`. Beats and channels are relative to the start
+//! of the exported region, and channels are counted across track
+//! boundaries. Beats are written as integers or fractions (`3/4`).
+//!
+//! Event syntax:
+//! - Notes: optional `^`/`v` arrows, nominal, optional `#`/`b` accidentals,
+//!   and octave, e.g. `C4`, `^F#3`, `vvBb-1`.
+//! - `off`: note off.
+//! - `p<digit>`, `m<digit>`: pressure and modulation, as hex digits.
+//! - `bend:<cents>`: MIDI-style pitch bend.
+//! - `tempo:<bpm>`, `ratio:<n>/<d>`: absolute and relative tempo.
+//! - `end`, `loop`, `section`: control markers.
+//! - `glide:<column>`, `glide-end:<column>`, `glide-tick:<column>`:
+//!   interpolation markers.
+//! - `curve:<column>/<shape>`: glide curve, where shape is `linear`, `exp`,
+//!   `s`, or `step`.
+//! - `tuning:<index>`: tuning change.
+//! - `vib:<depth>/<rate>`, `trem:<depth>/<rate>`: vibrato and tremolo, where
+//!   depth is a hex digit and rate is `<hz>hz`, `<cycles>`, or
+//!   `<cycles>/<beats>`.
+//! - `ramp:<cents>:<beats>:<shape>`: bend ramp, with the same shapes as
+//!   glide curves.
+//! - `retrig:<hits>:<beats>` or `retrig:<hits>:<beats>:<pressure>`:
+//!   retrigger, with an optional final pressure digit.
+//! - `delay:<beats>`, `cut:<beats>`: note delay and note cut.
+
+use std::fmt::Write;
+
+use crate::{module::{BendRamp, EventData, GlideCurve, LfoRate, LocatedEvent, PatternLfo, Retrigger}, pitch::{Nominal, Note}, timespan::Timespan};
+
+const HEADER: &str = "# osctet pattern text\n# beat channel event\n";
+
+/// Event parsed from pattern text.
+#[derive(Debug, PartialEq)]
+pub struct TextEvent {
+    pub tick: Timespan,
+    pub channel_offset: usize,
+    pub data: EventData,
+}
+
+/// Formats events as pattern text. Ticks are written relative to `start`,
+/// and `channel_offset` returns each event's channel relative to the start
+/// of the region.
+pub fn format_events(events: &[LocatedEvent], start: Timespan,
+    channel_offset: impl Fn(&LocatedEvent) -> usize
+) -> String {
+    let mut s = String::from(HEADER);
+    let mut lines: Vec<_> = events.iter()
+        .filter_map(|e| format_data(&e.event.data)
+            .map(|data| (e.event.tick - start, channel_offset(e), data)))
+        .collect();
+    lines.sort_by_key(|(tick, channel, _)| (*tick, *channel));
+
+    for (tick, channel, data) in lines {
+        writeln!(s, "{} {} {}", format_tick(tick), channel, data)
+            .expect("writing to a string should not fail");
+    }
+
+    s
+}
+
+/// Parses pattern text. Errors include the line number.
+pub fn parse_events(s: &str) -> Result<Vec<TextEvent>, String> {
+    let mut events = Vec::new();
+
+    for (i, line) in s.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue
+        }
+
+        let fields: Vec<_> = line.split_whitespace().collect();
+        let [tick, channel, data] = fields[..] else {
+            return Err(format!("line {}: expected 3 fields", i + 1))
+        };
+        let tick = parse_tick(tick)
+            .ok_or_else(|| format!("line {}: invalid beat: {tick}", i + 1))?;
+        let channel_offset = channel.parse()
+            .map_err(|_| format!("line {}: invalid channel: {channel}", i + 1))?;
+        let data = parse_data(data)
+            .ok_or_else(|| format!("line {}: invalid event: {data}", i + 1))?;
+
+        events.push(TextEvent { tick, channel_offset, data });
+    }
+
+    Ok(events)
+}
+
+pub fn format_tick(tick: Timespan) -> String {
+    if tick.den() == 1 {
+        tick.num().to_string()
+    } else {
+        format!("{}/{}", tick.num(), tick.den())
+    }
+}
+
+pub fn parse_tick(s: &str) -> Option<Timespan> {
+    let tick = match s.split_once('/') {
+        Some((n, d)) => Timespan::new(n.parse().ok()?, d.parse().ok().filter(|d| *d > 0)?),
+        None => Timespan::new(s.parse().ok()?, 1),
+    };
+    (tick >= Timespan::ZERO).then_some(tick)
+}
+
+/// Returns `None` for data that doesn't appear in patterns.
+pub fn format_data(data: &EventData) -> Option<String> {
+    Some(match data {
+        EventData::Pitch(note) => format_note(note),
+        EventData::NoteOff => String::from("off"),
+        EventData::Pressure(v) => format!("p{v:x}"),
+        EventData::Modulation(v) => format!("m{v:x}"),
+        EventData::Bend(c) => format!("bend:{c}"),
+        EventData::Tempo(t) => format!("tempo:{t}"),
+        EventData::RationalTempo(n, d) => format!("ratio:{n}/{d}"),
+        EventData::End => String::from("end"),
+        EventData::Loop => String::from("loop"),
+        EventData::Section => String::from("section"),
+        EventData::StartGlide(col) => format!("glide:{col}"),
+        EventData::EndGlide(col) => format!("glide-end:{col}"),
+        EventData::TickGlide(col) => format!("glide-tick:{col}"),
+        EventData::TuningChange(i) => format!("tuning:{i}"),
+        EventData::GlideCurve(col, curve) => format!("curve:{col}/{}", format_curve(curve)),
+        EventData::Vibrato(lfo) => format!("vib:{:x}/{}", lfo.depth, lfo.rate),
+        EventData::Tremolo(lfo) => format!("trem:{:x}/{}", lfo.depth, lfo.rate),
+        EventData::BendRamp(ramp) => format!("ramp:{}:{}:{}",
+            ramp.cents, format_tick(ramp.duration), format_curve(&ramp.curve)),
+        EventData::Retrigger(r) => match r.end_pressure {
+            Some(p) => format!("retrig:{}:{}:{p:x}", r.hits, format_tick(r.length)),
+            None => format!("retrig:{}:{}", r.hits, format_tick(r.length)),
+        },
+        EventData::NoteDelay(t) => format!("delay:{}", format_tick(*t)),
+        EventData::NoteCut(t) => format!("cut:{}", format_tick(*t)),
+        EventData::GlobalModulation(v) => format!("gmod:{v:x}"),
+        EventData::InterpolatedPitch(_)
+            | EventData::InterpolatedPressure(_)
+            | EventData::InterpolatedModulation(_) => return None,
+    })
+}
+
+fn format_curve(curve: &GlideCurve) -> &'static str {
+    match curve {
+        GlideCurve::Linear => "linear",
+        GlideCurve::Exponential => "exp",
+        GlideCurve::SCurve => "s",
+        GlideCurve::Stepped => "step",
+    }
+}
+
+fn parse_curve(s: &str) -> Option<GlideCurve> {
+    match s {
+        "linear" => Some(GlideCurve::Linear),
+        "exp" => Some(GlideCurve::Exponential),
+        "s" => Some(GlideCurve::SCurve),
+        "step" => Some(GlideCurve::Stepped),
+        _ => None,
+    }
+}
+
+/// Parses a single event token.
+pub fn parse_data(s: &str) -> Option<EventData> {
+    let digit = |s: &str| u8::from_str_radix(s, 16).ok()
+        .filter(|v| *v <= EventData::DIGIT_MAX);
+    let column = |s: &str| s.parse::<u8>().ok().filter(|col| *col <= 2);
+    let lfo = |s: &str| {
+        let (depth, rate) = s.split_once('/')?;
+        Some(PatternLfo { depth: digit(depth)?, rate: LfoRate::parse(rate)? })
+    };
+
+    match s.split_once(':') {
+        Some(("bend", c)) => c.parse().ok().map(EventData::Bend),
+        Some(("tempo", t)) => t.parse().ok()
+            .filter(|t: &f32| *t > 0.0)
+            .map(EventData::Tempo),
+        Some(("ratio", r)) => {
+            let (n, d) = r.split_once('/')?;
+            let (n, d) = (n.parse().ok()?, d.parse().ok()?);
+            (n > 0 && d > 0).then_some(EventData::RationalTempo(n, d))
+        }
+        Some(("glide", col)) => column(col).map(EventData::StartGlide),
+        Some(("glide-end", col)) => column(col).map(EventData::EndGlide),
+        Some(("glide-tick", col)) => column(col).map(EventData::TickGlide),
+        Some(("tuning", i)) => i.parse().ok().map(EventData::TuningChange),
+        Some(("curve", c)) => {
+            let (col, curve) = c.split_once('/')?;
+            let curve = parse_curve(curve)?;
+            column(col).map(|col| EventData::GlideCurve(col, curve))
+        }
+        Some(("ramp", r)) => {
+            let mut fields = r.split(':');
+            let ramp = BendRamp {
+                cents: fields.next()?.parse().ok()?,
+                duration: parse_tick(fields.next()?)?,
+                curve: parse_curve(fields.next()?)?,
+            };
+            fields.next().is_none().then_some(EventData::BendRamp(ramp))
+        }
+        Some(("retrig", r)) => {
+            let mut fields = r.split(':');
+            let retrig = Retrigger {
+                hits: fields.next()?.parse().ok().filter(|n| *n > 0)?,
+                length: parse_tick(fields.next()?)?,
+                end_pressure: match fields.next() {
+                    Some(p) => Some(digit(p)?),
+                    None => None,
+                },
+            };
+            fields.next().is_none().then_some(EventData::Retrigger(retrig))
+        }
+        Some(("delay", t)) => parse_tick(t).map(EventData::NoteDelay),
+        Some(("cut", t)) => parse_tick(t).map(EventData::NoteCut),
+        Some(("vib", v)) => lfo(v).map(EventData::Vibrato),
+        Some(("trem", v)) => lfo(v).map(EventData::Tremolo),
+        Some(("gmod", v)) => digit(v).map(EventData::GlobalModulation),
+        Some(_) => None,
+        None => match s {
+            "off" => Some(EventData::NoteOff),
+            "end" => Some(EventData::End),
+            "loop" => Some(EventData::Loop),
+            "section" => Some(EventData::Section),
+            _ => if let Some(v) = s.strip_prefix('p') {
+                digit(v).map(EventData::Pressure)
+            } else if let Some(v) = s.strip_prefix('m') {
+                digit(v).map(EventData::Modulation)
+            } else {
+                parse_note(s).map(EventData::Pitch)
+            }
+        }
+    }
+}
+
+/// Formats a note using ASCII characters for arrows and accidentals.
+pub fn format_note(note: &Note) -> String {
+    let arrow = if note.arrows < 0 { "v" } else { "^" };
+    let accidental = if note.sharps < 0 { "b" } else { "#" };
+    format!("{}{}{}{}",
+        arrow.repeat(note.arrows.unsigned_abs() as usize),
+        note.nominal.char(),
+        accidental.repeat(note.sharps.unsigned_abs() as usize),
+        note.equave)
+}
+
+/// Parses a note written by `format_note`.
+pub fn parse_note(s: &str) -> Option<Note> {
+    let body = s.trim_start_matches(['^', 'v']);
+    let arrows_str = &s[..s.len() - body.len()];
+    let arrows = arrows_str.matches('^').count() as i8 - arrows_str.matches('v').count() as i8;
+
+    let mut chars = body.chars();
+    let nominal = match chars.next()? {
+        'A' => Nominal::A,
+        'B' => Nominal::B,
+        'C' => Nominal::C,
+        'D' => Nominal::D,
+        'E' => Nominal::E,
+        'F' => Nominal::F,
+        'G' => Nominal::G,
+        _ => return None,
+    };
+
+    let rest = chars.as_str();
+    let equave_str = rest.trim_start_matches(['#', 'b']);
+    let accidentals = &rest[..rest.len() - equave_str.len()];
+    let sharps = accidentals.matches('#').count() as i8 - accidentals.matches('b').count() as i8;
+    let equave = equave_str.parse().ok()?;
+
+    Some(Note::new(arrows, nominal, sharps, equave))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::module::Event;
+
+    use super::*;
+
+    #[test]
+    fn test_note_round_trip() {
+        let notes = [
+            Note::new(0, Nominal::C, 0, 4),
+            Note::new(1, Nominal::F, 1, 3),
+            Note::new(-2, Nominal::B, -1, -1),
+            Note::new(0, Nominal::E, -2, 10),
+        ];
+        for note in notes {
+            assert_eq!(parse_note(&format_note(&note)), Some(note));
+        }
+        assert_eq!(format_note(&notes[2]), "vvBb-1");
+        assert_eq!(parse_note("H4"), None);
+        assert_eq!(parse_note("C"), None);
+    }
+
+    #[test]
+    fn test_events_round_trip() {
+        let data = [
+            EventData::Pitch(Note::new(0, Nominal::A, 0, 4)),
+            EventData::NoteOff,
+            EventData::Pressure(0xa),
+            EventData::Modulation(0),
+            EventData::Bend(-25),
+            EventData::Tempo(132.5),
+            EventData::RationalTempo(3, 2),
+            EventData::End,
+            EventData::Loop,
+            EventData::Section,
+            EventData::GlobalModulation(0xc),
+            EventData::StartGlide(1),
+            EventData::EndGlide(1),
+            EventData::TickGlide(2),
+            EventData::TuningChange(1),
+            EventData::GlideCurve(1, GlideCurve::Exponential),
+            EventData::Vibrato(PatternLfo { depth: 4, rate: LfoRate::Hz(5.5) }),
+            EventData::Tremolo(PatternLfo { depth: 0xf, rate: LfoRate::Sync(1, 2) }),
+            EventData::BendRamp(BendRamp {
+                cents: -200,
+                duration: Timespan::new(3, 4),
+                curve: GlideCurve::SCurve,
+            }),
+            EventData::Retrigger(Retrigger {
+                hits: 3,
+                length: Timespan::new(1, 4),
+                end_pressure: None,
+            }),
+            EventData::Retrigger(Retrigger {
+                hits: 8,
+                length: Timespan::new(1, 1),
+                end_pressure: Some(0xc),
+            }),
+            EventData::NoteDelay(Timespan::new(1, 8)),
+            EventData::NoteCut(Timespan::new(1, 3)),
+        ];
+        let events: Vec<_> = data.iter().enumerate().map(|(i, data)| LocatedEvent {
+            track: 1,
+            channel: i % 2,
+            event: Event {
+                tick: Timespan::new(i as i32 + 4, 3),
+                data: data.clone(),
+            },
+        }).collect();
+
+        let text = format_events(&events, Timespan::new(1, 1), |e| e.channel);
+        let parsed = parse_events(&text).unwrap();
+        assert_eq!(parsed.len(), events.len());
+        for (parsed, orig) in parsed.iter().zip(&events) {
+            assert_eq!(parsed.tick, orig.event.tick - Timespan::new(1, 1));
+            assert_eq!(parsed.channel_offset, orig.channel);
+            assert_eq!(parsed.data, orig.event.data);
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse_events("# comment\n\n0 0 C4\n").is_ok());
+        assert_eq!(parse_events("0 0").unwrap_err(), "line 1: expected 3 fields");
+        assert!(parse_events("-1 0 C4").is_err());
+        assert!(parse_events("1/0 0 C4").is_err());
+        assert!(parse_events("0 0 p10").is_err());
+        assert!(parse_events("0 0 glide:3").is_err());
+        assert!(parse_events("0 0 ramp:100:1").is_err());
+        assert!(parse_events("0 0 retrig:0:1").is_err());
+    }
+}
@@ -4,6 +4,7 @@ use std::error::Error;
 use std::{fmt, fs};
 use std::path::PathBuf;
 
+use gcd::Gcd;
 use serde::{Deserialize, Serialize};
 
 use crate::ui::text;
@@ -11,6 +12,9 @@ use crate::ui::text;
 /// Fixed reference point regardless of tuning.
 const REFERENCE_MIDI_PITCH: f32 = 69.0;
 
+/// Default frequency of the reference pitch (A4), in Hz.
+const DEFAULT_REFERENCE_FREQ: f32 = 440.0;
+
 /// Default root note for unequal scales.
 const DEFAULT_ROOT: Note = Note {
     arrows: 0,
@@ -19,11 +23,21 @@ const DEFAULT_ROOT: Note = Note {
     equave: 4,
 };
 
+/// Largest denominator considered when approximating JI ratios.
+const MAX_RATIO_DENOMINATOR: u32 = 64;
+
+/// Cents of error traded for each octave of Tenney height when
+/// approximating JI ratios, so that simple ratios are preferred.
+const RATIO_COMPLEXITY_WEIGHT: f32 = 3.0;
+
 /// Converts a freq ratio to cents.
 fn cents(ratio: f32) -> f32 {
     1200.0 * ratio.log2() / 2.0_f32.log2()
 }
 
+/// Default reference frequency for serde.
+fn default_reference_freq() -> f32 { DEFAULT_REFERENCE_FREQ }
+
 /// Converts cents to a freq ratio.
 fn find_ratio(cents: f32) -> f32 {
     2.0_f32.powf(2.0_f32.log2() * cents / 1200.0)
@@ -98,6 +112,9 @@ pub struct Tuning {
     /// Cents values of scale notes. The last value is also the scale period.
     pub scale: Vec<f32>,
     pub arrow_steps: u8,
+    /// Frequency of A4, in Hz.
+    #[serde(default = "default_reference_freq")]
+    pub reference_freq: f32,
 }
 
 impl Tuning {
@@ -113,6 +130,7 @@ impl Tuning {
             root: DEFAULT_ROOT,
             scale: (1..=steps).map(|i| i as f32 * step).collect(),
             arrow_steps,
+            reference_freq: DEFAULT_REFERENCE_FREQ,
         })
     }
 
@@ -138,6 +156,7 @@ impl Tuning {
             root,
             scale: scale?,
             arrow_steps: 1,
+            reference_freq: DEFAULT_REFERENCE_FREQ,
         })
     }
 
@@ -145,11 +164,24 @@ impl Tuning {
     pub fn midi_pitch(&self, note: &Note) -> f32 {
         let root_steps = self.raw_steps(&self.root);
         let steps = self.raw_steps(note) - root_steps;
+        let reference_pitch = REFERENCE_MIDI_PITCH
+            + 12.0 * (self.reference_freq / DEFAULT_REFERENCE_FREQ).log2();
         let root_pitch = self.pitch_from_steps(
-            root_steps, self.root.equave, REFERENCE_MIDI_PITCH, 4);
+            root_steps, self.root.equave, reference_pitch, 4);
         self.pitch_from_steps(steps, note.equave, root_pitch, self.root.equave)
     }
 
+    /// Returns the frequency of the scale root, in Hz.
+    pub fn root_freq(&self) -> f32 {
+        let pitch = self.midi_pitch(&self.root);
+        DEFAULT_REFERENCE_FREQ * 2.0_f32.powf((pitch - REFERENCE_MIDI_PITCH) / 12.0)
+    }
+
+    /// Sets the reference frequency so that the scale root has frequency `hz`.
+    pub fn set_root_freq(&mut self, hz: f32) {
+        self.reference_freq *= hz / self.root_freq();
+    }
+
     /// Returns a raw step count for a note.
     fn raw_steps(&self, note: &Note) -> i32 {
         let generator_steps = (self.scale.len() as f32 * 0.585).round() as i32;
@@ -181,6 +213,35 @@ impl Tuning {
         find_ratio(*self.scale.last().expect("scale cannot be empty"))
     }
 
+    /// Returns the frequency ratio above the root of the note in the tuning
+    /// nearest to `ratio`.
+    pub fn nearest_ratio(&self, ratio: f32) -> f32 {
+        let period = *self.scale.last().expect("scale cannot be empty");
+        let interval = cents(ratio);
+        let equaves = (interval / period).floor();
+        let offset = interval - equaves * period;
+        let nearest = std::iter::once(0.0).chain(self.scale.iter().copied())
+            .min_by(|a, b| (a - offset).abs().total_cmp(&(b - offset).abs()))
+            .expect("scale cannot be empty");
+        find_ratio(equaves * period + nearest)
+    }
+
+    /// Returns notation for the note in the tuning nearest to `pitch`, a
+    /// MIDI pitch. Returns `None` if the nearest note has no notation.
+    pub fn nearest_note(&self, pitch: f32) -> Option<Note> {
+        let period = *self.scale.last().expect("scale cannot be empty");
+        let offset = (pitch - self.midi_pitch(&self.root)) * 100.0;
+        let equaves = (offset / period).floor();
+        let offset = offset - equaves * period;
+        let (index, _) = std::iter::once(0.0).chain(self.scale.iter().copied())
+            .enumerate()
+            .min_by(|(_, a), (_, b)| (*a - offset).abs().total_cmp(&(*b - offset).abs()))
+            .expect("scale cannot be empty");
+        let equave = self.root.equave as i32 + equaves as i32
+            + (index / self.scale.len()) as i32;
+        self.notation(index % self.scale.len(), equave as i8).first().copied()
+    }
+
     /// Returns the number of steps in the period.
     pub fn size(&self) -> u16 {
         self.scale.len() as u16
@@ -269,6 +330,88 @@ impl Tuning {
     }
 }
 
+/// Alternative pitch readouts.
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum PitchDisplay {
+    #[default]
+    Notation,
+    Degree,
+    Cents,
+    Ratio,
+}
+
+impl PitchDisplay {
+    pub const VARIANTS: [PitchDisplay; 4] =
+        [Self::Notation, Self::Degree, Self::Cents, Self::Ratio];
+
+    /// Returns the UI string for this display mode.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Notation => "Notation only",
+            Self::Degree => "Scale degree",
+            Self::Cents => "Cents from root",
+            Self::Ratio => "Nearest JI ratio",
+        }
+    }
+}
+
+impl Tuning {
+    /// Returns a readout of a note's position in the scale, or `None` if
+    /// `mode` is `PitchDisplay::Notation`.
+    pub fn pitch_readout(&self, note: &Note, mode: PitchDisplay) -> Option<String> {
+        let (index, equave) = self.scale_index(note);
+        let root = Note { equave, ..self.root };
+        let cents = (self.midi_pitch(note) - self.midi_pitch(&root)) * 100.0;
+
+        match mode {
+            PitchDisplay::Notation => None,
+            PitchDisplay::Degree =>
+                Some(format!("{}\\{} (octave {})", index, self.size(), equave)),
+            PitchDisplay::Cents => Some(format!("{:.1}c", cents)),
+            PitchDisplay::Ratio => {
+                let (n, d, error) = approximate_ratio(cents);
+                Some(format!("{}/{} ({:+.1}c)", n, d, error))
+            }
+        }
+    }
+}
+
+/// Returns the numerator, denominator, and error in cents of a simple JI
+/// ratio near an interval. Favors low Tenney height over accuracy.
+pub fn approximate_ratio(interval: f32) -> (u32, u32, f32) {
+    let ratio = find_ratio(interval);
+    let mut best = (1, 1, interval, f32::INFINITY);
+
+    for d in 1..=MAX_RATIO_DENOMINATOR {
+        let n = (ratio * d as f32).round().max(1.0) as u32;
+        let gcd = n.gcd(d);
+        let (n, d) = (n / gcd, d / gcd);
+        let error = interval - cents(n as f32 / d as f32);
+        let score = error.abs() + RATIO_COMPLEXITY_WEIGHT * ((n * d) as f32).log2();
+        if score < best.3 {
+            best = (n, d, error, score);
+        }
+    }
+
+    (best.0, best.1, best.2)
+}
+
+/// Formats scale steps for editing.
+pub fn format_steps(steps: &[usize]) -> String {
+    steps.iter().map(|s| s.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+/// Parses scale steps separated by spaces or commas. The result is sorted
+/// and free of duplicates.
+pub fn parse_steps(s: &str) -> Result<Vec<usize>, String> {
+    let mut steps = s.split([' ', ',']).filter(|s| !s.is_empty())
+        .map(|s| s.parse().map_err(|_| format!("Invalid scale step: {s}")))
+        .collect::<Result<Vec<usize>, _>>()?;
+    steps.sort();
+    steps.dedup();
+    Ok(steps)
+}
+
 /// Parses a Scala file interval into cents.
 fn parse_interval(s: &str) -> Option<f32> {
     s.trim().split_ascii_whitespace().next().and_then(|s| {
@@ -419,6 +562,7 @@ mod tests {
             root: DEFAULT_ROOT,
             scale: vec![240.0, 480.0, 720.0, 960.0, 1200.0],
             arrow_steps: 1,
+            reference_freq: 440.0,
         });
         Tuning::divide(1.0, 5, 1).unwrap_err();
         Tuning::divide(0.5, 5, 1).unwrap_err();
@@ -438,6 +582,62 @@ mod tests {
         assert_eq!(t.midi_pitch(&A4), 69.0);
     }
 
+    #[test]
+    fn test_reference_freq() {
+        let mut t = Tuning::divide(2.0, 12, 1).unwrap();
+        t.reference_freq = 880.0;
+        assert!((t.midi_pitch(&A4) - 81.0).abs() < 0.001);
+        t.reference_freq = 440.0;
+        t.root = Note::new(0, Nominal::C, 0, 4);
+        assert!((t.root_freq() - 261.626).abs() < 0.01);
+        t.set_root_freq(256.0);
+        assert!((t.root_freq() - 256.0).abs() < 0.01);
+        assert!((t.reference_freq - 430.54).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_nearest_ratio() {
+        let t = Tuning::divide(2.0, 12, 1).unwrap();
+        assert!((t.nearest_ratio(2.0) - 2.0).abs() < 0.001);
+        assert!((t.nearest_ratio(5.0) - find_ratio(2800.0)).abs() < 0.001);
+        assert!((t.nearest_ratio(1.02) - 1.0).abs() < 0.001);
+        let t = Tuning::divide(3.0, 13, 1).unwrap();
+        assert!((t.nearest_ratio(3.0) - 3.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_nearest_note() {
+        let t = Tuning::divide(2.0, 12, 1).unwrap();
+        assert_eq!(t.nearest_note(69.2), Some(A4));
+        let note = t.nearest_note(60.9).unwrap();
+        assert_eq!(t.midi_pitch(&note), 61.0);
+        let t = Tuning::divide(2.0, 31, 1).unwrap();
+        for pitch in [55.0, 61.0, 69.0, 74.0] {
+            let note = t.nearest_note(pitch).unwrap();
+            assert!((t.midi_pitch(&note) - pitch).abs() < 0.2);
+        }
+    }
+
+    #[test]
+    fn test_approximate_ratio() {
+        assert_eq!(approximate_ratio(0.0), (1, 1, 0.0));
+        let (n, d, error) = approximate_ratio(700.0);
+        assert_eq!((n, d), (3, 2));
+        assert!((error + 1.955).abs() < 0.01);
+        assert_eq!(approximate_ratio(400.0).0, 5);
+        assert_eq!(approximate_ratio(968.8).0, 7);
+    }
+
+    #[test]
+    fn test_pitch_readout() {
+        let t = Tuning::divide(2.0, 12, 1).unwrap();
+        let e4 = Note::new(0, Nominal::E, 0, 4);
+        assert_eq!(t.pitch_readout(&e4, PitchDisplay::Notation), None);
+        assert_eq!(t.pitch_readout(&e4, PitchDisplay::Degree).unwrap(), "4\\12 (octave 4)");
+        assert_eq!(t.pitch_readout(&e4, PitchDisplay::Cents).unwrap(), "400.0c");
+        assert_eq!(t.pitch_readout(&e4, PitchDisplay::Ratio).unwrap(), "5/4 (+13.7c)");
+    }
+
     #[test]
     fn test_parse_interval() {
         assert_eq!(parse_interval("2"), Some(1200.0));
@@ -517,4 +717,11 @@ mod tests {
         assert_eq!(t.octave_offet(&Note::new(0, Nominal::A, 5, 4)), 1);
         assert_eq!(t.octave_offet(&Note::new(-1, Nominal::B, 0, 4)), 0);
     }
-}
\ No newline at end of file
+    #[test]
+    fn test_parse_steps() {
+        assert_eq!(parse_steps("7, 0 4  4"), Ok(vec![0, 4, 7]));
+        assert_eq!(parse_steps(""), Ok(vec![]));
+        assert!(parse_steps("0 x").is_err());
+        assert_eq!(format_steps(&[0, 4, 7]), "0 4 7");
+    }
+}
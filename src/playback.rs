@@ -1,56 +1,302 @@
-use std::{path::PathBuf, sync::{mpsc::{self, Receiver}, Arc, Mutex}, thread};
+use std::{collections::HashMap, path::PathBuf, sync::{mpsc::{self, Receiver, Sender}, Arc, Mutex}, thread};
 
-use fundsp::hacker32::*;
+use fundsp::{hacker32::*, wave::WavePlayer};
 
-use crate::{fx::GlobalFX, module::{Event, EventData, LocatedEvent, Module, TrackEdit, GLOBAL_COLUMN, MOD_COLUMN, NOTE_COLUMN, VEL_COLUMN}, synth::{Key, KeyOrigin, Patch, Synth, DEFAULT_PRESSURE}, timespan::Timespan};
+use crate::{fx::GlobalFX, mixer::Mixer, module::{BendRamp, Channel, Event, EventData, GlideCurve, LocatedEvent, Module, PatternLfo, Retrigger, TrackEdit, TrackTarget, GLOBAL_COLUMN, MOD_COLUMN, NOTE_COLUMN, VEL_COLUMN}, pitch::{approximate_ratio, Note, Tuning}, synth::{Key, KeyOrigin, Patch, Synth, DEFAULT_PRESSURE, SMOOTH_TIME}, timespan::Timespan, wavmeta::WavMetadata};
 
 pub const DEFAULT_TEMPO: f32 = 120.0;
 
 /// For rendering.
 const LOOP_FADEOUT_TIME: f64 = 10.0;
 
+/// Time to keep rendering after the end of a range render, for releases.
+const RANGE_TAIL_TIME: f64 = 2.0;
+
+/// Time that the tail of one pass of a loop render can ring into the next.
+const LOOP_TAIL_TIME: f64 = 5.0;
+
+/// Maximum number of passes in a loop render.
+const MAX_LOOP_REPEATS: u32 = 100;
+
+/// Time for peak DSP load to decay by 100%, in seconds.
+const PEAK_LOAD_DECAY_TIME: f64 = 1.0;
+
+/// Time for the output meter to decay from full scale to silence, in seconds.
+const OUTPUT_PEAK_DECAY_TIME: f64 = 1.0;
+
+/// Response time of the output correlation meter, in seconds.
+const CORRELATION_TIME: f64 = 0.3;
+
+/// Lowest buffer energy that updates the output correlation meter.
+const CORRELATION_FLOOR: f32 = 1e-8;
+
+/// Block size for offline rendering.
+const RENDER_BLOCK_SIZE: usize = 64;
+
+/// Duration of pattern note previews, in seconds.
+const PREVIEW_TIME: f64 = 0.3;
+
+/// Pre-rendered track audio, played in place of the track's voices.
+pub struct FrozenTrack {
+    /// Track output: dry stereo, then FX send stereo.
+    wave: Arc<Wave>,
+    /// Beat position at the start of each render block.
+    block_beats: Vec<f64>,
+}
+
+/// Playback position within a channel, so that each frame only needs to
+/// visit the events that are due.
+#[derive(Default)]
+struct ChannelCursor {
+    /// Index of the first event not yet played.
+    index: usize,
+    /// Data of the last played event in each logical column.
+    prev_data: [Option<EventData>; 3],
+    /// Tick of the last played event in each spatial column.
+    start_tick: [Timespan; 3],
+    /// Whether each spatial column is inside a glide.
+    glide: [bool; 3],
+    /// Curve of the current glide in each spatial column.
+    curve: [GlideCurve; 3],
+}
+
+impl ChannelCursor {
+    /// Move past events before `beat`, calling `f` on each.
+    fn advance(&mut self, channel: &Channel, beat: f64, mut f: impl FnMut(&Event)) {
+        while let Some(event) = channel.events.get(self.index) {
+            if event.tick.as_f64() >= beat {
+                break
+            }
+            self.index += 1;
+            f(event);
+
+            match event.data {
+                EventData::StartGlide(i) => if self.glide[i as usize] {
+                    continue
+                } else {
+                    self.glide[i as usize] = true;
+                    self.curve[i as usize] = channel.glide_curve(i, event.tick);
+                }
+                EventData::EndGlide(i) => self.glide[i as usize] = false,
+                EventData::GlideCurve(..) => continue,
+                _ => (),
+            }
+
+            if let Some(v) = self.prev_data.get_mut(event.data.logical_column() as usize) {
+                *v = Some(event.data.clone());
+            }
+
+            self.start_tick[event.data.spatial_column() as usize] = event.tick;
+        }
+    }
+
+    /// Returns the next unplayed event in a logical column.
+    fn next_event<'a>(&self, channel: &'a Channel, col: u8) -> Option<&'a Event> {
+        channel.events.iter().skip(self.index).find(|e| e.data.logical_column() == col)
+    }
+}
+
+/// Bend ramp in progress on a pattern channel.
+struct ActiveRamp {
+    track: usize,
+    channel: u8,
+    /// Bend at the start of the ramp, in semitones.
+    from: f32,
+    ramp: BendRamp,
+    /// Beat the ramp started on.
+    start: f64,
+}
+
+impl ActiveRamp {
+    /// Returns the ramp's progress at `beat`, from 0 to 1.
+    fn progress(&self, beat: f64) -> f32 {
+        let duration = self.ramp.duration.as_f64();
+        if duration <= 0.0 {
+            1.0
+        } else {
+            ((beat - self.start) / duration).clamp(0.0, 1.0) as f32
+        }
+    }
+
+    /// Returns the bend at `beat`, in semitones.
+    fn bend_at(&self, beat: f64) -> f32 {
+        let t = self.ramp.curve.apply(self.progress(beat));
+        lerp(self.from, self.ramp.cents as f32 / 100.0, t)
+    }
+}
+
+/// Retriggered hit waiting to be played.
+struct PendingHit {
+    track: usize,
+    channel: u8,
+    beat: f64,
+    /// Pressure of the hit, if ramping.
+    pressure: Option<f32>,
+}
+
+/// Delayed note, note off, or note cut waiting to be played.
+struct ScheduledEvent {
+    beat: f64,
+    event: LocatedEvent,
+}
+
+/// Freeze state of a track.
+enum Freeze {
+    /// Waiting on a render with the given ID.
+    Pending(usize),
+    Active(FrozenTrack, Option<EventId>),
+}
+
 /// Handles module playback. In methods that take a `track` argument, 0 can
 /// safely be used for keyjazz events (since track 0 will never sequence).
 pub struct Player {
-    seq: Sequencer,
+    mixer: Mixer,
     synths: Vec<Synth>, // one per track
     playing: bool,
     beat: f64,
     tempo: f32,
     looped: bool,
     metronome: bool,
+    /// Beats of metronome left before playback starts, when recording.
+    count_in: f64,
+    /// Beat that recording starts at, after any pre-roll.
+    record_start: f64,
     sample_rate: f32,
     pub stereo_width: Shared,
     pub buffer_size: usize,
+    /// Time between audio callback and playback, in seconds.
+    pub output_latency: f64,
+    /// Fraction of the last audio buffer's duration spent processing it.
+    pub dsp_load: f64,
+    /// Recent maximum of `dsp_load`.
+    pub peak_dsp_load: f64,
+    /// Master output gain, as amplitude. Not applied to renders.
+    pub output_gain: f32,
+    pub output_muted: bool,
+    /// If true, audition-only tracks are muted when playback starts.
+    pub mute_audition: bool,
+    /// Multiplier for the playback tempo. Not applied to renders.
+    pub speed: f32,
+    /// Recent peak amplitude of the master output.
+    pub output_peak: f32,
+    /// Recent correlation of the master output's channels, from -1 (out
+    /// of phase) to 1 (mono).
+    pub output_correlation: f32,
+    /// Editor-defined loop range. Not part of the module.
+    loop_range: Option<(Timespan, Timespan)>,
+    loop_enabled: bool,
+    frozen: Vec<Option<Freeze>>, // one per track
+    next_freeze_id: usize,
+    cursors: Vec<Vec<ChannelCursor>>, // one per channel
+    /// Module version that `cursors` were built for.
+    cursor_version: u64,
+    /// Beat that `cursors` have advanced to.
+    cursor_beat: f64,
+    /// Track and remaining time of the current note preview.
+    preview: Option<(usize, f64)>,
+    /// Index of the module tuning used by tracks without an override.
+    global_tuning: usize,
+    /// Per-track tuning index overrides.
+    track_tunings: Vec<Option<usize>>,
+    /// Current pitch bend of pattern channels, in semitones, by track and
+    /// channel.
+    pattern_bends: HashMap<(usize, u8), f32>,
+    bend_ramps: Vec<ActiveRamp>,
+    /// Last note played in each pattern channel, by track and channel.
+    pattern_notes: HashMap<(usize, u8), Note>,
+    pending_hits: Vec<PendingHit>,
+    scheduled: Vec<ScheduledEvent>,
 }
 
 impl Player {
-    pub fn new(seq: Sequencer, num_tracks: usize, sample_rate: f32) -> Self {
+    pub fn new(mixer: Mixer, sample_rate: f32) -> Self {
+        let num_tracks = mixer.track_count();
         Self {
-            seq,
+            mixer,
             synths: (0..num_tracks).map(|_| Synth::new(sample_rate)).collect(),
             playing: false,
             beat: 0.0,
             tempo: DEFAULT_TEMPO,
             looped: false,
             metronome: false,
+            count_in: 0.0,
+            record_start: 0.0,
             sample_rate,
             stereo_width: shared(1.0),
             buffer_size: 0,
+            output_latency: 0.0,
+            dsp_load: 0.0,
+            peak_dsp_load: 0.0,
+            output_gain: 1.0,
+            output_muted: false,
+            mute_audition: false,
+            speed: 1.0,
+            output_peak: 0.0,
+            output_correlation: 1.0,
+            loop_range: None,
+            loop_enabled: false,
+            frozen: (0..num_tracks).map(|_| None).collect(),
+            next_freeze_id: 0,
+            cursors: Vec::new(),
+            cursor_version: 0,
+            cursor_beat: 0.0,
+            preview: None,
+            global_tuning: 0,
+            track_tunings: vec![None; num_tracks],
+            pattern_bends: HashMap::new(),
+            bend_ramps: Vec::new(),
+            pattern_notes: HashMap::new(),
+            pending_hits: Vec::new(),
+            scheduled: Vec::new(),
         }
     }
 
     /// Reinitialize state.
     pub fn reinit(&mut self, num_tracks: usize) {
-        for synth in &mut self.synths {
-            synth.clear_all_notes(&mut self.seq);
+        for (i, synth) in self.synths.iter_mut().enumerate() {
+            synth.clear_all_notes(self.mixer.track(i));
         }
+        self.playing = false;
+        self.sync_frozen();
+        self.mixer.resize(num_tracks);
         self.synths = (0..num_tracks).map(|_| Synth::new(self.sample_rate)).collect();
+        self.frozen = (0..num_tracks).map(|_| None).collect();
+        self.preview = None;
+        self.global_tuning = 0;
+        self.track_tunings = vec![None; num_tracks];
+        self.pattern_bends.clear();
+        self.bend_ramps.clear();
+        self.pattern_notes.clear();
+        self.pending_hits.clear();
+        self.scheduled.clear();
         self.playing = false;
         self.beat = 0.0;
         self.tempo = DEFAULT_TEMPO;
         self.looped = false;
         self.metronome = false;
+        self.loop_range = None;
+        self.loop_enabled = false;
+    }
+
+    /// Play a metronome click.
+    fn click(&mut self) {
+        self.mixer.track(0).push_relative(0.0, 0.01, Fade::Smooth, 0.01, 0.01,
+            Box::new(square_hz(440.0 * 8.0) >> split::<U4>()));
+    }
+
+    /// Returns the tempo after the speed multiplier.
+    fn playback_tempo(&self) -> f32 {
+        self.tempo * self.speed
+    }
+
+    /// Returns the playhead position in beats.
+    pub fn beat(&self) -> f64 {
+        self.beat
+    }
+
+    /// Convert a wall clock interval to beats at the playback tempo.
+    pub fn interval_beats(&self, dt: f64) -> f64 {
+        interval_beats(dt, self.playback_tempo())
     }
 
     /// Return the closest `Timespan` to the playhead.
@@ -58,6 +304,99 @@ impl Player {
         Timespan::approximate(self.beat)
     }
 
+    /// Return the closest `Timespan` to what the listener is hearing, i.e. the
+    /// playhead minus output latency.
+    pub fn latency_compensated_tick(&self) -> Timespan {
+        let beat = self.beat - interval_beats(self.output_latency, self.playback_tempo());
+        Timespan::approximate(beat.max(0.0))
+    }
+
+    /// Set the editor loop range and enable looping.
+    pub fn set_loop_range(&mut self, start: Timespan, end: Timespan) {
+        if start < end {
+            self.loop_range = Some((start, end));
+            self.loop_enabled = true;
+        }
+    }
+
+    /// Returns the editor loop range, if any.
+    pub fn loop_range(&self) -> Option<(Timespan, Timespan)> {
+        self.loop_range
+    }
+
+    /// Returns true if the editor loop range is set and enabled.
+    pub fn loop_enabled(&self) -> bool {
+        self.loop_enabled && self.loop_range.is_some()
+    }
+
+    /// Enable/disable the editor loop range. Returns false if no range is set.
+    pub fn toggle_loop(&mut self) -> bool {
+        if self.loop_range.is_some() {
+            self.loop_enabled = !self.loop_enabled;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Record the time taken to process an audio buffer of `buffer_time`
+    /// seconds, for profiling.
+    pub fn record_dsp_time(&mut self, dsp_time: f64, buffer_time: f64) {
+        self.dsp_load = dsp_time / buffer_time;
+        self.peak_dsp_load = (self.peak_dsp_load - buffer_time / PEAK_LOAD_DECAY_TIME)
+            .max(self.dsp_load);
+    }
+
+    /// Record the peak amplitude of an audio buffer of `buffer_time` seconds,
+    /// for metering.
+    pub fn record_output_peak(&mut self, peak: f32, buffer_time: f64) {
+        self.output_peak = (self.output_peak
+            - (buffer_time / OUTPUT_PEAK_DECAY_TIME) as f32).max(peak);
+    }
+
+    /// Record the channel correlation of an audio buffer of `buffer_time`
+    /// seconds, given the sums of its left * right, left squared, and right
+    /// squared samples. Silent buffers are ignored.
+    pub fn record_output_correlation(&mut self, lr: f32, ll: f32, rr: f32, buffer_time: f64) {
+        let energy = ll * rr;
+        if energy > CORRELATION_FLOOR {
+            let c = (lr / energy.sqrt()).clamp(-1.0, 1.0);
+            let f = (buffer_time / CORRELATION_TIME).min(1.0) as f32;
+            self.output_correlation += (c - self.output_correlation) * f;
+        }
+    }
+
+    /// Returns the master output gain, taking muting into account.
+    pub fn effective_output_gain(&self) -> f32 {
+        if self.output_muted { 0.0 } else { self.output_gain }
+    }
+
+    /// Prebuild voices for patches that have been played, so that note-ons
+    /// don't need to build DSP.
+    pub fn fill_voice_pools(&self, module: &Module) {
+        for patch in &module.patches {
+            patch.fill_voice_pool(self.sample_rate, &self.stereo_width);
+        }
+    }
+
+    /// Returns the number of voices that are "on" in a track.
+    pub fn voice_count(&self, track: usize) -> usize {
+        self.synths.get(track).map(|x| x.voice_count()).unwrap_or_default()
+    }
+
+    /// Returns a description of the audio graph, one line per node, for
+    /// debugging.
+    pub fn describe_graph(&self) -> Vec<String> {
+        let mut lines = vec![format!("Master bus: {} nodes -> global FX",
+            self.mixer.node_count())];
+        lines.extend(self.synths.iter().enumerate().map(|(i, synth)| {
+            let source = if self.freeze_active(i) { "frozen audio" } else { "voices" };
+            let muted = if synth.muted { ", muted" } else { "" };
+            format!("Track {i}: {} {source}{muted} -> bus", synth.voice_count())
+        }));
+        lines
+    }
+
     pub fn is_playing(&self) -> bool {
         self.playing
     }
@@ -65,15 +404,26 @@ impl Player {
     pub fn stop(&mut self) {
         self.playing = false;
         self.metronome = false;
+        self.count_in = 0.0;
         self.clear_notes_with_origin(KeyOrigin::Pattern);
+        self.pattern_bends.clear();
+        self.bend_ramps.clear();
+        self.pattern_notes.clear();
+        self.pending_hits.clear();
+        self.scheduled.clear();
+        self.sync_frozen();
     }
 
     pub fn play(&mut self) {
         self.playing = true;
         self.looped = false;
+        self.sync_frozen();
     }
 
     pub fn play_from(&mut self, tick: Timespan, module: &Module) {
+        if self.mute_audition {
+            self.mute_audition_tracks(module);
+        }
         self.simulate_events(tick, module);
         self.beat = tick.as_f64();
         self.play();
@@ -87,19 +437,144 @@ impl Player {
         }
     }
 
-    /// Start playing at `tick` in record mode.
-    pub fn record_from(&mut self, tick: Timespan, module: &Module) {
+    /// Start playing in record mode after `count_in` beats of metronome.
+    /// Playback starts `pre_roll` beats before `tick`, but input is only
+    /// recorded from `tick`.
+    pub fn record_from(&mut self, tick: Timespan, module: &Module, count_in: f64,
+        pre_roll: f64
+    ) {
         self.metronome = true;
-        self.play_from(tick, module);
+        self.count_in = count_in;
+        self.record_start = tick.as_f64();
+        let start = Timespan::approximate((tick.as_f64() - pre_roll).max(0.0));
+        self.play_from(start, module);
+    }
+
+    /// Returns true if input should be recorded, i.e. playback is past the
+    /// count-in and pre-roll.
+    pub fn recording_armed(&self) -> bool {
+        self.playing && self.count_in <= 0.0 && self.beat >= self.record_start
     }
 
-    /// Update synths for track edits.
-    pub fn update_synths(&mut self, edits: Vec<TrackEdit>) {
+    /// Update synths for track edits. Modified tracks are unfrozen.
+    pub fn update_synths(&mut self, edits: Vec<TrackEdit>, module: &Module) {
         for edit in edits {
             match edit {
-                TrackEdit::Insert(i) =>
-                    self.synths.insert(i, Synth::new(self.sample_rate)),
-                TrackEdit::Remove(i) => { self.synths.remove(i); }
+                TrackEdit::Insert(i) => {
+                    self.mixer.insert_track(i);
+                    self.synths.insert(i, Synth::new(self.sample_rate));
+                    self.frozen.insert(i, None);
+                    if i <= self.track_tunings.len() {
+                        self.track_tunings.insert(i, None);
+                    }
+                    if let Some((track, _)) = &mut self.preview {
+                        if *track >= i {
+                            *track += 1;
+                        }
+                    }
+                }
+                TrackEdit::Remove(i) => {
+                    self.synths.remove(i);
+                    self.preview = match self.preview {
+                        Some((track, _)) if track == i => None,
+                        Some((track, time)) if track > i => Some((track - 1, time)),
+                        preview => preview,
+                    };
+                    self.mixer.remove_track(i);
+                    self.frozen.remove(i);
+                    if i < self.track_tunings.len() {
+                        self.track_tunings.remove(i);
+                    }
+                }
+                // tempo changes affect the timing of every track
+                TrackEdit::Modify(0) => for i in 1..self.frozen.len() {
+                    self.unfreeze(module, i);
+                }
+                TrackEdit::Modify(i) => self.unfreeze(module, i),
+            }
+        }
+    }
+
+    /// Start rendering a track for freezing. The result will be sent as a
+    /// `RenderUpdate::Frozen` and should be passed to `finish_freeze`.
+    pub fn freeze(&mut self, module: Arc<Module>, track: usize) -> Receiver<RenderUpdate> {
+        let id = self.next_freeze_id;
+        self.next_freeze_id += 1;
+        self.frozen[track] = Some(Freeze::Pending(id));
+        let sample_rate = self.sample_rate;
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || render_freeze_thread(module, track, id, sample_rate, tx));
+        rx
+    }
+
+    /// Replace a pending freeze with its rendered audio. Returns false if the
+    /// freeze was cancelled.
+    pub fn finish_freeze(&mut self, id: usize, data: FrozenTrack) -> bool {
+        let track = self.frozen.iter()
+            .position(|x| matches!(x, Some(Freeze::Pending(pending_id)) if *pending_id == id));
+
+        if let Some(track) = track {
+            self.synths[track].clear_notes_with_origin(
+                self.mixer.track(track), KeyOrigin::Pattern);
+            self.frozen[track] = Some(Freeze::Active(data, None));
+            self.sync_frozen_track(track);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Return a frozen track to live synthesis, or cancel a pending freeze.
+    pub fn unfreeze(&mut self, module: &Module, track: usize) {
+        match self.frozen.get_mut(track).and_then(|x| x.take()) {
+            Some(Freeze::Active(_, id)) => {
+                if let Some(id) = id {
+                    self.mixer.track(track).edit_relative(id, 0.0, SMOOTH_TIME as f64);
+                }
+                if self.playing && !self.synths[track].muted {
+                    self.simulate_track_events(
+                        Timespan::approximate(self.beat), module, track);
+                }
+            }
+            Some(Freeze::Pending(_)) | None => (),
+        }
+    }
+
+    /// Returns true if a track is frozen or being frozen.
+    pub fn track_frozen(&self, track: usize) -> bool {
+        self.frozen.get(track).is_some_and(|x| x.is_some())
+    }
+
+    /// Returns true if a track's voices are replaced by frozen audio.
+    fn freeze_active(&self, track: usize) -> bool {
+        matches!(self.frozen.get(track), Some(Some(Freeze::Active(..))))
+    }
+
+    /// Restart frozen track audio at the playhead, e.g. after a jump.
+    fn sync_frozen(&mut self) {
+        for i in 0..self.frozen.len() {
+            self.sync_frozen_track(i);
+        }
+    }
+
+    /// Restart a frozen track's audio at the playhead, or stop it if playback
+    /// is stopped or the track is muted.
+    fn sync_frozen_track(&mut self, track: usize) {
+        let play = self.playing && !self.synths[track].muted;
+
+        if let Some(Freeze::Active(data, event_id)) = &mut self.frozen[track] {
+            if let Some(id) = event_id.take() {
+                self.mixer.track(track).edit_relative(id, 0.0, SMOOTH_TIME as f64);
+            }
+
+            let block = data.block_beats.partition_point(|b| *b < self.beat);
+            let offset = block * RENDER_BLOCK_SIZE;
+            if play && offset < data.wave.len() {
+                let wave = &data.wave;
+                let ch = |i| An(WavePlayer::new(wave, i, offset, wave.len(), None));
+                let net = ch(0) | ch(1) | ch(2) | ch(3);
+                *event_id = Some(self.mixer.track(track).push_relative(
+                    0.0, f64::INFINITY, Fade::Smooth, SMOOTH_TIME as f64, 0.0, Box::new(net)));
             }
         }
     }
@@ -108,13 +583,28 @@ impl Player {
         pitch: f32, pressure: Option<f32>, patch: &Patch
     ) {
         if let Some(synth) = self.synths.get_mut(track) {
-            synth.note_on(key, pitch, pressure, patch, &mut self.seq, &self.stereo_width);
+            synth.note_on(key, pitch, pressure, patch,
+                self.mixer.track(track), &self.stereo_width);
+        }
+    }
+
+    /// Play a short one-shot note, replacing any previous preview.
+    pub fn preview_note(&mut self, track: usize, pitch: f32, pressure: f32, patch: &Patch) {
+        self.end_preview();
+        self.note_on(track, Key::new_preview(), pitch, Some(pressure), patch);
+        self.preview = Some((track, PREVIEW_TIME));
+    }
+
+    /// Release the current note preview, if any.
+    fn end_preview(&mut self) {
+        if let Some((track, _)) = self.preview.take() {
+            self.note_off(track, Key::new_preview());
         }
     }
 
     pub fn note_off(&mut self, track: usize, key: Key) {
         if let Some(synth) = self.synths.get_mut(track) {
-            synth.note_off(key, &mut self.seq);
+            synth.note_off(key, self.mixer.track(track));
         }
     }
 
@@ -131,19 +621,186 @@ impl Player {
         }
     }
 
+    /// Handle a pattern vibrato event.
+    pub fn vibrato(&mut self, track: usize, channel: u8, lfo: PatternLfo) {
+        if let Some(synth) = self.synths.get_mut(track) {
+            synth.vibrato(channel, lfo);
+        }
+    }
+
+    /// Handle a pattern tremolo event.
+    pub fn tremolo(&mut self, track: usize, channel: u8, lfo: PatternLfo) {
+        if let Some(synth) = self.synths.get_mut(track) {
+            synth.tremolo(channel, lfo);
+        }
+    }
+
     pub fn channel_pressure(&mut self, track: usize, channel: u8, pressure: f32) {
         if let Some(synth) = self.synths.get_mut(track) {
             synth.channel_pressure(channel, pressure);
         }
     }
 
+    pub fn channel_aftertouch(&mut self, track: usize, channel: u8, pressure: f32) {
+        if let Some(synth) = self.synths.get_mut(track) {
+            synth.channel_aftertouch(channel, pressure);
+        }
+    }
+
+    /// Handle a MIDI sustain pedal.
+    pub fn sustain(&mut self, track: usize, channel: u8, on: bool) {
+        if let Some(synth) = self.synths.get_mut(track) {
+            synth.sustain(channel, on, self.mixer.track(track));
+        }
+    }
+
+    /// Handle the keyjazz hold key.
+    pub fn keyboard_hold(&mut self, on: bool) {
+        for (i, synth) in self.synths.iter_mut().enumerate() {
+            synth.keyboard_hold(on, self.mixer.track(i));
+        }
+    }
+
+    /// Handle a MIDI sostenuto pedal.
+    pub fn sostenuto(&mut self, track: usize, channel: u8, on: bool) {
+        if let Some(synth) = self.synths.get_mut(track) {
+            synth.sostenuto(channel, on, self.mixer.track(track));
+        }
+    }
+
     /// MIDI-style pitch bend.
     pub fn pitch_bend(&mut self, track: usize, channel: u8, bend: f32) {
         if let Some(synth) = self.synths.get_mut(track) {
-            synth.pitch_bend(channel, bend);
+            synth.pitch_bend(KeyOrigin::Midi, channel, bend);
+        }
+    }
+
+    /// Set the pitch bend of a pattern channel, cancelling any bend ramp.
+    fn pattern_bend(&mut self, track: usize, channel: u8, bend: f32) {
+        self.bend_ramps.retain(|r| r.track != track || r.channel != channel);
+        self.set_pattern_bend(track, channel, bend);
+    }
+
+    fn set_pattern_bend(&mut self, track: usize, channel: u8, bend: f32) {
+        self.pattern_bends.insert((track, channel), bend);
+        if let Some(synth) = self.synths.get_mut(track) {
+            synth.pitch_bend(KeyOrigin::Pattern, channel, bend);
+        }
+    }
+
+    /// Start a bend ramp on a pattern channel, from its current bend.
+    fn start_bend_ramp(&mut self, track: usize, channel: u8, ramp: BendRamp, start: f64) {
+        let from = self.pattern_bends.get(&(track, channel)).copied().unwrap_or_default();
+        self.bend_ramps.retain(|r| r.track != track || r.channel != channel);
+        self.bend_ramps.push(ActiveRamp { track, channel, from, ramp, start });
+    }
+
+    /// Schedule the hits of a retrigger event, replacing any pending hits in
+    /// the channel.
+    fn retrigger(&mut self, track: usize, channel: u8, retrig: Retrigger, start: f64) {
+        self.pending_hits.retain(|h| h.track != track || h.channel != channel);
+        let from = self.synths.get(track).map_or(DEFAULT_PRESSURE, |s| s.vel_memory(channel));
+        let to = retrig.end_pressure.map(|p| p as f32 / EventData::DIGIT_MAX as f32);
+        for (offset, t) in retrig.hits() {
+            self.pending_hits.push(PendingHit {
+                track,
+                channel,
+                beat: start + offset,
+                pressure: to.map(|to| lerp(from, to, t)),
+            });
         }
     }
 
+    /// Play retriggered hits that are due at the playhead.
+    fn play_due_hits(&mut self, module: &Module) {
+        let beat = self.beat;
+        let (due, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.pending_hits)
+            .into_iter()
+            .partition(|h| h.beat <= beat);
+        self.pending_hits = pending;
+
+        for hit in due {
+            let Some(note) = self.pattern_notes.get(&(hit.track, hit.channel)).copied()
+                else { continue };
+            if let Some((patch, note)) = module.map_note(note, hit.track) {
+                let key = Key {
+                    origin: KeyOrigin::Pattern,
+                    channel: hit.channel,
+                    key: 0,
+                };
+                let pitch = self.adapt_pitch(module, hit.track, &key,
+                    module.play_pitch(self.tuning(module, hit.track), &note,
+                        module.is_kit_track(hit.track)));
+                self.note_on(hit.track, key, pitch, hit.pressure, patch);
+            }
+        }
+    }
+
+    /// Apply note delays and cuts to a due pattern event. Returns true if the
+    /// event was delayed, in which case it shouldn't be handled yet.
+    fn schedule_timing(&mut self, event: &LocatedEvent, module: &Module) -> bool {
+        let channel = &module.tracks[event.track].channels[event.channel];
+        let tick = event.event.tick;
+        let offset = self.track_offset(module, event.track);
+
+        match event.event.data {
+            EventData::Pitch(_) | EventData::NoteOff => {
+                // new notes override timing from earlier rows
+                self.scheduled.retain(|e| e.event.track != event.track
+                    || e.event.channel != event.channel || e.event.event.tick >= tick);
+                if let Some(delay) = channel.note_delay(tick) {
+                    self.scheduled.push(ScheduledEvent {
+                        beat: (tick + delay).as_f64() + offset,
+                        event: event.clone(),
+                    });
+                    return true
+                }
+            }
+            EventData::NoteCut(cut) => self.scheduled.push(ScheduledEvent {
+                beat: (tick + cut).as_f64() + offset,
+                event: LocatedEvent {
+                    event: Event { tick, data: EventData::NoteOff },
+                    ..*event
+                },
+            }),
+            _ => (),
+        }
+
+        false
+    }
+
+    /// Returns the playback offset of a track at the current tempo, in beats.
+    fn track_offset(&self, module: &Module, track: usize) -> f64 {
+        module.tracks.get(track)
+            .map_or(0.0, |t| interval_beats(t.offset as f64 / 1000.0, self.playback_tempo()))
+    }
+
+    /// Play delayed and cut events that are due at the playhead.
+    fn play_scheduled_events(&mut self, module: &Module) {
+        let beat = self.beat;
+        let (mut due, scheduled): (Vec<_>, Vec<_>) = std::mem::take(&mut self.scheduled)
+            .into_iter()
+            .partition(|e| e.beat <= beat);
+        self.scheduled = scheduled;
+        due.sort_by(|a, b| a.beat.total_cmp(&b.beat));
+
+        for e in due {
+            self.handle_event(&e.event.event, module, e.event.track, e.event.channel);
+        }
+    }
+
+    /// Advance bend ramps to the playhead, removing finished ones.
+    fn update_bend_ramps(&mut self) {
+        let beat = self.beat;
+        let bends: Vec<_> = self.bend_ramps.iter()
+            .map(|r| (r.track, r.channel, r.bend_at(beat)))
+            .collect();
+        for (track, channel, bend) in bends {
+            self.set_pattern_bend(track, channel, bend);
+        }
+        self.bend_ramps.retain(|r| r.progress(beat) < 1.0);
+    }
+
     /// Interpolation pitch bend.
     pub fn bend_to(&mut self, track: usize, key: Key, pitch: f32) {
         if let Some(synth) = self.synths.get_mut(track) {
@@ -153,78 +810,178 @@ impl Player {
 
     /// Release all notes from a given source.
     pub fn clear_notes_with_origin(&mut self, origin: KeyOrigin) {
-        for synth in self.synths.iter_mut() {
-            synth.clear_notes_with_origin(&mut self.seq, origin);
+        for (i, synth) in self.synths.iter_mut().enumerate() {
+            synth.clear_notes_with_origin(self.mixer.track(i), origin);
         }
     }
 
     /// Turns off all notes and stops playback.
     pub fn panic(&mut self) {
         self.stop();
-        for synth in self.synths.iter_mut() {
-            synth.panic(&mut self.seq);
+        for (i, synth) in self.synths.iter_mut().enumerate() {
+            synth.panic(self.mixer.track(i));
         }
     }
 
     /// Handle a frame of length `dt`.
     pub fn frame(&mut self, module: &Module, dt: f64) {
+        if let Some((_, time)) = &mut self.preview {
+            *time -= dt;
+            if *time <= 0.0 {
+                self.end_preview();
+            }
+        }
+        self.update_track_outputs(module);
+
         if !self.playing {
             return
         }
 
+        if self.count_in > 0.0 {
+            let prev_count = self.count_in;
+            self.count_in -= interval_beats(dt, self.playback_tempo());
+            // the click for the first beat of playback comes after
+            if self.count_in > 0.0 && self.count_in.floor() != prev_count.floor() {
+                self.click();
+            }
+            return
+        }
+
         let prev_time = self.beat;
-        self.beat += interval_beats(dt, self.tempo);
-        let current_timespan = Timespan::approximate(self.beat);
+        self.beat += interval_beats(dt, self.playback_tempo());
+
+        // stop at the end of the editor loop range, then jump back to the
+        // start once this frame's events have been handled
+        let loop_jump = self.loop_range
+            .filter(|(_, end)| self.loop_enabled
+                && prev_time < end.as_f64() && self.beat >= end.as_f64());
+        if let Some((_, end)) = loop_jump {
+            self.beat = end.as_f64();
+        }
 
-        let mut events = Vec::new();
+        let mut events = self.due_events(module, prev_time);
+        events.sort_by_key(|e| (e.event.tick, e.event.data.spatial_column()));
 
-        for (track_i, track) in module.tracks.iter().enumerate() {
-            for (channel_i, channel) in track.channels.iter().enumerate() {
-                let mut prev_data = [None, None, None];
-                let mut next_event = [None, None, None];
-                let mut start_tick = [Timespan::ZERO, Timespan::ZERO, Timespan::ZERO];
-                let mut glide = [false, false, false];
+        // set pressure/modulation memory so that new notes will use new values
+        for event in &events {
+            let track = &module.tracks[event.track];
+            match event.event.data {
+                EventData::Pressure(v) => self.synths[event.track].set_vel_memory(
+                    event.channel as u8,
+                    track.scale_pressure(v as f32 / EventData::DIGIT_MAX as f32)),
+                EventData::Modulation(v) => self.synths[event.track].set_mod_memory(
+                    event.channel as u8,
+                    track.scale_modulation(v as f32 / EventData::DIGIT_MAX as f32)),
+                _ => (),
+            }
+        }
 
-                for event in &channel.events {
-                    let col = event.data.logical_column();
-                    let t = event.tick.as_f64();
+        for event in events {
+            if self.schedule_timing(&event, module) {
+                continue
+            }
+            self.handle_event(&event.event, module, event.track, event.channel);
+            if let EventData::End = event.event.data {
+                break
+            }
+        }
+        self.play_scheduled_events(module);
+        self.update_bend_ramps();
+        self.play_due_hits(module);
 
-                    if t < self.beat {
-                        if t >= prev_time {
-                            events.push(LocatedEvent {
-                                event: event.clone(),
-                                track: track_i,
-                                channel: channel_i,
-                            });
-                        }
+        let tempo = self.playback_tempo();
+        for synth in &mut self.synths {
+            synth.set_tempo(tempo);
+        }
 
-                        match event.data {
-                            EventData::StartGlide(i) => if glide[i as usize] {
-                                continue
-                            } else {
-                                glide[i as usize] = true;
-                            }
-                            EventData::EndGlide(i) => glide[i as usize] = false,
-                            _ => (),
-                        }
+        if self.metronome && self.beat.ceil() != prev_time.ceil() {
+            self.click();
+        }
 
-                        if let Some(v) = prev_data.get_mut(col as usize) {
-                            *v = Some(&event.data);
-                        }
+        if let Some((start, end)) = loop_jump {
+            // an End event may already have stopped or moved playback
+            if self.playing && self.beat == end.as_f64() {
+                self.simulate_events(start, module);
+                self.beat = start.as_f64();
+                self.sync_frozen();
+            }
+        }
+    }
+
+    /// Set track output pans from their auto-pan settings at the playhead,
+    /// and track tone filters. Frozen tracks already have these in their
+    /// rendered audio.
+    fn update_track_outputs(&mut self, module: &Module) {
+        for (i, track) in module.tracks.iter().enumerate().take(self.mixer.track_count()) {
+            if self.freeze_active(i) {
+                self.mixer.set_pan(i, 0.0);
+                self.mixer.set_tone(i, None, None);
+            } else {
+                self.mixer.set_pan(i, track.auto_pan.map_or(0.0, |x| x.pan_at(self.beat)));
+                self.mixer.set_tone(i, track.highpass, track.lowpass);
+            }
+        }
+    }
+
+    /// Returns pattern events between `prev_time` and the playhead, plus
+    /// interpolated events at the playhead. Events are not sorted.
+    fn due_events(&mut self, module: &Module, prev_time: f64) -> Vec<LocatedEvent> {
+        // rebuild cursors if the module was edited or playback jumped
+        let jumped = self.cursor_beat != prev_time;
+        if self.cursor_version != module.version()
+            || jumped
+            || self.cursors.len() != module.tracks.len()
+            || self.cursors.iter().zip(&module.tracks)
+                .any(|(c, t)| c.len() != t.channels.len()) {
+            self.cursors = module.tracks.iter().enumerate().map(|(track_i, track)| {
+                // a jump simulates events up to the playhead, so offset
+                // tracks resume from there
+                let beat = if jumped {
+                    prev_time
+                } else {
+                    prev_time - self.track_offset(module, track_i)
+                };
+                track.channels.iter().map(|channel| {
+                    let mut cursor = ChannelCursor::default();
+                    cursor.advance(channel, beat, |_| ());
+                    cursor
+                }).collect()
+            }).collect();
+            self.cursor_version = module.version();
+        }
+        self.cursor_beat = self.beat;
+
+        let beat = self.beat;
+        let current_timespan = Timespan::approximate(beat);
+        let mut events = Vec::new();
+
+        for (track_i, track) in module.tracks.iter().enumerate() {
+            // frozen tracks still advance so that their cursors stay valid
+            let frozen = self.freeze_active(track_i);
+            let tuning = self.tuning(module, track_i);
+            let beat = beat - self.track_offset(module, track_i);
 
-                        start_tick[event.data.spatial_column() as usize] = event.tick;
-                    } else if let Some(v) = next_event.get_mut(col as usize) {
-                        if v.is_none() {
-                            *v = Some(event);
-                        }
-                    }
+            for (channel_i, channel) in track.channels.iter().enumerate() {
+                let cursor = &mut self.cursors[track_i][channel_i];
+                cursor.advance(channel, beat, |event| if !frozen {
+                    events.push(LocatedEvent {
+                        event: event.clone(),
+                        track: track_i,
+                        channel: channel_i,
+                    });
+                });
+
+                if frozen {
+                    continue
                 }
 
-                for i in 0..prev_data.len() {
-                    if glide[i] {
+                for i in 0..cursor.glide.len() {
+                    if cursor.glide[i] {
                         if let Some(data) = interpolate_events(
-                            prev_data[i], next_event[i], start_tick[i],
-                            self.beat as f32, module
+                            cursor.prev_data[i].as_ref(),
+                            cursor.next_event(channel, i as u8),
+                            cursor.start_tick[i], beat as f32, cursor.curve[i],
+                            module, tuning, track_i
                         ) {
                             events.push(LocatedEvent {
                                 track: track_i,
@@ -240,35 +997,14 @@ impl Player {
             }
         }
 
-        events.sort_by_key(|e| (e.event.tick, e.event.data.spatial_column()));
-
-        // set pressure/modulation memory so that new notes will use new values
-        for event in &events {
-            match event.event.data {
-                EventData::Pressure(v) => self.synths[event.track].set_vel_memory(
-                    event.channel as u8, v as f32 / EventData::DIGIT_MAX as f32),
-                EventData::Modulation(v) => self.synths[event.track].set_mod_memory(
-                    event.channel as u8, v as f32 / EventData::DIGIT_MAX as f32),
-                _ => (),
-            }
-        }
-
-        for event in events {
-            self.handle_event(&event.event, module, event.track, event.channel);
-            if let EventData::End = event.event.data {
-                break
-            }
-        }
-
-        if self.metronome && self.beat.ceil() != prev_time.ceil() {
-            self.seq.push_relative(0.0, 0.01, Fade::Smooth, 0.01, 0.01,
-                Box::new(square_hz(440.0 * 8.0) >> split::<U4>()));
-        }
+        events
     }
 
     /// Update state as if the module had been played up to a given tick.
     fn simulate_events(&mut self, tick: Timespan, module: &Module) {
+        self.clear_notes_with_origin(KeyOrigin::Pattern);
         self.tempo = DEFAULT_TEMPO;
+        (self.global_tuning, self.track_tunings) = module.tunings_before(tick);
 
         for track in 0..module.tracks.len() {
             self.simulate_track_events(tick, module, track);
@@ -278,48 +1014,93 @@ impl Player {
     /// Update one track's state as if the module had been played up to `tick`.
     fn simulate_track_events(&mut self, tick: Timespan, module: &Module, track_i: usize) {
         self.synths[track_i].reset_memory();
+        self.pattern_bends.retain(|(track, _), _| *track != track_i);
+        self.bend_ramps.retain(|r| r.track != track_i);
+        self.pattern_notes.retain(|(track, _), _| *track != track_i);
+        self.pending_hits.retain(|h| h.track != track_i);
+        self.scheduled.retain(|e| e.event.track != track_i);
+
+        if self.freeze_active(track_i) {
+            return
+        }
 
-        for (channel_i, channel) in module.tracks[track_i].channels.iter().enumerate() {
+        let track = &module.tracks[track_i];
+        for (channel_i, channel) in track.channels.iter().enumerate() {
             let mut events: Vec<_> = channel.events.iter()
                 .filter(|e| e.tick < tick)
                 .collect();
             events.sort_by_key(|e| (e.tick, e.data.spatial_column()));
 
+            let glides = self.glides_at(tick, module, track_i, channel);
             let mut active_note = None;
-            let mut bend_offset = 0;
+            let mut bend_offset = 0.0;
+            let mut bend_ramp = None;
+            let mut cut_tick = None;
 
             for evt in events {
                 match evt.data {
                     EventData::Pitch(note) => {
+                        self.pattern_notes.insert((track_i, channel_i as u8), note);
+                        cut_tick = channel.note_cut(evt.tick).map(|c| evt.tick + c);
                         if let Some((patch, note)) = module.map_note(note, track_i) {
                             if patch.sustains() {
                                 active_note = Some((patch, note));
-                                bend_offset = 0;
+                                bend_offset = 0.0;
+                                bend_ramp = None;
                             }
                         }
                     }
                     EventData::Pressure(v) =>
                         self.channel_pressure(track_i, channel_i as u8,
-                            v as f32 / EventData::DIGIT_MAX as f32),
+                            track.scale_pressure(v as f32 / EventData::DIGIT_MAX as f32)),
                     EventData::Modulation(v) =>
                         self.modulate(track_i, channel_i as u8,
-                            v as f32 / EventData::DIGIT_MAX as f32),
-                    EventData::NoteOff => active_note = None,
+                            track.scale_modulation(v as f32 / EventData::DIGIT_MAX as f32)),
+                    EventData::NoteOff => {
+                        active_note = None;
+                        cut_tick = None;
+                    }
+                    EventData::NoteCut(c) => cut_tick = Some(evt.tick + c),
                     EventData::Tempo(t) => self.tempo = t,
                     EventData::RationalTempo(n, d) => self.tempo *= n as f32 / d as f32,
                     EventData::End | EventData::Loop | EventData::StartGlide(_)
                         | EventData::EndGlide(_) | EventData::TickGlide(_)
-                        | EventData::Section => (),
+                        | EventData::GlideCurve(..) | EventData::Section => (),
                     EventData::InterpolatedPitch(_)
                         | EventData::InterpolatedPressure(_)
                         | EventData::InterpolatedModulation(_)
                         => panic!("interpolated event in pattern"),
-                    EventData::Bend(c) => bend_offset = c,
+                    EventData::Bend(c) => {
+                        bend_offset = c as f32 / 100.0;
+                        bend_ramp = None;
+                    }
+                    EventData::BendRamp(ramp) => {
+                        let active = ActiveRamp {
+                            track: track_i,
+                            channel: channel_i as u8,
+                            from: bend_offset,
+                            ramp,
+                            start: evt.tick.as_f64(),
+                        };
+                        bend_offset = active.bend_at(tick.as_f64());
+                        bend_ramp = (active.progress(tick.as_f64()) < 1.0).then_some(active);
+                    }
+                    EventData::TuningChange(_) | EventData::Retrigger(_)
+                        | EventData::NoteDelay(_) | EventData::GlobalModulation(_) => (),
+                    EventData::Vibrato(lfo) => self.vibrato(track_i, channel_i as u8, lfo),
+                    EventData::Tremolo(lfo) => self.tremolo(track_i, channel_i as u8, lfo),
                 }
             }
 
-            if channel.events.iter()
-                .any(|e| e.tick == tick && e.data == EventData::NoteOff) {
+            // notes at the start tick will be triggered by the first frame;
+            // starting the previous note here would cause a double attack.
+            if channel.events.iter().any(|e| e.tick == tick
+                && (e.data == EventData::NoteOff || matches!(e.data, EventData::Pitch(_))
+                    && !channel.is_interpolated(NOTE_COLUMN, e.tick))) {
+                active_note = None;
+            }
+
+            if cut_tick.is_some_and(|t| t <= tick) {
                 active_note = None;
             }
 
@@ -329,9 +1110,74 @@ impl Player {
                     channel: channel_i as u8,
                     key: 0,
                 };
-                let pitch = module.tuning.midi_pitch(&note);
+                let pitch = self.adapt_pitch(module, track_i, &key,
+                    module.play_pitch(self.tuning(module, track_i), &note,
+                        module.is_kit_track(track_i)));
                 self.note_on(track_i, key, pitch, None, patch);
-                self.pitch_bend(track_i, channel_i as u8, bend_offset as f32 / 100.0);
+                self.pattern_bend(track_i, channel_i as u8, bend_offset);
+                self.bend_ramps.extend(bend_ramp);
+                if let Some(cut_tick) = cut_tick {
+                    self.scheduled.push(ScheduledEvent {
+                        beat: cut_tick.as_f64(),
+                        event: LocatedEvent {
+                            track: track_i,
+                            channel: channel_i,
+                            event: Event { tick, data: EventData::NoteOff },
+                        },
+                    });
+                }
+            }
+
+            // glides in progress start from their value at the tick
+            for data in glides {
+                self.handle_event(&Event { tick, data }, module, track_i, channel_i);
+            }
+        }
+
+        self.simulate_global_modulation(tick, module, track_i);
+    }
+
+    /// Returns the interpolated data of glides in progress at `tick` in a
+    /// channel, as playback would send them on reaching `tick`.
+    fn glides_at(&self, tick: Timespan, module: &Module, track_i: usize, channel: &Channel
+    ) -> Vec<EventData> {
+        let mut cursor = ChannelCursor::default();
+        cursor.advance(channel, tick.as_f64(), |_| ());
+        let tuning = self.tuning(module, track_i);
+
+        (0..cursor.glide.len())
+            .filter(|&i| cursor.glide[i])
+            .filter_map(|i| interpolate_events(
+                cursor.prev_data[i].as_ref(),
+                cursor.next_event(channel, i as u8),
+                cursor.start_tick[i], tick.as_f32(), cursor.curve[i],
+                module, tuning, track_i
+            ))
+            .collect()
+    }
+
+    /// Apply the global modulation at `tick` to a track.
+    fn simulate_global_modulation(&mut self, tick: Timespan, module: &Module, track_i: usize) {
+        let glide = module.tracks[0].channels.iter()
+            .flat_map(|channel| self.glides_at(tick, module, 0, channel))
+            .find_map(|data| match data {
+                EventData::InterpolatedModulation(v) => Some(v),
+                _ => None,
+            });
+        let depth = glide.or(module.global_modulation_before(tick)
+            .map(|v| v as f32 / EventData::DIGIT_MAX as f32));
+        if let Some(depth) = depth {
+            for channel in 0..module.tracks[track_i].channels.len() {
+                self.modulate(track_i, channel as u8, depth);
+            }
+        }
+    }
+
+    /// Handle a global modulation event.
+    fn modulate_all(&mut self, module: &Module, depth: f32) {
+        for (i, track) in module.tracks.iter().enumerate() {
+            for channel in 0..track.channels.len() {
+                self.modulate(i, channel as u8, depth);
             }
         }
     }
@@ -345,6 +1191,7 @@ impl Player {
 
     /// Reinitialize vel/mod memory (for looping).
     fn reinit_memory(&mut self, tick: Timespan, module: &Module) {
+        (self.global_tuning, self.track_tunings) = module.tunings_before(tick);
         for track in 0..module.tracks.len() {
             self.reinit_track_memory(tick, module, track);
         }
@@ -354,25 +1201,31 @@ impl Player {
     fn reinit_track_memory(&mut self, tick: Timespan, module: &Module, track_i: usize) {
         self.synths[track_i].reset_memory();
 
-        for (channel_i, channel) in module.tracks[track_i].channels.iter().enumerate() {
+        let track = &module.tracks[track_i];
+        for (channel_i, channel) in track.channels.iter().enumerate() {
             let mut events: Vec<_> = channel.events.iter()
                 .filter(|e| e.tick < tick
-                    && (VEL_COLUMN..=MOD_COLUMN).contains(&e.data.logical_column()))
+                    && ((VEL_COLUMN..=MOD_COLUMN).contains(&e.data.logical_column())
+                        || matches!(e.data, EventData::Vibrato(_) | EventData::Tremolo(_))))
                 .collect();
             events.sort_by_key(|e| e.tick);
 
             for evt in events {
                 match evt.data {
                     EventData::Pressure(v) =>
-                        self.synths[track_i].set_vel_memory(
-                            channel_i as u8, v as f32 / EventData::DIGIT_MAX as f32),
+                        self.synths[track_i].set_vel_memory(channel_i as u8,
+                            track.scale_pressure(v as f32 / EventData::DIGIT_MAX as f32)),
                     EventData::Modulation(v) =>
-                        self.synths[track_i].set_mod_memory(
-                            channel_i as u8, v as f32 / EventData::DIGIT_MAX as f32),
+                        self.synths[track_i].set_mod_memory(channel_i as u8,
+                            track.scale_modulation(v as f32 / EventData::DIGIT_MAX as f32)),
+                    EventData::Vibrato(lfo) => self.synths[track_i].vibrato(channel_i as u8, lfo),
+                    EventData::Tremolo(lfo) => self.synths[track_i].tremolo(channel_i as u8, lfo),
                     _ => ()
                 }
             }
         }
+
+        self.simulate_global_modulation(tick, module, track_i);
     }
 
     /// Mute/unmute a track.
@@ -385,10 +1238,28 @@ impl Player {
         synth.muted = !synth.muted;
 
         if synth.muted {
-            synth.clear_all_notes(&mut self.seq);
+            synth.clear_all_notes(self.mixer.track(track_i));
         } else if self.playing {
             self.simulate_track_events(Timespan::approximate(self.beat), module, track_i);
         }
+        self.sync_frozen_track(track_i);
+    }
+
+    /// Seed the random values of each track's voices, for deterministic
+    /// offline renders.
+    pub fn seed(&mut self, seed: u32) {
+        for (i, synth) in self.synths.iter_mut().enumerate() {
+            synth.seed((seed as u64) << 32 | i as u64);
+        }
+    }
+
+    /// Mute tracks that are flagged as audition-only.
+    pub fn mute_audition_tracks(&mut self, module: &Module) {
+        for (i, track) in module.tracks.iter().enumerate() {
+            if track.audition_only && self.synths.get(i).is_some_and(|s| !s.muted) {
+                self.toggle_mute(module, i);
+            }
+        }
     }
 
     /// Solo/unsolo a track.
@@ -424,6 +1295,32 @@ impl Player {
         self.synths[i].muted
     }
 
+    /// Returns the tuning currently in effect for a track.
+    fn tuning<'a>(&self, module: &'a Module, track: usize) -> &'a Tuning {
+        let index = self.track_tunings.get(track).copied().flatten();
+        module.tuning_at(index.unwrap_or(self.global_tuning))
+    }
+
+    /// Returns `pitch` adjusted for adaptive JI, relative to the lowest note
+    /// sounding in other channels. Kit notes are left alone.
+    fn adapt_pitch(&self, module: &Module, track: usize, key: &Key, pitch: f32) -> f32 {
+        if !module.adaptive_ji || module.is_kit_track(track) {
+            return pitch
+        }
+
+        let reference = self.synths.iter().enumerate()
+            .filter(|(i, _)| !module.is_kit_track(*i))
+            .flat_map(|(i, synth)| synth.active_pitches()
+                .filter(move |(k, _)| i != track || *k != key)
+                .map(|(_, pitch)| pitch))
+            .min_by(|a, b| a.total_cmp(b));
+
+        match reference {
+            Some(reference) => adaptive_ji_pitch(pitch, reference, module.ji_tolerance),
+            None => pitch,
+        }
+    }
+
     /// Process a pattern event.
     fn handle_event(&mut self, event: &Event, module: &Module,
         track: usize, channel: usize
@@ -436,23 +1333,33 @@ impl Player {
 
         match event.data {
             EventData::Pitch(note) => {
+                self.pattern_notes.insert((track, key.channel), note);
                 if let Some((patch, note)) = module.map_note(note, track) {
-                    let pitch = module.tuning.midi_pitch(&note);
+                    let pitch = self.adapt_pitch(module, track, &key,
+                        module.play_pitch(self.tuning(module, track), &note,
+                            module.is_kit_track(track)));
                     let channel = &module.tracks[track].channels[channel];
                     if channel.is_interpolated(NOTE_COLUMN, event.tick) {
                         self.bend_to(track, key, pitch);
                     } else {
+                        // new notes start unbent and cancel retriggers
+                        self.bend_ramps.retain(|r| r.track != track || r.channel != key.channel);
+                        self.pending_hits.retain(|h| h.track != track || h.channel != key.channel);
+                        self.pattern_bends.remove(&(track, key.channel));
                         self.note_on(track, key, pitch, None, patch);
                     }
                 }
             }
             EventData::Pressure(v) =>
-                self.channel_pressure(track, channel as u8,
-                    v as f32 / EventData::DIGIT_MAX as f32),
+                self.channel_pressure(track, channel as u8, module.tracks[track]
+                    .scale_pressure(v as f32 / EventData::DIGIT_MAX as f32)),
             EventData::Modulation(v) =>
-                self.modulate(track, channel as u8,
-                    v as f32 / EventData::DIGIT_MAX as f32),
-            EventData::NoteOff => self.note_off(track, key),
+                self.modulate(track, channel as u8, module.tracks[track]
+                    .scale_modulation(v as f32 / EventData::DIGIT_MAX as f32)),
+            EventData::NoteOff => {
+                self.pending_hits.retain(|h| h.track != track || h.channel != key.channel);
+                self.note_off(track, key);
+            }
             EventData::Tempo(t) => self.tempo = t,
             EventData::RationalTempo(n, d) => {
                 let channel = &module.tracks[track].channels[channel];
@@ -461,24 +1368,74 @@ impl Player {
                 }
             }
             EventData::End => if let Some(tick) = module.find_loop_start(self.beat) {
+                // keep scheduled events at the same offset from the playhead
+                let shift = tick.as_f64() - self.beat;
+                for e in &mut self.scheduled {
+                    e.beat += shift;
+                }
                 self.beat = tick.as_f64();
                 self.reinit_memory(tick, module);
                 self.looped = true;
+                self.sync_frozen();
             } else {
                 self.stop();
             },
             EventData::Loop | EventData::StartGlide(_) | EventData::EndGlide(_)
-                | EventData::TickGlide(_) | EventData::Section => (),
+                | EventData::TickGlide(_) | EventData::GlideCurve(..)
+                | EventData::Section => (),
             EventData::InterpolatedPitch(pitch) => self.bend_to(track, key, pitch),
             EventData::InterpolatedPressure(v) =>
-                self.channel_pressure(track, channel as u8, v),
-            EventData::InterpolatedModulation(v) =>
-                self.modulate(track, channel as u8, v),
-            EventData::Bend(c) => self.pitch_bend(track, channel as u8, c as f32 / 100.0),
+                self.channel_pressure(track, channel as u8,
+                    module.tracks[track].scale_pressure(v)),
+            // only global modulation glides are interpolated in track 0
+            EventData::InterpolatedModulation(v) => if track == 0 {
+                self.modulate_all(module, v)
+            } else {
+                self.modulate(track, channel as u8, module.tracks[track].scale_modulation(v))
+            },
+            EventData::GlobalModulation(v) =>
+                self.modulate_all(module, v as f32 / EventData::DIGIT_MAX as f32),
+            EventData::Bend(c) => self.pattern_bend(track, channel as u8, c as f32 / 100.0),
+            EventData::BendRamp(ramp) => {
+                let start = event.tick.as_f64() + self.track_offset(module, track);
+                self.start_bend_ramp(track, channel as u8, ramp, start)
+            }
+            EventData::Retrigger(retrig) => {
+                let start = event.tick.as_f64() + self.track_offset(module, track);
+                self.retrigger(track, channel as u8, retrig, start)
+            }
+            // scheduled when the event is due
+            EventData::NoteDelay(_) | EventData::NoteCut(_) => (),
+            EventData::Vibrato(lfo) => self.vibrato(track, channel as u8, lfo),
+            EventData::Tremolo(lfo) => self.tremolo(track, channel as u8, lfo),
+            EventData::TuningChange(i) => if track == 0 {
+                self.global_tuning = i as usize;
+                self.track_tunings.fill(None);
+            } else {
+                if self.track_tunings.len() <= track {
+                    self.track_tunings.resize(track + 1, None);
+                }
+                self.track_tunings[track] = Some(i as usize);
+            },
         }
     }
 }
 
+/// Moves `pitch` to the nearest simple JI ratio from `reference`, if that
+/// ratio is within `tolerance` cents.
+fn adaptive_ji_pitch(pitch: f32, reference: f32, tolerance: f32) -> f32 {
+    let interval = (pitch - reference).abs() * 100.0;
+    let (_, _, error) = approximate_ratio(interval);
+
+    if error.abs() > tolerance {
+        pitch
+    } else if pitch >= reference {
+        pitch - error / 100.0
+    } else {
+        pitch + error / 100.0
+    }
+}
+
 /// Convert a time interval to beat-space.
 fn interval_beats(dt: f64, tempo: f32) -> f64 {
     dt * tempo as f64 / 60.0
@@ -492,84 +1449,241 @@ pub fn tick_interval(dtick: Timespan, tempo: f32) -> f64 {
 /// Used to communicate between the render thread and main thread.
 pub enum RenderUpdate {
     Progress(f64),
-    Done(Wave, PathBuf),
+    Done(Wave, PathBuf, WavMetadata),
+    /// Freeze ID and rendered track data.
+    Frozen(usize, FrozenTrack),
+}
+
+/// Section of the song for `render` to play.
+#[derive(Clone, Copy)]
+pub struct RenderBounds {
+    /// Position to start playback at.
+    pub start: Timespan,
+    /// Position to stop at, followed by a tail for note releases and
+    /// effects. Rendering stops early if the song ends or loops. If `None`,
+    /// plays to the End event, fading out if the song loops.
+    pub end: Option<Timespan>,
+    /// Number of times to play from `start` to `end`. If more than 1, only
+    /// the last pass is kept, without a tail, so that it starts with the
+    /// tail of the pass before it.
+    pub repeats: u32,
+}
+
+impl RenderBounds {
+    /// The whole song.
+    pub const SONG: Self = Self { start: Timespan::ZERO, end: None, repeats: 1 };
+
+    /// The timespan between `start` and `end`.
+    pub fn range(start: Timespan, end: Timespan) -> Self {
+        Self { start, end: Some(end), repeats: 1 }
+    }
+
+    /// The loop between `start` and `end`, repeated until the tail of the
+    /// previous pass has rung out, so that the render loops seamlessly.
+    pub fn seamless_loop(module: &Module, start: Timespan, end: Timespan) -> Self {
+        let pass_time = module.time_at(end) - module.time_at(start);
+        let tail_passes = (LOOP_TAIL_TIME / pass_time).ceil()
+            .clamp(1.0, (MAX_LOOP_REPEATS - 1) as f64);
+        Self { start, end: Some(end), repeats: 1 + tail_passes as u32 }
+    }
 }
 
-/// Renders module to PCM. Loops forever if module is missing End!
-/// If `track` is some, solo that track for rendering.
-pub fn render(module: Arc<Module>, path: PathBuf, track: Option<usize>
+/// Renders module to PCM. Loops forever if module is missing End and
+/// `bounds` has no end! If `track` is some, solo that track for rendering.
+pub fn render(module: Arc<Module>, path: PathBuf, track: Option<usize>,
+    bounds: RenderBounds
 ) -> Receiver<RenderUpdate> {
     let (tx, rx) = mpsc::channel();
 
     thread::spawn(move || {
         const SAMPLE_RATE: f64 = 44100.0;
-        const BLOCK_SIZE: i32 = 64;
-
-        let mut wave = Wave::new(2, SAMPLE_RATE);
-        let mut seq = Sequencer::new(false, 4);
-        seq.set_sample_rate(SAMPLE_RATE);
-        let mut fx = GlobalFX::new(seq.backend(), &module.fx);
-        let fadeout_gain = shared(1.0);
-        fx.net = fx.net * (var(&fadeout_gain) | var(&fadeout_gain));
-        fx.net.set_sample_rate(SAMPLE_RATE);
-        let mut player = Player::new(seq, module.tracks.len(), SAMPLE_RATE as f32);
-        if let Some(track) = track {
-            player.toggle_solo(&module, track);
-        }
-        let mut backend = BlockRateAdapter::new(Box::new(fx.net.backend()));
-        let dt = BLOCK_SIZE as f64 / SAMPLE_RATE;
-        let mut playtime = 0.0;
-        let mut time_since_loop = 0.0;
-        let render_time = if module.loops() {
-            module.playtime() + LOOP_FADEOUT_TIME
+
+        let rendered = render_blocks(&module, track, &bounds, SAMPLE_RATE, true, &tx);
+        let meta = if bounds.repeats > 1 {
+            WavMetadata {
+                loop_points: Some((0, rendered.len() as u32)),
+                ..WavMetadata::new(&module, bounds.start)
+            }
+        } else if bounds.end.is_some() {
+            WavMetadata::new(&module, bounds.start)
         } else {
-            module.playtime()
+            WavMetadata::for_song(&module, SAMPLE_RATE)
         };
-        let mut prev_progress = 0.0;
+        if let Err(e) = tx.send(RenderUpdate::Done(rendered.wave(SAMPLE_RATE), path, meta)) {
+            eprintln!("{e}");
+        }
+    });
 
-        player.play();
-        while player.playing && time_since_loop < LOOP_FADEOUT_TIME {
-            player.frame(&module, dt);
-            playtime += dt;
-            for _ in 0..BLOCK_SIZE {
-                wave.push(backend.get_stereo());
-            }
-            if player.looped {
-                fadeout_gain.set(1.0 - (time_since_loop / LOOP_FADEOUT_TIME) as f32);
-                time_since_loop += dt;
-            }
-
-            let progress = playtime / render_time;
-            if progress - prev_progress >= 0.01 {
-                prev_progress = progress;
-                if let Err(e) = tx.send(RenderUpdate::Progress(progress)) {
-                    eprintln!("{e}");
-                }
+    rx
+}
+
+/// Thread body for `Player::freeze`. Renders the track's dry and FX send
+/// output, without global FX, from the start of the song to the end or loop.
+fn render_freeze_thread(module: Arc<Module>, track: usize, id: usize,
+    sample_rate: f32, tx: Sender<RenderUpdate>
+) {
+    let end = module.last_event_tick().unwrap_or_default() + Timespan::new(1, 1);
+    let bounds = RenderBounds::range(Timespan::ZERO, end);
+    let rendered = render_blocks(&module, Some(track), &bounds,
+        sample_rate as f64, false, &tx);
+    let data = FrozenTrack {
+        wave: Arc::new(rendered.wave(sample_rate as f64)),
+        block_beats: rendered.block_beats,
+    };
+
+    if let Err(e) = tx.send(RenderUpdate::Frozen(id, data)) {
+        eprintln!("{e}");
+    }
+}
+
+/// Output of `render_blocks`.
+struct Rendered {
+    /// Samples of each output channel.
+    channels: Vec<Vec<f32>>,
+    /// Beat position at the start of each render block.
+    block_beats: Vec<f64>,
+}
+
+impl Rendered {
+    /// Returns the length in samples.
+    fn len(&self) -> usize {
+        self.channels[0].len()
+    }
+
+    fn wave(&self, sample_rate: f64) -> Wave {
+        let mut wave = Wave::from_samples(sample_rate, &self.channels[0]);
+        for (i, samples) in self.channels.iter().enumerate().skip(1) {
+            wave.insert_channel(i, samples);
+        }
+        wave
+    }
+}
+
+/// Plays `bounds` of the module offline, sending progress updates to `tx`.
+/// With `global_fx`, the output is the stereo master; otherwise it's the dry
+/// stereo and FX send stereo of the mixer.
+fn render_blocks(module: &Module, track: Option<usize>, bounds: &RenderBounds,
+    sample_rate: f64, global_fx: bool, tx: &Sender<RenderUpdate>
+) -> Rendered {
+    let mut mixer = Mixer::new(module.tracks.len(), sample_rate);
+    let mut fx = None;
+    let backend = if global_fx {
+        let fx = fx.insert(GlobalFX::new(mixer.backend(), &module.fx));
+        fx.net.set_sample_rate(sample_rate);
+        Box::new(fx.net.backend()) as Box<dyn AudioUnit>
+    } else {
+        mixer.backend()
+    };
+    let mut backend = BlockRateAdapter::new(backend);
+    let mut player = Player::new(mixer, sample_rate as f32);
+    player.seed(module.render_seed);
+    if let Some(track) = track {
+        player.toggle_solo(module, track);
+    } else {
+        player.mute_audition_tracks(module);
+    }
+    let repeats = bounds.repeats.max(1);
+    if let Some(end) = bounds.end.filter(|_| repeats > 1) {
+        player.set_loop_range(bounds.start, end);
+    }
+
+    let dt = RENDER_BLOCK_SIZE as f64 / sample_rate;
+    let start_beat = bounds.start.as_f64();
+    let end_beat = bounds.end.map(|t| t.as_f64());
+    let mut channels = vec![Vec::new(); backend.outputs()];
+    let mut frame = vec![0.0; backend.outputs()];
+    let mut block_beats = Vec::new();
+    let mut render_frame = |backend: &mut BlockRateAdapter,
+        channels: &mut [Vec<f32>], gain: f32
+    | {
+        backend.tick(&[], &mut frame);
+        for (samples, x) in channels.iter_mut().zip(&frame) {
+            samples.push(x * gain);
+        }
+    };
+    let mut pass = 1;
+    let mut pass_start = 0;
+    let mut playtime = 0.0;
+    let mut time_since_loop = 0.0;
+    let mut fadeout_gain = 1.0;
+    let render_time = if module.loops() {
+        module.playtime() + LOOP_FADEOUT_TIME
+    } else {
+        module.playtime()
+    };
+    let mut prev_progress = 0.0;
+
+    player.play_from(bounds.start, module);
+    while player.playing && match end_beat {
+        Some(_) if repeats > 1 => true,
+        Some(end) => !player.looped && player.beat < end,
+        None => time_since_loop < LOOP_FADEOUT_TIME,
+    } {
+        let prev_beat = player.beat;
+        player.frame(module, dt);
+        // the block after a jump back to the start belongs to the next pass
+        if repeats > 1 && player.beat < prev_beat {
+            if pass == repeats {
+                break
             }
+            pass += 1;
+            pass_start = block_beats.len();
+        }
+        block_beats.push(prev_beat);
+        playtime += dt;
+        for _ in 0..RENDER_BLOCK_SIZE {
+            render_frame(&mut backend, &mut channels, fadeout_gain);
+        }
+        if player.looped {
+            fadeout_gain = 1.0 - (time_since_loop / LOOP_FADEOUT_TIME) as f32;
+            time_since_loop += dt;
         }
 
-        if let Err(e) = tx.send(RenderUpdate::Done(wave, path)) {
-            eprintln!("{e}");
+        let progress = match end_beat {
+            Some(end) => ((pass - 1) as f64
+                + (player.beat - start_beat) / (end - start_beat)) / repeats as f64,
+            None => playtime / render_time,
+        };
+        if progress - prev_progress >= 0.01 {
+            prev_progress = progress;
+            if let Err(e) = tx.send(RenderUpdate::Progress(progress)) {
+                eprintln!("{e}");
+            }
         }
-    });
+    }
 
-    rx
+    if repeats > 1 {
+        // keep only the last pass, which starts with the tail of the one
+        // before it
+        block_beats.drain(..pass_start);
+        for samples in &mut channels {
+            samples.drain(..pass_start * RENDER_BLOCK_SIZE);
+        }
+    } else if end_beat.is_some() {
+        // release notes and let voices and effects ring out
+        player.stop();
+        for _ in 0..(RANGE_TAIL_TIME * sample_rate) as usize {
+            render_frame(&mut backend, &mut channels, fadeout_gain);
+        }
+    }
+
+    Rendered { channels, block_beats }
 }
 
-/// Renders each track to its own WAV file.
+/// Renders each track to its own WAV file, except audition-only tracks.
 pub fn render_tracks(module: Arc<Module>, path: PathBuf) -> Receiver<RenderUpdate> {
     let (tx, rx) = mpsc::channel();
-    let track_range = 1..module.tracks.len();
-    let progress = Arc::new(Mutex::new(
-        track_range.clone().map(|_| 0.0).collect::<Vec<_>>()
-    ));
+    let tracks: Vec<_> = (1..module.tracks.len())
+        .filter(|i| !module.tracks[*i].audition_only)
+        .collect();
+    let progress = Arc::new(Mutex::new(vec![0.0; tracks.len()]));
 
-    for i in track_range {
+    for (progress_i, i) in tracks.into_iter().enumerate() {
         let path = path
             .with_file_name(format!("{}_{}",
                 path.file_stem().and_then(|s| s.to_str()).unwrap_or_default(), i))
             .with_extension("wav");
-        let track_rx = render(module.clone(), path, Some(i));
+        let track_rx = render(module.clone(), path, Some(i), RenderBounds::SONG);
         let tx = tx.clone();
         let progress = progress.clone();
 
@@ -578,16 +1692,17 @@ pub fn render_tracks(module: Arc<Module>, path: PathBuf) -> Receiver<RenderUpdat
                 match msg {
                     RenderUpdate::Progress(f) => {
                         let mut progress = progress.lock().unwrap();
-                        progress[i - 1] = f;
+                        progress[progress_i] = f;
                         let total_progress = progress.iter().sum::<f64>()
                             / progress.len() as f64;
                         if let Err(e) = tx.send(RenderUpdate::Progress(total_progress)) {
                             eprintln!("{e}")
                         }
                     }
-                    RenderUpdate::Done(..) => if let Err(e) = tx.send(msg) {
-                        eprintln!("{e}")
-                    }
+                    RenderUpdate::Done(..) | RenderUpdate::Frozen(..) =>
+                        if let Err(e) = tx.send(msg) {
+                            eprintln!("{e}")
+                        }
                 }
             }
         });
@@ -596,17 +1711,19 @@ pub fn render_tracks(module: Arc<Module>, path: PathBuf) -> Receiver<RenderUpdat
     rx
 }
 
-/// Calculates interpolated event data.
+/// Calculates interpolated event data for a channel of `track`.
 fn interpolate_events(prev: Option<&EventData>, next: Option<&Event>,
-    start: Timespan, time: f32, module: &Module
+    start: Timespan, time: f32, curve: GlideCurve, module: &Module, tuning: &Tuning,
+    track: usize
 ) -> Option<EventData> {
     if let Some(next) = next {
-        let t = (time - start.as_f32()) / (next.tick.as_f32() - start.as_f32());
+        let t = curve.apply((time - start.as_f32()) / (next.tick.as_f32() - start.as_f32()));
 
         match next.data {
             EventData::Pitch(b) => if let Some(EventData::Pitch(a)) = prev {
-                let a = module.tuning.midi_pitch(a);
-                let b = module.tuning.midi_pitch(&b);
+                let kit = module.is_kit_track(track);
+                let a = module.play_pitch(tuning, a, kit);
+                let b = module.play_pitch(tuning, &b, kit);
                 Some(EventData::InterpolatedPitch(lerp(a, b, t)))
             } else {
                 None
@@ -646,9 +1763,381 @@ fn interpolate_events(prev: Option<&EventData>, next: Option<&Event>,
                 let b = b as f32 / EventData::DIGIT_MAX as f32;
                 Some(EventData::InterpolatedModulation(lerp(a, b, t)))
             }
+            EventData::GlobalModulation(b) => {
+                let a = if let Some(EventData::GlobalModulation(a)) = prev {
+                    *a as f32 / EventData::DIGIT_MAX as f32
+                } else {
+                    0.0
+                };
+                let b = b as f32 / EventData::DIGIT_MAX as f32;
+                Some(EventData::InterpolatedModulation(lerp(a, b, t)))
+            }
             _ => None,
         }
     } else {
         None
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use crate::{module::{Edit, KitEntry}, pitch::{Nominal, Note}};
+    use super::*;
+
+    /// Reference implementation of `Player::due_events` that scans every
+    /// event in the module.
+    fn scan_due_events(module: &Module, prev_time: f64, beat: f64) -> Vec<LocatedEvent> {
+        let mut events = Vec::new();
+
+        for (track_i, track) in module.tracks.iter().enumerate() {
+            for (channel_i, channel) in track.channels.iter().enumerate() {
+                let mut prev_data = [None, None, None];
+                let mut next_event = [None, None, None];
+                let mut start_tick = [Timespan::ZERO, Timespan::ZERO, Timespan::ZERO];
+                let mut glide = [false, false, false];
+                let mut curve = [GlideCurve::Linear; 3];
+
+                for event in &channel.events {
+                    let col = event.data.logical_column();
+                    let t = event.tick.as_f64();
+
+                    if t < beat {
+                        if t >= prev_time {
+                            events.push(LocatedEvent {
+                                event: event.clone(),
+                                track: track_i,
+                                channel: channel_i,
+                            });
+                        }
+
+                        match event.data {
+                            EventData::StartGlide(i) => if glide[i as usize] {
+                                continue
+                            } else {
+                                glide[i as usize] = true;
+                                curve[i as usize] = channel.glide_curve(i, event.tick);
+                            }
+                            EventData::EndGlide(i) => glide[i as usize] = false,
+                            EventData::GlideCurve(..) => continue,
+                            _ => (),
+                        }
+
+                        if let Some(v) = prev_data.get_mut(col as usize) {
+                            *v = Some(&event.data);
+                        }
+
+                        start_tick[event.data.spatial_column() as usize] = event.tick;
+                    } else if let Some(v) = next_event.get_mut(col as usize) {
+                        if v.is_none() {
+                            *v = Some(event);
+                        }
+                    }
+                }
+
+                for i in 0..prev_data.len() {
+                    if glide[i] {
+                        if let Some(data) = interpolate_events(
+                            prev_data[i], next_event[i], start_tick[i], beat as f32,
+                            curve[i], module, &module.tuning, track_i
+                        ) {
+                            events.push(LocatedEvent {
+                                track: track_i,
+                                channel: channel_i,
+                                event: Event {
+                                    tick: Timespan::approximate(beat),
+                                    data,
+                                },
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        events
+    }
+
+    fn event_strings(events: Vec<LocatedEvent>) -> Vec<String> {
+        let mut v: Vec<_> = events.iter().map(|e| format!("{e:?}")).collect();
+        v.sort();
+        v
+    }
+
+    fn located(track: usize, tick: Timespan, data: EventData) -> LocatedEvent {
+        LocatedEvent { track, channel: 0, event: Event { tick, data } }
+    }
+
+    #[test]
+    fn test_due_events() {
+        let mut module = Module::new(Default::default());
+        let note = |nominal| EventData::Pitch(Note::new(0, nominal, 0, 4));
+        let t = |n, d| Timespan::new(n, d);
+        module.push_edit(Edit::PatternData {
+            remove: Vec::new(),
+            add: vec![
+                located(0, t(0, 1), EventData::Tempo(120.0)),
+                located(0, t(1, 1), EventData::StartGlide(GLOBAL_COLUMN)),
+                located(0, t(2, 1), EventData::Tempo(180.0)),
+                located(0, t(2, 1), EventData::EndGlide(GLOBAL_COLUMN)),
+                located(0, t(3, 1), EventData::RationalTempo(3, 2)),
+                located(2, t(0, 1), note(Nominal::C)),
+                located(2, t(0, 1), EventData::StartGlide(NOTE_COLUMN)),
+                located(2, t(1, 2), note(Nominal::E)),
+                located(2, t(1, 1), note(Nominal::G)),
+                located(2, t(1, 1), EventData::EndGlide(NOTE_COLUMN)),
+                located(2, t(0, 1), EventData::Pressure(3)),
+                located(2, t(0, 1), EventData::StartGlide(VEL_COLUMN)),
+                located(2, t(0, 1), EventData::GlideCurve(VEL_COLUMN, GlideCurve::SCurve)),
+                located(2, t(2, 1), EventData::Pressure(15)),
+                located(2, t(2, 1), EventData::EndGlide(VEL_COLUMN)),
+                located(2, t(3, 2), EventData::Modulation(5)),
+                located(2, t(3, 2), EventData::TickGlide(MOD_COLUMN)),
+                located(2, t(3, 1), EventData::NoteOff),
+                located(2, t(4, 1), note(Nominal::D)),
+            ],
+        });
+
+        let mut player = Player::new(Mixer::new(module.tracks.len(), 44100.0), 44100.0);
+        let (mut edited, mut jumped) = (false, false);
+        let mut i = 0;
+
+        while player.beat < 5.0 {
+            let prev_time = player.beat;
+            player.beat += 0.013 * (1 + i % 7) as f64;
+            i += 1;
+            assert_eq!(event_strings(player.due_events(&module, prev_time)),
+                event_strings(scan_due_events(&module, prev_time, player.beat)),
+                "events differ at beat {}", player.beat);
+
+            // edits and jumps should invalidate cursors
+            if !edited && player.beat > 2.5 {
+                module.push_edit(Edit::PatternData {
+                    remove: Vec::new(),
+                    add: vec![located(2, t(11, 4), EventData::Modulation(10))],
+                });
+                edited = true;
+            }
+            if !jumped && player.beat > 3.5 {
+                player.beat = 0.75;
+                jumped = true;
+            }
+        }
+    }
+
+    #[test]
+    fn test_play_from_glide() {
+        let mut module = Module::new(Default::default());
+        let note = |nominal| EventData::Pitch(Note::new(0, nominal, 0, 4));
+        let t = |n, d| Timespan::new(n, d);
+        module.push_edit(Edit::PatternData {
+            remove: Vec::new(),
+            add: vec![
+                located(0, t(0, 1), EventData::Tempo(120.0)),
+                located(0, t(0, 1), EventData::StartGlide(GLOBAL_COLUMN)),
+                located(0, t(2, 1), EventData::Tempo(180.0)),
+                located(0, t(2, 1), EventData::EndGlide(GLOBAL_COLUMN)),
+                located(2, t(0, 1), note(Nominal::C)),
+                located(2, t(0, 1), EventData::StartGlide(NOTE_COLUMN)),
+                located(2, t(2, 1), note(Nominal::G)),
+                located(2, t(2, 1), EventData::EndGlide(NOTE_COLUMN)),
+                located(2, t(0, 1), EventData::Pressure(3)),
+                located(2, t(0, 1), EventData::StartGlide(VEL_COLUMN)),
+                located(2, t(0, 1), EventData::GlideCurve(VEL_COLUMN, GlideCurve::SCurve)),
+                located(2, t(2, 1), EventData::Pressure(15)),
+                located(2, t(2, 1), EventData::EndGlide(VEL_COLUMN)),
+                located(2, t(0, 1), EventData::Modulation(0)),
+                located(2, t(0, 1), EventData::StartGlide(MOD_COLUMN)),
+                located(2, t(2, 1), EventData::Modulation(12)),
+                located(2, t(2, 1), EventData::EndGlide(MOD_COLUMN)),
+            ],
+        });
+        let state = |player: &Player| {
+            let synth = &player.synths[2];
+            let pitches: Vec<_> = synth.active_pitches().map(|(_, p)| p).collect();
+            (player.tempo, synth.vel_memory(0), synth.mod_memory(0), pitches)
+        };
+
+        // 0.5 beats at 120 BPM, then 0.5625 beats at 135 BPM
+        let mut played = Player::new(Mixer::new(module.tracks.len(), 44100.0), 44100.0);
+        played.play_from(Timespan::ZERO, &module);
+        played.frame(&module, 0.25);
+        played.frame(&module, 0.25);
+        assert_eq!(played.beat, 1.0625);
+
+        let mut jumped = Player::new(Mixer::new(module.tracks.len(), 44100.0), 44100.0);
+        jumped.play_from(t(17, 16), &module);
+        let expected = state(&played);
+        assert_eq!(expected.3.len(), 1);
+        assert_eq!(state(&jumped), expected);
+    }
+
+    #[test]
+    fn test_track_offset() {
+        let mut module = Module::new(Default::default());
+        module.tracks[2].offset = 250.0;
+        module.push_edit(Edit::PatternData {
+            remove: Vec::new(),
+            add: vec![located(2, Timespan::new(1, 1), EventData::Pitch(Note::new(0, Nominal::C, 0, 4)))],
+        });
+
+        // 250 ms is half a beat at the default tempo
+        let mut player = Player::new(Mixer::new(module.tracks.len(), 44100.0), 44100.0);
+        player.beat = 1.25;
+        assert!(player.due_events(&module, 0.75).is_empty());
+        player.beat = 1.75;
+        assert_eq!(player.due_events(&module, 1.25).len(), 1);
+    }
+
+    #[test]
+    fn test_mute_audition_tracks() {
+        let mut module = Module::new(Default::default());
+        module.tracks[2].audition_only = true;
+        let mut player = Player::new(Mixer::new(module.tracks.len(), 44100.0), 44100.0);
+
+        player.play_from(Timespan::ZERO, &module);
+        assert!(!player.track_muted(2));
+        player.stop();
+
+        player.mute_audition = true;
+        player.play_from(Timespan::ZERO, &module);
+        assert!(player.track_muted(2));
+        assert!(!player.track_muted(1));
+    }
+
+    #[test]
+    fn test_unfreeze_on_edit() {
+        let mut module = Module::new(Default::default());
+        let mut player = Player::new(Mixer::new(module.tracks.len(), 44100.0), 44100.0);
+        let n = module.tracks.len();
+        let freeze = |player: &mut Player| for i in 1..n {
+            player.frozen[i] = Some(Freeze::Active(FrozenTrack {
+                wave: Arc::new(Wave::new(4, 44100.0)),
+                block_beats: Vec::new(),
+            }, None));
+        };
+
+        // only tracks that play the patch are unfrozen
+        freeze(&mut player);
+        module.patch_edited(0);
+        player.update_synths(module.drain_track_history(), &module);
+        assert!(player.track_frozen(1));
+        assert!(!player.track_frozen(2));
+
+        module.kit.push(KitEntry { patch_index: 0, ..Default::default() });
+        freeze(&mut player);
+        module.patch_edited(0);
+        player.update_synths(module.drain_track_history(), &module);
+        assert!(!player.track_frozen(1));
+
+        // tuning and tempo changes affect every track
+        freeze(&mut player);
+        module.global_edited();
+        player.update_synths(module.drain_track_history(), &module);
+        assert!(!player.track_frozen(1));
+        assert!(!player.track_frozen(2));
+
+        freeze(&mut player);
+        module.push_edit(Edit::PatternData {
+            remove: Vec::new(),
+            add: vec![LocatedEvent {
+                track: 0,
+                channel: 0,
+                event: Event { tick: Timespan::ZERO, data: EventData::Tempo(90.0) },
+            }],
+        });
+        player.update_synths(module.drain_track_history(), &module);
+        assert!(!player.track_frozen(1));
+        assert!(!player.track_frozen(2));
+    }
+
+    #[test]
+    fn test_playback_speed() {
+        let module = Module::new(Default::default());
+        let mut player = Player::new(Mixer::new(module.tracks.len(), 44100.0), 44100.0);
+        player.speed = 2.0;
+        player.play();
+        player.frame(&module, 60.0 / DEFAULT_TEMPO as f64);
+        assert!((player.beat - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_count_in() {
+        let module = Module::new(Default::default());
+        let mut player = Player::new(Mixer::new(module.tracks.len(), 44100.0), 44100.0);
+        let beat = 60.0 / DEFAULT_TEMPO as f64;
+
+        player.record_from(Timespan::new(8, 1), &module, 4.0, 4.0);
+        assert_eq!(player.beat, 4.0);
+        for _ in 0..4 {
+            player.frame(&module, beat);
+        }
+        assert_eq!(player.beat, 4.0);
+        assert!(!player.recording_armed());
+
+        player.frame(&module, beat * 4.5);
+        assert!(player.recording_armed());
+    }
+
+    #[test]
+    fn test_output_correlation() {
+        let module = Module::new(Default::default());
+        let mut player = Player::new(Mixer::new(module.tracks.len(), 44100.0), 44100.0);
+        player.record_output_correlation(-1.0, 1.0, 1.0, 1.0);
+        assert_eq!(player.output_correlation, -1.0);
+        player.record_output_correlation(0.0, 0.0, 0.0, 1.0);
+        assert_eq!(player.output_correlation, -1.0);
+        player.record_output_correlation(0.5, 1.0, 1.0, CORRELATION_TIME * 0.5);
+        assert!((player.output_correlation + 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_adaptive_ji_pitch() {
+        // 12-ET major third above and below is pulled to 5/4
+        assert!((adaptive_ji_pitch(64.0, 60.0, 20.0) - 63.863).abs() < 0.001);
+        assert!((adaptive_ji_pitch(56.0, 60.0, 20.0) - 56.137).abs() < 0.001);
+        // out of tolerance
+        assert_eq!(adaptive_ji_pitch(64.0, 60.0, 5.0), 64.0);
+        assert_eq!(adaptive_ji_pitch(60.0, 60.0, 20.0), 60.0);
+    }
+
+    #[test]
+    fn test_seamless_loop_bounds() {
+        let module = Module::new(Default::default());
+        let beat = 60.0 / DEFAULT_TEMPO as f64;
+
+        // enough passes for the tail to ring out before the kept pass
+        let bounds = RenderBounds::seamless_loop(&module,
+            Timespan::new(4, 1), Timespan::new(8, 1));
+        assert_eq!(bounds.repeats, 1 + (LOOP_TAIL_TIME / (beat * 4.0)).ceil() as u32);
+        let bounds = RenderBounds::seamless_loop(&module,
+            Timespan::new(0, 1), Timespan::new(64, 1));
+        assert_eq!(bounds.repeats, 2);
+        let bounds = RenderBounds::seamless_loop(&module,
+            Timespan::new(4, 1), Timespan::new(4, 1));
+        assert_eq!(bounds.repeats, MAX_LOOP_REPEATS);
+    }
+
+    #[test]
+    fn test_bend_ramp() {
+        let ramp = ActiveRamp {
+            track: 1,
+            channel: 0,
+            from: 1.0,
+            ramp: BendRamp {
+                cents: -100,
+                duration: Timespan::new(1, 2),
+                curve: GlideCurve::Linear,
+            },
+            start: 2.0,
+        };
+        assert_eq!(ramp.bend_at(1.0), 1.0);
+        assert_eq!(ramp.bend_at(2.25), 0.0);
+        assert_eq!(ramp.bend_at(3.0), -1.0);
+        assert_eq!(ramp.progress(2.5), 1.0);
+
+        let instant = ActiveRamp {
+            ramp: BendRamp { duration: Timespan::ZERO, ..ramp.ramp },
+            ..ramp
+        };
+        assert_eq!(instant.bend_at(2.0), -1.0);
+    }
+}
@@ -0,0 +1,258 @@
+//! Regression rendering of test modules. Each module in the test data folder
+//! is rendered and compared against a stored baseline, both exactly (by
+//! checksum) and within a tolerance (by coarse spectrum), so that synth and
+//! playback changes can be checked for unintended output changes.
+
+use std::{collections::BTreeMap, error::Error, f64::consts::TAU, fs, path::{Path, PathBuf}, sync::Arc};
+
+use fundsp::wave::Wave;
+use serde::{Deserialize, Serialize};
+
+use crate::{module::Module, playback::{self, RenderBounds, RenderUpdate}};
+
+/// Folder containing the test modules.
+pub const TESTDATA_DIR: &str = "testdata";
+
+/// Filename of the stored baseline, in the test data folder.
+const BASELINE_FILE: &str = "regression.toml";
+
+/// Center frequencies of the fingerprint bands, in Hz.
+const BAND_FREQS: [f64; 10] =
+    [50.0, 100.0, 200.0, 400.0, 800.0, 1600.0, 3200.0, 6400.0, 12800.0, 16000.0];
+
+/// Number of sample frames per spectrum analysis frame.
+const ANALYSIS_FRAME: usize = 4096;
+
+/// Largest band level difference, in dB, that still counts as a match.
+const TOLERANCE_DB: f32 = 0.5;
+
+/// Summary of a rendered module's output.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    /// Hex FNV-1a hash of the sample data.
+    pub checksum: String,
+    /// Length in sample frames.
+    pub length: u32,
+    /// Average level of each band in `BAND_FREQS`, in dB.
+    pub bands: Vec<f32>,
+}
+
+impl Fingerprint {
+    /// Returns the fingerprint of `wave`.
+    pub fn new(wave: &Wave) -> Self {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for i in 0..wave.len() {
+            for ch in 0..wave.channels() {
+                for b in wave.at(ch, i).to_bits().to_le_bytes() {
+                    hash = (hash ^ b as u64).wrapping_mul(0x100000001b3);
+                }
+            }
+        }
+
+        Self {
+            checksum: format!("{hash:016x}"),
+            length: wave.len() as u32,
+            bands: band_levels(wave),
+        }
+    }
+
+    /// Returns the largest band level difference from `other`, in dB.
+    fn band_difference(&self, other: &Self) -> f32 {
+        if self.bands.len() != other.bands.len() {
+            return f32::INFINITY
+        }
+        self.bands.iter().zip(&other.bands)
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f32::max)
+    }
+}
+
+/// Returns the average level in each band, in dB, using the Goertzel
+/// algorithm on a mono mix of `wave`.
+fn band_levels(wave: &Wave) -> Vec<f32> {
+    let sample_rate = wave.sample_rate();
+    let frames = wave.len() / ANALYSIS_FRAME;
+
+    BAND_FREQS.iter().map(|freq| {
+        let coeff = 2.0 * (TAU * freq / sample_rate).cos();
+        let mut power = 0.0;
+        for frame in 0..frames {
+            let (mut s1, mut s2) = (0.0, 0.0);
+            for i in frame * ANALYSIS_FRAME..(frame + 1) * ANALYSIS_FRAME {
+                let x = (0..wave.channels()).map(|ch| wave.at(ch, i) as f64).sum::<f64>()
+                    / wave.channels() as f64;
+                let s = x + coeff * s1 - s2;
+                s2 = s1;
+                s1 = s;
+            }
+            power += (s1 * s1 + s2 * s2 - coeff * s1 * s2) / ANALYSIS_FRAME as f64;
+        }
+        let power = power / frames.max(1) as f64;
+        (10.0 * (power + 1e-12).log10()) as f32
+    }).collect()
+}
+
+/// Result of comparing a render against the baseline.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Identical,
+    /// Checksum differs, but bands are within tolerance. Holds the largest
+    /// band difference in dB.
+    WithinTolerance(f32),
+    /// Output differs beyond tolerance. Holds the largest band difference
+    /// in dB.
+    Changed(f32),
+    /// No baseline exists for the module. Counts as a failure, since the
+    /// baseline should be updated along with the test modules.
+    New,
+    Failed(String),
+}
+
+impl Outcome {
+    /// Returns true if the outcome indicates a regression.
+    pub fn is_failure(&self) -> bool {
+        matches!(self, Self::Changed(_) | Self::New | Self::Failed(_))
+    }
+
+    /// Returns the UI string for the outcome.
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Identical => String::from("Identical"),
+            Self::WithinTolerance(d) => format!("Within tolerance ({d:.2} dB)"),
+            Self::Changed(d) if d.is_finite() => format!("Changed ({d:.2} dB)"),
+            Self::Changed(_) => String::from("Changed (length)"),
+            Self::New => String::from("No baseline"),
+            Self::Failed(e) => format!("Failed: {e}"),
+        }
+    }
+
+    /// Returns the outcome of comparing `new` to `old`.
+    fn compare(new: &Fingerprint, old: &Fingerprint) -> Self {
+        if new == old {
+            return Self::Identical
+        }
+        let d = if new.length == old.length {
+            new.band_difference(old)
+        } else {
+            f32::INFINITY
+        };
+        if d <= TOLERANCE_DB {
+            Self::WithinTolerance(d)
+        } else {
+            Self::Changed(d)
+        }
+    }
+}
+
+/// Regression result for one test module.
+#[derive(Debug)]
+pub struct RegressionResult {
+    pub name: String,
+    pub outcome: Outcome,
+}
+
+/// Stored fingerprints, by module filename.
+#[derive(Default, Serialize, Deserialize)]
+struct Baseline {
+    modules: BTreeMap<String, Fingerprint>,
+}
+
+/// Render every module in `dir` and compare against the stored baseline. If
+/// `update` is true, the baseline is replaced with the new fingerprints.
+/// Otherwise a missing or unreadable baseline is an error.
+pub fn run(dir: &Path, update: bool) -> Result<Vec<RegressionResult>, Box<dyn Error>> {
+    let baseline_path = dir.join(BASELINE_FILE);
+    let baseline = match read_baseline(&baseline_path) {
+        Ok(baseline) => baseline,
+        Err(_) if update => Default::default(),
+        Err(e) => return Err(format!(
+            "Error reading baseline {}: {e}. Update the baseline to create it.",
+            baseline_path.display()).into()),
+    };
+
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "osctet"))
+        .collect();
+    paths.sort();
+
+    let mut results = Vec::new();
+    let mut new_baseline = Baseline::default();
+    for path in paths {
+        let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let outcome = match render_wave(&path) {
+            Ok(wave) => {
+                let fp = Fingerprint::new(&wave);
+                let outcome = match baseline.modules.get(&name) {
+                    Some(old) => Outcome::compare(&fp, old),
+                    None => Outcome::New,
+                };
+                new_baseline.modules.insert(name.clone(), fp);
+                outcome
+            }
+            Err(e) => Outcome::Failed(e.to_string()),
+        };
+        results.push(RegressionResult { name, outcome });
+    }
+
+    if update {
+        fs::write(baseline_path, toml::to_string_pretty(&new_baseline)?)?;
+    }
+
+    Ok(results)
+}
+
+/// Load the stored baseline from `path`.
+fn read_baseline(path: &Path) -> Result<Baseline, Box<dyn Error>> {
+    Ok(toml::from_str(&fs::read_to_string(path)?)?)
+}
+
+/// Render the module at `path`, blocking until done.
+fn render_wave(path: &Path) -> Result<Wave, Box<dyn Error>> {
+    let module = Module::load(path)?;
+    if !module.ends() {
+        return Err("Module must have End event to render".into())
+    }
+    let rx = playback::render(Arc::new(module), path.to_owned(), None,
+        RenderBounds::SONG);
+    while let Ok(update) = rx.recv() {
+        if let RenderUpdate::Done(wave, ..) = update {
+            return Ok(wave)
+        }
+    }
+    Err("Render stopped".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a stereo sine wave at `freq` Hz.
+    fn sine(freq: f64, amp: f32) -> Wave {
+        let mut wave = Wave::new(2, 44100.0);
+        for i in 0..ANALYSIS_FRAME * 4 {
+            let x = amp * (TAU * freq * i as f64 / 44100.0).sin() as f32;
+            wave.push((x, x));
+        }
+        wave
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let a = Fingerprint::new(&sine(400.0, 0.5));
+        assert_eq!(a, Fingerprint::new(&sine(400.0, 0.5)));
+        let loudest = a.bands.iter().enumerate()
+            .max_by(|x, y| x.1.total_cmp(y.1))
+            .map(|(i, _)| i);
+        assert_eq!(loudest, Some(3));
+
+        let b = Fingerprint::new(&sine(400.0, 0.501));
+        assert_ne!(a.checksum, b.checksum);
+        assert!(matches!(Outcome::compare(&b, &a), Outcome::WithinTolerance(_)));
+
+        let c = Fingerprint::new(&sine(400.0, 0.25));
+        assert!(Outcome::compare(&c, &a).is_failure());
+        assert_eq!(Outcome::compare(&a, &a), Outcome::Identical);
+        assert!(Outcome::New.is_failure());
+    }
+}
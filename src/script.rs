@@ -0,0 +1,188 @@
+//! Rhai scripting for generative and transforming pattern tools.
+//!
+//! Scripts operate on the pattern selection. Beats and channels are relative
+//! to the top-left corner of the selection, and event data uses the same
+//! syntax as pattern text (see `patterntext`). Functions available to
+//! scripts:
+//!
+//! - `selection()`: array of `#{ beat, channel, data }` maps.
+//! - `selection_beats()`: length of the selection in beats.
+//! - `selection_channels()`: number of channels in the selection.
+//! - `clear()`: remove all events in the selection.
+//! - `insert(beat, channel, data)`: add an event.
+//! - `tuning_size()`: number of notes per equave in the module tuning.
+//! - `transpose(note, steps)`: shift a note by scale steps.
+//! - `pitch(note)`: MIDI pitch of a note in the module tuning.
+
+use std::{cell::RefCell, error::Error, rc::Rc};
+
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map};
+
+use crate::{module::{Edit, Event, LocatedEvent, Module, Position}, patterntext, pitch::Note, timespan::Timespan};
+
+/// Keeps runaway scripts from hanging the UI.
+const MAX_OPERATIONS: u64 = 10_000_000;
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// Result of running a script.
+pub struct ScriptOutput {
+    /// `None` if the script didn't change the pattern.
+    pub edit: Option<Edit>,
+    /// Output of `print` calls.
+    pub messages: Vec<String>,
+}
+
+/// Changes requested by a running script.
+#[derive(Default)]
+struct ScriptState {
+    clear: bool,
+    add: Vec<LocatedEvent>,
+    messages: Vec<String>,
+}
+
+/// Runs a script over the pattern region from `start` to `end`. The
+/// resulting edit is returned rather than applied, so that it can be pushed
+/// as a single undo step.
+pub fn run(src: &str, module: &Module, start: Position, end: Position)
+-> Result<ScriptOutput, Box<dyn Error>> {
+    let state = Rc::new(RefCell::new(ScriptState::default()));
+    let selection = module.scan_events(start, end);
+
+    // (track, channel) of each channel from the start of the selection
+    let channels: Vec<_> = module.tracks.iter().enumerate()
+        .flat_map(|(t, track)| (0..track.channels.len()).map(move |c| (t, c)))
+        .skip_while(|x| *x != (start.track, start.channel))
+        .collect();
+
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+
+    let print_state = state.clone();
+    engine.on_print(move |s| print_state.borrow_mut().messages.push(s.to_owned()));
+
+    let events: Array = selection.iter().filter_map(|e| {
+        let data = patterntext::format_data(&e.event.data)?;
+        let channel = module.channels_between(start, e.position());
+        let mut map = Map::new();
+        map.insert("beat".into(), Dynamic::from((e.event.tick - start.tick).as_f64()));
+        map.insert("channel".into(), Dynamic::from(channel as i64));
+        map.insert("data".into(), Dynamic::from(data));
+        Some(Dynamic::from(map))
+    }).collect();
+    engine.register_fn("selection", move || events.clone());
+
+    let beats = (end.tick - start.tick).as_f64();
+    engine.register_fn("selection_beats", move || beats);
+    let num_channels = module.channels_between(start, end) as i64 + 1;
+    engine.register_fn("selection_channels", move || num_channels);
+
+    let clear_state = state.clone();
+    engine.register_fn("clear", move || clear_state.borrow_mut().clear = true);
+
+    let insert_state = state.clone();
+    engine.register_fn("insert",
+        move |beat: Dynamic, channel: i64, data: &str| -> ScriptResult<()> {
+            let beat = number(&beat)?;
+            if beat < 0.0 {
+                return Err(format!("negative beat: {beat}").into())
+            }
+            let (track, channel) = usize::try_from(channel).ok()
+                .and_then(|i| channels.get(i).copied())
+                .ok_or_else(|| format!("channel out of range: {channel}"))?;
+            let data = patterntext::parse_data(data)
+                .filter(|d| d.goes_in_track(track))
+                .ok_or_else(|| format!("invalid event for channel: {data}"))?;
+
+            insert_state.borrow_mut().add.push(LocatedEvent {
+                track,
+                channel,
+                event: Event {
+                    tick: start.tick + Timespan::approximate(beat),
+                    data,
+                },
+            });
+            Ok(())
+        });
+
+    let tuning = Rc::new(module.tuning.clone());
+    let size_tuning = tuning.clone();
+    engine.register_fn("tuning_size", move || size_tuning.size() as i64);
+    let transpose_tuning = tuning.clone();
+    engine.register_fn("transpose", move |note: &str, steps: i64| -> ScriptResult<String> {
+        let note = parse_note(note)?.step_shift(steps as isize, &transpose_tuning);
+        Ok(patterntext::format_note(&note))
+    });
+    engine.register_fn("pitch", move |note: &str| -> ScriptResult<f64> {
+        Ok(tuning.midi_pitch(&parse_note(note)?) as f64)
+    });
+
+    engine.run(src)?;
+    drop(engine);
+    let state = state.take();
+
+    let mut remove: Vec<_> = state.add.iter().map(|e| e.position()).collect();
+    if state.clear {
+        remove.extend(selection.iter().map(|e| e.position()));
+    }
+
+    Ok(ScriptOutput {
+        edit: (!remove.is_empty()).then_some(Edit::PatternData {
+            remove,
+            add: state.add,
+        }),
+        messages: state.messages,
+    })
+}
+
+/// Converts a script number to a float.
+fn number(x: &Dynamic) -> ScriptResult<f64> {
+    x.as_float()
+        .or_else(|_| x.as_int().map(|i| i as f64))
+        .map_err(|t| format!("expected number, got {t}").into())
+}
+
+fn parse_note(s: &str) -> ScriptResult<Note> {
+    patterntext::parse_note(s).ok_or_else(|| format!("invalid note: {s}").into())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::module::EventData;
+
+    use super::*;
+
+    #[test]
+    fn test_run() {
+        let module = Module::new(Default::default());
+        let start = Position::new(Timespan::ZERO, 1, 0, 0);
+        let end = Position::new(Timespan::new(2, 1), 2, 0, 0);
+
+        let output = run(r#"
+            print(selection_channels());
+            for i in 0..4 {
+                insert(i * 0.5, 1, transpose("C4", i));
+            }
+            insert(2, 1, "off");
+        "#, &module, start, end).unwrap();
+        assert_eq!(output.messages, vec!["2"]);
+
+        let Some(Edit::PatternData { add, .. }) = output.edit else {
+            panic!("expected pattern data edit")
+        };
+        assert_eq!(add.len(), 5);
+        assert!(add.iter().all(|e| e.track == 2 && e.channel == 0));
+        assert_eq!(add[1].event.tick, Timespan::new(1, 2));
+        let EventData::Pitch(note) = add[3].event.data else {
+            panic!("expected pitch event")
+        };
+        let c4 = patterntext::parse_note("C4").unwrap();
+        let interval = module.tuning.midi_pitch(&note) - module.tuning.midi_pitch(&c4);
+        assert!((interval - 3.0).abs() < 0.01);
+        assert_eq!(add[4].event.data, EventData::NoteOff);
+
+        assert!(run("insert(0, 2, \"C4\")", &module, start, end).is_err());
+        assert!(run("insert(0, 0, \"tempo:120\")", &module, start, end).is_err());
+        assert!(run("", &module, start, end).unwrap().edit.is_none());
+    }
+}
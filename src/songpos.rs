@@ -0,0 +1,69 @@
+//! Song positions as bars:beats:rows and minutes:seconds.
+
+use crate::timespan::Timespan;
+
+/// Format `tick` as 1-based `bar:beat:row`.
+pub fn format_bbt(tick: Timespan, bar_beats: u8, division: u8) -> String {
+    let rows = (tick.as_f64() * division as f64 + 1e-6).floor().max(0.0) as u64;
+    let (division, bar_beats) = (division.max(1) as u64, bar_beats.max(1) as u64);
+    let beats = rows / division;
+    format!("{}:{}:{}", beats / bar_beats + 1, beats % bar_beats + 1, rows % division + 1)
+}
+
+/// Parse a 1-based `bar:beat:row` position. The beat and row can be left out.
+pub fn parse_bbt(s: &str, bar_beats: u8, division: u8) -> Option<Timespan> {
+    let mut fields = [1; 3];
+    let parts: Vec<_> = s.trim().split(':').collect();
+    if parts.len() > fields.len() {
+        return None
+    }
+    for (field, part) in fields.iter_mut().zip(parts) {
+        *field = part.trim().parse::<i32>().ok().filter(|x| *x >= 1)?;
+    }
+    let [bar, beat, row] = fields.map(|x| x - 1);
+    let division = division.max(1);
+    let rows = (bar * bar_beats.max(1) as i32 + beat) * division as i32 + row;
+    Some(Timespan::new(rows, division))
+}
+
+/// Format a time in seconds as `m:ss.mmm`.
+pub fn format_clock(secs: f64) -> String {
+    let ms = (secs.max(0.0) * 1000.0).round() as u64;
+    format!("{}:{:02}.{:03}", ms / 60000, ms / 1000 % 60, ms % 1000)
+}
+
+/// Parse a time as `m:ss.mmm` or as seconds, returning seconds.
+pub fn parse_clock(s: &str) -> Option<f64> {
+    let secs = match s.trim().split_once(':') {
+        Some((m, s)) => m.trim().parse::<u32>().ok()? as f64 * 60.0
+            + s.trim().parse::<f64>().ok().filter(|x| *x < 60.0)?,
+        None => s.trim().parse().ok()?,
+    };
+    (secs >= 0.0 && secs.is_finite()).then_some(secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bbt() {
+        assert_eq!(format_bbt(Timespan::ZERO, 4, 4), "1:1:1");
+        assert_eq!(format_bbt(Timespan::new(23, 4), 4, 4), "2:2:4");
+        assert_eq!(format_bbt(Timespan::new(1, 3), 3, 2), "1:1:1");
+        assert_eq!(parse_bbt("2:2:4", 4, 4), Some(Timespan::new(23, 4)));
+        assert_eq!(parse_bbt("3", 4, 4), Some(Timespan::new(8, 1)));
+        assert_eq!(parse_bbt("0:1", 4, 4), None);
+        assert_eq!(parse_bbt("1:1:1:1", 4, 4), None);
+    }
+
+    #[test]
+    fn test_clock() {
+        assert_eq!(format_clock(0.0), "0:00.000");
+        assert_eq!(format_clock(83.25), "1:23.250");
+        assert_eq!(parse_clock("1:23.25"), Some(83.25));
+        assert_eq!(parse_clock("4.5"), Some(4.5));
+        assert_eq!(parse_clock("1:75"), None);
+        assert_eq!(parse_clock("-1"), None);
+    }
+}
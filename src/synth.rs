@@ -4,15 +4,15 @@ pub(crate) mod pcm;
 pub(crate) mod lfo;
 
 use core::f64;
-use std::{collections::{HashMap, VecDeque}, error::Error, fmt::Display, fs, path::Path};
+use std::{collections::{HashMap, HashSet, VecDeque}, error::Error, fmt::Display, fs, path::Path, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}};
 
-use lfo::LFO;
+use lfo::{sin_lfo, LFO};
 use pcm::PcmData;
 use rand::prelude::*;
 use fundsp::hacker32::*;
 use serde::{Deserialize, Serialize};
 
-use crate::{dsp::*, ui::MAX_PATCH_NAME_CHARS};
+use crate::{dsp::*, module::{EventData, PatternLfo}, pitch::Tuning, playback::DEFAULT_TEMPO, ui::MAX_PATCH_NAME_CHARS};
 
 /// The MIDI pitch of the default note (C4). Used to adjust frequency controls
 /// of loaded samples.
@@ -29,6 +29,12 @@ const SEMITONE_RATIO: f32 = 1.059463;
 /// voices in the release phase.
 const VOICES_PER_CHANNEL: usize = 3;
 
+/// Number of prebuilt voices to keep ready for each played patch.
+const VOICE_POOL_SIZE: usize = 4;
+
+/// Maximum patch gain, as amplitude.
+pub const MAX_PATCH_GAIN: f32 = 2.0;
+
 /// Maximum scale when modulating envelopes. The minimum is just the inverse.
 pub const MAX_ENV_SCALE: f32 = 16.0;
 
@@ -45,6 +51,9 @@ pub const FILTER_CUTOFF_MOD_BASE: f32 = MAX_FILTER_CUTOFF / MIN_FILTER_CUTOFF;
 /// minimum resonance.
 pub const MIN_FILTER_RESONANCE: f32 = 0.1;
 
+/// Gain applied before saturation at full filter drive.
+const MAX_FILTER_DRIVE: f32 = 10.0;
+
 /// Minimum Hz value for pitch-based modulation (E1).
 const PITCH_FLOOR: f32 = 41.25;
 
@@ -60,6 +69,15 @@ pub const SMOOTH_TIME: f32 = 0.01;
 /// Arbitrary constant for scaling FM depth.
 const FM_DEPTH_MULTIPLIER: f32 = 20.0;
 
+/// Pattern vibrato depth per digit, in semitones.
+const VIBRATO_DEPTH_UNIT: f32 = 0.125;
+
+/// Rate of random changes in analog drift, in Hz.
+const DRIFT_RATE: f32 = 0.5;
+
+/// Number of partials in an additive generator.
+pub const NUM_PARTIALS: usize = 32;
+
 /// Wraps a Shared value for serialization.
 /// Cloning creates a new Shared value.
 #[derive(Serialize, Deserialize)]
@@ -84,18 +102,35 @@ impl From<Parameter> for f32 {
     }
 }
 
+impl Parameter {
+    /// Returns a smoothed DSP node for the parameter, so that edits made
+    /// during playback don't cause clicks.
+    pub fn smoothed(&self) -> An<impl AudioNode<Inputs = U0, Outputs = U1>> {
+        var(&self.0) >> param_smooth()
+    }
+}
+
 impl Default for Parameter {
     fn default() -> Self {
         Self(shared(1.0))
     }
 }
 
+/// Serde default for parameters added to existing types.
+fn zero_parameter() -> Parameter {
+    Parameter(shared(0.0))
+}
+
 /// Source type for note keys.
 #[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
 pub enum KeyOrigin {
     Keyboard,
     Midi,
     Pattern,
+    /// One-shot note auditioned from the pattern editor.
+    Preview,
+    /// Note generated by the arpeggiator.
+    Arpeggiator,
 }
 
 /// Source for note keys, to track on/offs.
@@ -122,6 +157,14 @@ impl Key {
             key,
         }
     }
+
+    pub fn new_preview() -> Self {
+        Self {
+            origin: KeyOrigin::Preview,
+            channel: 0,
+            key: 0,
+        }
+    }
 }
 
 /// How to behave when a note starts before the last has ended.
@@ -145,6 +188,38 @@ impl PlayMode {
     }
 }
 
+/// How MIDI aftertouch combines with a voice's note-on pressure.
+#[derive(PartialEq, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum AftertouchMode {
+    #[default]
+    Replace,
+    Max,
+    Add,
+}
+
+impl AftertouchMode {
+    pub const VARIANTS: [AftertouchMode; 3] = [Self::Replace, Self::Max, Self::Add];
+
+    /// Returns the UI string for this mode.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Replace => "Replace",
+            Self::Max => "Max",
+            Self::Add => "Add",
+        }
+    }
+
+    /// Returns the voice pressure for `aftertouch` after a note-on pressure
+    /// of `base`.
+    pub fn apply(&self, base: f32, aftertouch: f32) -> f32 {
+        match self {
+            Self::Replace => aftertouch,
+            Self::Max => base.max(aftertouch),
+            Self::Add => (base + aftertouch).min(1.0),
+        }
+    }
+}
+
 /// Generator/LFO wave source.
 #[derive(Clone, Serialize, Deserialize)]
 pub enum Waveform {
@@ -155,11 +230,16 @@ pub enum Waveform {
     Hold,
     Noise,
     Pcm(Option<PcmData>),
+    /// Consecutive single-cycle frames, selected by the tone control.
+    Wavetable(Option<PcmData>),
+    Additive(Harmonics),
+    /// Karplus-Strong string, damped by the tone control.
+    Pluck,
 }
 
 impl Waveform {
     /// Variants that generators can be set to.
-    pub const VARIANTS: [Waveform; 7] = [
+    pub const VARIANTS: [Waveform; 10] = [
         Self::Sawtooth,
         Self::Pulse,
         Self::Triangle,
@@ -167,6 +247,9 @@ impl Waveform {
         Self::Hold,
         Self::Noise,
         Self::Pcm(None),
+        Self::Wavetable(None),
+        Self::Additive(Harmonics::SINE),
+        Self::Pluck,
     ];
 
     /// Variants that LFOs can be set to.
@@ -189,12 +272,20 @@ impl Waveform {
             Self::Hold => "S&H",
             Self::Noise => "Noise",
             Self::Pcm(_) => "PCM",
+            Self::Wavetable(_) => "Wavetable",
+            Self::Additive(_) => "Additive",
+            Self::Pluck => "Pluck",
         }
     }
 
     /// Returns true if this waveform makes use of the `tone` control.
     pub fn uses_tone(&self) -> bool {
-        matches!(self, Self::Pulse | Self::Noise)
+        matches!(self, Self::Pulse | Self::Noise | Self::Wavetable(_) | Self::Pluck)
+    }
+
+    /// Returns true if this waveform plays audio loaded from a file.
+    pub fn uses_sample(&self) -> bool {
+        matches!(self, Self::Pcm(_) | Self::Wavetable(_))
     }
 
     /// Returns true if this waveform makes uses of frequency controls.
@@ -204,15 +295,61 @@ impl Waveform {
 
     /// Check whether this waveform is affected by the "tone" control.
     fn has_tone_control(&self) -> bool {
-        matches!(*self, Waveform::Pulse | Waveform::Noise)
+        matches!(*self,
+            Waveform::Pulse | Waveform::Noise | Waveform::Wavetable(_) | Waveform::Pluck)
     }
 
     /// Check whether this waveform can use oversampling.
     pub fn uses_oversampling(&self) -> bool {
-        !matches!(*self, Waveform::Hold | Waveform::Noise | Waveform::Pcm(_))
+        !matches!(*self,
+            Waveform::Hold | Waveform::Noise | Waveform::Pcm(_) | Waveform::Additive(_)
+            | Waveform::Pluck)
     }
 }
 
+/// Partials of an additive generator.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Harmonics {
+    /// Amplitude of each partial, from 0 to 1.
+    pub amps: [f32; NUM_PARTIALS],
+    /// Frequency ratio of each partial to the fundamental.
+    pub ratios: [f32; NUM_PARTIALS],
+    /// True if the ratios have been moved to notes in a tuning.
+    pub retuned: bool,
+}
+
+impl Harmonics {
+    /// Only the fundamental, at harmonic ratios.
+    pub const SINE: Self = {
+        let mut amps = [0.0; NUM_PARTIALS];
+        amps[0] = 1.0;
+        Self { amps, ratios: harmonic_ratios(), retuned: false }
+    };
+
+    /// Move partials to the nearest notes in `tuning`, or back to whole-number
+    /// harmonics if `tuning` is `None`.
+    pub fn retune(&mut self, tuning: Option<&Tuning>) {
+        self.ratios = harmonic_ratios();
+        if let Some(tuning) = tuning {
+            for ratio in self.ratios.iter_mut() {
+                *ratio = tuning.nearest_ratio(*ratio);
+            }
+        }
+        self.retuned = tuning.is_some();
+    }
+}
+
+/// Returns the whole-number ratios of the harmonic series.
+const fn harmonic_ratios() -> [f32; NUM_PARTIALS] {
+    let mut ratios = [0.0; NUM_PARTIALS];
+    let mut i = 0;
+    while i < NUM_PARTIALS {
+        ratios[i] = (i + 1) as f32;
+        i += 1;
+    }
+    ratios
+}
+
 /// Default pressure at song start. Equivalent to 0xA/0xF.
 pub const DEFAULT_PRESSURE: f32 = 2.0/3.0;
 
@@ -228,12 +365,29 @@ pub struct Synth {
     mod_memory: Vec<f32>,
     /// Per-channel pressure level memory.
     pressure_memory: Vec<f32>,
+    /// Per-channel MIDI sustain pedal state.
+    sustain_memory: Vec<bool>,
+    /// Keys that were on when a MIDI sostenuto pedal was pressed.
+    sostenuto_keys: HashSet<Key>,
+    /// Keys that have been released, but are held on by a pedal.
+    held_keys: HashSet<Key>,
+    /// Whether keyboard notes are held as if by a sustain pedal.
+    keyboard_hold: bool,
+    /// Per-channel pattern vibrato memory.
+    vibrato_memory: Vec<PatternLfo>,
+    /// Per-channel pattern tremolo memory.
+    tremolo_memory: Vec<PatternLfo>,
+    /// Tempo that synced vibrato and tremolo rates follow.
+    tempo: f32,
     /// Previous frequency played by any note.
     prev_freq: Option<f32>,
     /// Sample rate to pass when creating DSP.
     sample_rate: f32,
     /// If true, note-ons are ignored.
     pub muted: bool,
+    /// Source of random voice values, if seeded. Unseeded synths use the
+    /// thread RNG and prebuilt voices.
+    rng: Option<StdRng>,
 }
 
 impl Synth {
@@ -244,17 +398,32 @@ impl Synth {
             bend_memory: vec![0.0],
             mod_memory: vec![0.0],
             pressure_memory: vec![DEFAULT_PRESSURE],
+            sustain_memory: vec![false],
+            sostenuto_keys: HashSet::new(),
+            held_keys: HashSet::new(),
+            keyboard_hold: false,
+            vibrato_memory: vec![PatternLfo::OFF],
+            tremolo_memory: vec![PatternLfo::OFF],
+            tempo: DEFAULT_TEMPO,
             prev_freq: None,
             sample_rate,
             muted: false,
+            rng: None,
         }
     }
 
+    /// Seed the random values of new voices, making output deterministic.
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = Some(StdRng::seed_from_u64(seed));
+    }
+
     /// Reset channel-state-type memory.
     pub fn reset_memory(&mut self) {
         self.bend_memory.fill(0.0);
         self.mod_memory.fill(0.0);
         self.pressure_memory.fill(DEFAULT_PRESSURE);
+        self.vibrato_memory.fill(PatternLfo::OFF);
+        self.tremolo_memory.fill(PatternLfo::OFF);
         self.prev_freq = None;
     }
 
@@ -269,6 +438,15 @@ impl Synth {
         while self.pressure_memory.len() <= index {
             self.pressure_memory.push(DEFAULT_PRESSURE);
         }
+        while self.sustain_memory.len() <= index {
+            self.sustain_memory.push(false);
+        }
+        while self.vibrato_memory.len() <= index {
+            self.vibrato_memory.push(PatternLfo::OFF);
+        }
+        while self.tremolo_memory.len() <= index {
+            self.tremolo_memory.push(PatternLfo::OFF);
+        }
         while self.released_voices.len() <= index {
             self.released_voices.push(VecDeque::new());
         }
@@ -281,6 +459,7 @@ impl Synth {
         if self.muted {
             return
         }
+        self.held_keys.remove(&key);
 
         // turn off prev note(s) in channel
         // TODO: this won't work right for non-poly play modes!
@@ -312,9 +491,13 @@ impl Synth {
                     voice.off(seq);
                     self.released_voices[key.channel as usize].push_back(voice);
                 }
+                self.held_keys.clear();
                 true
             },
             PlayMode::SingleTrigger => {
+                // the voice moves to the new key, so pedals no longer hold
+                // the old one
+                self.held_keys.clear();
                 if self.active_voices.is_empty() {
                     true
                 } else {
@@ -338,7 +521,10 @@ impl Synth {
                 self.pressure_memory[channel]
             };
             let voice = Voice::new(pitch, bend, pressure, self.mod_memory[channel],
-                self.prev_freq, patch, seq, self.sample_rate, pan_polarity);
+                self.prev_freq, patch, seq, self.sample_rate, pan_polarity,
+                self.rng.as_mut());
+            voice.set_vibrato(self.vibrato_memory[channel], self.tempo);
+            voice.set_tremolo(self.tremolo_memory[channel], self.tempo);
 
             self.insert_voice(key, voice);
             self.check_truncate_voices(channel, seq);
@@ -362,16 +548,82 @@ impl Synth {
         }
     }
 
-    /// Handle a note off event.
+    /// Handle a note off event. MIDI notes are held on while a pedal is down.
     pub fn note_off(&mut self, key: Key, seq: &mut Sequencer) {
+        if self.pedal_holds(&key) {
+            if self.active_voices.contains_key(&key) {
+                self.held_keys.insert(key);
+            }
+            return
+        }
+        self.release(key, seq);
+    }
+
+    /// Returns true if a pedal should keep `key` on after release.
+    fn pedal_holds(&self, key: &Key) -> bool {
+        match key.origin {
+            KeyOrigin::Keyboard => self.keyboard_hold,
+            KeyOrigin::Midi => self.sustain_memory.get(key.channel as usize) == Some(&true)
+                || self.sostenuto_keys.contains(key),
+            _ => false,
+        }
+    }
+
+    /// Release a note, regardless of pedals.
+    fn release(&mut self, key: Key, seq: &mut Sequencer) {
         if let Some(voice) = self.active_voices.remove(&key) {
             voice.off(seq);
             self.released_voices[key.channel as usize].push_back(voice);
         }
     }
 
+    /// Handle a MIDI sustain (hold) pedal.
+    pub fn sustain(&mut self, channel: u8, on: bool, seq: &mut Sequencer) {
+        self.expand_memory(channel as usize);
+        self.sustain_memory[channel as usize] = on;
+        if !on {
+            self.release_held_keys(seq);
+        }
+    }
+
+    /// Handle the computer keyboard equivalent of a sustain pedal.
+    pub fn keyboard_hold(&mut self, on: bool, seq: &mut Sequencer) {
+        self.keyboard_hold = on;
+        if !on {
+            self.release_held_keys(seq);
+        }
+    }
+
+    /// Handle a MIDI sostenuto pedal. Only notes that are on when the pedal
+    /// is pressed are held.
+    pub fn sostenuto(&mut self, channel: u8, on: bool, seq: &mut Sequencer) {
+        if on {
+            let keys: Vec<_> = self.active_voices.keys()
+                .filter(|k| k.origin == KeyOrigin::Midi && k.channel == channel)
+                .filter(|k| !self.held_keys.contains(k))
+                .cloned().collect();
+            self.sostenuto_keys.extend(keys);
+        } else {
+            self.sostenuto_keys.retain(|k| k.channel != channel);
+            self.release_held_keys(seq);
+        }
+    }
+
+    /// Release held keys that are no longer held by any pedal.
+    fn release_held_keys(&mut self, seq: &mut Sequencer) {
+        let keys: Vec<_> = self.held_keys.iter()
+            .filter(|k| !self.pedal_holds(k))
+            .cloned().collect();
+        for key in keys {
+            self.held_keys.remove(&key);
+            self.release(key, seq);
+        }
+    }
+
     /// Turns off all notes from a specific origin.
     pub fn clear_notes_with_origin(&mut self, seq: &mut Sequencer, origin: KeyOrigin) {
+        self.held_keys.retain(|k| k.origin != origin);
+        self.sostenuto_keys.retain(|k| k.origin != origin);
         let remove_keys: Vec<_> = self.active_voices.keys()
             .filter(|k| k.origin == origin)
             .cloned().collect();
@@ -384,8 +636,21 @@ impl Synth {
         }
     }
 
+    /// Iterates over the keys and pitches of voices that are "on", before
+    /// MIDI pitch bend.
+    pub fn active_pitches(&self) -> impl Iterator<Item = (&Key, f32)> {
+        self.active_voices.iter().map(|(k, v)| (k, v.base_pitch))
+    }
+
+    /// Returns the number of voices that are "on".
+    pub fn voice_count(&self) -> usize {
+        self.active_voices.len()
+    }
+
     /// Turns off all notes.
     pub fn clear_all_notes(&mut self, seq: &mut Sequencer) {
+        self.held_keys.clear();
+        self.sostenuto_keys.clear();
         for (k, voice) in self.active_voices.drain() {
             voice.off(seq);
             self.released_voices[k.channel as usize].push_back(voice);
@@ -394,6 +659,8 @@ impl Synth {
 
     /// Cuts all notes.
     pub fn panic(&mut self, seq: &mut Sequencer) {
+        self.held_keys.clear();
+        self.sostenuto_keys.clear();
         for (_, voice) in self.active_voices.drain() {
             voice.cut(seq);
         }
@@ -404,12 +671,15 @@ impl Synth {
         }
     }
 
-    /// Handle a MIDI-style pitch bend.
-    pub fn pitch_bend(&mut self, channel: u8, bend: f32) {
-        self.expand_memory(channel as usize);
-        self.bend_memory[channel as usize] = bend;
+    /// Handle a MIDI-style pitch bend for notes from `origin`. Only MIDI
+    /// bends are remembered for later notes.
+    pub fn pitch_bend(&mut self, origin: KeyOrigin, channel: u8, bend: f32) {
+        if origin == KeyOrigin::Midi {
+            self.expand_memory(channel as usize);
+            self.bend_memory[channel as usize] = bend;
+        }
         for (key, voice) in self.active_voices.iter_mut() {
-            if key.origin == KeyOrigin::Midi && key.channel == channel {
+            if key.origin == origin && key.channel == channel {
                 voice.vars.freq.set(midi_hz(voice.base_pitch + bend));
             }
         }
@@ -427,15 +697,27 @@ impl Synth {
     /// Handle polyphonic aftertouch.
     pub fn poly_pressure(&mut self, key: Key, pressure: f32) {
         if let Some(v) = self.active_voices.get(&key) {
-            v.vars.pressure.set(pressure);
+            v.vars.pressure.set(v.poly_aftertouch.apply(v.note_pressure, pressure));
         }
     }
 
-    /// Handle MIDI-channel-based aftertouch.
+    /// Handle MIDI channel aftertouch.
+    pub fn channel_aftertouch(&mut self, channel: u8, pressure: f32) {
+        self.set_vel_memory(channel, pressure);
+        for (key, voice) in self.active_voices.iter_mut() {
+            if key.channel == channel {
+                voice.vars.pressure.set(
+                    voice.channel_aftertouch.apply(voice.note_pressure, pressure));
+            }
+        }
+    }
+
+    /// Handle a pattern pressure change.
     pub fn channel_pressure(&mut self, channel: u8, pressure: f32) {
         self.set_vel_memory(channel, pressure);
         for (key, voice) in self.active_voices.iter_mut() {
             if key.channel == channel {
+                voice.note_pressure = pressure;
                 voice.vars.pressure.set(pressure);
             }
         }
@@ -451,6 +733,50 @@ impl Synth {
         }
     }
 
+    /// Set pattern vibrato for notes in a channel.
+    pub fn vibrato(&mut self, channel: u8, lfo: PatternLfo) {
+        self.expand_memory(channel as usize);
+        self.vibrato_memory[channel as usize] = lfo;
+        for (key, voice) in self.active_voices.iter() {
+            if key.channel == channel {
+                voice.set_vibrato(lfo, self.tempo);
+            }
+        }
+    }
+
+    /// Set pattern tremolo for notes in a channel.
+    pub fn tremolo(&mut self, channel: u8, lfo: PatternLfo) {
+        self.expand_memory(channel as usize);
+        self.tremolo_memory[channel as usize] = lfo;
+        for (key, voice) in self.active_voices.iter() {
+            if key.channel == channel {
+                voice.set_tremolo(lfo, self.tempo);
+            }
+        }
+    }
+
+    /// Set the tempo that synced vibrato and tremolo rates follow.
+    pub fn set_tempo(&mut self, tempo: f32) {
+        if tempo == self.tempo {
+            return
+        }
+        self.tempo = tempo;
+        for (key, voice) in self.active_voices.iter() {
+            let channel = key.channel as usize;
+            if let Some(lfo) = self.vibrato_memory.get(channel) {
+                voice.set_vibrato(*lfo, tempo);
+            }
+            if let Some(lfo) = self.tremolo_memory.get(channel) {
+                voice.set_tremolo(*lfo, tempo);
+            }
+        }
+    }
+
+    /// Returns the pressure that new notes will use.
+    pub fn vel_memory(&self, channel: u8) -> f32 {
+        self.pressure_memory.get(channel as usize).copied().unwrap_or(DEFAULT_PRESSURE)
+    }
+
     /// Set pressure that new notes will use.
     pub fn set_vel_memory(&mut self, channel: u8, pressure: f32) {
         self.expand_memory(channel as usize);
@@ -462,6 +788,12 @@ impl Synth {
         self.expand_memory(channel as usize);
         self.mod_memory[channel as usize] = depth;
     }
+
+    /// Returns the modulation level that new notes will use.
+    #[cfg(test)]
+    pub fn mod_memory(&self, channel: u8) -> f32 {
+        self.mod_memory.get(channel as usize).copied().unwrap_or(0.0)
+    }
 }
 
 /// A Patch is a configuration of synthesis parameters.
@@ -479,8 +811,30 @@ pub struct Patch {
     pub mod_matrix: Vec<Modulation>,
     pub fx_send: Parameter,
     pub distortion: Parameter,
+    /// Maximum random pitch offset of each note, in cents.
+    #[serde(default = "zero_parameter")]
+    pub detune: Parameter,
+    /// Depth of slow random pitch drift, in cents.
+    #[serde(default = "zero_parameter")]
+    pub drift: Parameter,
     #[serde(default)]
     pub version: u8,
+    /// How MIDI channel pressure affects playing voices.
+    #[serde(default)]
+    pub channel_aftertouch: AftertouchMode,
+    /// How MIDI polyphonic aftertouch affects playing voices.
+    #[serde(default)]
+    pub poly_aftertouch: AftertouchMode,
+    /// Pressure of notes played from the computer keyboard, if fixed.
+    #[serde(default)]
+    pub keyjazz_pressure: Option<f32>,
+    /// Tags for grouping and searching patches.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(skip)]
+    voice_pool: VoicePool,
+    #[serde(skip)]
+    monitor: VoiceMonitor,
 }
 
 impl Patch {
@@ -493,6 +847,8 @@ impl Patch {
             gain: Parameter(shared(0.5)),
             fx_send: Parameter(shared(1.0)),
             distortion: Parameter(shared(0.0)),
+            detune: zero_parameter(),
+            drift: zero_parameter(),
             oscs: vec![Oscillator::default()],
             envs: vec![ADSR::default()],
             filters: Vec::new(),
@@ -513,17 +869,22 @@ impl Patch {
                 },
             ],
             version: Self::VERSION,
+            channel_aftertouch: AftertouchMode::Replace,
+            poly_aftertouch: AftertouchMode::Replace,
+            keyjazz_pressure: None,
+            tags: Vec::new(),
+            voice_pool: Default::default(),
+            monitor: Default::default(),
         }
     }
 
-    /// Initialize a loaded patch.
-    pub fn init(&mut self) {
-        // initialize PCM generators
-        for osc in self.oscs.iter_mut() {
-            if let Waveform::Pcm(Some(data)) = &mut osc.waveform {
-                if let Err(e) = data.init() {
-                    eprintln!("{}", e);
-                }
+    /// Initialize a loaded patch. `base_dir` is used to resolve relative
+    /// sample references.
+    pub fn init(&mut self, base_dir: Option<&Path>) {
+        // initialize PCM and wavetable generators
+        for data in self.pcm_data_mut() {
+            if let Err(e) = data.init(base_dir) {
+                eprintln!("{}", e);
             }
         }
 
@@ -546,11 +907,19 @@ impl Patch {
         self.version = Self::VERSION;
     }
 
+    /// Iterate over the PCM and wavetable data of the patch's generators.
+    pub fn pcm_data_mut(&mut self) -> impl Iterator<Item = &mut PcmData> {
+        self.oscs.iter_mut().filter_map(|osc| match &mut osc.waveform {
+            Waveform::Pcm(Some(data)) | Waveform::Wavetable(Some(data)) => Some(data),
+            _ => None,
+        })
+    }
+
     /// Load a patch from disk.
     pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
         let input = fs::read(path)?;
         let mut patch = rmp_serde::from_slice::<Self>(&input)?;
-        patch.init();
+        patch.init(path.parent());
         patch.set_name_from_path(path);
         Ok(patch)
     }
@@ -577,6 +946,15 @@ impl Patch {
         Ok(fs::write(path, contents)?)
     }
 
+    /// Returns true if the patch name or a tag contains every word of
+    /// `query`, ignoring case.
+    pub fn matches(&self, query: &str) -> bool {
+        let name = self.name.to_lowercase();
+        let tags: Vec<_> = self.tags.iter().map(|t| t.to_lowercase()).collect();
+        query.split_whitespace().map(|w| w.to_lowercase())
+            .all(|w| name.contains(&w) || tags.iter().any(|t| t.contains(&w)))
+    }
+
     /// Create a copy of the patch. Copies share access to wave data.
     pub fn duplicate(&self) -> Self {
         let mut patch = self.clone();
@@ -589,6 +967,77 @@ impl Patch {
         patch
     }
 
+    /// Discard prebuilt voices. Must be called when patch settings that
+    /// affect DSP structure change.
+    pub fn invalidate_voice_pool(&self) {
+        self.voice_pool.voices.lock().unwrap().clear();
+    }
+
+    /// Build a prebuilt voice if the patch has been played and its pool isn't
+    /// full. Only one voice is built per call, to spread the cost over time.
+    pub fn fill_voice_pool(&self, sample_rate: f32, pan_polarity: &Shared) {
+        if self.voice_pool.used.load(Ordering::Relaxed)
+            && self.voice_pool.voices.lock().unwrap().len() < VOICE_POOL_SIZE {
+            // build without holding the lock, so the audio thread can take
+            // voices meanwhile
+            let voice = PooledVoice::new(self, sample_rate, pan_polarity,
+                &mut thread_rng());
+            self.voice_pool.voices.lock().unwrap().push(voice);
+        }
+    }
+
+    /// Take a prebuilt voice from the pool, or build a new one.
+    fn take_voice(&self, sample_rate: f32, pan_polarity: &Shared) -> PooledVoice {
+        self.voice_pool.used.store(true, Ordering::Relaxed);
+        let voice = {
+            let mut voices = self.voice_pool.voices.lock().unwrap();
+            voices.retain(|v| v.vars.sample_rate == sample_rate);
+            voices.pop()
+        };
+        voice.unwrap_or_else(||
+            PooledVoice::new(self, sample_rate, pan_polarity, &mut thread_rng()))
+    }
+
+    /// Returns the modulated values of the most recently played voice, if it
+    /// hasn't been cut.
+    pub fn voice_levels(&self) -> Option<VoiceLevels> {
+        let taps = self.monitor.0.lock().unwrap().clone()?;
+        if taps.active.value() == 0.0 {
+            return None
+        }
+        let values = |v: &[Shared]| v.iter().map(|x| x.value()).collect();
+        Some(VoiceLevels {
+            gate: taps.gate.value() > 0.0,
+            envs: values(&taps.envs),
+            levels: values(&taps.levels),
+            cutoffs: values(&taps.cutoffs),
+        })
+    }
+
+    /// Play a note offline for `hold` seconds and return the peak and RMS
+    /// levels of the dry output.
+    pub fn measure_note(&self, pitch: f32, hold: f32, sample_rate: f32) -> (f32, f32) {
+        let PooledVoice { vars, mut net } =
+            PooledVoice::new(self, sample_rate, &shared(1.0), &mut thread_rng());
+        let freq = midi_hz(pitch);
+        vars.freq.set(freq);
+        vars.prev_freq.set(freq);
+        net.set_sample_rate(sample_rate as f64);
+
+        let mut output = [0.0; 4];
+        let (mut peak, mut sum) = (0.0_f32, 0.0_f64);
+        let n = (hold * sample_rate) as usize;
+        for _ in 0..n {
+            net.tick(&[], &mut output);
+            for x in &output[..2] {
+                peak = peak.max(x.abs());
+                sum += (x * x) as f64;
+            }
+        }
+
+        (peak, (sum / (n * 2).max(1) as f64).sqrt() as f32)
+    }
+
     /// Returns the DSP net for a modulation, given voice parameters.
     fn mod_net(&self, vars: &VoiceVars, target: ModTarget, path: &[ModSource]) -> Net {
         let mut net = Net::wrap(Box::new(
@@ -640,11 +1089,15 @@ impl Patch {
             if osc.waveform.has_tone_control() {
                 v.push(ModTarget::Tone(i));
             }
+            if matches!(osc.waveform, Waveform::Pcm(_)) {
+                v.push(ModTarget::SampleStart(i));
+            }
         }
 
         for i in 0..self.filters.len() {
             v.push(ModTarget::FilterCutoff(i));
             v.push(ModTarget::FilterQ(i));
+            v.push(ModTarget::FilterDrive(i));
         }
 
         for i in 0..self.envs.len() {
@@ -774,6 +1227,25 @@ impl Patch {
         }
     }
 
+    /// Set generator gains from their mute and solo states. While any
+    /// generator is soloed, only soloed generators and the generators that
+    /// modulate them are heard.
+    pub fn update_audition(&self) {
+        let any_solo = self.oscs.iter().any(|x| x.audition.solo);
+        let mut heard: Vec<bool> = Vec::with_capacity(self.oscs.len());
+
+        for osc in &self.oscs {
+            let modulates_heard = match osc.output {
+                OscOutput::AM(i) | OscOutput::RM(i) | OscOutput::FM(i) =>
+                    heard.get(i).copied().unwrap_or_default(),
+                OscOutput::Mix(_) => false,
+            };
+            let is_heard = !any_solo || osc.audition.solo || modulates_heard;
+            heard.push(is_heard);
+            osc.audition.gain.set(if is_heard && !osc.audition.mute { 1.0 } else { 0.0 });
+        }
+    }
+
     /// Construct a DSP net for generator `i`.
     fn make_osc(&self, i: usize, vars: &VoiceVars) -> Net {
         let mut freq_mod = Net::new(0, 1);
@@ -786,7 +1258,10 @@ impl Patch {
 
         let level = {
             let modu = self.mod_net(vars, ModTarget::Level(i), &[]);
-            (var(&self.oscs[i].level.0) >> smooth()) * (modu >> shape_fn(|x| x*x))
+            self.oscs[i].level.smoothed()
+                * (var(&self.oscs[i].audition.gain) >> smooth())
+                * (modu >> shape_fn(|x| x*x))
+                >> monitor(&vars.taps.levels[i], Meter::Sample)
         };
         let mut net = self.oscs[i].make_net(self, vars, i, freq_mod) * level;
 
@@ -871,6 +1346,36 @@ pub struct Oscillator {
     pub output: OscOutput,
     #[serde(default)]
     pub oversample: bool,
+    /// Start position of PCM playback, from 0 to 1.
+    #[serde(default = "zero_parameter")]
+    pub start: Parameter,
+    #[serde(skip)]
+    pub audition: Audition,
+}
+
+/// Mute and solo state of a generator, for auditioning while editing. Not
+/// saved, and cloning produces the default state, so renders are unaffected.
+pub struct Audition {
+    pub mute: bool,
+    pub solo: bool,
+    /// Gain applied to the generator, set by `Patch::update_audition`.
+    gain: Shared,
+}
+
+impl Default for Audition {
+    fn default() -> Self {
+        Self {
+            mute: false,
+            solo: false,
+            gain: shared(1.0),
+        }
+    }
+}
+
+impl Clone for Audition {
+    fn clone(&self) -> Self {
+        Default::default()
+    }
 }
 
 impl Default for Oscillator {
@@ -883,6 +1388,8 @@ impl Default for Oscillator {
             waveform: Waveform::Sine,
             output: OscOutput::Mix(0),
             oversample: false,
+            start: zero_parameter(),
+            audition: Default::default(),
         }
     }
 }
@@ -894,20 +1401,22 @@ impl Oscillator {
         let var_freq = Net::wrap(if settings.glide_time == 0.0 {
             Box::new(var(&vars.freq))
         } else {
-            let prev_freq = vars.prev_freq.unwrap_or(vars.freq.value());
-            let env = envelope2(move |t, x| if t == 0.0 { prev_freq } else { x });
+            let prev_freq = vars.prev_freq.clone();
+            let env = envelope2(move |t, x| if t == 0.0 { prev_freq.value() } else { x });
             Box::new(var(&vars.freq) >> env >> follow(settings.glide_time * 0.5))
         });
         let base_freq = var_freq
-            * var(&self.freq_ratio.0)
+            * self.freq_ratio.smoothed()
             * (settings.mod_net(vars, ModTarget::OscPitch(index), &[])
                 + settings.mod_net(vars, ModTarget::Pitch, &[])
                 >> pow_shape(MAX_PITCH_MOD))
             * ((settings.mod_net(vars, ModTarget::OscFinePitch(index), &[])
                 + settings.mod_net(vars, ModTarget::FinePitch, &[]))
-                * 0.5 + var(&self.fine_pitch.0) >> pow_shape(SEMITONE_RATIO))
-            * (1.0 + freq_mod * FM_DEPTH_MULTIPLIER);
-        let tone = var(&self.tone.0)
+                * 0.5 + self.fine_pitch.smoothed() >> pow_shape(SEMITONE_RATIO))
+            * (1.0 + freq_mod * FM_DEPTH_MULTIPLIER)
+            * vibrato_net(vars)
+            * drift_net(settings, vars);
+        let tone = self.tone.smoothed()
             + settings.mod_net(vars, ModTarget::Tone(index), &[])
             >> shape_fn(clamp01);
 
@@ -932,13 +1441,26 @@ impl Oscillator {
             } else {
                 base_freq >> sine().phase(0.0)
             },
-            Waveform::Hold => (noise().seed(random()) | base_freq) >> hold(0.0),
-            Waveform::Noise => (noise().seed(random()) | tone)
+            Waveform::Hold => (noise().seed(vars.osc_seeds[index]) | base_freq) >> hold(0.0),
+            Waveform::Noise => (noise().seed(vars.osc_seeds[index]) | tone)
                 >> (pinkpass() * (1.0 - pass()) & pass() * pass()),
             Waveform::Pcm(data) => if let Some(data) = data {
                 let f = data.wave.sample_rate() as f32 / vars.sample_rate / REF_FREQ;
-                base_freq * f >>
-                    resample(wavech(&data.wave, 0, data.loop_point))
+                let start = self.start.smoothed()
+                    + settings.mod_net(vars, ModTarget::SampleStart(index), &[]);
+                (base_freq * f | start) >> pcm_player(data.wave.clone(), data.loop_point)
+            } else {
+                Net::new(0, 1)
+            },
+            Waveform::Pluck => (base_freq | tone) >> pluck_string(vars.osc_seeds[index]),
+            Waveform::Additive(harmonics) =>
+                base_freq >> additive_osc(&harmonics.amps, &harmonics.ratios),
+            Waveform::Wavetable(data) => if let Some(data) = data {
+                if self.oversample {
+                    (base_freq | tone) >> oversample(wavetable_osc(data.wave.clone()))
+                } else {
+                    (base_freq | tone) >> wavetable_osc(data.wave.clone())
+                }
             } else {
                 Net::new(0, 1)
             },
@@ -1011,6 +1533,8 @@ pub struct Filter {
     pub cutoff: Parameter,
     pub resonance: Parameter,
     pub key_tracking: KeyTracking,
+    #[serde(default = "zero_parameter")]
+    pub drive: Parameter,
 }
 
 impl Filter {
@@ -1018,18 +1542,19 @@ impl Filter {
     fn filter(&self, settings: &Patch, vars: &VoiceVars, index: usize, net: Net) -> Net {
         let cutoff = {
             let kt_freq = Net::wrap(match self.key_tracking {
-                KeyTracking::None => Box::new(var(&self.cutoff.0)),
-                KeyTracking::Partial => Box::new(var(&self.cutoff.0)
+                KeyTracking::None => Box::new(self.cutoff.smoothed()),
+                KeyTracking::Partial => Box::new(self.cutoff.smoothed()
                     * var_fn(&vars.freq, |x| pow(x/REF_FREQ, 0.5))),
-                KeyTracking::Full => Box::new(var(&self.cutoff.0)
+                KeyTracking::Full => Box::new(self.cutoff.smoothed()
                     * var_fn(&vars.freq, |x| x/REF_FREQ)),
             });
             let modu = settings.mod_net(vars, ModTarget::FilterCutoff(index), &[])
                 >> pow_shape(FILTER_CUTOFF_MOD_BASE);
             kt_freq * modu
                 >> shape_fn(|x| clamp(MIN_FILTER_CUTOFF, MAX_FILTER_CUTOFF, x))
+                >> monitor(&vars.taps.cutoffs[index], Meter::Sample)
         };
-        let reso = var(&self.resonance.0)
+        let reso = self.resonance.smoothed()
             + settings.mod_net(vars, ModTarget::FilterQ(index), &[])
             >> shape_fn(clamp01);
         let filter = Net::wrap(match self.filter_type {
@@ -1038,8 +1563,27 @@ impl Filter {
             FilterType::Highpass => Box::new(highpass()),
             FilterType::Bandpass => Box::new(bandpass()),
             FilterType::Notch => Box::new(notch()),
+            FilterType::Lowpass24 => Box::new((pass() | multisplit::<U2, U2>())
+                >> (lowpass() | multipass::<U2>()) >> lowpass()),
+            FilterType::Highpass24 => Box::new((pass() | multisplit::<U2, U2>())
+                >> (highpass() | multipass::<U2>()) >> highpass()),
+            FilterType::Formant => Box::new((pass()
+                | shape_fn(|f| (f / MIN_FILTER_CUTOFF).ln() / FILTER_CUTOFF_MOD_BASE.ln())
+                | pass()) >> formant_filter()),
         });
-        (net | cutoff | reso) >> filter
+
+        // use dry signal when drive is zero
+        let drive = (
+            self.drive.smoothed()
+                + settings.mod_net(vars, ModTarget::FilterDrive(index), &[])
+            | net
+        ) >> map(|i: &Frame<f32, U2>| if i[0] <= 0.0 {
+            i[1]
+        } else {
+            (i[1] * (1.0 + clamp01(i[0]) * (MAX_FILTER_DRIVE - 1.0))).tanh()
+        });
+
+        (drive | cutoff | reso) >> filter
     }
 }
 
@@ -1050,6 +1594,7 @@ impl Default for Filter {
             resonance: Parameter(shared(MIN_FILTER_RESONANCE)),
             key_tracking: KeyTracking::None,
             filter_type: FilterType::Ladder,
+            drive: zero_parameter(),
         }
     }
 }
@@ -1061,20 +1606,35 @@ pub enum FilterType {
     Highpass,
     Bandpass,
     Notch,
+    Lowpass24,
+    Highpass24,
+    /// Vowel filter. Cutoff selects the vowel.
+    Formant,
 }
 
 impl FilterType {
-    pub const VARIANTS: [FilterType; 5] =
-        [Self::Ladder, Self::Lowpass, Self::Highpass, Self::Bandpass, Self::Notch];
+    pub const VARIANTS: [FilterType; 8] = [
+        Self::Ladder,
+        Self::Lowpass,
+        Self::Lowpass24,
+        Self::Highpass,
+        Self::Highpass24,
+        Self::Bandpass,
+        Self::Notch,
+        Self::Formant,
+    ];
 
     /// Returns the UI string for the filter type.
     pub fn name(&self) -> &str {
         match self {
             Self::Ladder => "Ladder",
-            Self::Lowpass => "Lowpass",
-            Self::Highpass => "Highpass",
+            Self::Lowpass => "Lowpass 12",
+            Self::Highpass => "Highpass 12",
             Self::Bandpass => "Bandpass",
             Self::Notch => "Notch",
+            Self::Lowpass24 => "Lowpass 24",
+            Self::Highpass24 => "Highpass 24",
+            Self::Formant => "Formant",
         }
     }
 }
@@ -1101,6 +1661,14 @@ impl ADSR {
             sqrt_attack);
 
         (var(&vars.gate) | scale) >> adsr
+            >> monitor(&vars.taps.envs[index], Meter::Sample)
+    }
+
+    /// Returns the level of the envelope at `time`, if the gate is released
+    /// at `release_start`. Used to draw the envelope shape.
+    pub fn level_at(&self, time: f32, release_start: f32) -> f32 {
+        adsr_level(self.attack, self.decay, self.sustain, self.release,
+            release_start, time)
     }
 }
 
@@ -1122,6 +1690,8 @@ pub struct Modulation {
     pub source: ModSource,
     pub target: ModTarget,
     pub depth: Parameter,
+    #[serde(default)]
+    pub curve: ModCurve,
 }
 
 impl Default for Modulation {
@@ -1130,6 +1700,7 @@ impl Default for Modulation {
             source: ModSource::Modulation,
             target: ModTarget::Gain,
             depth: Parameter(shared(0.0)),
+            curve: ModCurve::Linear,
         }
     }
 }
@@ -1144,9 +1715,10 @@ impl Modulation {
         let net = match self.source {
             ModSource::Pitch => Net::wrap(Box::new(
                 var_fn(&vars.freq,|f| dexerp(PITCH_FLOOR, PITCH_CEILING, f)))),
-            ModSource::Pressure => Net::wrap(Box::new(var(&vars.pressure) >> smooth())),
+            ModSource::Pressure =>
+                Net::wrap(Box::new(var(&vars.pressure) >> pressure_smooth())),
             ModSource::Modulation =>
-                Net::wrap(Box::new(var(&vars.modulation) >> smooth())),
+                Net::wrap(Box::new(var(&vars.modulation) >> mod_smooth())),
             ModSource::Random => Net::wrap(Box::new(constant(vars.random_values[index]))),
             ModSource::Envelope(i) => match settings.envs.get(i) {
                 Some(env) => env.make_net(
@@ -1158,7 +1730,11 @@ impl Modulation {
                 None => Net::new(0, 1),
             }
         };
-        let depth = var(&self.depth.0) >> smooth()
+        let net = match self.curve {
+            ModCurve::Linear => net,
+            curve => net >> shape_fn(move |x| curve.apply(x)),
+        };
+        let depth = self.depth.smoothed()
             + settings.mod_net(vars, ModTarget::ModDepth(index), &path) * 2.0;
 
         if self.target.is_additive() {
@@ -1175,6 +1751,34 @@ impl Modulation {
     }
 }
 
+/// Response curve applied to a modulation source.
+#[derive(PartialEq, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum ModCurve {
+    #[default]
+    Linear,
+    Exponential,
+}
+
+impl ModCurve {
+    pub const VARIANTS: [ModCurve; 2] = [Self::Linear, Self::Exponential];
+
+    /// Returns the UI string for the curve.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Linear => "Linear",
+            Self::Exponential => "Exp",
+        }
+    }
+
+    /// Shape a source value. Preserves sign and the range -1..1.
+    pub fn apply(&self, x: f32) -> f32 {
+        match self {
+            Self::Linear => x,
+            Self::Exponential => x * x.abs(),
+        }
+    }
+}
+
 #[derive(PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub enum ModSource {
     Pitch,
@@ -1201,7 +1805,7 @@ impl Display for ModSource {
 
 impl ModSource {
     /// Returns true if the source oscillates in -1..1 rather than 0..1.
-    fn is_bipolar(&self) -> bool {
+    pub fn is_bipolar(&self) -> bool {
         matches!(*self, ModSource::LFO(_))
     }
 }
@@ -1224,6 +1828,8 @@ pub enum ModTarget {
     /// Distortion. Inaccurate name for legacy reasons.
     ClipGain,
     FxSend,
+    FilterDrive(usize),
+    SampleStart(usize),
 }
 
 impl ModTarget {
@@ -1235,8 +1841,8 @@ impl ModTarget {
     /// Returns the generator index, if any.
     fn osc(&self) -> Option<usize> {
         match *self {
-            Self::Level(n) | Self::OscPitch(n) |
-                Self::OscFinePitch(n) | Self::Tone(n) => Some(n),
+            Self::Level(n) | Self::OscPitch(n) | Self::OscFinePitch(n)
+                | Self::Tone(n) | Self::SampleStart(n) => Some(n),
             _ => None,
         }
     }
@@ -1244,8 +1850,8 @@ impl ModTarget {
     /// Returns the generator index, if any.
     fn osc_mut(&mut self) -> Option<&mut usize> {
         match self {
-            Self::Level(n) | Self::OscPitch(n) |
-                Self::OscFinePitch(n) | Self::Tone(n) => Some(n),
+            Self::Level(n) | Self::OscPitch(n) | Self::OscFinePitch(n)
+                | Self::Tone(n) | Self::SampleStart(n) => Some(n),
             _ => None,
         }
     }
@@ -1253,7 +1859,7 @@ impl ModTarget {
     /// Returns the filter index, if any.
     fn filter(&self) -> Option<usize> {
         match *self {
-            Self::FilterCutoff(i) | Self::FilterQ(i) => Some(i),
+            Self::FilterCutoff(i) | Self::FilterQ(i) | Self::FilterDrive(i) => Some(i),
             _ => None,
         }
     }
@@ -1261,7 +1867,7 @@ impl ModTarget {
     /// Returns the filter index, if any.
     fn filter_mut(&mut self) -> Option<&mut usize> {
         match self {
-            Self::FilterCutoff(i) | Self::FilterQ(i) => Some(i),
+            Self::FilterCutoff(i) | Self::FilterQ(i) | Self::FilterDrive(i) => Some(i),
             _ => None,
         }
     }
@@ -1286,6 +1892,8 @@ impl Display for ModTarget {
             Self::Tone(n) => &format!("Gen {} tone", n + 1),
             Self::FilterCutoff(n) => &format!("Filter {} freq", n + 1),
             Self::FilterQ(n) => &format!("Filter {} reso", n + 1),
+            Self::FilterDrive(n) => &format!("Filter {} drive", n + 1),
+            Self::SampleStart(n) => &format!("Gen {} start", n + 1),
             Self::EnvScale(n) => &format!("Env {} scale", n + 1),
             Self::LFORate(n) => &format!("LFO {} rate", n + 1),
             Self::ModDepth(n) => &format!("Mod {} depth", n + 1),
@@ -1300,6 +1908,10 @@ struct Voice {
     vars: VoiceVars,
     /// MIDI pitch before MIDI pitch bend.
     base_pitch: f32,
+    /// Pressure before aftertouch.
+    note_pressure: f32,
+    channel_aftertouch: AftertouchMode,
+    poly_aftertouch: AftertouchMode,
     /// Estimated length of release before deallocation.
     release_time: f32,
     event_id: EventId,
@@ -1307,26 +1919,97 @@ struct Voice {
 
 impl Voice {
     /// Create and play a new voice.
+    /// If `rng` is some, the voice is built from it instead of taken from the
+    /// patch's pool.
     fn new(pitch: f32, bend: f32, pressure: f32, modulation: f32, prev_freq: Option<f32>,
         settings: &Patch, seq: &mut Sequencer, rate: f32, pan_polarity: &Shared,
+        rng: Option<&mut StdRng>,
     ) -> Self {
+        let (PooledVoice { vars, net }, detune) = match rng {
+            Some(rng) => (PooledVoice::new(settings, rate, pan_polarity, rng), rng.gen::<f32>()),
+            None => (settings.take_voice(rate, pan_polarity), random()),
+        };
+        *settings.monitor.0.lock().unwrap() = Some(vars.taps.clone());
+        let freq = midi_hz(pitch + bend);
+        vars.freq.set(freq);
+        vars.pressure.set(pressure);
+        vars.modulation.set(modulation);
+        vars.prev_freq.set(prev_freq.unwrap_or(freq));
+        vars.detune.set(detune * 2.0 - 1.0);
+
+        Self {
+            vars,
+            base_pitch: pitch,
+            note_pressure: pressure,
+            channel_aftertouch: settings.channel_aftertouch,
+            poly_aftertouch: settings.poly_aftertouch,
+            release_time: settings.release_time(),
+            event_id: seq.push_relative(
+                0.0, f64::INFINITY, Fade::Smooth, 0.0, 0.0, Box::new(net)),
+        }
+    }
+
+    fn set_vibrato(&self, lfo: PatternLfo, tempo: f32) {
+        self.vars.vibrato_depth.set(lfo.depth as f32 * VIBRATO_DEPTH_UNIT);
+        self.vars.vibrato_rate.set(lfo.rate.hz(tempo));
+    }
+
+    fn set_tremolo(&self, lfo: PatternLfo, tempo: f32) {
+        self.vars.tremolo_depth.set(lfo.depth as f32 / EventData::DIGIT_MAX as f32);
+        self.vars.tremolo_rate.set(lfo.rate.hz(tempo));
+    }
+
+    fn off(&self, seq: &mut Sequencer) {
+        self.vars.gate.set(0.0);
+        seq.edit_relative(self.event_id, self.release_time as f64, SMOOTH_TIME as f64);
+    }
+
+    fn cut(&self, seq: &mut Sequencer) {
+        self.vars.taps.active.set(0.0);
+        seq.edit_relative(self.event_id, 0.0, SMOOTH_TIME as f64);
+    }
+}
+
+/// Voice DSP built ahead of time. Voice variables are set when it's played.
+struct PooledVoice {
+    vars: VoiceVars,
+    net: Net,
+}
+
+impl PooledVoice {
+    fn new(settings: &Patch, rate: f32, pan_polarity: &Shared, rng: &mut impl Rng) -> Self {
         let gate = shared(1.0);
         let vars = VoiceVars {
-            freq: shared(midi_hz(pitch + bend)),
-            gate,
-            pressure: shared(pressure),
-            modulation: shared(modulation),
-            random_values: settings.mod_matrix.iter().map(|_| random()).collect(),
-            lfo_phases: settings.lfos.iter().map(|_| random()).collect(),
-            prev_freq,
+            freq: shared(REF_FREQ),
+            gate: gate.clone(),
+            pressure: shared(DEFAULT_PRESSURE),
+            modulation: shared(0.0),
+            vibrato_depth: shared(0.0),
+            vibrato_rate: shared(0.0),
+            tremolo_depth: shared(0.0),
+            tremolo_rate: shared(0.0),
+            detune: shared(0.0),
+            drift_seed: rng.gen(),
+            osc_seeds: settings.oscs.iter().map(|_| rng.gen()).collect(),
+            random_values: settings.mod_matrix.iter().map(|_| rng.gen()).collect(),
+            lfo_phases: settings.lfos.iter().map(|_| rng.gen()).collect(),
+            prev_freq: shared(REF_FREQ),
             sample_rate: rate,
+            taps: Arc::new(VoiceTaps {
+                envs: settings.envs.iter().map(|_| shared(0.0)).collect(),
+                levels: settings.oscs.iter().map(|_| shared(0.0)).collect(),
+                cutoffs: settings.filters.iter().map(|_| shared(0.0)).collect(),
+                gate,
+                active: shared(1.0),
+            }),
         };
-        let gain = (var(&settings.gain.0) >> smooth())
-            * (settings.mod_net(&vars, ModTarget::Gain, &[]) >> shape_fn(|x| x*x));
+        let gain = settings.gain.smoothed()
+            * (settings.mod_net(&vars, ModTarget::Gain, &[]) >> shape_fn(|x| x*x))
+            * tremolo_net(&vars);
 
         // use dry signal when distortion is zero
         let clip = (
-            var(&settings.distortion.0)
+            settings.distortion.smoothed()
                 + settings.mod_net(&vars, ModTarget::ClipGain, &[])
             | pass()
         ) >> map(|i: &Frame<f32, U2>| if i[0] == 0.0 {
@@ -1336,10 +2019,10 @@ impl Voice {
         });
 
         let signal = (settings.filter(&vars, settings.make_osc(0, &vars)) >> clip) * gain;
-        let pan = (var(&settings.pan.0) >> smooth()
+        let pan = (settings.pan.smoothed()
             + settings.mod_net(&vars, ModTarget::Pan, &[]) * 2.0)
             * var(pan_polarity) >> shape_fn(clamp11);
-        let fx_send = (var(&settings.fx_send.0)
+        let fx_send = (settings.fx_send.smoothed()
             + settings.mod_net(&vars, ModTarget::FxSend, &[]))
             >> shape_fn(clamp01);
 
@@ -1348,22 +2031,46 @@ impl Voice {
             >> (multipass::<U2>()
                 | multipass::<U2>() * (fx_send >> split::<U2>()));
 
-        Self {
-            vars,
-            base_pitch: pitch,
-            release_time: settings.release_time(),
-            event_id: seq.push_relative(
-                0.0, f64::INFINITY, Fade::Smooth, 0.0, 0.0, Box::new(net)),
-        }
+        Self { vars, net }
     }
+}
 
-    fn off(&self, seq: &mut Sequencer) {
-        self.vars.gate.set(0.0);
-        seq.edit_relative(self.event_id, self.release_time as f64, SMOOTH_TIME as f64);
-    }
+/// Make a DSP net for pattern vibrato, as a frequency multiplier.
+fn vibrato_net(vars: &VoiceVars) -> Net {
+    let lfo = Net::wrap(Box::new(var(&vars.vibrato_rate) >> sin_lfo(0.0)));
+    let depth = Net::wrap(Box::new(var(&vars.vibrato_depth) >> smooth()));
+    depth * lfo >> pow_shape(SEMITONE_RATIO)
+}
 
-    fn cut(&self, seq: &mut Sequencer) {
-        seq.edit_relative(self.event_id, 0.0, SMOOTH_TIME as f64);
+/// Make a DSP net for random detune and analog drift, as a frequency
+/// multiplier. Every generator in a voice drifts together.
+fn drift_net(settings: &Patch, vars: &VoiceVars) -> Net {
+    let noise = (noise().seed(vars.drift_seed) | dc(DRIFT_RATE)) >> hold(0.0)
+        >> follow(0.5 / DRIFT_RATE);
+    let drift = Net::wrap(Box::new(settings.drift.smoothed())) * Net::wrap(Box::new(noise));
+    let detune = Net::wrap(Box::new(settings.detune.smoothed())) * var(&vars.detune);
+    (drift + detune) * 0.01 >> pow_shape(SEMITONE_RATIO)
+}
+
+/// Make a DSP net for pattern tremolo, as a gain multiplier. Full depth
+/// dips to silence.
+fn tremolo_net(vars: &VoiceVars) -> Net {
+    let lfo = Net::wrap(Box::new(var(&vars.tremolo_rate) >> sin_lfo(0.0)));
+    let depth = Net::wrap(Box::new(var(&vars.tremolo_depth) >> smooth()));
+    1.0 - depth * (0.5 - lfo * 0.5)
+}
+
+/// Prebuilt voices for a patch. Cloning produces an empty pool.
+#[derive(Default)]
+struct VoicePool {
+    voices: Mutex<Vec<PooledVoice>>,
+    /// Set when a voice is taken, so that only played patches are pooled.
+    used: AtomicBool,
+}
+
+impl Clone for VoicePool {
+    fn clone(&self) -> Self {
+        Default::default()
     }
 }
 
@@ -1372,6 +2079,18 @@ struct VoiceVars {
     freq: Shared,
     pressure: Shared,
     modulation: Shared,
+    /// Pattern vibrato depth in semitones.
+    vibrato_depth: Shared,
+    vibrato_rate: Shared,
+    /// Pattern tremolo depth, from 0 to 1.
+    tremolo_depth: Shared,
+    tremolo_rate: Shared,
+    /// Random value from -1 to 1, chosen for each note.
+    detune: Shared,
+    /// Noise seed for analog drift.
+    drift_seed: u64,
+    /// Noise seed for each oscillator.
+    osc_seeds: Vec<u64>,
     /// Triggers envelope release when zero.
     gate: Shared,
     /// Used by the "Random" modulation source.
@@ -1379,6 +2098,40 @@ struct VoiceVars {
     /// Used to synchronize multiple DSP instances of the same logical LFO.
     lfo_phases: Vec<f32>,
     /// Initial frequency to glide from.
-    prev_freq: Option<f32>,
+    prev_freq: Shared,
     sample_rate: f32,
+    taps: Arc<VoiceTaps>,
+}
+
+/// Modulated values written by a voice's DSP, for display.
+struct VoiceTaps {
+    envs: Vec<Shared>,
+    levels: Vec<Shared>,
+    cutoffs: Vec<Shared>,
+    gate: Shared,
+    /// Set to zero when the voice is cut.
+    active: Shared,
+}
+
+/// Taps of the most recently played voice of a patch. Cloning produces an
+/// empty monitor.
+#[derive(Default)]
+struct VoiceMonitor(Mutex<Option<Arc<VoiceTaps>>>);
+
+impl Clone for VoiceMonitor {
+    fn clone(&self) -> Self {
+        Default::default()
+    }
+}
+
+/// Snapshot of the modulated values of a playing voice.
+pub struct VoiceLevels {
+    /// True until the note is released.
+    pub gate: bool,
+    /// Output of each envelope, from 0 to 1.
+    pub envs: Vec<f32>,
+    /// Modulated level of each generator.
+    pub levels: Vec<f32>,
+    /// Modulated cutoff of each filter, in Hz.
+    pub cutoffs: Vec<f32>,
 }
\ No newline at end of file
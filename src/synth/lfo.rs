@@ -42,7 +42,7 @@ impl LFO {
         let f = {
             let f_mod = settings.mod_net(vars, ModTarget::LFORate(index), path)
                 >> pow_shape(MAX_LFO_RATE/MIN_LFO_RATE);
-            let f = var(&self.freq.0) * f_mod
+            let f = self.freq.smoothed() * f_mod
                 >> shape_fn(|x| clamp(MIN_LFO_RATE, MAX_LFO_RATE, x));
             if self.audio_rate {
                 f * AR_RATE_MULTIPLIER
@@ -89,6 +89,9 @@ impl LFO {
             } else {
                 Box::new(zero())
             }),
+            // not offered for LFOs
+            Waveform::Wavetable(_) | Waveform::Additive(_) | Waveform::Pluck =>
+                Net::wrap(Box::new(zero())),
         }
     }
 }
@@ -145,7 +148,7 @@ fn tri_lfo(phase: f32) -> An<EnvelopeIn<f32, impl FnMut(f32, &Frame<f32, U1>) ->
 }
 
 /// Sine LFO in -1..1. Takes frequency as an input.
-fn sin_lfo(phase: f32) -> An<EnvelopeIn<f32, impl FnMut(f32, &Frame<f32, U1>) -> f32 + Clone, U1, f32>> {
+pub(super) fn sin_lfo(phase: f32) -> An<EnvelopeIn<f32, impl FnMut(f32, &Frame<f32, U1>) -> f32 + Clone, U1, f32>> {
     let phase = var(&shared(phase));
     let prev_time = var(&shared(0.0));
     lfo_in(move |t, i| {
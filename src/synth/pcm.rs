@@ -12,9 +12,14 @@ use serde::{Deserialize, Serialize};
 #[derive(Clone, Serialize, Deserialize)]
 pub struct PcmData {
     data: Vec<u8>, // for serialization
+    /// Wave used for playback, with any loop crossfade applied.
     #[serde(skip)]
     #[serde(default = "empty_wave")]
     pub wave: Arc<Wave>,
+    /// Wave as loaded, before crossfading.
+    #[serde(skip)]
+    #[serde(default = "empty_wave")]
+    source: Arc<Wave>,
     pub loop_point: Option<usize>,
     #[serde(skip)]
     pub path: Option<PathBuf>,
@@ -22,8 +27,18 @@ pub struct PcmData {
     pub midi_pitch: Option<f32>,
     #[serde(default)]
     pub filename: String,
+    /// If set, the sample is stored as a reference to this file instead of
+    /// being embedded. Relative paths are relative to the module directory.
+    #[serde(default)]
+    pub reference: Option<PathBuf>,
+    /// Length of the loop crossfade, in sample frames.
+    #[serde(default)]
+    pub crossfade: usize,
 }
 
+/// Subdirectory of the module directory that samples are collected into.
+pub const SAMPLE_DIR: &str = "samples";
+
 /// Default for serde.
 fn empty_wave() -> Arc<Wave> {
     Arc::new(Wave::new(1, 44100.0))
@@ -35,7 +50,7 @@ impl PcmData {
         ["aac", "aiff", "caf", "flac", "m4a", "mkv", "mp3", "mp4", "ogg", "wav", "webm"];
 
     /// Check whether a path has a loadable file extension.
-    fn can_load_path(path: &Path) -> bool {
+    pub fn can_load_path(path: &Path) -> bool {
         path.extension().and_then(|ext| ext.to_str()).is_some_and(|ext| {
             let ext = ext.to_ascii_lowercase();
             Self::FILE_EXTENSIONS.iter().any(|x| x.to_ascii_lowercase() == ext)
@@ -59,13 +74,17 @@ impl PcmData {
             .unwrap_or_default()
             .to_string();
 
+        let wave = Arc::new(wave);
         Ok(Self {
-            wave: Arc::new(wave),
+            wave: wave.clone(),
+            source: wave,
             data,
             loop_point,
             path: Some(path.as_ref().to_path_buf()),
             midi_pitch,
             filename,
+            reference: None,
+            crossfade: 0,
         })
     }
 
@@ -91,15 +110,140 @@ impl PcmData {
         }
     }
 
-    /// Initialize deserialized PcmData before use.
-    pub fn init(&mut self) -> Result<(), Box<dyn Error>> {
+    /// Initialize deserialized PcmData before use. `base_dir` is used to
+    /// resolve relative sample references.
+    pub fn init(&mut self, base_dir: Option<&Path>) -> Result<(), Box<dyn Error>> {
+        if let Some(reference) = &self.reference {
+            let path = match base_dir {
+                Some(dir) if reference.is_relative() => dir.join(reference),
+                _ => reference.clone(),
+            };
+            match fs::read(&path) {
+                Ok(data) => {
+                    self.data = data;
+                    self.path = Some(path);
+                }
+                // fall back to embedded data if present (e.g. in patch files)
+                Err(e) => if self.data.is_empty() {
+                    return Err(format!("could not read {}: {e}", path.display()).into())
+                }
+            }
+        }
+
         let mut wave = Wave::load_slice(self.data.clone())?;
         // the stored data is the raw file, so we have to normalize on init
         wave.normalize();
-        self.wave = Arc::new(wave);
+        self.source = Arc::new(wave);
+        self.update_wave();
         Ok(())
     }
 
+    /// Rebuild the playback wave from the source wave. Must be called when
+    /// the loop point or crossfade changes.
+    pub fn update_wave(&mut self) {
+        self.wave = match self.loop_point {
+            Some(pt) if self.crossfade > 0 =>
+                Arc::new(crossfade_loop(&self.source, pt, self.crossfade)),
+            _ => self.source.clone(),
+        };
+    }
+
+    /// Returns the index nearest to `index` where the first channel of the
+    /// sample crosses zero, or `index` if there are no crossings.
+    pub fn nearest_zero_crossing(&self, index: usize) -> usize {
+        let wave = &self.source;
+        let crosses = |i: usize| i > 0 && i < wave.len()
+            && (wave.at(0, i - 1) < 0.0) != (wave.at(0, i) < 0.0);
+
+        (0..wave.len())
+            .flat_map(|d| [index.checked_sub(d), index.checked_add(d)])
+            .flatten()
+            .find(|&i| crosses(i))
+            .unwrap_or(index)
+    }
+
+    /// Returns true if the sample is stored as an external reference.
+    pub fn is_linked(&self) -> bool {
+        self.reference.is_some()
+    }
+
+    /// Store the sample as a reference to its source file, if it has one.
+    pub fn link(&mut self) -> Result<(), Box<dyn Error>> {
+        let path = self.path.as_ref().ok_or("sample has no source file")?;
+        self.reference = Some(path.canonicalize()?);
+        Ok(())
+    }
+
+    /// Store the sample data in the module instead of as a reference.
+    pub fn embed(&mut self) {
+        self.reference = None;
+    }
+
+    /// Update the reference path for saving a module to `base_dir`. Paths
+    /// inside `base_dir` become relative, and others stay absolute.
+    fn relativize(&mut self, base_dir: &Path) {
+        if let Some(path) = &self.path {
+            let path = path.canonicalize().unwrap_or(path.clone());
+            let base_dir = base_dir.canonicalize().unwrap_or(base_dir.to_path_buf());
+            self.reference = Some(path.strip_prefix(&base_dir)
+                .map(|p| p.to_path_buf())
+                .unwrap_or(path));
+        }
+    }
+
+    /// Prepare a linked sample for saving into `base_dir`. Returns the sample
+    /// data, which is removed so that it won't be serialized. The data should
+    /// be returned with `restore_data` after saving.
+    pub fn strip_data(&mut self, base_dir: Option<&Path>) -> Option<Vec<u8>> {
+        if !self.is_linked() {
+            return None
+        }
+        if let Some(dir) = base_dir {
+            self.relativize(dir);
+        }
+        Some(std::mem::take(&mut self.data))
+    }
+
+    /// Restore data removed by `strip_data`.
+    pub fn restore_data(&mut self, data: Vec<u8>) {
+        self.data = data;
+    }
+
+    /// Copy a linked sample's source file into the sample subdirectory of
+    /// `base_dir`, and reference the copy. A file with the same name but
+    /// different contents is never overwritten; the copy is numbered
+    /// instead. Returns true if the sample's path changed.
+    pub fn collect(&mut self, base_dir: &Path) -> Result<bool, Box<dyn Error>> {
+        let Some(path) = self.path.clone().filter(|_| self.is_linked()) else {
+            return Ok(false)
+        };
+        let dir = base_dir.join(SAMPLE_DIR);
+        let file_name = path.file_name().ok_or("invalid sample path")?;
+        let contents = fs::read(&path)?;
+        let mut dest = dir.join(file_name);
+        let mut n = 1;
+
+        // reuse an identical file, if one is in the way
+        while dest.exists() {
+            if dest.canonicalize()? == path.canonicalize()? {
+                return Ok(false)
+            }
+            if fs::read(&dest)? == contents {
+                break
+            }
+            n += 1;
+            dest = dir.join(numbered_file_name(Path::new(file_name), n));
+        }
+
+        if !dest.exists() {
+            fs::create_dir_all(&dir)?;
+            fs::write(&dest, &contents)?;
+        }
+        self.path = Some(dest);
+        self.relativize(base_dir);
+        Ok(true)
+    }
+
     /// Adjust loop point to be smoother.
     pub fn fix_loop_point(&mut self) {
         // look for a sample that's after a similar sample to the last sample
@@ -111,23 +255,23 @@ impl PcmData {
         if let Some(pt) = &mut self.loop_point {
             // don't mess with the loop point if it's zero -- it might be a
             // single-cycle wave
-            if *pt == 0 || self.wave.len() < 3 {
+            if *pt == 0 || self.source.len() < 3 {
                 return
             }
 
             // don't move the point by more than 2 ms
-            let max_distance = (self.wave.sample_rate() as f32 * 0.002) as usize;
+            let max_distance = (self.source.sample_rate() as f32 * 0.002) as usize;
             let window_start = pt.saturating_sub(max_distance);
-            let window_end = (*pt + max_distance).min(self.wave.len() - 3);
+            let window_end = (*pt + max_distance).min(self.source.len() - 3);
 
-            let last_sample = self.wave.at(0, self.wave.len() - 1);
-            let second_last_sample = self.wave.at(0, self.wave.len() - 2);
+            let last_sample = self.source.at(0, self.source.len() - 1);
+            let second_last_sample = self.source.at(0, self.source.len() - 2);
             let delta = last_sample - second_last_sample;
             let mut matches = Vec::new();
 
             for i in window_start..window_end {
-                let s1 = self.wave.at(0, i);
-                let s2 = self.wave.at(0, i + 1);
+                let s1 = self.source.at(0, i);
+                let s2 = self.source.at(0, i + 1);
                 let test_delta = s2 - s1;
 
                 if test_delta.signum() == delta.signum() {
@@ -153,6 +297,26 @@ impl PcmData {
     }
 }
 
+/// Returns a copy of `wave` with the `len` frames before its end crossfaded
+/// into the frames before `loop_point`, so that looping is seamless. The
+/// crossfade is shortened if there isn't enough audio on either side.
+fn crossfade_loop(wave: &Wave, loop_point: usize, len: usize) -> Wave {
+    let n = len.min(loop_point).min(wave.len().saturating_sub(loop_point));
+    let start = wave.len() - n;
+    let mut out = wave.clone();
+
+    for ch in 0..wave.channels() {
+        for k in 0..n {
+            let t = (k + 1) as f32 / (n + 1) as f32;
+            let a = wave.at(ch, start + k);
+            let b = wave.at(ch, loop_point - n + k);
+            out.set(ch, start + k, a + (b - a) * t);
+        }
+    }
+
+    out
+}
+
 /// Relevant data from a "smpl" chunk.
 #[derive(Debug)]
 pub struct SmplData {
@@ -193,6 +357,15 @@ fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
     Some(u32::from_le_bytes(bytes.try_into().ok()?))
 }
 
+/// Returns `file_name` with `-n` appended to its stem.
+fn numbered_file_name(file_name: &Path, n: usize) -> PathBuf {
+    let stem = file_name.file_stem().unwrap_or_default().to_string_lossy();
+    match file_name.extension() {
+        Some(ext) => PathBuf::from(format!("{stem}-{n}.{}", ext.to_string_lossy())),
+        None => PathBuf::from(format!("{stem}-{n}")),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -207,4 +380,41 @@ mod tests {
         assert_eq!(PcmData::can_load_path(wav_upper), true);
         assert_eq!(PcmData::can_load_path(png), false);
     }
+
+    #[test]
+    fn test_numbered_file_name() {
+        assert_eq!(numbered_file_name(Path::new("kick.wav"), 2), Path::new("kick-2.wav"));
+        assert_eq!(numbered_file_name(Path::new("snare"), 3), Path::new("snare-3"));
+    }
+
+    #[test]
+    fn test_loop_editing() {
+        let samples = [0.5, 0.25, -0.25, -0.5, 0.0, 0.5, 1.0, 0.5];
+        let mut data = PcmData {
+            data: Vec::new(),
+            wave: empty_wave(),
+            source: Arc::new(Wave::from_samples(44100.0, &samples)),
+            loop_point: Some(4),
+            path: None,
+            midi_pitch: None,
+            filename: String::new(),
+            reference: None,
+            crossfade: 0,
+        };
+
+        assert_eq!(data.nearest_zero_crossing(1), 2);
+        assert_eq!(data.nearest_zero_crossing(5), 4);
+
+        data.update_wave();
+        assert_eq!(data.wave.at(0, 7), 0.5);
+
+        // the end of the sample fades into the frames before the loop point
+        data.crossfade = 2;
+        data.update_wave();
+        assert_eq!(data.wave.len(), samples.len());
+        assert_eq!(data.wave.at(0, 5), 0.5);
+        assert!((data.wave.at(0, 6) - (1.0 - 1.25 / 3.0)).abs() < 1e-6);
+        assert!((data.wave.at(0, 7) - (0.5 - 2.0 / 3.0)).abs() < 1e-6);
+        assert_eq!(data.source.at(0, 7), 0.5);
+    }
 }
\ No newline at end of file
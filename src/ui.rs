@@ -3,9 +3,9 @@
 //! Not polished for general reuse. Macroquad also has its own built-in UI
 //! library, but the demos don't give me much faith in it.
 
-use std::{collections::HashMap, fmt::Display, mem, ops::RangeInclusive};
+use std::{collections::{BTreeSet, HashMap}, fmt::Display, mem, ops::{Range, RangeInclusive}};
 
-use fundsp::shared::Shared;
+use fundsp::{shared::Shared, wave::Wave};
 use info::{ControlInfo, Info};
 use macroquad::prelude::*;
 use rfd::FileDialog;
@@ -23,20 +23,45 @@ pub mod developer;
 pub mod theme;
 pub mod text;
 mod textedit;
+mod units;
 pub mod info;
+pub mod palette;
 
 const LINE_THICKNESS: f32 = 1.0;
 const SLIDER_WIDTH: f32 = 100.0;
+/// Maximum time between clicks of a double-click, in seconds.
+const DOUBLE_CLICK_TIME: f64 = 0.4;
+const METER_WIDTH: f32 = 50.0;
+const BAR_WIDTH: f32 = 8.0;
+const BAR_EDITOR_LINES: f32 = 4.0;
+const CURVE_GRAPH_POINTS: usize = 16;
+const WAVEFORM_WIDTH: f32 = 400.0;
+const WAVEFORM_LINES: f32 = 4.0;
 
 const PANEL_Z_OFFSET: i8 = 10;
 const COMBO_Z_OFFSET: i8 = 20;
 const TOOLTIP_Z_OFFSET: i8 = 30;
 
+const PALETTE_ID: &str = "palette";
+const PALETTE_CHARS: usize = 50;
+const PALETTE_ROWS: usize = 12;
+
 /// Seconds before info popup.
 const INFO_DELAY: f32 = 0.1;
 
+/// Maximum number of entries kept in the message log.
+const MESSAGE_LOG_LEN: usize = 100;
+
 pub const MAX_PATCH_NAME_CHARS: usize = 20;
 
+/// Wrap a block of UI code in a labeled column.
+pub fn labeled_group(ui: &mut Ui, label: &str, info: Info, f: impl FnOnce(&mut Ui)) {
+    ui.start_group();
+    ui.label(label, info);
+    f(ui);
+    ui.end_group();
+}
+
 /// Return a new file dialog. Use this instead of using `rfd` directly.
 pub fn new_file_dialog(player: &mut Player) -> FileDialog {
     // macroquad currently doesn't handle focus lost events, which means that
@@ -92,6 +117,8 @@ struct ComboBoxState {
     options: Vec<String>,
     button_rect: Rect,
     list_rect: Rect,
+    /// Option highlighted by keyboard navigation.
+    key_index: Option<usize>,
 }
 
 enum Graphic {
@@ -143,6 +170,8 @@ enum Focus {
     Text(TextEditState),
     Hotkey(usize),
     Note(String),
+    /// Keyboard focus on a button, checkbox, or combo box.
+    Control(String),
 }
 
 impl Focus {
@@ -153,7 +182,7 @@ impl Focus {
     fn id(&self) -> Option<&str> {
         match self {
             Self::ComboBox(state) => Some(&state.id),
-            Self::Slider(s) | Self::Note(s) => Some(s),
+            Self::Slider(s) | Self::Note(s) | Self::Control(s) => Some(s),
             Self::Text(state) => Some(&state.id),
             _ => None,
         }
@@ -206,6 +235,31 @@ pub struct Ui {
     lost_focus: Focus,
     /// (Position, ID) pairs for tab key navigation.
     tab_nav_list: Vec<(Vec2, String)>,
+    /// Status messages for screen readers, since the last call to
+    /// `take_announcements`.
+    announcements: Vec<String>,
+    /// Last announced index of each tab menu.
+    announced_tabs: HashMap<String, usize>,
+    /// Reported errors and notifications, oldest first.
+    message_log: Vec<LogEntry>,
+    /// Whether reported errors open an alert dialog instead of a
+    /// notification.
+    error_alerts: bool,
+    /// Query and selected option index of the open command palette.
+    palette: Option<(String, usize)>,
+    /// Whether the modulate parameter command was used this frame.
+    modulate_requested: bool,
+    /// ID of the slider that the modulate parameter command applied to.
+    modulated_slider: Option<String>,
+    /// ID, time, and previous value of the last slider click, for detecting
+    /// double-clicks.
+    slider_click: Option<(String, f64, f32)>,
+    /// Whether a control changed a value since the last call to
+    /// `take_edited`, not counting shared parameter sliders.
+    edited: bool,
+    /// Whether a shared parameter slider changed a value since the last call
+    /// to `take_edited`.
+    shared_edited: bool,
 }
 
 impl Ui {
@@ -250,6 +304,16 @@ impl Ui {
             pending_focus: None,
             lost_focus: Focus::None,
             tab_nav_list: Vec::new(),
+            announcements: Vec::new(),
+            announced_tabs: HashMap::new(),
+            message_log: Vec::new(),
+            error_alerts: true,
+            palette: None,
+            modulate_requested: false,
+            modulated_slider: None,
+            slider_click: None,
+            edited: false,
+            shared_edited: false,
         }
     }
 
@@ -275,6 +339,7 @@ impl Ui {
         self.cursor_x = self.style.margin;
         self.cursor_y = self.style.margin;
         self.cursor_z = 0;
+        self.error_alerts = conf.error_alerts;
 
         if !is_mouse_button_down(MouseButton::Left)
             && !is_mouse_button_released(MouseButton::Left) {
@@ -377,12 +442,21 @@ impl Ui {
         // drain input queues
         while get_char_pressed().is_some() {}
         self.note_queue.clear();
+        self.modulate_requested = false;
+        self.modulated_slider = None;
 
         self.bottom_right_corner = Vec2 {
             x: self.bounds.x + self.bounds.w,
             y: self.bounds.y + self.bounds.h,
         };
 
+        // drop keyboard focus from controls that are no longer drawn
+        if let Focus::Control(id) = &self.focus {
+            if !self.tab_nav_list.iter().any(|(_, s)| s == id) {
+                self.focus = Focus::None;
+            }
+        }
+
         if tab_nav && is_key_pressed(KeyCode::Tab) && !is_alt_down() && !is_ctrl_down() {
             let offset = if is_shift_down() { -1 } else { 1 };
             self.tab_focus(offset);
@@ -405,6 +479,50 @@ impl Ui {
         }
     }
 
+    /// Register a control for tab navigation. Returns whether the control
+    /// has keyboard focus, and whether it was activated with Enter or Space
+    /// this frame. `describe` returns the text announced on focus.
+    fn nav_control(&mut self, id: &str, describe: impl FnOnce() -> String) -> (bool, bool) {
+        self.tab_nav_list.push((self.cursor_vec(), id.to_owned()));
+
+        if self.pending_focus.as_deref() == Some(id) {
+            self.set_focus(Focus::Control(id.to_owned()));
+            self.announce(describe());
+        }
+
+        let focused = matches!(&self.focus, Focus::Control(s) if s == id);
+        if focused && is_key_pressed(KeyCode::Escape) {
+            self.focus = Focus::None;
+            return (false, false)
+        }
+        let activated = focused
+            && (is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::Space));
+        (focused, activated)
+    }
+
+    /// Returns true if the key is used by the control with keyboard focus,
+    /// and shouldn't trigger a hotkey.
+    pub fn consumes_key(&self, hotkey: &Hotkey) -> bool {
+        hotkey.mods == Modifiers::None && match &self.focus {
+            Focus::Control(_) =>
+                matches!(hotkey.key, KeyCode::Enter | KeyCode::Space | KeyCode::Escape),
+            Focus::ComboBox(_) =>
+                matches!(hotkey.key, KeyCode::Enter | KeyCode::Up | KeyCode::Down
+                    | KeyCode::Escape),
+            _ => false,
+        }
+    }
+
+    /// Queue a status message for screen readers.
+    pub fn announce(&mut self, message: impl Into<String>) {
+        self.announcements.push(message.into());
+    }
+
+    /// Returns and clears queued status messages.
+    pub fn take_announcements(&mut self) -> Vec<String> {
+        mem::take(&mut self.announcements)
+    }
+
     /// Offset the placement cursor by `scale` margins of space.
     fn space(&mut self, scale: f32) {
         match self.layout {
@@ -674,6 +792,163 @@ impl Ui {
         self.end_widget("label", info, ControlInfo::None);
     }
 
+    /// Draws a horizontal level meter. `level` is the filled fraction of the
+    /// meter; levels above 1 are drawn in the warning color.
+    pub fn meter(&mut self, level: f32, info: Info) {
+        self.start_widget();
+        let rect = Rect {
+            x: self.cursor_x + self.style.margin,
+            y: self.cursor_y + self.style.margin,
+            w: METER_WIDTH,
+            h: self.style.line_height(),
+        };
+        self.push_rect(rect, self.style.theme.content_bg(),
+            Some(self.style.theme.border_unfocused()));
+        let fill = if level > 1.0 {
+            self.style.theme.accent2_fg()
+        } else {
+            self.style.theme.fg()
+        };
+        self.push_rect(Rect {
+            w: rect.w * level.clamp(0.0, 1.0),
+            ..rect
+        }, fill, None);
+        self.end_widget("meter", info, ControlInfo::None);
+    }
+
+    /// Draws a graph of bars with values from 0 to 1, which can be set by
+    /// clicking and dragging. Returns true if a value changed this frame.
+    pub fn bar_editor(&mut self, id: &str, values: &mut [f32], info: Info) -> bool {
+        self.start_widget();
+        let rect = Rect {
+            x: self.cursor_x + self.style.margin,
+            y: self.cursor_y + self.style.margin,
+            w: BAR_WIDTH * values.len() as f32,
+            h: self.style.line_height() * BAR_EDITOR_LINES,
+        };
+
+        // share slider focus so that dragging counts as grabbing
+        if self.mouse_hits(rect, id) && is_mouse_button_pressed(MouseButton::Left) {
+            self.set_focus(Focus::Slider(id.to_string()));
+            self.mouse_consumed = Some(id.to_string());
+        }
+        let grabbed = matches!(&self.focus, Focus::Slider(s) if s == id);
+
+        let mut changed = false;
+        if grabbed {
+            let (x, y) = mouse_position();
+            let i = ((x - rect.x) / BAR_WIDTH).floor();
+            if i >= 0.0 && (i as usize) < values.len() {
+                let value = (1.0 - (y - rect.y) / rect.h).clamp(0.0, 1.0);
+                changed = values[i as usize] != value;
+                values[i as usize] = value;
+            }
+        }
+
+        let stroke = if grabbed {
+            self.style.theme.border_focused()
+        } else {
+            self.style.theme.border_unfocused()
+        };
+        self.push_rect(rect, self.style.theme.content_bg(), Some(stroke));
+        for (i, value) in values.iter().enumerate() {
+            let h = (value * rect.h).round();
+            self.push_rect(Rect {
+                x: rect.x + i as f32 * BAR_WIDTH,
+                y: rect.y + rect.h - h,
+                w: BAR_WIDTH - LINE_THICKNESS,
+                h,
+            }, self.style.theme.fg(), None);
+        }
+
+        self.end_widget(id, info, ControlInfo::None);
+        self.edited |= changed;
+        changed
+    }
+
+    /// Draws the first channel of `wave` over a range of frames, with
+    /// vertical lines at `markers`. Returns the frame that was clicked, if any.
+    pub fn waveform(&mut self, id: &str, wave: &Wave, range: Range<usize>,
+        markers: &[usize], info: Info
+    ) -> Option<usize> {
+        self.start_widget();
+        let rect = Rect {
+            x: self.cursor_x + self.style.margin,
+            y: self.cursor_y + self.style.margin,
+            w: WAVEFORM_WIDTH,
+            h: self.style.line_height() * WAVEFORM_LINES,
+        };
+        self.push_rect(rect, self.style.theme.content_bg(),
+            Some(self.style.theme.border_unfocused()));
+
+        let frames_per_px = range.len() as f32 / rect.w;
+        let x_of = |frame: usize| rect.x + (frame as f32 - range.start as f32) / frames_per_px;
+        let y_of = |y: f32| rect.y + rect.h * 0.5 * (1.0 - y.clamp(-1.0, 1.0));
+
+        // draw the range of values in each pixel column
+        for px in 0..rect.w as usize {
+            let start = range.start + (px as f32 * frames_per_px) as usize;
+            let end = (range.start + ((px + 1) as f32 * frames_per_px) as usize)
+                .max(start + 1).min(range.end);
+            let (lo, hi) = (start..end).map(|i| wave.at(0, i))
+                .fold((f32::MAX, f32::MIN), |(lo, hi), y| (lo.min(y), hi.max(y)));
+            if lo <= hi {
+                let x = rect.x + px as f32 + 0.5;
+                self.push_line(x, y_of(hi), x, y_of(lo) + LINE_THICKNESS,
+                    self.style.theme.fg());
+            }
+        }
+
+        for &frame in markers {
+            if range.contains(&frame) {
+                let x = x_of(frame).round() + 0.5;
+                self.push_line(x, rect.y, x, rect.y + rect.h, self.style.theme.accent2_fg());
+            }
+        }
+
+        let mut clicked = None;
+        if self.mouse_hits(rect, id) && is_mouse_button_pressed(MouseButton::Left) {
+            self.mouse_consumed = Some(id.to_string());
+            let (x, _) = mouse_position();
+            let frame = range.start as f32 + (x - rect.x) * frames_per_px;
+            clicked = Some((frame.max(0.0) as usize).min(range.end.saturating_sub(1)));
+        }
+
+        self.end_widget(id, info, ControlInfo::None);
+        clicked
+    }
+
+    /// Draws a small graph of `f` over `x_range`, with output in -1..1.
+    pub fn curve_graph(&mut self, f: impl Fn(f32) -> f32, x_range: RangeInclusive<f32>,
+        info: Info
+    ) {
+        self.start_widget();
+        let h = self.style.line_height();
+        let rect = Rect {
+            x: self.cursor_x + self.style.margin,
+            y: self.cursor_y + self.style.margin,
+            w: h * 2.0,
+            h,
+        };
+        self.push_rect(rect, self.style.theme.content_bg(),
+            Some(self.style.theme.border_unfocused()));
+        let y_of = |y: f32| rect.y + rect.h * 0.5 * (1.0 - y.clamp(-1.0, 1.0));
+        self.push_line(rect.x, y_of(0.0), rect.x + rect.w, y_of(0.0),
+            self.style.theme.border_unfocused());
+
+        let mut prev: Option<(f32, f32)> = None;
+        for i in 0..=CURVE_GRAPH_POINTS {
+            let t = i as f32 / CURVE_GRAPH_POINTS as f32;
+            let point = (rect.x + rect.w * t, y_of(f(interpolate(t, &x_range))));
+            if let Some((x, y)) = prev {
+                self.push_line(x, y, point.0, point.1, self.style.theme.fg());
+            }
+            prev = Some(point);
+        }
+
+        self.end_widget("curve_graph", info, ControlInfo::None);
+    }
+
     /// Section header. `label` should be uppercase.
     pub fn header(&mut self, label: &str, info: Info) {
         let rect = Rect {
@@ -690,7 +965,7 @@ impl Ui {
     }
 
     /// Generic interactable rectangle widget with text inside.
-    fn text_rect(&mut self, label: &str, enabled: bool, x: f32, y: f32,
+    fn text_rect(&mut self, label: &str, enabled: bool, focused: bool, x: f32, y: f32,
         bg: &Color, bg_hover: &Color, bg_click: &Color,
     ) -> (Rect, MouseEvent) {
         let id = "text_rect_".to_string() + label;
@@ -710,6 +985,8 @@ impl Ui {
             } else {
                 bg_hover
             }, self.style.theme.border_focused())
+        } else if focused {
+            (bg, self.style.theme.border_focused())
         } else if enabled {
             (bg, self.style.theme.border_unfocused())
         } else {
@@ -736,16 +1013,23 @@ impl Ui {
 
     /// Draws a button and returns true if it was clicked this frame.
     pub fn button(&mut self, label: &str, enabled: bool, info: Info) -> bool {
+        let (focused, activated) = if enabled {
+            self.nav_control(&format!("button_{label}"), || format!("{label}, button"))
+        } else {
+            (false, false)
+        };
         self.start_widget();
 
-        let (_, event) = self.text_rect(label, enabled,
+        let (_, event) = self.text_rect(label, enabled, focused,
             self.cursor_x + self.style.margin, self.cursor_y + self.style.margin,
             &self.style.theme.control_bg(),
             &self.style.theme.control_bg_hover(),
             &self.style.theme.control_bg_click());
 
         self.end_widget("button", info, ControlInfo::None);
-        event == MouseEvent::Released
+        let clicked = event == MouseEvent::Released || activated;
+        self.edited |= clicked;
+        clicked
     }
 
     /// Draws a checkbox and returns true if it was changed this frame.
@@ -755,8 +1039,15 @@ impl Ui {
 
         // TODO: since label activates checkbox, highlight on label hover too
         let button_text = if *value { "X" } else { " " };
+        let (focused, activated) = if enabled {
+            let state = if *value { "checked" } else { "not checked" };
+            self.nav_control(&format!("checkbox_{label}"),
+                || format!("{label}, checkbox, {state}"))
+        } else {
+            (false, false)
+        };
         self.start_widget();
-        let (rect, event) = self.text_rect(button_text, enabled,
+        let (rect, event) = self.text_rect(button_text, enabled, focused,
             self.cursor_x + self.style.margin, self.cursor_y + self.style.margin,
             &self.style.theme.content_bg(),
             &self.style.theme.content_bg(),
@@ -768,12 +1059,16 @@ impl Ui {
             } else {
                 self.style.theme.border_disabled()
             });
-        let clicked = event == MouseEvent::Released
+        let clicked = event == MouseEvent::Released || activated
             || self.mouse_hits(rect, ID) && is_mouse_button_released(MouseButton::Left);
         if clicked {
             *value = !*value;
+            if activated {
+                self.announce(if *value { "Checked" } else { "Not checked" });
+            }
         }
         self.end_widget("checkbox", info, ControlInfo::None);
+        self.edited |= clicked;
         clicked
     }
 
@@ -781,11 +1076,15 @@ impl Ui {
     pub fn combo_box(&mut self, id: &str, label: &str, button_text: &str,
         info: Info, get_options: impl Fn() -> Vec<String>
     ) -> Option<usize> {
+        let (focused, activated) = self.nav_control(id, || {
+            let parts = [label, "combo box", button_text];
+            parts.into_iter().filter(|s| !s.is_empty()).collect::<Vec<_>>().join(", ")
+        });
         self.start_widget();
         let margin = self.style.margin;
 
         // draw button and label
-        let (button_rect, event) = self.text_rect(button_text, true,
+        let (button_rect, event) = self.text_rect(button_text, true, focused,
             self.cursor_x + margin, self.cursor_y + margin,
             &self.style.theme.control_bg(),
             &self.style.theme.control_bg_hover(),
@@ -800,14 +1099,18 @@ impl Ui {
             Focus::ComboBox(state) => state.id == id,
             _ => false,
         };
-        if event == MouseEvent::Pressed && !open {
+        if (event == MouseEvent::Pressed || activated) && !open {
             let options = get_options();
             let list_rect = combo_box_list_rect(&self.style, button_rect, &options);
+            let key_index = activated
+                .then(|| options.iter().position(|x| x == button_text))
+                .flatten();
             self.set_focus(Focus::ComboBox(ComboBoxState {
                 id: id.to_owned(),
                 options,
                 button_rect,
                 list_rect,
+                key_index,
             }));
         }
 
@@ -831,11 +1134,13 @@ impl Ui {
         }
 
         self.end_widget(id, info, ControlInfo::None);
+        self.edited |= return_val.is_some();
         return_val
     }
 
     /// Draw the list of the active combo box.
     fn combo_box_list(&mut self, already_open: bool, info: Info) -> Option<usize> {
+        let key_selection = self.combo_box_keys();
         self.cursor_z += COMBO_Z_OFFSET;
         if let Focus::ComboBox(state) = &self.focus {
             let mut gfx = vec![
@@ -854,16 +1159,16 @@ impl Ui {
                 h: state.button_rect.h,
             };
             let mouse_pos = mouse_position_vec2();
-            let mut return_val = None;
+            let mut return_val = key_selection;
             let lmb = is_mouse_button_released(MouseButton::Left);
             for (i, option) in state.options.iter().enumerate() {
-                if hit_rect.contains(mouse_pos) {
+                if hit_rect.contains(mouse_pos) || state.key_index == Some(i) {
                     gfx.push(Graphic::Rect(
                         hit_rect, self.style.theme.panel_bg_hover(), None));
-                    if lmb {
-                        return_val = Some(i);
-                        self.mouse_consumed = Some(state.id.clone());
-                    }
+                }
+                if hit_rect.contains(mouse_pos) && lmb {
+                    return_val = Some(i);
+                    self.mouse_consumed = Some(state.id.clone());
                 }
                 gfx.push(Graphic::Text(hit_rect.x - 1.0, hit_rect.y - 1.0,
                     option.to_owned(), self.style.theme.fg()));
@@ -871,7 +1176,10 @@ impl Ui {
             }
 
             // check to close. other close conditions are in combo_box()
-            if return_val.is_some() || (already_open
+            if key_selection.is_some() {
+                // keep keyboard focus for further navigation
+                self.focus = Focus::Control(state.id.clone());
+            } else if return_val.is_some() || (already_open
                 && is_mouse_button_pressed(MouseButton::Left)
                 && !state.list_rect.contains(mouse_position_vec2())) {
                 self.focus = Focus::None;
@@ -886,6 +1194,34 @@ impl Ui {
         }
     }
 
+    /// Handle keyboard navigation of the active combo box list. Returns the
+    /// index of the option selected with Enter, if any.
+    fn combo_box_keys(&mut self) -> Option<usize> {
+        let Focus::ComboBox(state) = &mut self.focus else {
+            return None
+        };
+        let n = state.options.len();
+        if n == 0 {
+            return None
+        }
+
+        let prev_index = state.key_index;
+        if is_key_pressed(KeyCode::Down) {
+            state.key_index = Some(state.key_index.map_or(0, |i| (i + 1) % n));
+        }
+        if is_key_pressed(KeyCode::Up) {
+            state.key_index = Some(state.key_index.map_or(n - 1, |i| (i + n - 1) % n));
+        }
+        let selection = state.key_index.filter(|_| is_key_pressed(KeyCode::Enter));
+
+        if state.key_index != prev_index {
+            if let Some(option) = state.key_index.map(|i| state.options[i].clone()) {
+                self.announce(option);
+            }
+        }
+        selection
+    }
+
     /// Draws a tab menu. Returns the index of the selected tab.
     pub fn tab_menu(&mut self, id: &str, labels: &[&str], version: &str) -> usize {
         if !self.tabs.contains_key(id) {
@@ -949,6 +1285,12 @@ impl Ui {
             }
         }
         self.push_graphics(gfx);
+        if self.announced_tabs.get(id) != Some(&selected_index) {
+            self.announced_tabs.insert(id.to_owned(), selected_index);
+            if let Some(label) = labels.get(selected_index) {
+                self.announce(format!("{label} tab"));
+            }
+        }
         {
             let w = self.style.atlas.text_width(version) + self.style.margin * 2.0;
             let x = self.bounds.x + self.bounds.w - w;
@@ -968,6 +1310,10 @@ impl Ui {
         self.instrument_edit_index = None;
     }
 
+    pub fn set_tab(&mut self, id: &str, index: usize) {
+        self.tabs.insert(id.to_owned(), index);
+    }
+
     pub fn next_tab(&mut self, id: &str, n: usize) {
         if let Some(i) = self.tabs.get_mut(id) {
             *i = (*i + 1) % n;
@@ -995,10 +1341,21 @@ impl Ui {
         range: RangeInclusive<f32>, power: i32, enabled: bool, info: Info,
         display: impl Fn(f32) -> String, convert: impl FnOnce(f32) -> f32,
     ) -> bool {
+        // tab navigation goes straight to text entry mode
+        if enabled {
+            self.tab_nav_list.push((self.cursor_vec(), id.to_owned()));
+            if self.pending_focus.as_deref() == Some(id) {
+                let text = units::entry_text(&display(*val));
+                self.announce(format!("{label}, slider, {text}"));
+                self.set_focus(Focus::Text(TextEditState::new(id.to_owned(), text)));
+            }
+        }
+
         // are we in text entry mode?
         if let Focus::Text(state) = &self.focus {
             if state.id == id {
-                return self.slider_text_entry(id, label, val, range, convert);
+                return self.slider_text_entry(id, label, val, range, &display(*val),
+                    convert);
             }
         }
 
@@ -1019,18 +1376,32 @@ impl Ui {
         };
         let mouse_pos = mouse_position_vec2();
         let hit = enabled && self.mouse_hits(hit_rect, id);
+        let mut changed = false;
         if hit {
             if is_mouse_button_pressed(MouseButton::Left) {
-                self.set_focus(Focus::Slider(id.to_string()));
+                let now = get_time();
+                match self.slider_click.take() {
+                    Some((s, t, old)) if s == id && now - t < DOUBLE_CLICK_TIME => {
+                        // the first click moved the handle, so undo that
+                        changed = *val != old;
+                        *val = old;
+                        let text = units::entry_text(&display(*val));
+                        self.set_focus(Focus::Text(TextEditState::new(id.to_owned(), text)));
+                    }
+                    _ => {
+                        self.slider_click = Some((id.to_owned(), now, *val));
+                        self.set_focus(Focus::Slider(id.to_string()));
+                    }
+                }
                 self.mouse_consumed = Some(id.to_string());
             }
             if is_mouse_button_pressed(MouseButton::Right) {
-                let text = display(*val).trim_start_matches('x')
-                    .split([' ', ':']).next()
-                    .expect("at least 1 token should be present")
-                    .to_owned();
+                let text = units::entry_text(&display(*val));
                 self.set_focus(Focus::Text(TextEditState::new(id.to_owned(), text)));
             }
+            if self.modulate_requested {
+                self.modulated_slider = Some(id.to_owned());
+            }
         }
         let grabbed = if let Focus::Slider(s) = &self.focus {
             s == id
@@ -1039,21 +1410,20 @@ impl Ui {
         };
 
         // update position, get handle color
-        let (fill, stroke, mut changed) = if grabbed {
+        let (fill, stroke) = if grabbed {
             let f = ((mouse_pos.x - groove_x) / groove_w).max(0.0).powi(power);
             let new_val = interpolate(f, &range)
                 .max(*range.start())
                 .min(*range.end());
-            let changed = new_val != *val;
+            changed |= new_val != *val;
             *val = new_val;
-            (self.style.theme.control_bg_click(), self.style.theme.border_focused(),
-                changed)
+            (self.style.theme.control_bg_click(), self.style.theme.border_focused())
         } else if hit {
-            (self.style.theme.control_bg_hover(), self.style.theme.border_focused(), false)
+            (self.style.theme.control_bg_hover(), self.style.theme.border_focused())
         } else if enabled {
-            (self.style.theme.control_bg(), self.style.theme.border_unfocused(), false)
+            (self.style.theme.control_bg(), self.style.theme.border_unfocused())
         } else {
-            (self.style.theme.panel_bg(), self.style.theme.border_disabled(), false)
+            (self.style.theme.panel_bg(), self.style.theme.border_disabled())
         };
 
         // draw groove & handle
@@ -1086,7 +1456,7 @@ impl Ui {
         // TODO: duplication with slider_text_entry
         match &self.lost_focus {
             Focus::Text(state) if state.id == id => {
-                match state.text.parse::<f32>() {
+                match units::parse_value(&state.text, &display(*val)) {
                     Ok(f) => {
                         *val = convert(f).max(*range.start()).min(*range.end());
                         changed = true;
@@ -1099,12 +1469,13 @@ impl Ui {
         }
 
         self.end_widget(id, info, ControlInfo::Slider);
+        self.edited |= changed;
         changed
     }
 
     /// Handle a slider's text entry state.
     fn slider_text_entry(&mut self, id: &str, label: &str, val: &mut f32,
-        range: RangeInclusive<f32>, convert: impl FnOnce(f32) -> f32,
+        range: RangeInclusive<f32>, display: &str, convert: impl FnOnce(f32) -> f32,
     ) -> bool {
         let text = if let Focus::Text(state) = &mut self.focus {
             state.text.clone()
@@ -1115,7 +1486,7 @@ impl Ui {
         let mut changed = false;
         let w = SLIDER_WIDTH + self.style.margin * 2.0;
         if self.text_box(id, label, w, &text, 10, Info::None) {
-            match text.parse::<f32>() {
+            match units::parse_value(&text, display) {
                 Ok(f) => {
                     *val = convert(f).max(*range.start()).min(*range.end());
                     changed = true;
@@ -1160,15 +1531,23 @@ impl Ui {
 
     /// Widget for editing a value as text.
     pub fn edit_box(&mut self, label: &str, chars_wide: usize,
+        text: String, info: Info
+    ) -> Option<String> {
+        self.id_edit_box(label, label, chars_wide, text, info)
+    }
+
+    /// Edit box with an ID separate from its label, for when several boxes
+    /// share a label.
+    pub fn id_edit_box(&mut self, id: &str, label: &str, chars_wide: usize,
         mut text: String, info: Info
     ) -> Option<String> {
-        self.tab_nav_list.push((self.cursor_vec(), label.to_string()));
+        self.tab_nav_list.push((self.cursor_vec(), id.to_string()));
 
         let w = chars_wide as f32 * self.style.atlas.char_width()
             + self.style.margin * 2.0;
 
         let mut result = match &self.lost_focus {
-            Focus::Text(state) if state.id == label => {
+            Focus::Text(state) if state.id == id => {
                 let s = state.text.clone();
                 text = s.clone();
                 self.lost_focus = Focus::None;
@@ -1177,7 +1556,7 @@ impl Ui {
             _ => None,
         };
 
-        if self.text_box(label, label, w, &text, chars_wide, info) {
+        if self.text_box(id, label, w, &text, chars_wide, info) {
             if let Focus::Text(state) = &self.focus {
                 let s = state.text.clone();
                 self.focus = Focus::None;
@@ -1187,6 +1566,7 @@ impl Ui {
             }
         }
 
+        self.edited |= result.is_some();
         result
     }
 
@@ -1198,6 +1578,7 @@ impl Ui {
             Some(s) if s == id => {
                 let f = Focus::Text(TextEditState::new(id.to_owned(), text.to_owned()));
                 self.set_focus(f);
+                self.announce(format!("{label}, edit box, {text}"));
             }
             _ => (),
         }
@@ -1254,8 +1635,10 @@ impl Ui {
     }
 
     /// List box with editable values. Returns a string when an edit is submitted.
+    /// `selection` holds items selected with ctrl- and shift-clicks, in
+    /// addition to the current item.
     pub fn instrument_list(&mut self, options: &[String], index: &mut usize,
-        min_chars: usize,
+        selection: &mut BTreeSet<usize>, min_chars: usize,
     ) -> Option<String> {
         const TEXT_ID: &str = "instrument_list";
         let pointer = String::from(char::from_u32(0xbb).unwrap());
@@ -1299,8 +1682,21 @@ impl Ui {
             } else if self.mouse_hits(hit_rect, "instrument_list") {
                 self.push_rect(hit_rect, self.style.theme.content_bg_hover(), None);
                 if lmb {
-                    *index = i;
+                    // ctrl toggles and shift extends the selection, keeping
+                    // the current item
+                    if is_ctrl_down() {
+                        if !selection.remove(&i) {
+                            selection.insert(i);
+                        }
+                    } else if is_shift_down() {
+                        selection.extend(i.min(*index)..=i.max(*index));
+                    } else {
+                        selection.clear();
+                        *index = i;
+                    }
                 }
+            } else if selection.contains(&i) {
+                self.push_rect(hit_rect, self.style.theme.accent1_bg(), None);
             }
 
             // check for unfocus
@@ -1463,10 +1859,13 @@ impl Ui {
         display: impl Fn(f32) -> String, convert: impl FnOnce(f32) -> f32,
     ) {
         let mut val = param.value();
+        let edited = self.edited;
         if self.formatted_slider(id, label, &mut val, range, power, enabled, info,
             display, convert) {
             param.set(val);
+            self.shared_edited = true;
         }
+        self.edited = edited;
     }
 
     fn open_dialog(&mut self, dialog: Dialog) {
@@ -1474,9 +1873,17 @@ impl Ui {
         self.dialog_first_frame = true;
     }
 
-    /// Report an error in an alert dialog.
+    /// Report an error in an alert dialog, or in a notification if alerts
+    /// are disabled.
     pub fn report(&mut self, e: impl Display) {
-        self.open_dialog(Dialog::Alert(e.to_string()));
+        let message = e.to_string();
+        self.log(Severity::Error, message.clone());
+        if self.error_alerts {
+            self.announce(message.clone());
+            self.open_dialog(Dialog::Alert(message));
+        } else {
+            self.notify_unlogged(message);
+        }
     }
 
     /// Prompt for confirmation before performing an action.
@@ -1484,14 +1891,43 @@ impl Ui {
         self.open_dialog(Dialog::OkCancel(prompt.to_owned(), action));
     }
 
-    /// Temporarily use the info box to display a message.
+    /// Temporarily use the info box to display a message, and add it to
+    /// the message log.
     pub fn notify(&mut self, message: String) {
+        self.log(Severity::Info, message.clone());
+        self.notify_unlogged(message);
+    }
+
+    /// Display a transient status message, such as progress, without
+    /// logging or announcing it.
+    pub fn status(&mut self, message: String) {
         self.notification = Some(Notification {
             time_remaining: 1.0 + message.chars().count() as f32 * 0.1,
             message,
         });
     }
 
+    fn notify_unlogged(&mut self, message: String) {
+        self.announce(message.clone());
+        self.status(message);
+    }
+
+    fn log(&mut self, severity: Severity, message: String) {
+        if self.message_log.len() >= MESSAGE_LOG_LEN {
+            self.message_log.remove(0);
+        }
+        self.message_log.push(LogEntry { severity, time: get_time(), message });
+    }
+
+    /// Returns logged messages, oldest first.
+    pub fn message_log(&self) -> &[LogEntry] {
+        &self.message_log
+    }
+
+    pub fn clear_message_log(&mut self) {
+        self.message_log.clear();
+    }
+
     pub fn accepting_keyboard_input(&self) -> bool {
         matches!(self.focus, Focus::Text(_) | Focus::Hotkey(_))
             || matches!(self.dialog, Some(Dialog::Alert(_)))
@@ -1503,7 +1939,7 @@ impl Ui {
 
     pub fn tooltip(&mut self, text: &str, x: f32, y: f32) {
         self.cursor_z += TOOLTIP_Z_OFFSET;
-        self.text_rect(text, true, x, y,
+        self.text_rect(text, true, false, x, y,
             &self.style.theme.panel_bg(),
             &self.style.theme.panel_bg(),
             &self.style.theme.panel_bg());
@@ -1557,6 +1993,7 @@ impl Ui {
         self.push_text(rect.x, rect.y, label, self.style.theme.fg());
         self.end_widget(id, info, ControlInfo::Note);
 
+        self.edited |= key.is_some();
         key
     }
 
@@ -1669,6 +2106,134 @@ impl Ui {
         }
     }
 
+    /// Apply the modulate parameter command to the slider under the mouse,
+    /// if any, this frame.
+    pub fn request_modulation(&mut self) {
+        self.modulate_requested = true;
+    }
+
+    /// Returns the ID of the slider that the modulate parameter command
+    /// applied to this frame, if any.
+    pub fn take_modulated_slider(&mut self) -> Option<String> {
+        self.modulated_slider.take()
+    }
+
+    /// Returns true if a control changed a value since the last call.
+    pub fn take_edited(&mut self) -> bool {
+        mem::take(&mut self.edited) | mem::take(&mut self.shared_edited)
+    }
+
+    /// Returns true if a control other than a shared parameter slider changed
+    /// a value since the last call to `take_edited`.
+    pub fn structure_edited(&self) -> bool {
+        self.edited
+    }
+
+    /// Open the command palette with an empty query.
+    pub fn open_palette(&mut self) {
+        self.palette = Some((String::new(), 0));
+        self.focus(PALETTE_ID);
+    }
+
+    /// Draw the command palette, if open. `get_options` returns the options
+    /// matching a query. Returns the index of the chosen option.
+    pub fn palette(&mut self, get_options: impl FnOnce(&str) -> Vec<String>
+    ) -> Option<usize> {
+        let (prev_query, mut index) = self.palette.clone()?;
+        let query = match &self.focus {
+            Focus::Text(state) if state.id == PALETTE_ID => state.text.clone(),
+            _ if self.pending_focus.as_deref() == Some(PALETTE_ID) => String::new(),
+            _ => {
+                self.palette = None;
+                return None
+            }
+        };
+        let options = get_options(&query);
+        let n = options.len();
+
+        // keyboard navigation
+        let prev_index = index;
+        if query != prev_query {
+            index = 0;
+        }
+        if n > 0 {
+            if is_key_pressed(KeyCode::Down) {
+                index = (index + 1) % n;
+            }
+            if is_key_pressed(KeyCode::Up) {
+                index = (index + n - 1) % n;
+            }
+        }
+        index = index.min(n.saturating_sub(1));
+        if (index != prev_index || query != prev_query) && n > 0 {
+            self.announce(options[index].clone());
+        }
+
+        let margin = self.style.margin;
+        let line_h = self.style.line_height();
+        let w = PALETTE_CHARS as f32 * self.style.atlas.char_width() + margin * 4.0;
+        let h = line_h * (PALETTE_ROWS + 1) as f32 + margin * 4.0;
+        let rect = Rect {
+            x: ((screen_width() - w) * 0.5).round(),
+            y: (screen_height() * 0.2).round(),
+            w,
+            h,
+        };
+
+        let (saved_x, saved_y) = (self.cursor_x, self.cursor_y);
+        self.cursor_z += PANEL_Z_OFFSET;
+        self.push_rect(rect, self.style.theme.panel_bg(),
+            Some(self.style.theme.border_unfocused()));
+        self.cursor_x = rect.x + margin;
+        self.cursor_y = rect.y + margin;
+        let submit = self.text_box(PALETTE_ID, "", w - margin * 4.0, &query,
+            PALETTE_CHARS, Info::None);
+
+        // draw options, scrolled to keep the selection visible
+        let first = index.saturating_sub(PALETTE_ROWS - 1);
+        let mouse_pos = mouse_position_vec2();
+        let mut hovered = None;
+        let mut hit_rect = Rect {
+            x: rect.x + margin,
+            y: rect.y + line_h + margin * 3.0,
+            w: w - margin * 2.0,
+            h: line_h,
+        };
+        for (i, option) in options.iter().enumerate().skip(first).take(PALETTE_ROWS) {
+            if hit_rect.contains(mouse_pos) {
+                hovered = Some(i);
+            }
+            if hovered == Some(i) || index == i {
+                self.push_rect(hit_rect, self.style.theme.panel_bg_hover(), None);
+            }
+            self.push_text(hit_rect.x, hit_rect.y, option.to_owned(),
+                self.style.theme.fg());
+            hit_rect.y += line_h;
+        }
+
+        self.cursor_z -= PANEL_Z_OFFSET;
+        (self.cursor_x, self.cursor_y) = (saved_x, saved_y);
+
+        // keep clicks from reaching controls under the palette
+        if rect.contains(mouse_pos) && is_mouse_button_pressed(MouseButton::Left) {
+            self.mouse_consumed = Some(PALETTE_ID.to_owned());
+        }
+
+        self.palette = Some((query, index));
+        if submit {
+            // the text box also submits when clicked off
+            self.palette = None;
+            self.focus = Focus::None;
+            if is_key_pressed(KeyCode::Enter) {
+                (n > 0).then_some(index)
+            } else {
+                hovered
+            }
+        } else {
+            None
+        }
+    }
+
     /// Focus the control with the given ID.
     pub fn focus(&mut self, id: &str) {
         self.pending_focus = Some(id.to_owned());
@@ -1830,6 +2395,38 @@ fn combo_box_list_rect(style: &Style, button_rect: Rect, options: &[String]) ->
     }
 }
 
+/// Severity of a logged message.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Severity {
+    Info,
+    Error,
+}
+
+/// Message in the notification log.
+pub struct LogEntry {
+    pub severity: Severity,
+    /// Seconds since startup.
+    pub time: f64,
+    pub message: String,
+}
+
+impl LogEntry {
+    /// Returns the time since startup as H:MM:SS.
+    pub fn timestamp(&self) -> String {
+        let secs = self.time as u64;
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    }
+
+    /// Returns the entry as text, with timestamp and severity.
+    pub fn details(&self) -> String {
+        let severity = match self.severity {
+            Severity::Info => "Info",
+            Severity::Error => "Error",
+        };
+        format!("[{}] {severity}: {}", self.timestamp(), self.message)
+    }
+}
+
 struct Notification {
     message: String,
     time_remaining: f32,
@@ -1854,7 +2451,7 @@ fn display_unit(unit: Option<&'static str>) -> Box<dyn Fn(f32) -> String> {
 }
 
 /// Returns true if either Shift key is down.
-fn is_shift_down() -> bool {
+pub fn is_shift_down() -> bool {
     is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift)
 }
 
@@ -1,6 +1,12 @@
+#[cfg(feature = "regression")]
+use std::{path::Path, sync::mpsc::{self, Receiver}, thread};
+
 use cpal::StreamConfig;
 use macroquad::time::get_frame_time;
 
+#[cfg(feature = "regression")]
+use crate::regression::{self, RegressionResult, TESTDATA_DIR};
+
 use crate::playback::Player;
 
 use super::{info::Info, Layout, Ui};
@@ -14,6 +20,11 @@ pub struct DevState {
     scroll: f32,
     stream_config: Option<StreamConfig>,
     pub only_draw_on_input: bool,
+    /// Channel for results of a running regression render.
+    #[cfg(feature = "regression")]
+    regression_rx: Option<Receiver<Result<Vec<RegressionResult>, String>>>,
+    #[cfg(feature = "regression")]
+    regression_results: Vec<RegressionResult>,
 }
 
 impl DevState {
@@ -24,6 +35,10 @@ impl DevState {
             scroll: 0.0,
             stream_config,
             only_draw_on_input: false,
+            #[cfg(feature = "regression")]
+            regression_rx: None,
+            #[cfg(feature = "regression")]
+            regression_results: Vec::new(),
         }
     }
 }
@@ -37,7 +52,14 @@ pub fn draw(ui: &mut Ui, state: &mut DevState, player: &Player) {
 
     draw_diagnostics(ui, state, player);
     ui.vertical_space();
+    draw_audio_graph(ui, player);
+    ui.vertical_space();
     draw_options(ui, state);
+    #[cfg(feature = "regression")]
+    {
+        ui.vertical_space();
+        draw_regression(ui, state);
+    }
 
     let scroll_h = ui.end_group().unwrap().h + ui.style.margin;
     ui.cursor_z += 1;
@@ -65,7 +87,51 @@ fn draw_diagnostics(ui: &mut Ui, state: &mut DevState, player: &Player) {
     ui.label(&format!("Buffer size: {}", player.buffer_size), Info::None);
 }
 
+fn draw_audio_graph(ui: &mut Ui, player: &Player) {
+    ui.header("AUDIO GRAPH", Info::None);
+    for line in player.describe_graph() {
+        ui.label(&line, Info::None);
+    }
+}
+
 fn draw_options(ui: &mut Ui, state: &mut DevState) {
     ui.header("OPTIONS", Info::None);
     ui.checkbox("Skip UI if no input", &mut state.only_draw_on_input, true, Info::None);
-}
\ No newline at end of file
+}
+#[cfg(feature = "regression")]
+fn draw_regression(ui: &mut Ui, state: &mut DevState) {
+    ui.header("REGRESSION", Info::None);
+
+    if let Some(rx) = &state.regression_rx {
+        if let Ok(result) = rx.try_recv() {
+            state.regression_rx = None;
+            match result {
+                Ok(results) => state.regression_results = results,
+                Err(e) => ui.report(e),
+            }
+        }
+    }
+
+    let idle = state.regression_rx.is_none();
+    ui.start_group();
+    let run = ui.button("Run", idle, Info::None);
+    let update = ui.button("Update baseline", idle, Info::None);
+    ui.end_group();
+    if run || update {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let result = regression::run(Path::new(TESTDATA_DIR), update)
+                .map_err(|e| e.to_string());
+            let _ = tx.send(result);
+        });
+        state.regression_rx = Some(rx);
+        state.regression_results.clear();
+    }
+
+    if !idle {
+        ui.label("Rendering...", Info::None);
+    }
+    for result in &state.regression_results {
+        ui.label(&format!("{}: {}", result.name, result.outcome.describe()), Info::None);
+    }
+}
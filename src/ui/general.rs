@@ -1,15 +1,51 @@
+use std::path::{Path, PathBuf};
+
 use fundsp::math::{amp_db, db_amp};
 use info::Info;
+use macroquad::miniquad::window::clipboard_set;
 
-use crate::{config::{self, Config}, fx::{Compression, GlobalFX, SpatialFx}, module::Module, pitch::Tuning};
+use crate::{arp::{ArpMode, ArpSettings}, batch::BatchExport, cliplib::{self, Clip}, config::{self, Config}, fx::{Compression, Equalizer, GlobalFX, SpatialFx, MAX_EQ_FREQ, MAX_EQ_GAIN, MAX_EQ_Q, MAX_SIDECHAIN_HZ, MAX_STEREO_WIDTH, MIN_EQ_FREQ, MIN_EQ_Q}, input::Action, melody::MelodySettings, module::{BendRamp, GlideCurve, LfoRate, Module, PatternLfo, TrackTarget}, patterntext, pitch::{self, Tuning}, timespan::Timespan, ui::pattern::{parse_tempo_ratio, tempo_ratio_options, PatternEditor, ValueScaling}};
 
 use super::*;
 
+/// Gain reduction that fills the compressor meter, in dB.
+const GAIN_REDUCTION_RANGE: f32 = 24.0;
+
 /// State for the general tab UI.
 #[derive(Default)]
 pub struct GeneralState {
-    scroll: f32,
+    pub scroll: f32,
     table_cache: Option<TableCache>,
+    import: Option<ImportState>,
+    /// Index of the module tuning being edited.
+    tuning_index: usize,
+    /// Recent module picked this frame.
+    pub recent_selection: Option<PathBuf>,
+    pub batch: BatchExport,
+    /// Name to save the pattern selection under in the clip library.
+    clip_name: String,
+    /// Clip picked from the clip library.
+    clip_selection: Option<String>,
+}
+
+impl GeneralState {
+    /// Begin selecting data to import from `module`.
+    pub fn start_import(&mut self, module: Module) {
+        self.import = Some(ImportState {
+            patches: vec![false; module.patches.len()],
+            kit: false,
+            tracks: vec![false; module.tracks.len()],
+            module,
+        });
+    }
+}
+
+/// Pending module import selection.
+struct ImportState {
+    module: Module,
+    patches: Vec<bool>,
+    kit: bool,
+    tracks: Vec<bool>,
 }
 
 /// Interval table cache.
@@ -19,7 +55,8 @@ struct TableCache {
 }
 
 pub fn draw(ui: &mut Ui, module: &mut Module, fx: &mut GlobalFX, cfg: &mut Config,
-    player: &mut Player, state: &mut GeneralState
+    player: &mut Player, state: &mut GeneralState, save_path: Option<&PathBuf>,
+    pattern_editor: &PatternEditor
 ) {
     ui.layout = Layout::Horizontal;
     let old_y = ui.cursor_y;
@@ -27,15 +64,71 @@ pub fn draw(ui: &mut Ui, module: &mut Module, fx: &mut GlobalFX, cfg: &mut Confi
     ui.cursor_z -= 1;
     ui.start_group();
 
+    if state.import.is_some() {
+        import_controls(ui, module, player, &mut state.import);
+        ui.vertical_space();
+    }
+    recent_controls(ui, &cfg.recent_modules, &mut state.recent_selection);
+    ui.vertical_space();
     metadata_controls(ui, module);
     ui.vertical_space();
+    template_controls(ui, module, pattern_editor.beat_division);
+    ui.vertical_space();
+    sample_controls(ui, module, save_path);
+    ui.vertical_space();
     spatial_fx_controls(ui, &mut module.fx.spatial, fx);
     ui.vertical_space();
     compression_controls(ui, &mut module.fx.comp, fx);
     ui.vertical_space();
-    tuning_controls(ui, &mut module.tuning, cfg, player, &mut state.table_cache);
+    eq_controls(ui, &mut module.fx.eq, fx);
+    ui.vertical_space();
+    stereo_controls(ui, &mut module.fx.width, fx, player);
+    ui.vertical_space();
+    tuning_select_controls(ui, module, &mut state.tuning_index);
+    ui.take_edited();
+    tuning_controls(ui, module.tuning_at_mut(state.tuning_index), cfg, player,
+        &mut state.table_cache);
+    adaptive_ji_controls(ui, module);
+    ui.vertical_space();
+    transpose_controls(ui, module);
+    // pitch settings affect every track
+    if ui.take_edited() {
+        module.global_edited();
+    }
+    ui.vertical_space();
+    key_map_controls(ui, module);
+    ui.vertical_space();
+    interval_table(ui, module.tuning_at_mut(state.tuning_index), &mut state.table_cache);
+    ui.vertical_space();
+    melody_controls(ui, &mut cfg.melody);
     ui.vertical_space();
-    interval_table(ui, &mut module.tuning, &mut state.table_cache);
+    value_scaling_controls(ui, &mut cfg.value_scaling);
+    ui.vertical_space();
+    lfo_controls(ui, &mut cfg.vibrato, &mut cfg.tremolo);
+    ui.vertical_space();
+    bend_ramp_controls(ui, &mut cfg.bend_ramp);
+    ui.vertical_space();
+    retrigger_controls(ui, cfg);
+    ui.vertical_space();
+    note_timing_controls(ui, &mut cfg.note_timing);
+    ui.vertical_space();
+    step_record_controls(ui, cfg);
+    ui.vertical_space();
+    arp_controls(ui, &mut cfg.arp);
+    ui.vertical_space();
+    tempo_ratio_controls(ui, &mut cfg.tempo_ratio, pattern_editor.beat_division,
+        module.tempo_at(pattern_editor.cursor_tick()));
+    ui.vertical_space();
+    song_length_controls(ui, cfg);
+    ui.vertical_space();
+    macro_controls(ui, cfg);
+    ui.vertical_space();
+    clip_controls(ui, module, pattern_editor, &mut state.clip_name,
+        &mut state.clip_selection);
+    ui.vertical_space();
+    batch_controls(ui, &mut state.batch, cfg, player);
+    ui.vertical_space();
+    message_log_controls(ui);
 
     let scroll_h = ui.end_group().unwrap().h + ui.style.margin;
     ui.cursor_z += 1;
@@ -44,6 +137,17 @@ pub fn draw(ui: &mut Ui, module: &mut Module, fx: &mut GlobalFX, cfg: &mut Confi
         scroll_h, ui.bounds.y + ui.bounds.h - ui.cursor_y, true);
 }
 
+fn recent_controls(ui: &mut Ui, recent: &[String], selection: &mut Option<PathBuf>) {
+    ui.header("RECENT SONGS", Info::RecentSongs);
+    let name = |p: &String| Path::new(p).file_name()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| p.clone());
+    if let Some(i) = ui.combo_box("recent_modules", "Open", "(select)",
+        Info::RecentSongs, || recent.iter().map(name).collect()) {
+        *selection = recent.get(i).map(PathBuf::from);
+    }
+}
+
 fn metadata_controls(ui: &mut Ui, module: &mut Module) {
     ui.header("METADATA", Info::None);
     if let Some(s) = ui.edit_box("Title", 40, module.title.clone(), Info::None) {
@@ -52,6 +156,102 @@ fn metadata_controls(ui: &mut Ui, module: &mut Module) {
     if let Some(s) = ui.edit_box("Author", 40, module.author.clone(), Info::None) {
         module.author = s;
     }
+    ui.start_group();
+    if let Some(s) = ui.edit_box("Render seed", 10, module.render_seed.to_string(),
+        Info::RenderSeed
+    ) {
+        match s.parse() {
+            Ok(seed) => module.render_seed = seed,
+            Err(e) => ui.report(e),
+        }
+    }
+    if ui.button("New seed", true, Info::RenderSeed) {
+        module.render_seed = rand::random();
+    }
+    ui.end_group();
+}
+
+fn template_controls(ui: &mut Ui, module: &Module, division: u8) {
+    ui.header("TEMPLATE", Info::ModuleTemplate);
+    let path = config::module_template_path();
+    ui.start_group();
+    if ui.button("Save as template", true, Info::None) {
+        match module.template().save(division, &path) {
+            Ok(_) => ui.notify(String::from("Saved module template.")),
+            Err(e) => ui.report(format!("Error saving module template: {e}")),
+        }
+    }
+    if ui.button("Reset template", path.exists(), Info::None) {
+        match std::fs::remove_file(&path) {
+            Ok(_) => ui.notify(String::from("Reset module template.")),
+            Err(e) => ui.report(format!("Error removing module template: {e}")),
+        }
+    }
+    ui.end_group();
+}
+
+fn import_controls(ui: &mut Ui, module: &mut Module, player: &mut Player,
+    import: &mut Option<ImportState>
+) {
+    let Some(state) = import else { return };
+    ui.header("IMPORT", Info::Action(Action::ImportModule));
+
+    ui.start_group();
+    labeled_group(ui, "Patches", Info::None, |ui| {
+        for (i, patch) in state.module.patches.iter().enumerate() {
+            ui.checkbox(&patch.name, &mut state.patches[i], true, Info::None);
+        }
+    });
+    labeled_group(ui, "Kit", Info::None, |ui| {
+        ui.checkbox(&format!("{} mappings", state.module.kit.len()), &mut state.kit,
+            !state.module.kit.is_empty(), Info::ImportKit);
+    });
+    labeled_group(ui, "Tracks", Info::None, |ui| {
+        for (i, track) in state.module.tracks.iter().enumerate() {
+            let name = match track.target {
+                TrackTarget::Global => continue,
+                TrackTarget::Kit => "Kit",
+                TrackTarget::None => "(none)",
+                TrackTarget::Patch(j) => state.module.patches.get(j)
+                    .map(|p| p.name.as_ref())
+                    .unwrap_or("(unknown)"),
+            };
+            ui.checkbox(&format!("{i}: {name}"), &mut state.tracks[i], true, Info::None);
+        }
+    });
+    ui.end_group();
+
+    ui.start_group();
+    if ui.button("Import", true, Info::None) {
+        let indices = |v: &[bool]| v.iter().enumerate()
+            .filter_map(|(i, b)| b.then_some(i))
+            .collect::<Vec<_>>();
+        module.import(&state.module, &indices(&state.patches), state.kit,
+            &indices(&state.tracks));
+        player.update_synths(module.drain_track_history(), module);
+        *import = None;
+    } else if ui.button("Cancel", true, Info::None) {
+        *import = None;
+    }
+    ui.end_group();
+}
+
+fn sample_controls(ui: &mut Ui, module: &mut Module, save_path: Option<&PathBuf>) {
+    ui.header("SAMPLES", Info::Samples);
+    ui.start_group();
+    if ui.button("Collect", save_path.is_some(), Info::CollectSamples) {
+        if let Some(path) = save_path {
+            match module.collect_samples(path) {
+                Ok(n) => ui.notify(format!("Collected {n} samples.")),
+                Err(e) => ui.report(format!("Error collecting samples: {e}")),
+            }
+        }
+    }
+    if ui.button("Embed all", true, Info::EmbedSamples) {
+        let n = module.embed_samples();
+        ui.notify(format!("Embedded {n} samples."));
+    }
+    ui.end_group();
 }
 
 fn spatial_fx_controls(ui: &mut Ui, spatial: &mut SpatialFx, fx: &mut GlobalFX) {
@@ -103,6 +303,61 @@ fn spatial_fx_controls(ui: &mut Ui, spatial: &mut SpatialFx, fx: &mut GlobalFX)
     }
 }
 
+fn eq_controls(ui: &mut Ui, eq: &mut Equalizer, fx: &mut GlobalFX) {
+    ui.header("EQ", Info::Equalizer);
+
+    let mut commit = false;
+
+    for (id, label, band) in [
+        ("eq_low", "Low shelf", &mut eq.low),
+        ("eq_mid", "Mid peak", &mut eq.mid),
+        ("eq_high", "High shelf", &mut eq.high),
+    ] {
+        ui.start_group();
+        if ui.formatted_slider(&format!("{id}_freq"), "", &mut band.freq,
+            MIN_EQ_FREQ..=MAX_EQ_FREQ, 3, true, Info::EqFreq,
+            |x| format!("{x:.0} Hz"), |x| x) {
+            commit = true;
+        }
+        if ui.formatted_slider(&format!("{id}_gain"), "", &mut band.gain,
+            -MAX_EQ_GAIN..=MAX_EQ_GAIN, 1, true, Info::EqGain,
+            |x| format!("{x:+.1} dB"), |x| x) {
+            commit = true;
+        }
+        if ui.formatted_slider(&format!("{id}_q"), label, &mut band.q,
+            MIN_EQ_Q..=MAX_EQ_Q, 2, true, Info::EqQ,
+            |x| format!("Q {x:.2}"), |x| x) {
+            commit = true;
+        }
+        ui.end_group();
+    }
+
+    if commit {
+        fx.commit_eq(eq);
+    }
+}
+
+fn stereo_controls(ui: &mut Ui, width: &mut f32, fx: &mut GlobalFX, player: &Player) {
+    ui.header("STEREO", Info::None);
+
+    if ui.formatted_slider("master_width", "Width", width,
+        0.0..=MAX_STEREO_WIDTH, 1, true, Info::MasterWidth,
+        |x| format!("{:.0}%", x * 100.0), |x| x / 100.0) {
+        fx.set_width(*width);
+    }
+
+    let mut mono = fx.mono_monitor();
+    if ui.checkbox("Mono monitor", &mut mono, true, Info::MonoMonitor) {
+        fx.set_mono_monitor(mono);
+    }
+
+    ui.start_group();
+    ui.offset_label("Correlation", Info::Correlation);
+    ui.meter((player.output_correlation + 1.0) * 0.5, Info::Correlation);
+    ui.offset_label(&format!("{:+.2}", player.output_correlation), Info::Correlation);
+    ui.end_group();
+}
+
 fn compression_controls(ui: &mut Ui, comp: &mut Compression, fx: &mut GlobalFX) {
     ui.header("COMPRESSION", Info::Compression);
 
@@ -138,16 +393,60 @@ fn compression_controls(ui: &mut Ui, comp: &mut Compression, fx: &mut GlobalFX)
         0.0..=1.0, Some("s"), 2, true, Info::CompRelease) {
         commit = true;
     }
+    if ui.formatted_slider("comp_makeup", "Makeup", &mut comp.makeup,
+        0.0..=4.0, 2, true, Info::CompMakeup,
+        |x| format!("{:+.1} dB", amp_db(x)), db_amp) {
+        commit = true;
+    }
+    if ui.formatted_slider("comp_sidechain", "Sidechain HP", &mut comp.sidechain_hz,
+        0.0..=MAX_SIDECHAIN_HZ, 2, true, Info::CompSidechain,
+        |x| if x > 0.0 { format!("{x:.0} Hz") } else { String::from("Off") }, |x| x) {
+        commit = true;
+    }
+
+    let reduction = if comp.is_active() { fx.comp_reduction() } else { 0.0 };
+    ui.start_group();
+    ui.offset_label("Reduction", Info::GainReduction);
+    ui.meter(reduction / GAIN_REDUCTION_RANGE, Info::GainReduction);
+    ui.offset_label(&format!("{:.1} dB", -reduction), Info::GainReduction);
+    ui.end_group();
 
     if commit {
         fx.commit_comp(comp);
     }
 }
 
+fn tuning_select_controls(ui: &mut Ui, module: &mut Module, index: &mut usize) {
+    let name = |i: usize| if i == 0 {
+        String::from("0 (main)")
+    } else {
+        i.to_string()
+    };
+    *index = (*index).min(module.tuning_count() - 1);
+
+    ui.start_group();
+    let count = module.tuning_count();
+    if let Some(i) = ui.combo_box("tuning_index", "Tuning", &name(*index),
+        Info::TuningIndex, || (0..count).map(name).collect()) {
+        *index = i;
+    }
+    if ui.button("Add", count <= u8::MAX as usize, Info::Add("tuning")) {
+        module.extra_tunings.push(module.tuning_at(*index).clone());
+        *index = module.tuning_count() - 1;
+    }
+    if ui.button("Remove", *index > 0 && *index == count - 1, Info::Remove("tuning")) {
+        module.extra_tunings.pop();
+        module.global_edited();
+        *index -= 1;
+    }
+    ui.end_group();
+}
+
 fn tuning_controls(ui: &mut Ui, tuning: &mut Tuning, cfg: &mut Config,
     player: &mut Player, table_cache: &mut Option<TableCache>
 ) {
     const OCTAVE_CHARS: usize = 7;
+    const FREQ_CHARS: usize = 7;
 
     ui.header("TUNING", Info::Tuning);
 
@@ -157,7 +456,7 @@ fn tuning_controls(ui: &mut Ui, tuning: &mut Tuning, cfg: &mut Config,
         match s.parse() {
             Ok(ratio) => match Tuning::divide(ratio, tuning.size(), tuning.arrow_steps) {
                 Ok(t) => {
-                    *tuning = t;
+                    *tuning = Tuning { reference_freq: tuning.reference_freq, ..t };
                     *table_cache = None;
                 }
                 Err(e) => ui.report(e),
@@ -172,7 +471,7 @@ fn tuning_controls(ui: &mut Ui, tuning: &mut Tuning, cfg: &mut Config,
         match s.parse() {
             Ok(steps) => match Tuning::divide(tuning.equave(), steps, tuning.arrow_steps) {
                 Ok(t) => {
-                    *tuning = t;
+                    *tuning = Tuning { reference_freq: tuning.reference_freq, ..t };
                     *table_cache = None;
                 }
                 Err(e) => ui.report(e),
@@ -193,6 +492,32 @@ fn tuning_controls(ui: &mut Ui, tuning: &mut Tuning, cfg: &mut Config,
         }
     }
 
+    if let Some(s) = ui.edit_box("A4 frequency (Hz)", FREQ_CHARS,
+        format_freq(tuning.reference_freq), Info::ReferenceFreq
+    ) {
+        match s.parse::<f32>() {
+            Ok(hz) if hz > 0.0 => {
+                tuning.reference_freq = hz;
+                *table_cache = None;
+            }
+            Ok(_) => ui.report("Frequency must be positive"),
+            Err(e) => ui.report(e),
+        }
+    }
+
+    if let Some(s) = ui.edit_box("Root frequency (Hz)", FREQ_CHARS,
+        format_freq(tuning.root_freq()), Info::RootFreq
+    ) {
+        match s.parse::<f32>() {
+            Ok(hz) if hz > 0.0 => {
+                tuning.set_root_freq(hz);
+                *table_cache = None;
+            }
+            Ok(_) => ui.report("Frequency must be positive"),
+            Err(e) => ui.report(e),
+        }
+    }
+
     // unequal scale controls
     ui.start_group();
     if ui.button("Load scale", true, Info::LoadScale) {
@@ -203,7 +528,7 @@ fn tuning_controls(ui: &mut Ui, tuning: &mut Tuning, cfg: &mut Config,
             cfg.scale_folder = config::dir_as_string(&path);
             match Tuning::load(path, tuning.root) {
                 Ok(t) => {
-                    *tuning = t;
+                    *tuning = Tuning { reference_freq: tuning.reference_freq, ..t };
                     *table_cache = None;
                 }
                 Err(e) => ui.report(format!("Error loading scale: {e}")),
@@ -217,6 +542,64 @@ fn tuning_controls(ui: &mut Ui, tuning: &mut Tuning, cfg: &mut Config,
     ui.end_group();
 }
 
+fn adaptive_ji_controls(ui: &mut Ui, module: &mut Module) {
+    ui.start_group();
+    ui.checkbox("Adaptive JI", &mut module.adaptive_ji, true, Info::AdaptiveJi);
+    ui.formatted_slider("ji_tolerance", "Tolerance", &mut module.ji_tolerance,
+        0.0..=50.0, 1, module.adaptive_ji, Info::AdaptiveJi,
+        |f| format!("{:.1} cents", f), |f| f);
+    ui.end_group();
+}
+
+fn transpose_controls(ui: &mut Ui, module: &mut Module) {
+    ui.header("TRANSPOSE", Info::Transpose);
+    ui.start_group();
+    if let Some(s) = ui.edit_box("Scale steps", 5, module.transpose.to_string(),
+        Info::Transpose
+    ) {
+        match s.trim().parse() {
+            Ok(steps) => module.transpose = steps,
+            Err(_) => ui.report(format!("Invalid transpose: {s}")),
+        }
+    }
+    ui.formatted_slider("pitch_shift", "Pitch shift", &mut module.pitch_shift,
+        -100.0..=100.0, 1, true, Info::PitchShift,
+        |f| format!("{:+.1} cents", f), |f| f);
+    ui.end_group();
+}
+
+fn key_map_controls(ui: &mut Ui, module: &mut Module) {
+    ui.header("KEY MAPPING", Info::KeyMap);
+
+    let map = &mut module.key_map;
+    if let Some(s) = ui.edit_box("Key degrees", 30, pitch::format_steps(&map.degrees),
+        Info::KeyMap
+    ) {
+        match pitch::parse_steps(&s) {
+            Ok(degrees) => map.degrees = degrees,
+            Err(e) => ui.report(e),
+        }
+    }
+
+    ui.start_group();
+    if let Some(s) = ui.edit_box("Rotation", 3, map.rotation.to_string(),
+        Info::KeyMapRotation
+    ) {
+        match s.parse() {
+            Ok(rotation) => map.rotation = rotation,
+            Err(e) => ui.report(e),
+        }
+    }
+    if ui.button("All degrees", true, Info::KeyMapAll) {
+        map.degrees = (0..module.tuning.size() as usize).collect();
+    }
+    if ui.button("Clear", map.is_active(), Info::KeyMapClear) {
+        map.degrees.clear();
+        map.rotation = 0;
+    }
+    ui.end_group();
+}
+
 fn interval_table(ui: &mut Ui, tuning: &mut Tuning, table_cache: &mut Option<TableCache>) {
     ui.header("INVERVAL TABLE", Info::None);
     ui.start_group();
@@ -232,6 +615,340 @@ fn interval_table(ui: &mut Ui, tuning: &mut Tuning, table_cache: &mut Option<Tab
     ui.end_group();
 }
 
+fn melody_controls(ui: &mut Ui, settings: &mut MelodySettings) {
+    ui.header("MELODY GENERATOR", Info::Action(Action::GenerateMelody));
+
+    if let Some(s) = ui.edit_box("Scale steps", 30, pitch::format_steps(&settings.steps),
+        Info::MelodySteps
+    ) {
+        match pitch::parse_steps(&s) {
+            Ok(steps) => settings.steps = steps,
+            Err(e) => ui.report(e),
+        }
+    }
+    ui.slider("melody_density", "Density", &mut settings.density,
+        0.0..=1.0, None, 1, true, Info::MelodyDensity);
+
+    ui.start_group();
+    ui.note_input("melody_low", &mut settings.low, Info::MelodyRange);
+    ui.offset_label("to", Info::MelodyRange);
+    ui.note_input("melody_high", &mut settings.high, Info::MelodyRange);
+    ui.offset_label("Range", Info::MelodyRange);
+    ui.end_group();
+
+    ui.start_group();
+    if let Some(s) = ui.edit_box("Seed", 10, settings.seed.to_string(), Info::MelodySeed) {
+        match s.parse() {
+            Ok(seed) => settings.seed = seed,
+            Err(e) => ui.report(e),
+        }
+    }
+    if ui.button("New seed", true, Info::MelodySeed) {
+        settings.seed = rand::random();
+    }
+    ui.end_group();
+}
+
+fn value_scaling_controls(ui: &mut Ui, settings: &mut ValueScaling) {
+    ui.header("VALUE SCALING", Info::Action(Action::ScaleValues));
+
+    ui.slider("value_scale_percent", "Scale", &mut settings.percent,
+        0.0..=400.0, Some("%"), 1, true, Info::ValueScalePercent);
+    if let Some(s) = ui.edit_box("Offset", 4, settings.offset.to_string(),
+        Info::ValueScaleOffset
+    ) {
+        match s.parse::<i8>() {
+            Ok(offset) => settings.offset = offset.clamp(
+                -(EventData::DIGIT_MAX as i8), EventData::DIGIT_MAX as i8),
+            Err(e) => ui.report(e),
+        }
+    }
+}
+
+fn lfo_controls(ui: &mut Ui, vibrato: &mut PatternLfo, tremolo: &mut PatternLfo) {
+    ui.header("VIBRATO & TREMOLO", Info::Action(Action::InsertVibrato));
+    lfo_settings(ui, "Vibrato", vibrato);
+    lfo_settings(ui, "Tremolo", tremolo);
+}
+
+fn lfo_settings(ui: &mut Ui, label: &str, lfo: &mut PatternLfo) {
+    ui.start_group();
+    if let Some(s) = ui.edit_box(&format!("{label} depth"), 2,
+        format!("{:X}", lfo.depth), Info::LfoDepth
+    ) {
+        match u8::from_str_radix(s.trim(), 16) {
+            Ok(depth) => lfo.depth = depth.min(EventData::DIGIT_MAX),
+            Err(e) => ui.report(e),
+        }
+    }
+    if let Some(s) = ui.edit_box(&format!("{label} rate"), 8, lfo.rate.to_string(),
+        Info::LfoRate
+    ) {
+        match LfoRate::parse(&s) {
+            Some(rate) => lfo.rate = rate,
+            None => ui.report(format!("Invalid rate: {s}")),
+        }
+    }
+    ui.end_group();
+}
+
+fn bend_ramp_controls(ui: &mut Ui, ramp: &mut BendRamp) {
+    ui.header("BEND RAMP", Info::Action(Action::InsertBendRamp));
+    if let Some(s) = ui.edit_box("Target (cents)", 6, ramp.cents.to_string(),
+        Info::Action(Action::InsertBendRamp)
+    ) {
+        match s.parse() {
+            Ok(cents) => ramp.cents = cents,
+            Err(e) => ui.report(e),
+        }
+    }
+    if let Some(s) = ui.edit_box("Length (beats)", 6, patterntext::format_tick(ramp.duration),
+        Info::Action(Action::InsertBendRamp)
+    ) {
+        match patterntext::parse_tick(&s) {
+            Some(tick) => ramp.duration = tick,
+            None => ui.report(format!("Invalid length: {s}")),
+        }
+    }
+    if let Some(i) = ui.combo_box("bend_ramp_curve", "Curve", ramp.curve.name(),
+        Info::Action(Action::InsertBendRamp),
+        || GlideCurve::VARIANTS.map(|v| v.name().to_owned()).to_vec()) {
+        ramp.curve = GlideCurve::VARIANTS[i];
+    }
+}
+
+fn retrigger_controls(ui: &mut Ui, cfg: &mut Config) {
+    ui.header("RETRIGGER", Info::Action(Action::InsertRetrigger));
+    if let Some(s) = ui.edit_box("Hits", 3, cfg.retrigger_hits.to_string(),
+        Info::Action(Action::InsertRetrigger)
+    ) {
+        match s.parse::<u8>() {
+            Ok(n) => cfg.retrigger_hits = n.max(1),
+            Err(e) => ui.report(e),
+        }
+    }
+    let pressure = cfg.retrigger_pressure.map(|p| format!("{p:X}")).unwrap_or_default();
+    if let Some(s) = ui.edit_box("End pressure", 2, pressure, Info::RetriggerPressure) {
+        let s = s.trim();
+        if s.is_empty() {
+            cfg.retrigger_pressure = None;
+        } else {
+            match u8::from_str_radix(s, 16) {
+                Ok(p) => cfg.retrigger_pressure = Some(p.min(EventData::DIGIT_MAX)),
+                Err(e) => ui.report(e),
+            }
+        }
+    }
+}
+
+fn note_timing_controls(ui: &mut Ui, offset: &mut Timespan) {
+    ui.header("NOTE TIMING", Info::Action(Action::InsertNoteDelay));
+    if let Some(s) = ui.edit_box("Offset (rows)", 6, patterntext::format_tick(*offset),
+        Info::Action(Action::InsertNoteDelay)
+    ) {
+        match patterntext::parse_tick(&s) {
+            Some(tick) => *offset = tick,
+            None => ui.report(format!("Invalid offset: {s}")),
+        }
+    }
+}
+
+fn step_record_controls(ui: &mut Ui, cfg: &mut Config) {
+    ui.header("STEP RECORD", Info::Action(Action::ToggleStepRecord));
+    if let Some(s) = ui.edit_box("Step (rows)", 3, cfg.step_record_rows.to_string(),
+        Info::Action(Action::ToggleStepRecord)
+    ) {
+        match s.parse::<u8>() {
+            Ok(n) => cfg.step_record_rows = n.max(1),
+            Err(e) => ui.report(e),
+        }
+    }
+}
+
+fn arp_controls(ui: &mut Ui, settings: &mut ArpSettings) {
+    ui.header("ARPEGGIATOR", Info::Arpeggiator);
+    ui.checkbox("Enabled", &mut settings.enabled, true, Info::Arpeggiator);
+    if let Some(i) = ui.combo_box("arp_mode", "Mode", settings.mode.name(),
+        Info::ArpMode, || ArpMode::VARIANTS.map(|x| x.name().to_owned()).to_vec()) {
+        settings.mode = ArpMode::VARIANTS[i];
+    }
+    if let Some(s) = ui.edit_box("Steps per beat", 3, settings.steps_per_beat.to_string(),
+        Info::ArpRate
+    ) {
+        match s.parse::<u8>() {
+            Ok(n) => settings.steps_per_beat = n.max(1),
+            Err(e) => ui.report(e),
+        }
+    }
+    ui.checkbox("Record arpeggiated notes", &mut settings.capture, true, Info::ArpCapture);
+}
+
+fn clip_controls(ui: &mut Ui, module: &mut Module, pattern_editor: &PatternEditor,
+    name: &mut String, selection: &mut Option<String>
+) {
+    ui.header("CLIP LIBRARY", Info::ClipLibrary);
+    let dir = cliplib::clip_dir();
+
+    ui.start_group();
+    if let Some(s) = ui.edit_box("Clip name", 20, name.clone(), Info::ClipName) {
+        *name = s.trim().to_owned();
+    }
+    if ui.button("Save selection", !name.is_empty(), Info::SaveClip) {
+        match pattern_editor.selection_clip(module).save(&dir, name) {
+            Ok(_) => ui.notify(format!("Saved clip: {name}")),
+            Err(e) => ui.report(format!("Error saving clip: {e}")),
+        }
+    }
+    ui.end_group();
+
+    ui.start_group();
+    let label = selection.as_deref().unwrap_or("(select)");
+    if let Some(i) = ui.combo_box("clip_library", "Clip", label,
+        Info::ClipLibrary, || cliplib::list(&dir)) {
+        *selection = cliplib::list(&dir).into_iter().nth(i);
+    }
+    if ui.button("Paste clip", selection.is_some(), Info::PasteClip) {
+        if let Some(clip_name) = selection {
+            let result = Clip::load(&dir, clip_name)
+                .map_err(|e| e.to_string())
+                .and_then(|clip| pattern_editor.paste_clip(module, &clip));
+            if let Err(e) = result {
+                ui.report(format!("Error pasting clip: {e}"));
+            }
+        }
+    }
+    if ui.button("Delete clip", selection.is_some(), Info::Remove("clip")) {
+        if let Some(clip_name) = selection.take() {
+            match Clip::delete(&dir, &clip_name) {
+                Ok(_) => ui.notify(format!("Deleted clip: {clip_name}")),
+                Err(e) => ui.report(format!("Error deleting clip: {e}")),
+            }
+        }
+    }
+    ui.end_group();
+}
+
+fn batch_controls(ui: &mut Ui, batch: &mut BatchExport, cfg: &mut Config,
+    player: &mut Player
+) {
+    ui.header("BATCH EXPORT", Info::BatchExport);
+    for item in &batch.items {
+        let name = item.path.file_name().unwrap_or_default().to_string_lossy();
+        ui.label(&format!("{name}: {}", item.status.describe()), Info::None);
+    }
+
+    ui.start_group();
+    if ui.button("Add modules", true, Info::BatchExport) {
+        let dir = cfg.module_folder.clone().unwrap_or(String::from("."));
+        if let Some(paths) = super::new_file_dialog(player)
+            .add_filter(crate::MODULE_FILETYPE_NAME, &[crate::MODULE_EXT])
+            .set_directory(dir)
+            .pick_files() {
+            batch.add(paths);
+        }
+    }
+    if ui.button("Render all", !batch.is_running() && batch.has_queued(),
+        Info::BatchExport
+    ) {
+        let dir = cfg.render_folder.clone().unwrap_or(String::from("."));
+        if let Some(dir) = super::new_file_dialog(player).set_directory(dir).pick_folder() {
+            cfg.render_folder = dir.to_str().map(|s| s.to_owned());
+            batch.start(dir);
+        }
+    }
+    if ui.button("Clear", !batch.items.is_empty(), Info::None) {
+        batch.clear();
+    }
+    ui.end_group();
+}
+
+fn message_log_controls(ui: &mut Ui) {
+    ui.header("MESSAGE LOG", Info::MessageLog);
+    let lines: Vec<_> = ui.message_log().iter().rev().map(|entry| {
+        let color = match entry.severity {
+            Severity::Info => ui.style.theme.fg(),
+            Severity::Error => ui.style.theme.accent2_fg(),
+        };
+        (entry.details().lines().collect::<Vec<_>>().join(" / "), color)
+    }).collect();
+    for (line, color) in lines {
+        ui.colored_label(&line, Info::MessageLog, color);
+    }
+
+    ui.start_group();
+    let last_error = ui.message_log().iter()
+        .rfind(|x| x.severity == Severity::Error)
+        .map(|x| x.details());
+    if ui.button("Copy last error", last_error.is_some(), Info::MessageLog) {
+        if let Some(s) = last_error {
+            clipboard_set(&s);
+        }
+    }
+    if ui.button("Copy all", !ui.message_log().is_empty(), Info::MessageLog) {
+        let s: Vec<_> = ui.message_log().iter().map(|x| x.details()).collect();
+        clipboard_set(&s.join("\n"));
+    }
+    if ui.button("Clear log", !ui.message_log().is_empty(), Info::None) {
+        ui.clear_message_log();
+    }
+    ui.end_group();
+}
+
+fn tempo_ratio_controls(ui: &mut Ui, ratio: &mut (u8, u8), division: u8, tempo: f32) {
+    ui.header("RATIONAL TEMPO", Info::Action(Action::RationalTempo));
+    let label = |(n, d): (u8, u8)| format!("{n}:{d} ({:.1} BPM)", tempo * n as f32 / d as f32);
+    let options = tempo_ratio_options(division);
+    if let Some(i) = ui.combo_box("tempo_ratio", "Ratio", &label(*ratio), Info::TempoRatio,
+        || options.iter().map(|r| label(*r)).collect()) {
+        *ratio = options[i];
+    }
+    if let Some(s) = ui.edit_box("Custom ratio", 7, format!("{}:{}", ratio.0, ratio.1),
+        Info::TempoRatio
+    ) {
+        match parse_tempo_ratio(&s) {
+            Some(r) => *ratio = r,
+            None => ui.report(format!("Invalid ratio: {s}")),
+        }
+    }
+}
+
+fn song_length_controls(ui: &mut Ui, cfg: &mut Config) {
+    ui.header("SONG LENGTH", Info::Action(Action::PadSong));
+    if let Some(s) = ui.edit_box("Pad bars", 3, cfg.pad_bars.to_string(),
+        Info::Action(Action::PadSong)
+    ) {
+        match s.parse() {
+            Ok(n) => cfg.pad_bars = n,
+            Err(e) => ui.report(e),
+        }
+    }
+    if let Some(s) = ui.edit_box("Beats per bar", 3, cfg.bar_beats.to_string(),
+        Info::BarBeats
+    ) {
+        match s.parse::<u8>() {
+            Ok(n) => cfg.bar_beats = n.max(1),
+            Err(e) => ui.report(e),
+        }
+    }
+}
+
+fn macro_controls(ui: &mut Ui, cfg: &mut Config) {
+    ui.header("MACRO", Info::Action(Action::RecordMacro));
+    if let Some(s) = ui.edit_box("Repeats", 3, cfg.macro_repeats.to_string(),
+        Info::Action(Action::PlayMacro)
+    ) {
+        match s.parse::<u8>() {
+            Ok(n) => cfg.macro_repeats = n.max(1),
+            Err(e) => ui.report(e),
+        }
+    }
+}
+
+/// Formats a frequency for editing, to two decimal places at most.
+fn format_freq(hz: f32) -> String {
+    ((hz * 100.0).round() / 100.0).to_string()
+}
+
 /// Construct an interval table (as column-major strings) from a tuning.
 fn make_table(t: &Tuning) -> Vec<Vec<String>> {
     let data = t.interval_table(&Note::new(0, crate::pitch::Nominal::C, 0, 4));
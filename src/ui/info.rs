@@ -16,10 +16,22 @@ pub enum Info {
     CompRatio,
     CompAttack,
     CompRelease,
+    CompMakeup,
+    CompSidechain,
+    GainReduction,
     StereoWidth,
+    MasterWidth,
+    MonoMonitor,
+    Correlation,
+    OutputGain,
+    OutputMute,
+    PlaybackSpeed,
+    OutputMeter,
     Gamma,
     Chroma,
     GlideTime,
+    RandomDetune,
+    Drift,
     Distortion,
     FxSend,
     LoopPoint,
@@ -27,14 +39,19 @@ pub enum Info {
     FreqRatio,
     FilterCutoff,
     FilterResonance,
+    FilterDrive,
     Attack,
     Decay,
     Sustain,
     Release,
     LfoDelay,
     ModDepth,
+    ModCurve,
+    ModResponse,
     LoadScale,
     SavePatch,
+    PatchTemplate,
+    ModuleTemplate,
     LoadPatch,
     DuplicatePatch,
     LoadSample,
@@ -47,23 +64,56 @@ pub enum Info {
     FontSize(&'static str),
     ResetSettings,
     UseAftertouch,
+    AftertouchMode,
+    KeyjazzPressure,
     UseVelocity,
     TuningRoot,
+    ReferenceFreq,
+    RetriggerPressure,
+    RootFreq,
     KitNoteIn,
     KitNoteOut,
     Action(Action),
     GlobalTrack,
     KitTrack,
     MidiInput,
+    MidiOutput,
+    MidiSendTuning,
+    MidiChannelRouting,
+    MidiKeySplit,
+    RenderSeed,
+    MacroCcMode,
+    RecentSongs,
+    ClipLibrary,
+    ClipName,
+    SaveClip,
+    PasteClip,
+    RestoreSession,
+    AnnounceStatus,
+    ErrorAlerts,
+    QuickExportName,
+    MessageLog,
+    ProgramRemapsTrack,
     SpatialFxType,
     KitPatch,
     Waveform,
+    Partials,
+    RetunePartials,
     GenOutput,
     FilterType,
     FilterKeytrack,
     ModSource,
     ModDest,
     TrackPatch,
+    TrackName,
+    TrackColor,
+    AuditionOnly,
+    TrackScale,
+    TrackOffset,
+    AutoPan,
+    TrackTone,
+    SongPosition,
+    MuteAuditionTracks,
     SmoothPlayhead,
     ControlColumn,
     NoteColumn,
@@ -71,12 +121,26 @@ pub enum Info {
     ModulationColumn,
     NoteLayout,
     Compression,
+    Equalizer,
+    EqFreq,
+    EqGain,
+    EqQ,
     Tuning,
+    BulkLevel,
+    BulkTags,
+    AnalyzeLevels,
     Generators,
+    SampleWaveform,
+    SampleStart,
+    SnapLoop,
+    LoopCrossfade,
+    MuteGen,
+    SoloGen,
     Filters,
     Envelopes,
     Lfos,
     ModMatrix,
+    VoiceMonitor,
     DisplayInfo,
     DesiredSampleRate,
     VerticalScrollbar,
@@ -88,6 +152,66 @@ pub enum Info {
     Oversample,
     DuplicateKitEntry,
     LfoAudioRate,
+    BufferSize,
+    OutputLatency,
+    LatencyCompensation,
+    RecordCountIn,
+    RecordPreRoll,
+    RecordMode,
+    RecordPunch,
+    LinkSample,
+    Samples,
+    BatchExport,
+    CollectSamples,
+    EmbedSamples,
+    ImportKit,
+    KitAutoFill,
+    KitName,
+    KitLearn,
+    GhostTrack,
+    CopyPatches,
+    PastePatches,
+    ShowProfiler,
+    ParamSmoothTime,
+    ModSmoothTime,
+    PressureSmoothTime,
+    PreviewNotes,
+    KeyjazzLatch,
+    BarBeats,
+    ShowMinimap,
+    Minimap,
+    ShowTempoLane,
+    TempoLane,
+    ShowPianoRoll,
+    PianoRoll,
+    AdaptiveJi,
+    Transpose,
+    PitchShift,
+    TuningIndex,
+    KeyMap,
+    KeyMapRotation,
+    KeyMapAll,
+    KeyMapClear,
+    PitchDisplay,
+    PitchReadout,
+    MelodySteps,
+    MelodyDensity,
+    MelodyRange,
+    MelodySeed,
+    ValueScalePercent,
+    ValueScaleOffset,
+    LfoDepth,
+    LfoRate,
+    Arpeggiator,
+    ArpMode,
+    ArpRate,
+    ArpCapture,
+    TempoRatio,
+    PatchFilter,
+    PatchTags,
+    MovePatch,
+    DspLoad,
+    VoiceCount,
 }
 
 impl Default for Info {
@@ -131,7 +255,9 @@ high-pitched modulators.".to_string(),
         Info::Font =>
             text = "Font is a modified version of Dina by Jørgen Ibsen.".to_string(),
         Info::InstrumentList => text =
-"Patch list. Right-click to edit names.
+"Patch list. Right-click to edit names. Ctrl-click
+or shift-click to select multiple patches for
+removing, saving, or adjusting level.
 
 Up - Previous entry
 Down - Next entry".to_string(),
@@ -150,14 +276,253 @@ Shift+Wheel - Scroll".to_string(),
 "Audio output sample rate to request, in Hz. Requires
 program restart to take effect. Does not affect
 sample rate of WAV export.".to_string(),
+        Info::BufferSize => text =
+"Audio buffer size to request, in frames. Smaller
+buffers reduce latency but may cause dropouts.
+Leave blank to use the device default. Requires
+program restart to take effect.".to_string(),
+        Info::OutputLatency => text =
+"Measured time between audio generation and
+playback. Input latency isn't included.".to_string(),
+        Info::LatencyCompensation => text =
+"If enabled, offset recorded notes by the output
+latency so that they land where they were heard.
+Input latency isn't compensated.".to_string(),
+        Info::RecordCountIn => text =
+"If enabled, play one or two bars of metronome
+before recording starts.".to_string(),
+        Info::RecordPreRoll => text =
+"If enabled, recording playback starts a bar before
+the cursor. Input is only recorded from the cursor
+onward.".to_string(),
+        Info::RecordMode => text =
+"Overdub merges recorded events with existing ones.
+Replace erases events in the cursor channel as
+recording passes over them.".to_string(),
+        Info::RecordPunch => text =
+"If enabled and the selection spans rows, only
+record input within the selected rows.".to_string(),
+        Info::LinkSample => text =
+"Store the sample as a reference to its source file
+instead of embedding it in the module. Keeps module
+files small and allows sharing samples between
+songs.".to_string(),
+        Info::BatchExport => text =
+"Render several module files to WAV, one after
+another, in the background. Files are named after
+their modules and written to the chosen folder.
+Modules without an End event fail.".to_string(),
+        Info::Samples => text =
+"Manage samples that are stored as references to
+external files.".to_string(),
+        Info::CollectSamples => text =
+"Copy linked samples into a \"samples\" folder next
+to the module file. The module must have been
+saved first.".to_string(),
+        Info::EmbedSamples => text =
+"Embed all linked samples in the module, so that
+it no longer depends on external files.".to_string(),
+        Info::ImportKit => text =
+"Import all kit mappings. Patches used by the kit
+will also be imported.".to_string(),
+        Info::KitAutoFill => text =
+"Replace the kit with mappings for the selected
+patches, or all patches if none are selected.
+Input notes rise by one step from the given note.".to_string(),
+        Info::KitName => text =
+"A name for this kit mapping, shown in place of the
+note in kit tracks. Leave empty to show the note.".to_string(),
+        Info::KitLearn => text =
+"Assign input notes to the selected patches, or all
+patches if none are selected, by playing a note for
+each patch in turn.".to_string(),
         Info::DisplayInfo =>
             text = "Display mouseover help text for UI elements.".to_string(),
+        Info::ShowProfiler => text =
+"Display DSP load in the status bar and voice counts
+in pattern track headers. Useful for finding which
+patches are causing audio dropouts.".to_string(),
+        Info::ParamSmoothTime => text =
+"Ramp time for patch parameter changes made from the
+UI. Higher values avoid clicks when editing sounds
+during playback. Applies to newly triggered notes.".to_string(),
+        Info::ModSmoothTime => text =
+"Ramp time for modulation controller changes. Higher
+values avoid zipper noise from 7-bit controllers.
+14-bit modulation (CC 1 and 33) is also supported.
+Applies to newly triggered notes.".to_string(),
+        Info::PressureSmoothTime => text =
+"Ramp time for aftertouch changes. Higher values
+avoid zipper noise from 7-bit controllers. Applies
+to newly triggered notes.".to_string(),
+        Info::PreviewNotes => text =
+"Play a short preview of pitch events in the pattern
+editor when they're entered or when the cursor moves
+onto them. Only active while playback is stopped.".to_string(),
+        Info::BarBeats => text = "Number of beats in a bar.".to_string(),
+        Info::KeyjazzLatch => text =
+"Keep computer keyboard notes on after their keys
+are released. Press a latched key again to release
+it. With mono patches, each new note replaces the
+latched one.".to_string(),
+        Info::ShowMinimap => text =
+"Display an overview of the whole song at the right
+edge of the pattern editor.".to_string(),
+        Info::Minimap => text =
+"Song overview. Each lane shows the event density of
+a track. The highlighted box is the visible area.
+
+Click/drag - Scroll to position".to_string(),
+        Info::ShowTempoLane => text =
+"Display a graph of the song tempo beside the
+pattern.".to_string(),
+        Info::TempoLane => text =
+"Tempo over time, from tempo events in the global
+track. Further right is faster.".to_string(),
+        Info::ShowPianoRoll => text =
+"Display the notes of the cursor track as a piano
+roll below the pattern.".to_string(),
+        Info::PianoRoll => text =
+"Notes of the cursor track over the visible beats,
+by scale degree. Sloped lines are glides. Notes in
+the cursor channel are highlighted. Zooming rows
+also zooms the roll.
+
+Click - Move cursor to note
+Drag - Transpose note by scale steps".to_string(),
+        Info::TuningIndex => text =
+"Tuning to edit. Tuning 0 is used until a tuning
+change event switches to another. Only the last
+tuning can be removed.".to_string(),
+        Info::KeyMap => text =
+"Scale degrees of the song tuning played by the note
+keys, counted from the tuning root and separated by
+spaces. Keys play the degrees in order, bottom row
+first, continuing into the next octave when the list
+runs out. Leave empty to use the standard layout.".to_string(),
+        Info::KeyMapRotation => text =
+"Number of degrees to skip before the first key.
+Rotating a subset of degrees plays its modes.".to_string(),
+        Info::KeyMapAll =>
+            text = "Map every degree of the tuning to keys.".to_string(),
+        Info::KeyMapClear =>
+            text = "Return to the standard key layout.".to_string(),
+        Info::AdaptiveJi => text =
+"If enabled, pattern notes are retuned on note-on
+toward the nearest simple just intonation ratio
+above the lowest sounding note, if that ratio is
+within the tolerance. Notes already sounding are
+not retuned.".to_string(),
+        Info::Transpose => text =
+"Transpose pattern playback, keyjazz, and MIDI input
+by a number of scale steps, without editing
+patterns. Kit notes are not transposed.".to_string(),
+        Info::PitchShift => text =
+"Shift all played pitches, including kit notes, by
+a number of cents. Applies to playback and export.".to_string(),
+        Info::AftertouchMode => text =
+"How MIDI aftertouch affects the pressure of notes
+that are on. Replace uses the aftertouch value, Max
+uses the greater of it and the note's velocity,
+and Add adds it to the velocity.".to_string(),
+        Info::KeyjazzPressure => text =
+"If enabled, notes played from the computer
+keyboard use a fixed pressure instead of the last
+pressure in the channel.".to_string(),
+        Info::PitchDisplay => text =
+"Alternative pitch readout shown for the note at the
+pattern cursor and for kit output notes: scale
+degree, cents from the tuning root, or the nearest
+simple just intonation ratio with its error.".to_string(),
+        Info::PitchReadout => text =
+"Pitch of the note at or above the pattern cursor,
+relative to the tuning root.".to_string(),
+        Info::MelodySteps => text =
+"Scale steps of the song tuning to draw notes from,
+counted from the tuning root and separated by
+spaces. Leave empty to use every step.".to_string(),
+        Info::MelodyDensity =>
+            text = "Chance of placing a note on each row.".to_string(),
+        Info::MelodyRange =>
+            text = "Lowest and highest notes to generate.".to_string(),
+        Info::MelodySeed => text =
+"Random seed. The same seed and settings always
+generate the same melody.".to_string(),
+        Info::ValueScalePercent => text =
+"Percentage to multiply pressure and modulation
+values by.".to_string(),
+        Info::ValueScaleOffset => text =
+"Amount to add to pressure and modulation values
+after multiplying. Results are clamped to 0..F.".to_string(),
+        Info::LfoDepth => text =
+"Depth of inserted events, as a hex digit. Vibrato
+depth is in eighths of a semitone; tremolo depth F
+dips to silence.".to_string(),
+        Info::LfoRate => text =
+"Rate of inserted events. Enter a frequency such as
+5hz, or a number of cycles per beat such as 2 or
+1/4 to follow the tempo.".to_string(),
+        Info::Arpeggiator => text =
+"Play held keyjazz and MIDI notes one at a time, in
+time with the playhead.".to_string(),
+        Info::ArpMode => text =
+"Order to play held notes in. Up/down doesn't repeat
+the highest and lowest notes.".to_string(),
+        Info::ArpRate => text =
+"Number of arpeggiator notes per beat.".to_string(),
+        Info::ArpCapture => text =
+"If enabled, recording writes the arpeggiated notes
+instead of the held notes.".to_string(),
+        Info::TempoRatio => text =
+"Ratio to multiply the tempo by. The first options
+make that many rows of the current beat division
+last as long as a beat did. BPM is shown for the
+tempo at the pattern cursor.".to_string(),
+        Info::DspLoad => text =
+"Time spent processing audio, relative to the time
+available. Audio will drop out above 100%.
+
+Load is measured for the whole mix, not per track,
+since tracks are processed together. Track voice
+counts show which tracks are busiest.".to_string(),
+        Info::VoiceCount => text =
+"Number of notes playing on this track. Frozen
+tracks use no voices.".to_string(),
         Info::Generators => text =
 "Generators create the initial signal that other
 patch parameters shape.".to_string(),
         Info::Filters => text =
 "Filters attenuate certain parts of the frequency
 spectrum to change the timbre of a sound.".to_string(),
+        Info::BulkLevel => text =
+"Adjust the level of the selected patches by 1 dB.".to_string(),
+        Info::BulkTags => text =
+"Add a tag to, or remove a tag from, each selected
+patch.".to_string(),
+        Info::AnalyzeLevels => text =
+"Play a reference note with each patch, or each
+selected patch, and suggest level trims so that
+patches start at consistent levels. Applying the
+trims can be undone.".to_string(),
+        Info::SampleWaveform => text =
+"Sample waveform. The loop point and start position
+are marked. Click to set the loop point, snapped
+to the nearest zero crossing.".to_string(),
+        Info::SampleStart => text =
+"Position in the sample where playback starts. Can
+be modulated per note.".to_string(),
+        Info::SnapLoop => text =
+"Move the loop point to the nearest zero crossing.".to_string(),
+        Info::LoopCrossfade => text =
+"Blend the end of the sample into the audio before
+the loop point, to smooth out the loop.".to_string(),
+        Info::MuteGen => text =
+"Mute this generator while editing. Muting is not
+saved and doesn't affect exports.".to_string(),
+        Info::SoloGen => text =
+"Solo this generator while editing, along with any
+generators that modulate it. Soloing is not saved
+and doesn't affect exports.".to_string(),
         Info::Envelopes => text =
 "Envelopes modulate parameters between different
 levels over time. They have no effect unless
@@ -169,10 +534,23 @@ assigned in the mod matrix.".to_string(),
         Info::ModMatrix => text =
 "Assign modulation inputs and outputs. Modulation
 must not contain loops.".to_string(),
+        Info::VoiceMonitor => text =
+"Live values of the last note played with this
+instrument. Envelope shapes are drawn with a fixed
+sustain time.".to_string(),
         Info::Compression => text =
 "Dynamic range compression. Reduces the output level
 based on the input level. Can be used to clip peaks,
 shape transients, regulate overall volume, etc.".to_string(),
+        Info::Equalizer => text =
+"Master EQ, applied before the compressor. Each row
+sets the frequency, gain, and Q of a band. Bands
+with zero gain have no effect.".to_string(),
+        Info::EqFreq => text = "Corner or center frequency of the band.".to_string(),
+        Info::EqGain => text = "Boost or cut of the band.".to_string(),
+        Info::EqQ => text =
+"Sharpness of the band. Higher values affect a
+narrower range of frequencies.".to_string(),
         Info::Tuning => text =
 "Song tuning. Notation is always diatonic, based
 on the tuning's octave and best fifth.".to_string(),
@@ -221,10 +599,40 @@ when the input level rises.".to_string(),
         Info::CompRelease => text =
 "Approximate time the compressor takes to disengage
 when the input level falls.".to_string(),
+        Info::CompMakeup => text = "Post-compression gain.".to_string(),
+        Info::CompSidechain => text =
+"High-pass cutoff for the compressor's level
+detector, so that bass doesn't drive compression
+of the whole mix.".to_string(),
+        Info::GainReduction => text =
+"Current gain reduction by the compressor.".to_string(),
         Info::StereoWidth => text =
 "Multiplier to instrument pan values. Can be used
 to check the mono mix, or to reverse panning. Does
 not affect render output.".to_string(),
+        Info::MasterWidth => text =
+"Stereo width of the master output, applied after
+the compressor. 0% is mono and 100% is unchanged.
+Affects render output.".to_string(),
+        Info::MonoMonitor => text =
+"Sum the output to mono, to check for parts that
+cancel out. Does not affect render output.".to_string(),
+        Info::Correlation => text =
+"Phase correlation of the output channels. +1 is
+mono, 0 is unrelated, and negative values mean
+parts will cancel out when summed to mono.".to_string(),
+        Info::OutputGain => text =
+"Master volume, applied after the compressor. Does
+not affect render output.".to_string(),
+        Info::OutputMute => text =
+"Silence audio output without stopping playback.".to_string(),
+        Info::PlaybackSpeed => text =
+"Multiplier for the playback tempo, for practice or
+auditioning fast passages. Not saved, and not
+applied to WAV export.".to_string(),
+        Info::OutputMeter => text =
+"Peak level of the audio output. The meter changes
+color above 0 dB, where the output will clip.".to_string(),
         Info::Gamma => text =
 "Gamma correction. Applies a brightness curve to
 make value differences look approximately uniform.".to_string(),
@@ -234,6 +642,12 @@ at different points in the 130-180 range.".to_string(),
         Info::GlideTime => text =
 "Approximate time the patch takes to glide to new
 pitches.".to_string(),
+        Info::RandomDetune => text =
+"Maximum amount each note is detuned by, chosen at
+random when the note starts.".to_string(),
+        Info::Drift => text =
+"Depth of slow random pitch wandering, emulating
+unstable analog oscillators.".to_string(),
         Info::Distortion =>
             text = "Portion of the signal to be hard clipped.".to_string(),
         Info::FxSend =>
@@ -244,7 +658,9 @@ smaller discontinuities. Loop end point is always
 the end of the sample.".to_string(),
         Info::Tone => text =
 "For pulse waves, sets the duty cycle. For noise,
-mixes between pink and white noise.".to_string(),
+mixes between pink and white noise. For wavetables,
+sets the position in the table. For plucked strings,
+sets the brightness.".to_string(),
         Info::FreqRatio => text =
 "Frequency ratio of this generator compared to the
 base frequency of the note. Integer values give
@@ -257,6 +673,9 @@ filter.".to_string(),
         Info::FilterResonance => text =
 "How much to emphasize frequencies near the cutoff
 frequency.".to_string(),
+        Info::FilterDrive => text =
+"How hard to saturate the signal going into the
+filter.".to_string(),
         Info::Attack => text = "Time to reach initial peak level.".to_string(),
         Info::Decay =>
             text = "Time to transition between peak and sustain levels.".to_string(),
@@ -270,14 +689,48 @@ after note is released.".to_string(),
 the destination. For gain/level controls, depth
 indicates the amount of attenuation rather than
 the amount of increase.".to_string(),
+        Info::ModCurve => text =
+"Response curve applied to the source. Exp keeps
+small source values small, for finer control near
+zero.".to_string(),
+        Info::ModResponse => text =
+"Modulation amount over the range of the source,
+after depth and curve are applied.".to_string(),
         Info::LoadScale => text =
 "Load a tuning from a Scala .scl file. The tuning
 will be notated the same as an equal temperament
 with the same number of notes.".to_string(),
-        Info::SavePatch => text = "Write the selected patch to disk.".to_string(),
-        Info::LoadPatch => text = "Load patches or samples from disk.".to_string(),
+        Info::SavePatch => text =
+"Write the selected patch to disk. With multiple
+patches selected, choose a folder to save them in.".to_string(),
+        Info::PatchTemplate => text =
+"Set the selected patch as the template used by
+the Add button, or restore the default template.".to_string(),
+        Info::ModuleTemplate => text =
+"The template used by the new song command. A
+template keeps the tracks, patches, tuning, FX,
+and beat division of the module it was saved
+from, but not its pattern data.".to_string(),
+        Info::LoadPatch => text =
+"Load patches or samples from disk. Files can also
+be dropped onto this tab.".to_string(),
         Info::DuplicatePatch =>
             text = "Create a copy of the selected patch.".to_string(),
+        Info::CopyPatches => text =
+"Copy the selected patches. They can be pasted into
+this song or another open song.".to_string(),
+        Info::PastePatches =>
+            text = "Add copies of the copied patches to this song.".to_string(),
+        Info::PatchFilter => text =
+"Only list patches whose name or tags contain every
+word of the filter. The current patch is always
+listed.".to_string(),
+        Info::PatchTags => text =
+"Comma-separated tags for the current patch, for
+grouping patches with the filter.".to_string(),
+        Info::MovePatch => text =
+"Move the current patch in the list. Tracks and kit
+entries keep using the same patch.".to_string(),
         Info::LoadSample => text =
 "Load an audio file from disk. For multichannel
 audio, only the first channel will be used. Most
@@ -308,6 +761,16 @@ values.".to_string(),
 "Determines which note is mapped to the start of
 the loaded scale. For equal-step scales, this has
 no effect.".to_string(),
+        Info::RetriggerPressure => text =
+"Pressure digit of the last retriggered hit. Hits
+ramp from the current pressure to this value.
+Leave empty to keep the pressure constant.".to_string(),
+        Info::ReferenceFreq => text =
+"The frequency of A4. Other notes are tuned
+relative to it.".to_string(),
+        Info::RootFreq => text =
+"The frequency of the scale root. Setting this
+adjusts the A4 frequency to match.".to_string(),
         Info::KitNoteIn =>
             text = "The note that activates this kit mapping.".to_string(),
         Info::KitNoteOut =>
@@ -329,6 +792,18 @@ applied on a per-track basis.".to_string(),
             Action::PlayFromCursor =>
                 text = "Play/stop from the pattern cursor.".to_string(),
             Action::RenderSong => text = "Render song to WAV.".to_string(),
+            Action::RenderSelection => text =
+"Render the selected timespan to WAV. If no
+timespan is selected, render the song loop.".to_string(),
+            Action::RenderLoop => text =
+"Render one cycle of the song loop to WAV, with the
+reverb and delay tail after the End marker mixed
+into the start of the loop. The file can be looped
+seamlessly, e.g. as game music.".to_string(),
+            Action::QuickExport => text =
+"Render song to WAV in the module's folder without
+a file dialog, overwriting any file with the same
+name. The filename is set in the settings tab.".to_string(),
             Action::Undo => text = "Undo last pattern action.".to_string(),
             Action::Redo => text = "Redo last undone pattern action.".to_string(),
             Action::MixPaste => text =
@@ -349,10 +824,9 @@ Ctrl channel.".to_string(),
 "Insert a tempo change event. Tap in time to set
 tempo. Can only be placed in a Ctrl channel.".to_string(),
             Action::RationalTempo => text =
-"Insert a tempo change event. Tempo will change so
-that the selected timespan will receive the same
-time that 1 beat previously received. Can only be
-placed in a Ctrl channel.".to_string(),
+"Insert a tempo change event using the ratio chosen
+in the General tab. Can only be placed in a Ctrl
+channel.".to_string(),
             Action::InsertRows =>
                 text = "Push pattern events by inserting rows.".to_string(),
             Action::DeleteRows =>
@@ -379,8 +853,17 @@ also be held to transpose note input.".to_string(),
 "Replace the selected notes with enharmonic
 alternatives. Can also be held to remap note input.
 Enharmonic notes have unequal values in most tunings.".to_string(),
+            Action::SetLoopRange => text =
+"Loop playback over the selected timespan. The loop
+range is not saved with the song.".to_string(),
+            Action::ToggleLoopRange =>
+                text = "Enable/disable the loop range.".to_string(),
             Action::ToggleFollow => text =
 "Toggle whether the pattern view tracks the playhead.".to_string(),
+            Action::ToggleRecord => text =
+"Start or stop recording from the cursor. Input is
+written at the playhead. See the Settings tab for
+count-in, pre-roll, record mode, and punch-in.".to_string(),
             Action::SelectAllChannels =>
                 text = "Expand the pattern selection to all channels.".to_string(),
             Action::SelectAllRows =>
@@ -411,7 +894,151 @@ levels, or modulation levels. If a timespan is
 selected, interpolate over that timespan. Otherwise,
 interpolate from the cursor position to the next
 column event.".to_string(),
+            Action::CycleGlideCurve => text =
+"Cycle the interpolation curve of the glide at the
+cursor between linear, exponential, S-curve, and
+stepped. Non-linear glides are drawn dashed.".to_string(),
+            Action::InsertRetrigger => text =
+"Insert a retrigger event in the note column, using
+the settings in the retrigger section of the
+General tab. The channel's last note is repeated
+the given number of times within the row.".to_string(),
+            Action::InsertNoteDelay => text =
+"Insert a note delay at the cursor. The note or
+note off on the same row starts late by the offset
+set in the note timing section of the General
+tab. Delays are drawn as a line inside the row.".to_string(),
+            Action::InsertNoteCut => text =
+"Insert a note cut at the cursor. The channel's note
+is released after the offset set in the note
+timing section of the General tab. Cuts are drawn
+as a line inside the row.".to_string(),
+            Action::InsertBendRamp => text =
+"Insert a bend ramp event in the note column, using
+the settings in the bend ramp section of the
+General tab. The bend moves from its current value
+to the target offset over the ramp length.".to_string(),
+            Action::InsertVibrato => text =
+"Insert a vibrato event in the note column, using
+the settings in the vibrato & tremolo section of
+the General tab. Vibrato applies to all notes in
+the channel until changed; depth 0 turns it off.".to_string(),
+            Action::InsertTremolo => text =
+"Insert a tremolo event in the note column, using
+the settings in the vibrato & tremolo section of
+the General tab. Tremolo applies to all notes in
+the channel until changed; depth 0 turns it off.".to_string(),
             Action::MuteTrack => text = "Toggle muting the current track.".to_string(),
+            Action::FreezeTrack => text =
+"Toggle freezing the current track. A frozen track
+is rendered in the background and played back as
+audio to save CPU. Editing the track's pattern
+unfreezes it.".to_string(),
+            Action::CopyPatternText => text =
+"Copy the pattern selection to the system clipboard
+as plain text. If nothing is selected, copies the
+whole pattern.".to_string(),
+            Action::PastePatternText => text =
+"Insert pattern text from the system clipboard at
+the cursor.".to_string(),
+            Action::ExportPatternText => text =
+"Save the pattern selection to a text file. If
+nothing is selected, saves the whole pattern.".to_string(),
+            Action::ImportPatternText => text =
+"Insert pattern text from a file at the cursor.".to_string(),
+            Action::ImportMidi => text =
+"Browse for a standard MIDI file and add a track for
+each MIDI track and channel with notes. Notes are
+mapped to the nearest pitch in the module tuning.".to_string(),
+            Action::HoldNotes => text =
+"While held, keeps keyjazz notes on after their keys
+are released, like a sustain pedal.".to_string(),
+            Action::ToggleLatch => text =
+"Toggle keyjazz latch mode. See the Settings tab
+for details.".to_string(),
+            Action::MoveEnd => text =
+"Replace all End events with a single End event at
+the cursor.".to_string(),
+            Action::TrimToEnd => text =
+"Delete all pattern events after the first End
+event.".to_string(),
+            Action::LoopSelection => text =
+"Replace all Loop and End events with a Loop event
+at the start of the selection and an End event at
+the end of the selection.".to_string(),
+            Action::CheckControlEvents => text =
+"Check Loop and End events for likely mistakes,
+such as multiple End events or a Loop event after
+the End.".to_string(),
+            Action::RecordMacro => text =
+"Start or stop recording a macro. Pattern editing
+commands and typed values are recorded until
+recording is stopped.".to_string(),
+            Action::PlayMacro => text =
+"Replay the last recorded macro, repeated the
+number of times set in the General tab.".to_string(),
+            Action::PadSong => text =
+"Move the End event later by the number of bars set
+in the General tab. If the song has no End event,
+add one after the last event.".to_string(),
+            Action::RunScript => text =
+"Browse for a Rhai script and run it over the pattern
+selection. The script's changes can be undone in one
+step.".to_string(),
+            Action::RerunScript =>
+                text = "Run the last script again.".to_string(),
+            Action::TuningChange => text =
+"Insert an event that switches to another module
+tuning. In the global track, the change applies to
+all tracks; otherwise, only to its own track. Press
+again to cycle through tunings.".to_string(),
+            Action::ToggleFullscreen =>
+                text = "Switch between fullscreen and windowed mode.".to_string(),
+            Action::OpenRecent =>
+                text = "Load a recently opened song.".to_string(),
+            Action::ToggleStepRecord => text =
+"Toggle step record. MIDI notes are written at the
+cursor, which then advances by the step size set in
+the General tab. Notes played together are written
+in following channels.".to_string(),
+            Action::NewTab => text =
+"Open a new song in another tab. Patterns and
+patches can be copied and pasted between songs.
+Only the current song plays.".to_string(),
+            Action::CloseTab => text = "Close the current song tab.".to_string(),
+            Action::ModulateParameter => text =
+"Add a mod matrix route targeting the instrument
+parameter under the mouse, and focus its source.".to_string(),
+            Action::CommandPalette => text =
+"Open a searchable list of key commands, tracks,
+markers, and recent songs. Type a bar:beat:row
+position or a time to jump there.".to_string(),
+            Action::RecoverSong => text =
+"Load the readable parts of a damaged song file.
+Unreadable sections are replaced with defaults.".to_string(),
+            Action::ZoomInRows => text =
+"Increase the height of pattern rows. Ctrl+Shift+
+scroll also zooms.".to_string(),
+            Action::ZoomOutRows => text =
+"Decrease the height of pattern rows. Rows shorter
+than text show events as bars, for an overview.
+Ctrl+Shift+scroll also zooms.".to_string(),
+            Action::SendTuning => text =
+"Send the song tuning to the MIDI output as a MIDI
+Tuning Standard bulk dump. Key 60 plays C4, and
+each key is one step of the tuning.".to_string(),
+            Action::GenerateMelody => text =
+"Fill the note columns of the pattern selection
+with random notes, using the settings in the
+melody generator section of the General tab.".to_string(),
+            Action::ScaleValues => text =
+"Scale and offset the pressure and modulation
+values in the pattern selection, using the
+settings in the value scaling section of the
+General tab.".to_string(),
+            Action::CollapseTrack => text =
+"Show/hide the pressure and modulation columns of
+the current track, to fit more tracks on screen.".to_string(),
             Action::SoloTrack => text =
 "Toggle muting all tracks except for the current
 track.".to_string(),
@@ -432,7 +1059,9 @@ the selected timespan.".to_string(),
             Action::StopPlayback => text = "Stop song playback.".to_string(),
             Action::NewSong =>
                 text = "Close the open song and start a new one.".to_string(),
-            Action::OpenSong => text = "Load a song from disk.".to_string(),
+            Action::OpenSong => text =
+"Load a song from disk. Songs can also be dropped
+onto the window.".to_string(),
             Action::SaveSong => text =
 "Save the open song, using the path it was last
 saved to or loaded from.".to_string(),
@@ -458,6 +1087,9 @@ track channels.".to_string(),
             Action::PrevTab => text = "View the previous UI tab.".to_string(),
             Action::UnmuteAllTracks => text = "Unmute all muted tracks.".to_string(),
             Action::Quit => text = "Close the program.".to_string(),
+            Action::ImportModule => text =
+"Choose patches, kit mappings, and tracks from
+another song to merge into the open song.".to_string(),
         }
         Info::GlobalTrack =>
             text = "Holds control events like tempo, loop, and end.".to_string(),
@@ -465,6 +1097,83 @@ track channels.".to_string(),
 "Uses the patch & note mappings from the Kit entry
 in the Instruments tab.".to_string(),
         Info::MidiInput => text = "MIDI input to use for note input.".to_string(),
+        Info::MidiOutput => text =
+"MIDI output to send the song tuning to, so that
+external synthesizers can follow it.".to_string(),
+        Info::MidiSendTuning => text =
+"If enabled, send the song tuning to the MIDI output
+as a MIDI Tuning Standard bulk dump whenever it
+changes.".to_string(),
+        Info::MidiChannelRouting => text =
+"Track played and recorded by each MIDI input
+channel. Track 0 follows the pattern cursor, like
+the computer keyboard. Routed events are recorded
+in the first channel of their track.".to_string(),
+        Info::MidiKeySplit => text =
+"Splits the keyboard on a MIDI input channel so
+that each hand can play a different track. Keys
+below the split point (60 is middle C) play the
+split track instead of the channel's track. A
+split point of 0 disables the split. Controllers
+and bend still follow the channel's track.".to_string(),
+        Info::RenderSeed => text =
+"Seed for random voice values, such as detune,
+drift, noise, and random modulation, in renders.
+Renders with the same seed are identical. Live
+playback is random regardless of the seed.".to_string(),
+        Info::MacroCcMode => text =
+"How values of each macro controller (CC 41-48)
+are read. Absolute suits knobs and faders. The
+relative modes suit endless encoders, which send
+steps up or down from the current value.".to_string(),
+        Info::ProgramRemapsTrack => text =
+"If enabled, MIDI program changes set the patch of
+the track they're routed to, when it's a patch
+track. Otherwise, program changes select a patch in
+the instruments tab. Program 0 is the kit or no
+patch; program 1 is the first patch.".to_string(),
+        Info::RecentSongs => text =
+"Load a recently opened or saved song.".to_string(),
+        Info::ClipLibrary => text =
+"Pattern selections saved for reuse in any song.
+Clips are stored in the clips folder next to the
+program.".to_string(),
+        Info::ClipName => text =
+"Name to save the pattern selection under.".to_string(),
+        Info::SaveClip => text =
+"Save the pattern selection to the clip library,
+replacing any clip with the same name. If nothing
+is selected, the whole pattern is saved.".to_string(),
+        Info::PasteClip => text =
+"Insert the clip at the pattern cursor. Notes are
+moved to the nearest pitches in the tuning at the
+cursor if it differs from the clip's.".to_string(),
+        Info::RestoreSession => text =
+"If enabled, reopen the last song on startup, along
+with the window size, selected tab, pattern cursor,
+and scroll positions.".to_string(),
+        Info::AnnounceStatus => text =
+"If enabled, write status messages to status.txt
+in the program folder as they happen, for use with
+a screen reader. The file is cleared each session.
+Messages include the selected tab, focused control,
+pattern cursor location, and reported errors.
+
+Tab and Shift+Tab move focus between controls.
+Enter or Space activates the focused control.".to_string(),
+        Info::ErrorAlerts => text =
+"If enabled, errors open a dialog that must be
+dismissed. Otherwise, errors are shown briefly in
+the info box. Either way, errors are recorded in
+the message log on the general tab.".to_string(),
+        Info::QuickExportName => text =
+"Filename template for quick export. {title} is the
+song title, or the module filename if the title is
+empty. {file} is the module filename. {date} and
+{time} are the current UTC date and time.".to_string(),
+        Info::MessageLog => text =
+"Errors and notifications from this session, newest
+first. Times are since startup.".to_string(),
         Info::SpatialFxType => text =
 "Type of global spatial FX to use. Individual send
 levels can be set in patch settings.".to_string(),
@@ -473,7 +1182,16 @@ levels can be set in patch settings.".to_string(),
 "Waveform used by the generator. S&H is periodically
 sampled white noise. For generators, Noise is pink
 to white noise based on the Tone control. For LFOs,
-Noise is brown noise.".to_string(),
+Noise is brown noise. Wavetable loads a file of
+2048-sample single-cycle frames. Additive sums up
+to 32 sine partials. Pluck is a plucked string
+whose brightness is set by the Tone control.".to_string(),
+        Info::Partials => text =
+"Levels of an additive generator's partials. Click
+or drag to draw levels.".to_string(),
+        Info::RetunePartials => text =
+"Move partials from the harmonic series to the
+nearest notes in the module tuning.".to_string(),
         Info::GenOutput => text =
 "The destination for this generator's signal. The
 signal can be mixed with the final outputs of other
@@ -489,7 +1207,10 @@ used to modulate the previous generator.
   would create.".to_string(),
         Info::FilterType => text =
 "Filter type. Ladder is 24 dB/oct and can self-
-oscillate; other filters are 12 dB/oct.".to_string(),
+oscillate. Lowpass and highpass filters come in 12
+and 24 dB/oct slopes. Formant is a vowel filter;
+its cutoff sweeps through the vowels A, E, I, O,
+and U.".to_string(),
         Info::FilterKeytrack => text =
 "How much the filter cutoff follows the fundamental
 of the note. The break-even point for key tracking
@@ -500,6 +1221,42 @@ operate in the range 0..1, but LFOs oscillate in
 the range -1..1.".to_string(),
         Info::ModDest => text = "The modulated parameter.".to_string(),
         Info::TrackPatch => text = "The patch controlled by this track.".to_string(),
+        Info::GhostTrack => text =
+"Another track whose notes are shown faintly behind
+this track's, for lining up parts.".to_string(),
+        Info::TrackName => text = "A name for this track. Leave empty to show
+only the patch name.".to_string(),
+        Info::TrackColor => text = "A color label for this track. Colored tracks
+are tinted in the pattern background.".to_string(),
+        Info::AuditionOnly => text =
+"If enabled, this track is left out of WAV export,
+for scratch and reference parts.".to_string(),
+        Info::SongPosition => text =
+"Cursor position as bar:beat:row and as playback
+time (m:ss.mmm). Enter a position in either box
+to move the cursor there. Time is derived from
+tempo events.".to_string(),
+        Info::TrackScale => text =
+"Scales this track's pressure and modulation
+events in playback, from 0 to 200%. Pattern data
+is unchanged. Scaled values are capped at F.".to_string(),
+        Info::TrackOffset => text =
+"Delays this track's playback by a number of
+milliseconds, from -500 to 500. Negative values
+play early, to compensate for slow attacks or
+external latency.".to_string(),
+        Info::AutoPan => text =
+"Sweep this track's output between left and right
+in sync with the tempo. A cycle lasts the given
+number of beats, and phase shifts the sweep by a
+fraction of a cycle.".to_string(),
+        Info::TrackTone => text =
+"High-pass and low-pass filter cutoffs for this
+track's output, from 20 to 20000 Hz. Enter - to
+turn a filter off.".to_string(),
+        Info::MuteAuditionTracks => text =
+"If enabled, tracks marked as audition only are
+muted when playback starts.".to_string(),
         Info::SmoothPlayhead => text =
 "If disabled, playhead visual and pattern follow
 will be quantized to the nearest row.".to_string(),
@@ -507,16 +1264,20 @@ will be quantized to the nearest row.".to_string(),
 "Pressure column.
 
 0..F - Enter digit
-Shift+0..F - Track enter digit".to_string(),
+Shift+0..F - Track enter digit
+Ctrl+drag - Paint values, higher toward the right".to_string(),
         Info::ModulationColumn => text =
 "Modulation column.
 
 0..F - Enter digit
-Shift+0..F - Track enter digit".to_string(),
+Shift+0..F - Track enter digit
+Ctrl+drag - Paint values, higher toward the right".to_string(),
         Info::ControlColumn => {
             text =
 "Control column. Type to enter BPM values (ex. 120)
-or tempo ratios (ex. 3:2 or 3/2).".to_string();
+or tempo ratios (ex. 3:2 or 3/2). Type M and a
+digit for modulation of all tracks (ex. M8), which
+can be glided. Ctrl+drag an event to move it.".to_string();
             actions =
                 vec![Action::TapTempo, Action::Loop, Action::End];
         },
@@ -528,7 +1289,9 @@ or tempo ratios (ex. 3:2 or 3/2).".to_string();
             text = format!(
 "Note column.
 
-{}..{} - Enter note", first_note, last_note);
+{}..{} - Enter note
+Ctrl+click - Place last note
+Ctrl+drag - Move event", first_note, last_note);
             custom_actions = true;
             actions = vec![Action::NoteOff, Action::CycleNotation, Action::UseLastNote];
         },
@@ -553,7 +1316,9 @@ or tempo ratios (ex. 3:2 or 3/2).".to_string();
     match ctrl {
         ControlInfo::None => (),
         ControlInfo::Slider => {
-            text.push_str("Right-click slider to edit value as text.")
+            text.push_str(
+"Double-click or right-click slider to type a value.
+Values can include units such as Hz, ms, or cents.")
         }
         ControlInfo::Note => {
             text.push_str(
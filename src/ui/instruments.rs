@@ -1,20 +1,46 @@
+use std::{collections::{BTreeSet, HashMap}, error::Error, path::Path};
+
+use fundsp::math::{amp_db, db_amp};
 use lfo::{AR_RATE_MULTIPLIER, LFO, MAX_LFO_RATE, MIN_LFO_RATE};
 use macroquad::input::{KeyCode, is_key_pressed};
 use pcm::PcmData;
 
-use crate::{config::{self, Config}, module::{Edit, Module}, playback::Player, synth::*};
+use crate::{config::{self, Config}, gainstage::{self, LevelReport}, module::{Edit, EventData, Module}, pitch::{Note, PitchDisplay, Tuning}, playback::Player, synth::*};
 
-use super::{info::Info, Layout, Ui};
+use super::{info::Info, labeled_group, Layout, Ui};
 
 // for file dialogs
 const PATCH_FILTER_NAME: &str = "Instrument";
 const PATCH_FILTER_EXT: &str = "oscins";
 
+/// Gain change of the bulk level buttons, in dB.
+const LEVEL_STEP_DB: f32 = 1.0;
+
+const MAX_SAMPLE_ZOOM: f32 = 256.0;
+const MAX_CROSSFADE_MS: f32 = 500.0;
+
+/// Width of kit name boxes. Only the first 4 characters show in patterns.
+const KIT_NAME_CHARS: usize = 8;
+
 /// State for the instruments tab UI.
 pub struct InstrumentsState {
-    scroll: f32,
+    pub scroll: f32,
     /// If None, kit is selected.
     pub patch_index: Option<usize>,
+    /// List indices selected in addition to the current patch.
+    selection: BTreeSet<usize>,
+    /// Results of the last level analysis.
+    level_reports: Vec<LevelReport>,
+    /// Waveform views of PCM generators, by generator index.
+    sample_views: HashMap<usize, SampleView>,
+    /// Patches waiting for kit input notes in learn mode.
+    kit_learn: Vec<usize>,
+    /// First input note for kit auto-fill.
+    kit_fill_note: Note,
+    /// Copied patches, which can be pasted into any open song.
+    patch_clipboard: Vec<Patch>,
+    /// Query that patches in the list must match.
+    patch_filter: String,
 }
 
 impl InstrumentsState {
@@ -22,10 +48,39 @@ impl InstrumentsState {
         Self {
             scroll: 0.0,
             patch_index,
+            selection: BTreeSet::new(),
+            level_reports: Vec::new(),
+            sample_views: HashMap::new(),
+            kit_learn: Vec::new(),
+            kit_fill_note: Note::default(),
+            patch_clipboard: Vec::new(),
+            patch_filter: String::new(),
         }
     }
 }
 
+/// Visible range of a sample waveform.
+struct SampleView {
+    zoom: f32,
+    /// Position of the visible range, from 0 to 1.
+    pos: f32,
+}
+
+impl Default for SampleView {
+    fn default() -> Self {
+        Self { zoom: 1.0, pos: 0.0 }
+    }
+}
+
+impl SampleView {
+    /// Returns the visible range of frames in a sample of `len` frames.
+    fn range(&self, len: usize) -> std::ops::Range<usize> {
+        let width = ((len as f32 / self.zoom) as usize).clamp(len.min(1), len);
+        let start = ((len - width) as f32 * self.pos) as usize;
+        start..start + width
+    }
+}
+
 pub fn draw(ui: &mut Ui, module: &mut Module, state: &mut InstrumentsState,
     cfg: &mut Config, player: &mut Player
 ) {
@@ -40,15 +95,42 @@ pub fn draw(ui: &mut Ui, module: &mut Module, state: &mut InstrumentsState,
     ui.cursor_y -= state.scroll;
     ui.cursor_z -= 1;
 
-    patch_list(ui, module, &mut state.patch_index, cfg, player);
+    patch_list(ui, module, &mut state.patch_index, &mut state.selection,
+        &mut state.level_reports, &mut state.patch_clipboard, &mut state.patch_filter,
+        cfg, player);
     ui.space(1.0);
     ui.start_group();
-    if let Some(index) = &state.patch_index {
-        if let Some(patch) = module.patches.get_mut(*index) {
-            patch_controls(ui, patch, cfg, player);
+    if let Some(index) = state.patch_index {
+        if let Some(patch) = module.patches.get_mut(index) {
+            ui.take_edited();
+            patch_controls(ui, patch, &module.tuning, cfg, player,
+                &mut state.sample_views);
+            // shared parameters are read by voices as they play, so only
+            // other edits can change the DSP structure of pooled voices
+            let mut structure_edited = ui.structure_edited();
+            let mut edited = ui.take_edited();
+
+            if let Some(target) = ui.take_modulated_slider()
+                .and_then(|id| slider_mod_target(&id)) {
+                if patch.mod_targets().contains(&target) {
+                    patch.mod_matrix.push(Modulation { target, ..Default::default() });
+                    ui.focus(&format!("mod_{}_source", patch.mod_matrix.len() - 1));
+                    ui.notify(format!("Added modulation of {target}."));
+                    edited = true;
+                    structure_edited = true;
+                }
+            }
+
+            if structure_edited {
+                patch.invalidate_voice_pool();
+            }
+
+            if edited {
+                module.patch_edited(index);
+            }
         }
     } else {
-        kit_controls(ui, module, player);
+        kit_controls(ui, module, player, cfg.pitch_display, state);
     }
 
     ui.cursor_z += 1;
@@ -60,52 +142,97 @@ pub fn draw(ui: &mut Ui, module: &mut Module, state: &mut InstrumentsState,
 }
 
 fn patch_list(ui: &mut Ui, module: &mut Module, patch_index: &mut Option<usize>,
-    cfg: &mut Config, player: &mut Player
+    selection: &mut BTreeSet<usize>, reports: &mut Vec<LevelReport>,
+    clipboard: &mut Vec<Patch>, filter: &mut String, cfg: &mut Config, player: &mut Player
 ) {
     ui.start_group();
 
     let mut edits = Vec::new();
     let patches = &mut module.patches;
 
-    let mut names = vec![String::from("Kit")];
-    names.extend(patches.iter().map(|x| x.name.clone()));
+    if let Some(s) = ui.edit_box("Filter", 10, filter.clone(), Info::PatchFilter) {
+        *filter = s;
+        selection.clear();
+    }
 
-    let mut list_index = patch_index.map(|i| i + 1).unwrap_or_default();
-    if let Some(s) = ui.instrument_list(&names, &mut list_index, 10) {
-        if list_index > 0 {
-            if let Some(patch) = patches.get_mut(list_index - 1) {
-                patch.name = s;
-            }
+    // the current patch stays listed even if it doesn't match the filter
+    let visible: Vec<_> = (0..patches.len())
+        .filter(|&i| Some(i) == *patch_index || patches[i].matches(filter))
+        .collect();
+    let mut names = vec![String::from("Kit")];
+    names.extend(visible.iter().map(|&i| patches[i].name.clone()));
+
+    // list rows are the kit followed by visible patches, while the selection
+    // is stored by unfiltered list index
+    let row_of = |i: usize| visible.iter().position(|&j| j == i).map(|row| row + 1);
+    let patch_of = |row: usize| row.checked_sub(1).map(|row| visible[row]);
+    let mut list_index = patch_index.and_then(row_of).unwrap_or_default();
+    let mut rows: BTreeSet<_> = selection.iter()
+        .filter_map(|&i| i.checked_sub(1).map_or(Some(0), row_of))
+        .collect();
+    if let Some(s) = ui.instrument_list(&names, &mut list_index, &mut rows, 10) {
+        if let Some(patch) = patch_of(list_index).and_then(|i| patches.get_mut(i)) {
+            patch.name = s;
+        }
+    }
+    *selection = rows.into_iter().map(|row| patch_of(row).map_or(0, |i| i + 1)).collect();
+    *patch_index = patch_of(list_index);
+    let selected = selected_patches(*patch_index, selection, patches.len());
+
+    if let Some(patch) = patch_index.and_then(|i| patches.get_mut(i)) {
+        if let Some(s) = ui.edit_box("Tags", 20, patch.tags.join(", "), Info::PatchTags) {
+            patch.tags = s.split(',')
+                .map(|tag| tag.trim().to_owned())
+                .filter(|tag| !tag.is_empty())
+                .collect();
         }
     }
-    *patch_index = match list_index {
-        0 => None,
-        i => Some(i - 1),
-    };
 
     ui.start_group();
     if ui.button("Add", true, Info::Add("a new patch with default settings")) {
         let mut name = String::from("Init");
         let mut i = 0;
-        while names.contains(&name) {
+        while patches.iter().any(|p| p.name == name) {
             i += 1;
             name = format!("Init {}", i);
         }
-        edits.push(Edit::InsertPatch(patches.len(), Patch::new(name)));
+        edits.push(Edit::InsertPatch(patches.len(), new_patch(ui, name)));
         *patch_index = Some(patches.len());
     }
 
-    if ui.button("Remove", patch_index.is_some(), Info::Remove("the selected patch")) {
-        if let Some(index) = patch_index {
-            edits.push(Edit::RemovePatch(*index));
+    if ui.button("Remove", !selected.is_empty(), Info::Remove("the selected patches")) {
+        // remove from the end so that earlier indices stay valid
+        edits.push(Edit::Group(selected.iter().rev()
+            .map(|&i| Edit::RemovePatch(i))
+            .collect()));
+        selection.clear();
+    }
+    ui.end_group();
+
+    ui.start_group();
+    if ui.button("Move up", patch_index.is_some_and(|i| i > 0), Info::MovePatch) {
+        if let Some(i) = *patch_index {
+            edits.push(Edit::MovePatch(i, i - 1));
+            *patch_index = Some(i - 1);
+            selection.clear();
+        }
+    }
+    if ui.button("Move down", patch_index.is_some_and(|i| i + 1 < patches.len()),
+        Info::MovePatch) {
+        if let Some(i) = *patch_index {
+            edits.push(Edit::MovePatch(i, i + 1));
+            *patch_index = Some(i + 1);
+            selection.clear();
         }
     }
     ui.end_group();
 
     ui.start_group();
     let patches = &mut module.patches;
-    if ui.button("Save", patch_index.is_some(), Info::SavePatch) {
-        if let Some(patch) = patch_index.map(|i| patches.get(i)).flatten() {
+    if ui.button("Save", !selected.is_empty(), Info::SavePatch) {
+        if selected.len() > 1 {
+            save_patches(ui, patches, &selected, cfg, player);
+        } else if let Some(patch) = patch_index.map(|i| patches.get(i)).flatten() {
             let dialog = super::new_file_dialog(player)
                 .add_filter(PATCH_FILTER_NAME, &[PATCH_FILTER_EXT])
                 .set_directory(cfg.patch_folder.clone().unwrap_or(String::from(".")))
@@ -129,14 +256,7 @@ fn patch_list(ui: &mut Ui, module: &mut Module, patch_index: &mut Option<usize>,
         if let Some(paths) = dialog.pick_files() {
             for (i, path) in paths.iter().enumerate() {
                 cfg.patch_folder = config::dir_as_string(path);
-                let patch = if path.extension().and_then(|s| s.to_str())
-                    .is_some_and(|s| s == PATCH_FILTER_EXT)
-                {
-                    Patch::load(path)
-                } else {
-                    Patch::load_sample(path)
-                };
-                match patch {
+                match load_patch_file(path) {
                     Ok(p) => {
                         edits.push(Edit::InsertPatch(patches.len() + i, p));
                         *patch_index = Some(patches.len() + i);
@@ -156,6 +276,86 @@ fn patch_list(ui: &mut Ui, module: &mut Module, patch_index: &mut Option<usize>,
         }
     }
 
+    ui.start_group();
+    if ui.button("Copy", !selected.is_empty(), Info::CopyPatches) {
+        *clipboard = selected.iter().map(|&i| patches[i].clone()).collect();
+    }
+    if ui.button("Paste", !clipboard.is_empty(), Info::PastePatches) {
+        for (i, patch) in clipboard.iter().enumerate() {
+            edits.push(Edit::InsertPatch(patches.len() + i, patch.clone()));
+        }
+        *patch_index = Some(patches.len());
+    }
+    ui.end_group();
+
+    ui.start_group();
+    for (label, db) in [("Level -", -LEVEL_STEP_DB), ("Level +", LEVEL_STEP_DB)] {
+        if ui.button(label, !selected.is_empty(), Info::BulkLevel) {
+            edits.push(Edit::PatchGains(selected.iter().map(|&i| {
+                let gain = patches[i].gain.0.value();
+                (i, (gain * db_amp(db)).min(MAX_PATCH_GAIN))
+            }).collect()));
+        }
+    }
+    ui.end_group();
+
+    if selected.len() > 1 {
+        ui.start_group();
+        if let Some(tag) = ui.edit_box("Add tag", 12, String::new(), Info::BulkTags) {
+            let tag = tag.trim();
+            for &i in &selected {
+                let tags = &mut patches[i].tags;
+                if !tag.is_empty() && !tags.iter().any(|t| t == tag) {
+                    tags.push(tag.to_owned());
+                }
+            }
+        }
+        if let Some(tag) = ui.edit_box("Remove tag", 12, String::new(), Info::BulkTags) {
+            for &i in &selected {
+                patches[i].tags.retain(|t| t != tag.trim());
+            }
+        }
+        ui.end_group();
+    }
+
+    ui.start_group();
+    let template_path = config::patch_template_path();
+    if ui.button("Set as Init", patch_index.is_some(), Info::PatchTemplate) {
+        if let Some(patch) = patch_index.and_then(|i| patches.get(i)) {
+            match patch.save(&template_path) {
+                Ok(_) => ui.notify(String::from("Saved patch template.")),
+                Err(e) => ui.report(format!("Error saving patch template: {e}")),
+            }
+        }
+    }
+    if ui.button("Reset Init", template_path.exists(), Info::PatchTemplate) {
+        match std::fs::remove_file(&template_path) {
+            Ok(_) => ui.notify(String::from("Reset patch template.")),
+            Err(e) => ui.report(format!("Error removing patch template: {e}")),
+        }
+    }
+    ui.end_group();
+
+    if ui.button("Analyze levels", !patches.is_empty(), Info::AnalyzeLevels) {
+        let indices: Vec<_> = if selected.len() > 1 {
+            selected.clone()
+        } else {
+            (0..patches.len()).collect()
+        };
+        *reports = gainstage::analyze(patches, &indices);
+    }
+
+    if !reports.is_empty() {
+        if let Some(edit) = level_report(ui, patches, reports) {
+            edits.push(edit);
+        }
+    }
+
+    // indices in the reports would be stale after an edit
+    if !edits.is_empty() {
+        reports.clear();
+    }
+
     for edit in edits {
         module.push_edit(edit);
         fix_patch_index(patch_index, module.patches.len());
@@ -164,6 +364,123 @@ fn patch_list(ui: &mut Ui, module: &mut Module, patch_index: &mut Option<usize>,
     ui.end_group();
 }
 
+/// Draw the results of a level analysis. Returns an edit if trims are applied.
+fn level_report(ui: &mut Ui, patches: &[Patch], reports: &mut Vec<LevelReport>
+) -> Option<Edit> {
+    ui.start_group();
+    labeled_group(ui, "Patch", Info::AnalyzeLevels, |ui| {
+        for r in reports.iter() {
+            let name = patches.get(r.patch_index).map(|p| p.name.as_str());
+            ui.offset_label(name.unwrap_or_default(), Info::None);
+        }
+    });
+    labeled_group(ui, "Peak", Info::AnalyzeLevels, |ui| {
+        for r in reports.iter() {
+            ui.offset_label(&format!("{:.1} dB", r.peak_db), Info::None);
+        }
+    });
+    labeled_group(ui, "RMS", Info::AnalyzeLevels, |ui| {
+        for r in reports.iter() {
+            ui.offset_label(&format!("{:.1} dB", r.rms_db), Info::None);
+        }
+    });
+    labeled_group(ui, "Trim", Info::AnalyzeLevels, |ui| {
+        for r in reports.iter() {
+            let gain = patches.get(r.patch_index).map(|p| p.gain.0.value());
+            let s = match (r.trim, gain) {
+                (Some(trim), Some(gain)) if gain > 0.0 =>
+                    format!("{:+.1} dB", amp_db(trim / gain)),
+                _ => String::from("-"),
+            };
+            ui.offset_label(&s, Info::None);
+        }
+    });
+    ui.end_group();
+
+    let mut edit = None;
+    ui.start_group();
+    if ui.button("Apply trims", true, Info::AnalyzeLevels) {
+        edit = Some(Edit::PatchGains(reports.iter()
+            .filter(|r| r.patch_index < patches.len())
+            .filter_map(|r| r.trim.map(|trim| (r.patch_index, trim)))
+            .collect()));
+    }
+    if ui.button("Dismiss", true, Info::None) {
+        reports.clear();
+    }
+    ui.end_group();
+    edit
+}
+
+/// Returns the sorted indices of selected patches, given the current patch and
+/// additionally selected list items. List item 0 is the kit.
+fn selected_patches(patch_index: Option<usize>, selection: &BTreeSet<usize>, len: usize
+) -> Vec<usize> {
+    let mut v: Vec<_> = selection.iter()
+        .filter_map(|i| i.checked_sub(1))
+        .chain(patch_index)
+        .filter(|&i| i < len)
+        .collect();
+    v.sort();
+    v.dedup();
+    v
+}
+
+/// Browse for a folder and save patches to it, named after the patches.
+fn save_patches(ui: &mut Ui, patches: &[Patch], indices: &[usize], cfg: &mut Config,
+    player: &mut Player
+) {
+    let dialog = super::new_file_dialog(player)
+        .set_directory(cfg.patch_folder.clone().unwrap_or(String::from(".")));
+
+    if let Some(dir) = dialog.pick_folder() {
+        cfg.patch_folder = Some(dir.to_string_lossy().to_string());
+        let mut saved = 0;
+        for patch in indices.iter().filter_map(|&i| patches.get(i)) {
+            let path = dir.join(&patch.name).with_extension(PATCH_FILTER_EXT);
+            match patch.save(&path) {
+                Ok(_) => saved += 1,
+                Err(e) => ui.report(format!("Error saving {}: {e}", patch.name)),
+            }
+        }
+        ui.notify(format!("Saved {saved} patches."));
+    }
+}
+
+/// Returns a new patch from the saved template, or with default settings if
+/// there is no template.
+fn new_patch(ui: &mut Ui, name: String) -> Patch {
+    let path = config::patch_template_path();
+    let mut patch = if path.exists() {
+        Patch::load(&path).unwrap_or_else(|e| {
+            ui.report(format!("Error loading patch template: {e}"));
+            Patch::new(String::new())
+        })
+    } else {
+        Patch::new(String::new())
+    };
+    patch.name = name;
+    patch
+}
+
+/// Returns true if `path` has a patch or sample file extension.
+pub fn is_patch_file(path: &Path) -> bool {
+    is_patch_ext(path) || PcmData::can_load_path(path)
+}
+
+fn is_patch_ext(path: &Path) -> bool {
+    path.extension().and_then(|s| s.to_str()).is_some_and(|s| s == PATCH_FILTER_EXT)
+}
+
+/// Loads a patch, or a new patch from a sample file.
+pub fn load_patch_file(path: &Path) -> Result<Patch, Box<dyn Error>> {
+    if is_patch_ext(path) {
+        Patch::load(path)
+    } else {
+        Patch::load_sample(path)
+    }
+}
+
 /// Correct the patch index if it's out of bounds.
 pub fn fix_patch_index(index: &mut Option<usize>, len: usize) {
     if len == 0 {
@@ -173,7 +490,9 @@ pub fn fix_patch_index(index: &mut Option<usize>, len: usize) {
     }
 }
 
-fn kit_controls(ui: &mut Ui, module: &mut Module, player: &mut Player) {
+fn kit_controls(ui: &mut Ui, module: &mut Module, player: &mut Player,
+    pitch_display: PitchDisplay, state: &mut InstrumentsState
+) {
     if !module.kit.is_empty() {
         ui.start_group();
         let mut removed_index = None;
@@ -208,16 +527,31 @@ fn kit_controls(ui: &mut Ui, module: &mut Module, player: &mut Player) {
             }
         });
 
+        labeled_group(ui, "Name", Info::KitName, |ui| {
+            for (i, entry) in module.kit.iter_mut().enumerate() {
+                if let Some(s) = ui.id_edit_box(&format!("kit_{}_name", i), "",
+                    KIT_NAME_CHARS, entry.name.clone(), Info::KitName) {
+                    entry.name = s;
+                }
+            }
+        });
+
         labeled_group(ui, "Note out", Info::KitNoteOut, |ui| {
             for (i, entry) in module.kit.iter_mut().enumerate() {
                 let label = format!("kit_{}_output", i);
+                ui.start_group();
                 let key = ui.note_input(&label, &mut entry.patch_note, Info::KitNoteOut);
                 if let Some(key) = key {
                     if let Some(patch) = module.patches.get(entry.patch_index) {
-                        let pitch = module.tuning.midi_pitch(&entry.patch_note);
+                        let pitch = module.play_pitch(&module.tuning, &entry.patch_note,
+                            true);
                         player.note_on(0, key, pitch, None, patch);
                     }
                 }
+                if let Some(s) = module.tuning.pitch_readout(&entry.patch_note, pitch_display) {
+                    ui.offset_label(&s, Info::PitchDisplay);
+                }
+                ui.end_group();
             }
         });
 
@@ -238,31 +572,68 @@ fn kit_controls(ui: &mut Ui, module: &mut Module, player: &mut Player) {
     if ui.button("+", !module.patches.is_empty(), Info::Add("a new mapping")) {
         module.kit.push(Default::default());
     }
+
+    // bulk mapping applies to the selected patches, or all if none
+    let mut targets = selected_patches(None, &state.selection, module.patches.len());
+    if targets.is_empty() {
+        targets = (0..module.patches.len()).collect();
+    }
+
+    ui.vertical_space();
+    ui.start_group();
+    ui.note_input("kit_fill_note", &mut state.kit_fill_note, Info::KitAutoFill);
+    if ui.button("Auto-fill", !targets.is_empty(), Info::KitAutoFill) {
+        module.fill_kit(&targets, state.kit_fill_note);
+        state.kit_learn.clear();
+    }
+    ui.end_group();
+
+    // learn mode assigns played notes to patches in order
+    state.kit_learn.retain(|&i| i < module.patches.len());
+    if !ui.accepting_note_input() {
+        for (_, data) in &ui.note_queue {
+            if let (EventData::Pitch(note), Some(&i)) = (data, state.kit_learn.first()) {
+                module.learn_kit_note(i, *note);
+                state.kit_learn.remove(0);
+            }
+        }
+    }
+
+    ui.start_group();
+    if let Some(name) = state.kit_learn.first().map(|&i| module.patches[i].name.clone()) {
+        if ui.button("Stop", true, Info::KitLearn) {
+            state.kit_learn.clear();
+        }
+        ui.offset_label(&format!("Play note for {name}"), Info::KitLearn);
+    } else if ui.button("Learn", !targets.is_empty(), Info::KitLearn) {
+        state.kit_learn = targets;
+    }
+    ui.end_group();
 }
 
-fn patch_controls(ui: &mut Ui, patch: &mut Patch, cfg: &mut Config, player: &mut Player) {
+fn patch_controls(ui: &mut Ui, patch: &mut Patch, tuning: &Tuning, cfg: &mut Config,
+    player: &mut Player, sample_views: &mut HashMap<usize, SampleView>
+) {
     ui.header("GENERAL", Info::None);
-    ui.shared_slider("gain", "Level", &patch.gain.0, 0.0..=2.0, None, 2, true, Info::None);
+    ui.shared_slider("gain", "Level", &patch.gain.0, 0.0..=MAX_PATCH_GAIN,
+        None, 2, true, Info::None);
     ui.formatted_shared_slider("pan", "Pan", &patch.pan.0, -1.0..=1.0, 1, true, Info::None,
         |f| format!("{f:+.2}"), |f| f);
     ui.slider("glide_time", "Glide time", &mut patch.glide_time,
         0.0..=0.5, Some("s"), 2, true, Info::GlideTime);
-
-    // TODO: re-enable this if & when recording is implemented
-    // if let Some(i) = ui.combo_box("play_mode",
-    //     "Play mode", patch.play_mode.name(), Info::PlayMode,
-    //     || PlayMode::VARIANTS.map(|v| v.name().to_owned()).to_vec()
-    // ) {
-    //     patch.play_mode = PlayMode::VARIANTS[i];
-    // }
+    ui.formatted_shared_slider("detune", "Random detune", &patch.detune.0,
+        0.0..=50.0, 2, true, Info::RandomDetune, |f| format!("{f:.1} cents"), |f| f);
+    ui.formatted_shared_slider("drift", "Drift", &patch.drift.0,
+        0.0..=50.0, 2, true, Info::Drift, |f| format!("{f:.1} cents"), |f| f);
 
     ui.formatted_shared_slider("distortion", "Distortion", &patch.distortion.0,
         0.0..=1.0, 1, true, Info::Distortion, |f| format!("{f:.2}"), |f| f);
     ui.shared_slider("fx_send", "FX send",
         &patch.fx_send.0, 0.0..=1.0, None, 1, true, Info::FxSend);
+    pressure_controls(ui, patch);
 
     ui.vertical_space();
-    generator_controls(ui, patch, cfg, player);
+    generator_controls(ui, patch, tuning, cfg, player, sample_views);
     ui.vertical_space();
     filter_controls(ui, patch);
     ui.vertical_space();
@@ -271,10 +642,33 @@ fn patch_controls(ui: &mut Ui, patch: &mut Patch, cfg: &mut Config, player: &mut
     lfo_controls(ui, patch);
     ui.vertical_space();
     modulation_controls(ui, patch);
+    ui.vertical_space();
+    voice_monitor(ui, patch);
 }
 
-fn generator_controls(ui: &mut Ui, patch: &mut Patch, cfg: &mut Config,
-    player: &mut Player
+fn pressure_controls(ui: &mut Ui, patch: &mut Patch) {
+    let options = || AftertouchMode::VARIANTS.map(|v| v.name().to_owned()).to_vec();
+    if let Some(i) = ui.combo_box("channel_aftertouch", "Channel pressure",
+        patch.channel_aftertouch.name(), Info::AftertouchMode, options) {
+        patch.channel_aftertouch = AftertouchMode::VARIANTS[i];
+    }
+    if let Some(i) = ui.combo_box("poly_aftertouch", "Poly aftertouch",
+        patch.poly_aftertouch.name(), Info::AftertouchMode, options) {
+        patch.poly_aftertouch = AftertouchMode::VARIANTS[i];
+    }
+
+    let mut fixed = patch.keyjazz_pressure.is_some();
+    if ui.checkbox("Fixed key pressure", &mut fixed, true, Info::KeyjazzPressure) {
+        patch.keyjazz_pressure = fixed.then_some(DEFAULT_PRESSURE);
+    }
+    if let Some(pressure) = &mut patch.keyjazz_pressure {
+        ui.slider("keyjazz_pressure", "Key pressure", pressure,
+            0.0..=1.0, None, 1, true, Info::KeyjazzPressure);
+    }
+}
+
+fn generator_controls(ui: &mut Ui, patch: &mut Patch, tuning: &Tuning, cfg: &mut Config,
+    player: &mut Player, sample_views: &mut HashMap<usize, SampleView>
 ) {
     ui.header("GENERATORS", Info::Generators);
 
@@ -288,7 +682,7 @@ fn generator_controls(ui: &mut Ui, patch: &mut Patch, cfg: &mut Config,
         for (i, osc) in patch.oscs.iter().enumerate() {
             ui.offset_label(&(i + 1).to_string(), Info::None);
 
-            if let Waveform::Pcm(_) = osc.waveform {
+            if osc.waveform.uses_sample() {
                 ui.offset_label("", Info::None);
             }
         }
@@ -299,7 +693,8 @@ fn generator_controls(ui: &mut Ui, patch: &mut Patch, cfg: &mut Config,
             ui.shared_slider(&format!("osc_{}_level", i),
                 "", &osc.level.0, 0.0..=1.0, None, 2, true, Info::None);
 
-            if let Waveform::Pcm(data) = &mut osc.waveform {
+            let is_pcm = matches!(osc.waveform, Waveform::Pcm(_));
+            if let Waveform::Pcm(data) | Waveform::Wavetable(data) = &mut osc.waveform {
                 ui.start_group();
                 let mut loaded_sample = false;
 
@@ -319,34 +714,50 @@ fn generator_controls(ui: &mut Ui, patch: &mut Patch, cfg: &mut Config,
                         }
                     }
 
-                    if ui.button("Detect pitch", true, Info::DetectPitch) {
-                        match data.detect_pitch() {
-                            Some(freq) => {
-                                osc.freq_ratio.0
-                                    .set(clamp_freq_ratio(REF_FREQ / freq as f32));
-                                osc.fine_pitch.0.set(0.0);
-                            },
-                            None => ui.report("Could not detect pitch"),
+                    if is_pcm {
+                        if ui.button("Detect pitch", true, Info::DetectPitch) {
+                            match data.detect_pitch() {
+                                Some(freq) => {
+                                    osc.freq_ratio.0
+                                        .set(clamp_freq_ratio(REF_FREQ / freq as f32));
+                                    osc.fine_pitch.0.set(0.0);
+                                },
+                                None => ui.report("Could not detect pitch"),
+                            }
                         }
-                    }
 
-                    let mut on = data.loop_point.is_some();
-                    if ui.checkbox("Loop", &mut on, true, Info::None) {
-                        data.loop_point = if on {
-                            Some(0)
-                        } else {
-                            None
-                        };
+                        let mut on = data.loop_point.is_some();
+                        if ui.checkbox("Loop", &mut on, true, Info::None) {
+                            data.loop_point = if on {
+                                Some(0)
+                            } else {
+                                None
+                            };
+                            data.update_wave();
+                        }
+
+                        if let Some(pt) = &mut data.loop_point {
+                            let sr = data.wave.sample_rate() as f32;
+                            let mut pt2 = *pt as f32 / sr;
+                            if ui.slider(&format!("osc_{}_loop", i), "Loop point", &mut pt2,
+                                0.0..=data.wave.duration() as f32, Some("s"), 1, true,
+                                Info::LoopPoint) {
+                                *pt = (pt2 * sr).round() as usize;
+                                data.fix_loop_point();
+                                data.update_wave();
+                            }
+                        }
                     }
 
-                    if let Some(pt) = &mut data.loop_point {
-                        let sr = data.wave.sample_rate() as f32;
-                        let mut pt2 = *pt as f32 / sr;
-                        if ui.slider(&format!("osc_{}_loop", i), "Loop point", &mut pt2,
-                            0.0..=data.wave.duration() as f32, Some("s"), 1, true,
-                            Info::LoopPoint) {
-                            *pt = (pt2 * sr).round() as usize;
-                            data.fix_loop_point();
+                    let mut linked = data.is_linked();
+                    if ui.checkbox("Link", &mut linked, data.path.is_some(),
+                        Info::LinkSample) {
+                        if linked {
+                            if let Err(e) = data.link() {
+                                ui.report(format!("Error linking sample: {e}"));
+                            }
+                        } else {
+                            data.embed();
                         }
                     }
 
@@ -355,7 +766,7 @@ fn generator_controls(ui: &mut Ui, patch: &mut Patch, cfg: &mut Config,
                     }
                 }
 
-                if loaded_sample {
+                if loaded_sample && is_pcm {
                     if let Some(pitch) = data.as_ref().and_then(|d| d.midi_pitch) {
                         osc.freq_ratio.0.set(clamp_freq_ratio(
                             2.0_f32.powf((REF_PITCH as f32 - pitch) / 12.0)));
@@ -374,7 +785,7 @@ fn generator_controls(ui: &mut Ui, patch: &mut Patch, cfg: &mut Config,
             ui.shared_slider(&format!("osc_{}_tone", i), "", &osc.tone.0,
                 0.0..=1.0, None, 1, osc.waveform.uses_tone(), Info::Tone);
 
-            if let Waveform::Pcm(_) = osc.waveform {
+            if osc.waveform.uses_sample() {
                 ui.offset_label("", Info::None);
             }
         }
@@ -386,7 +797,7 @@ fn generator_controls(ui: &mut Ui, patch: &mut Patch, cfg: &mut Config,
                 "", &osc.freq_ratio.0, MIN_FREQ_RATIO..=MAX_FREQ_RATIO, None, 2,
                 osc.waveform.uses_freq(), Info::FreqRatio);
 
-            if let Waveform::Pcm(_) = osc.waveform {
+            if osc.waveform.uses_sample() {
                 ui.offset_label("" , Info::None);
             }
         }
@@ -398,7 +809,7 @@ fn generator_controls(ui: &mut Ui, patch: &mut Patch, cfg: &mut Config,
                 "", &osc.fine_pitch.0, -0.5..=0.5, 1, osc.waveform.uses_freq(), Info::None,
                 |f| format!("{:+.1} cents", f * 100.0), |f| f * 0.01);
 
-            if let Waveform::Pcm(_) = osc.waveform {
+            if osc.waveform.uses_sample() {
                 ui.offset_label("", Info::None);
             }
         }
@@ -412,7 +823,7 @@ fn generator_controls(ui: &mut Ui, patch: &mut Patch, cfg: &mut Config,
                 osc.waveform = Waveform::VARIANTS[i].clone();
             }
 
-            if let Waveform::Pcm(_) = osc.waveform {
+            if osc.waveform.uses_sample() {
                 ui.offset_label("" , Info::None);
             }
         }
@@ -427,7 +838,7 @@ fn generator_controls(ui: &mut Ui, patch: &mut Patch, cfg: &mut Config,
                 osc.output = outputs[i];
             }
 
-            if let Waveform::Pcm(_) = osc.waveform {
+            if osc.waveform.uses_sample() {
                 ui.offset_label("", Info::None);
             }
         }
@@ -438,7 +849,27 @@ fn generator_controls(ui: &mut Ui, patch: &mut Patch, cfg: &mut Config,
             ui.checkbox("", &mut osc.oversample,
                 osc.waveform.uses_oversampling(), Info::Oversample);
 
-            if let Waveform::Pcm(_) = osc.waveform {
+            if osc.waveform.uses_sample() {
+                ui.offset_label("", Info::None);
+            }
+        }
+    });
+
+    labeled_group(ui, "M", Info::MuteGen, |ui| {
+        for osc in patch.oscs.iter_mut() {
+            ui.checkbox("", &mut osc.audition.mute, true, Info::MuteGen);
+
+            if osc.waveform.uses_sample() {
+                ui.offset_label("", Info::None);
+            }
+        }
+    });
+
+    labeled_group(ui, "S", Info::SoloGen, |ui| {
+        for osc in patch.oscs.iter_mut() {
+            ui.checkbox("", &mut osc.audition.solo, true, Info::SoloGen);
+
+            if osc.waveform.uses_sample() {
                 ui.offset_label("", Info::None);
             }
         }
@@ -452,7 +883,7 @@ fn generator_controls(ui: &mut Ui, patch: &mut Patch, cfg: &mut Config,
                 removed_osc = Some(i);
             }
 
-            if let Waveform::Pcm(_) = osc.waveform {
+            if osc.waveform.uses_sample() {
                 ui.offset_label("", Info::None);
             }
         }
@@ -461,11 +892,74 @@ fn generator_controls(ui: &mut Ui, patch: &mut Patch, cfg: &mut Config,
     if let Some(i) = removed_osc {
         patch.remove_osc(i);
     }
+    patch.update_audition();
     ui.end_group();
 
     if ui.button("+", true, Info::Add("a generator")) {
         patch.oscs.push(Oscillator::default());
     }
+
+    for (i, osc) in patch.oscs.iter_mut().enumerate() {
+        if let Waveform::Additive(harmonics) = &mut osc.waveform {
+            ui.label(&format!("Gen {} partials", i + 1), Info::Partials);
+            ui.bar_editor(&format!("osc_{}_partials", i), &mut harmonics.amps,
+                Info::Partials);
+            let mut retuned = harmonics.retuned;
+            if ui.checkbox("Retune to scale", &mut retuned, true, Info::RetunePartials) {
+                harmonics.retune(retuned.then_some(tuning));
+            }
+        }
+
+        if let Waveform::Pcm(Some(data)) = &mut osc.waveform {
+            sample_editor(ui, i, data, &osc.start, sample_views.entry(i).or_default());
+        }
+    }
+}
+
+/// Draw the waveform of a PCM generator, with controls for the loop and
+/// start position.
+fn sample_editor(ui: &mut Ui, i: usize, data: &mut PcmData, start: &Parameter,
+    view: &mut SampleView
+) {
+    ui.label(&format!("Gen {} sample", i + 1), Info::SampleWaveform);
+
+    let len = data.wave.len();
+    let start_frame = (start.0.value() * len.saturating_sub(1) as f32) as usize;
+    let markers: Vec<_> = data.loop_point.into_iter().chain([start_frame]).collect();
+    if let Some(frame) = ui.waveform(&format!("osc_{}_waveform", i), &data.wave,
+        view.range(len), &markers, Info::SampleWaveform) {
+        data.loop_point = Some(data.nearest_zero_crossing(frame));
+        data.update_wave();
+    }
+
+    ui.start_group();
+    ui.formatted_slider(&format!("osc_{}_zoom", i), "Zoom", &mut view.zoom,
+        1.0..=MAX_SAMPLE_ZOOM, 2, true, Info::None, |f| format!("x{f:.1}"), |f| f);
+    ui.slider(&format!("osc_{}_view", i), "Scroll", &mut view.pos, 0.0..=1.0,
+        None, 1, view.zoom > 1.0, Info::None);
+    ui.end_group();
+
+    ui.start_group();
+    ui.formatted_shared_slider(&format!("osc_{}_start", i), "Start", &start.0,
+        0.0..=1.0, 1, true, Info::SampleStart,
+        |f| format!("{:.1}%", f * 100.0), |f| f * 0.01);
+
+    let looped = data.loop_point.is_some();
+    if ui.button("Snap loop", looped, Info::SnapLoop) {
+        if let Some(pt) = data.loop_point {
+            data.loop_point = Some(data.nearest_zero_crossing(pt));
+            data.update_wave();
+        }
+    }
+
+    let sr = data.wave.sample_rate() as f32;
+    let mut ms = data.crossfade as f32 / sr * 1000.0;
+    if ui.slider(&format!("osc_{}_crossfade", i), "Crossfade", &mut ms,
+        0.0..=MAX_CROSSFADE_MS, Some("ms"), 2, looped, Info::LoopCrossfade) {
+        data.crossfade = (ms * sr / 1000.0).round() as usize;
+        data.update_wave();
+    }
+    ui.end_group();
 }
 
 /// Browse for and load an audio file into `data`. Returns true if successful.
@@ -547,6 +1041,14 @@ fn filter_controls(ui: &mut Ui, patch: &mut Patch) {
             }
         });
 
+        labeled_group(ui, "Drive", Info::FilterDrive, |ui| {
+            for (i, filter) in patch.filters.iter_mut().enumerate() {
+                ui.formatted_shared_slider(&format!("filter_{}_drive", i), "",
+                    &filter.drive.0, 0.0..=1.0, 1, true,
+                    Info::FilterDrive, |f| format!("{f:.2}"), |f| f);
+            }
+        });
+
         labeled_group(ui, "Keytrack", Info::FilterKeytrack, |ui| {
             for (i, filter) in patch.filters.iter_mut().enumerate() {
                 if let Some(i) = ui.combo_box(&format!("filter_{}_keytrack", i),
@@ -738,6 +1240,24 @@ fn modulation_controls(ui: &mut Ui, patch: &mut Patch) {
             }
         });
 
+        labeled_group(ui, "Curve", Info::ModCurve, |ui| {
+            for (i, m) in patch.mod_matrix.iter_mut().enumerate() {
+                if let Some(i) = ui.combo_box(&format!("mod_{}_curve", i),
+                    "", m.curve.name(), Info::ModCurve,
+                    || ModCurve::VARIANTS.map(|x| x.name().to_owned()).to_vec()) {
+                    m.curve = ModCurve::VARIANTS[i];
+                }
+            }
+        });
+
+        labeled_group(ui, "Response", Info::ModResponse, |ui| {
+            for m in &patch.mod_matrix {
+                let (depth, curve) = (m.depth.0.value(), m.curve);
+                let range = if m.source.is_bipolar() { -1.0..=1.0 } else { 0.0..=1.0 };
+                ui.curve_graph(|x| depth * curve.apply(x), range, Info::ModResponse);
+            }
+        });
+
         labeled_group(ui, "", Info::None, |ui| {
             for i in 0..patch.mod_matrix.len() {
                 if ui.button("X", true, Info::Remove("this modulation")) {
@@ -758,6 +1278,60 @@ fn modulation_controls(ui: &mut Ui, patch: &mut Patch) {
     }
 }
 
+/// Draw envelope shapes and the live modulated values of the last played voice.
+fn voice_monitor(ui: &mut Ui, patch: &Patch) {
+    ui.header("VOICE MONITOR", Info::VoiceMonitor);
+
+    let levels = patch.voice_levels();
+    ui.label(match &levels {
+        None => "No note playing",
+        Some(v) if v.gate => "Note on",
+        Some(_) => "Note released",
+    }, Info::VoiceMonitor);
+
+    ui.start_group();
+
+    if !patch.envs.is_empty() {
+        index_group(ui, patch.envs.len());
+
+        labeled_group(ui, "Envelope", Info::VoiceMonitor, |ui| {
+            for env in &patch.envs {
+                // show sustain for a third of the graph
+                let hold = (env.attack + env.decay + env.release) * 0.5;
+                let release_start = env.attack + env.decay + hold;
+                ui.curve_graph(|t| env.level_at(t, release_start),
+                    0.0..=release_start + env.release, Info::VoiceMonitor);
+            }
+        });
+
+        labeled_group(ui, "Level", Info::VoiceMonitor, |ui| {
+            for i in 0..patch.envs.len() {
+                let level = levels.as_ref().and_then(|v| v.envs.get(i));
+                ui.meter(level.copied().unwrap_or_default(), Info::VoiceMonitor);
+            }
+        });
+    }
+
+    labeled_group(ui, "Gen level", Info::VoiceMonitor, |ui| {
+        for i in 0..patch.oscs.len() {
+            let level = levels.as_ref().and_then(|v| v.levels.get(i));
+            ui.meter(level.copied().unwrap_or_default(), Info::VoiceMonitor);
+        }
+    });
+
+    if !patch.filters.is_empty() {
+        labeled_group(ui, "Cutoff", Info::VoiceMonitor, |ui| {
+            for i in 0..patch.filters.len() {
+                let cutoff = levels.as_ref().and_then(|v| v.cutoffs.get(i));
+                ui.offset_label(&cutoff.map(|f| format!("{f:.0} Hz"))
+                    .unwrap_or(String::from("-")), Info::VoiceMonitor);
+            }
+        });
+    }
+
+    ui.end_group();
+}
+
 /// Draw a column of indices.
 fn index_group(ui: &mut Ui, len: usize) {
     ui.start_group();
@@ -768,14 +1342,6 @@ fn index_group(ui: &mut Ui, len: usize) {
     ui.end_group();
 }
 
-/// Wrap a block of UI code in a labeled column.
-fn labeled_group(ui: &mut Ui, label: &str, info: Info, f: impl FnOnce(&mut Ui)) {
-    ui.start_group();
-    ui.label(label, info);
-    f(ui);
-    ui.end_group();
-}
-
 /// Look up the display function for a modulation target.
 fn display_mod(target: &ModTarget) -> Box<dyn Fn(f32) -> String> {
     // TODO: this would ideally be a recursive lookup with loop detection in the
@@ -785,8 +1351,9 @@ fn display_mod(target: &ModTarget) -> Box<dyn Fn(f32) -> String> {
             Box::new(|d| format!("x{:.2}", MAX_ENV_SCALE.powf(d))),
         ModTarget::FilterCutoff(_) =>
             Box::new(|d| format!("{:+.2} octaves", d * FILTER_CUTOFF_MOD_BASE.log2())),
-        ModTarget::ClipGain | ModTarget::FilterQ(_) | ModTarget::Tone(_)
-            | ModTarget::FxSend => Box::new(|d| format!("{:+.2}", d)),
+        ModTarget::ClipGain | ModTarget::FilterQ(_) | ModTarget::FilterDrive(_)
+            | ModTarget::Tone(_) | ModTarget::FxSend | ModTarget::SampleStart(_) =>
+            Box::new(|d| format!("{:+.2}", d)),
         ModTarget::FinePitch | ModTarget::OscFinePitch(_) =>
             Box::new(|d| format!("{:+.1} cents", d * 50.0)),
         ModTarget::Gain | ModTarget::Level(_) =>
@@ -807,8 +1374,9 @@ fn convert_mod(target: &ModTarget) -> Box<dyn FnOnce(f32) -> f32> {
             Box::new(|f| f.log(MAX_ENV_SCALE)),
         ModTarget::FilterCutoff(_) =>
             Box::new(|f| f / FILTER_CUTOFF_MOD_BASE.log2()),
-        ModTarget::ClipGain | ModTarget::FilterQ(_) | ModTarget::Tone(_)
-            | ModTarget::FxSend => Box::new(|f| f),
+        ModTarget::ClipGain | ModTarget::FilterQ(_) | ModTarget::FilterDrive(_)
+            | ModTarget::Tone(_) | ModTarget::FxSend | ModTarget::SampleStart(_) =>
+            Box::new(|f| f),
         ModTarget::FinePitch | ModTarget::OscFinePitch(_) =>
             Box::new(|f| f / 50.0),
         ModTarget::Gain | ModTarget::Level(_) =>
@@ -822,6 +1390,35 @@ fn convert_mod(target: &ModTarget) -> Box<dyn FnOnce(f32) -> f32> {
 }
 
 /// Shift the patch index while keeping it in bounds.
+/// Returns the mod target of the patch parameter slider with ID `id`, if
+/// any.
+fn slider_mod_target(id: &str) -> Option<ModTarget> {
+    match id {
+        "gain" => return Some(ModTarget::Gain),
+        "pan" => return Some(ModTarget::Pan),
+        "distortion" => return Some(ModTarget::ClipGain),
+        "fx_send" => return Some(ModTarget::FxSend),
+        _ => (),
+    }
+
+    let (kind, rest) = id.split_once('_')?;
+    let (i, param) = rest.split_once('_')?;
+    let i = i.parse().ok()?;
+    match (kind, param) {
+        ("osc", "level") => Some(ModTarget::Level(i)),
+        ("osc", "ratio") => Some(ModTarget::OscPitch(i)),
+        ("osc", "tune") => Some(ModTarget::OscFinePitch(i)),
+        ("osc", "tone") => Some(ModTarget::Tone(i)),
+        ("osc", "start") => Some(ModTarget::SampleStart(i)),
+        ("filter", "cutoff") => Some(ModTarget::FilterCutoff(i)),
+        ("filter", "q") => Some(ModTarget::FilterQ(i)),
+        ("filter", "drive") => Some(ModTarget::FilterDrive(i)),
+        ("lfo", "rate") => Some(ModTarget::LFORate(i)),
+        ("mod", "depth") => Some(ModTarget::ModDepth(i)),
+        _ => None,
+    }
+}
+
 fn shift_patch_index(offset: isize, patch_index: &mut Option<usize>, n: usize) {
     if let Some(index) = patch_index {
         if let Some(i) = index.checked_add_signed(offset) {
@@ -868,4 +1465,35 @@ mod tests {
         assert_eq!(clamp_freq_ratio(40.0), 10.0);
         assert_eq!(clamp_freq_ratio(0.1), 0.4);
     }
+
+    #[test]
+    fn test_selected_patches() {
+        let selection = BTreeSet::from([0, 2, 3, 9]);
+        assert_eq!(selected_patches(Some(0), &selection, 4), [0, 1, 2]);
+        assert_eq!(selected_patches(None, &BTreeSet::new(), 4), []);
+        assert_eq!(selected_patches(Some(1), &BTreeSet::from([2]), 4), [1]);
+    }
+
+    #[test]
+    fn test_slider_mod_target() {
+        assert!(slider_mod_target("fx_send") == Some(ModTarget::FxSend));
+        assert!(slider_mod_target("osc_1_level") == Some(ModTarget::Level(1)));
+        assert!(slider_mod_target("filter_0_q") == Some(ModTarget::FilterQ(0)));
+        assert!(slider_mod_target("mod_2_depth") == Some(ModTarget::ModDepth(2)));
+        assert!(slider_mod_target("glide_time").is_none());
+        assert!(slider_mod_target("env_0_A").is_none());
+    }
+
+    #[test]
+    fn test_sample_view() {
+        let mut view = SampleView::default();
+        assert_eq!(view.range(1000), 0..1000);
+        view.zoom = 4.0;
+        view.pos = 1.0;
+        assert_eq!(view.range(1000), 750..1000);
+        view.zoom = 10000.0;
+        view.pos = 0.0;
+        assert_eq!(view.range(1000), 0..1);
+        assert_eq!(view.range(0), 0..0);
+    }
 }
\ No newline at end of file
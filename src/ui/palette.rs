@@ -0,0 +1,137 @@
+//! Command palette items: key commands, navigation targets, and recent
+//! songs, filtered by a typed query.
+
+use std::path::{Path, PathBuf};
+
+use crate::{config::Config, input::Action, module::{EventData, Module}, songpos, timespan::Timespan};
+
+use super::pattern;
+
+/// Actions without default hotkeys, which aren't in the keymap unless bound
+/// by hand.
+const UNBOUND_ACTIONS: [Action; 3] =
+    [Action::StopPlayback, Action::SendTuning, Action::ToggleStepRecord];
+
+/// Command run by choosing a palette item.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PaletteCommand {
+    Action(Action),
+    GoTo(Timespan),
+    Track(usize),
+    Open(PathBuf),
+}
+
+/// Option in the command palette.
+pub struct PaletteItem {
+    pub label: String,
+    pub command: PaletteCommand,
+}
+
+/// Returns the palette items matching `query`. Every word of the query must
+/// appear in an item's label, ignoring case. A query that parses as a
+/// position or time adds an item to go there.
+pub fn items(query: &str, module: &Module, cfg: &Config, division: u8) -> Vec<PaletteItem> {
+    let mut items = Vec::new();
+    let bbt = |tick| songpos::format_bbt(tick, cfg.bar_beats, division);
+
+    let query = query.trim();
+    if let Some(tick) = songpos::parse_bbt(query, cfg.bar_beats, division) {
+        items.push(PaletteItem {
+            label: format!("Go to position {}", bbt(tick)),
+            command: PaletteCommand::GoTo(tick),
+        });
+    } else if let Some(secs) = songpos::parse_clock(query.trim_end_matches('s')) {
+        items.push(PaletteItem {
+            label: format!("Go to time {}", songpos::format_clock(secs)),
+            command: PaletteCommand::GoTo(Timespan::approximate(module.beat_at_time(secs))),
+        });
+    }
+
+    let mut all = Vec::new();
+
+    // key commands, with their hotkeys
+    let mut actions: Vec<Action> = Vec::new();
+    for (_, action) in cfg.keymap() {
+        if !actions.contains(action) {
+            actions.push(*action);
+        }
+    }
+    for action in UNBOUND_ACTIONS {
+        if !actions.contains(&action) {
+            actions.push(action);
+        }
+    }
+    for action in actions {
+        let keys: Vec<_> = cfg.keymap().iter()
+            .filter(|(_, a)| *a == action)
+            .map(|(k, _)| k.to_string())
+            .collect();
+        let label = if keys.is_empty() {
+            action.name().to_owned()
+        } else {
+            format!("{} ({})", action.name(), keys.join(", "))
+        };
+        all.push(PaletteItem { label, command: PaletteCommand::Action(action) });
+    }
+
+    // tracks
+    for (i, track) in module.tracks.iter().enumerate().skip(1) {
+        let name = pattern::track_display_name(track, &module.patches);
+        all.push(PaletteItem {
+            label: format!("Go to track {i}: {name}"),
+            command: PaletteCommand::Track(i),
+        });
+    }
+
+    // markers
+    for evt in module.ctrl_events() {
+        let name = match evt.data {
+            EventData::Section => "section",
+            EventData::Loop => "loop",
+            EventData::End => "end",
+            _ => continue,
+        };
+        all.push(PaletteItem {
+            label: format!("Go to {name} at {}", bbt(evt.tick)),
+            command: PaletteCommand::GoTo(evt.tick),
+        });
+    }
+
+    // recent songs
+    for path in &cfg.recent_modules {
+        let name = Path::new(path).file_name()
+            .map_or_else(|| path.clone(), |s| s.to_string_lossy().into_owned());
+        all.push(PaletteItem {
+            label: format!("Open recent: {name}"),
+            command: PaletteCommand::Open(PathBuf::from(path)),
+        });
+    }
+
+    let words: Vec<_> = query.split_whitespace().map(|w| w.to_lowercase()).collect();
+    items.extend(all.into_iter().filter(|item| {
+        let label = item.label.to_lowercase();
+        words.iter().all(|w| label.contains(w))
+    }));
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_items() {
+        let module = Module::new(Default::default());
+        let cfg = Config::default();
+        let commands = |query| items(query, &module, &cfg, 4).into_iter()
+            .map(|x| x.command)
+            .collect::<Vec<_>>();
+
+        assert_eq!(commands("2:1")[0], PaletteCommand::GoTo(Timespan::new(4, 1)));
+        assert_eq!(commands("0:02.5s")[0], PaletteCommand::GoTo(Timespan::new(5, 1)));
+        assert!(commands("save song").contains(&PaletteCommand::Action(Action::SaveSong)));
+        assert!(commands("step rec").contains(&PaletteCommand::Action(Action::ToggleStepRecord)));
+        assert!(commands("track 1").contains(&PaletteCommand::Track(1)));
+        assert!(!commands("save song").contains(&PaletteCommand::Track(1)));
+    }
+}
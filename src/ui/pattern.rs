@@ -1,8 +1,10 @@
-use std::collections::HashSet;
+use std::{collections::{BTreeMap, HashMap, HashSet}, error::Error};
 
 use fundsp::math::delerp;
+use gcd::Gcd;
+use serde::{Deserialize, Serialize};
 
-use crate::{config::Config, input::{self, Action}, module::*, playback::Player, synth::Patch, timespan::Timespan};
+use crate::{cliplib::Clip, config::Config, input::{self, Action}, melody::{self, MelodySettings}, mixer::{MAX_TONE_HZ, MIN_TONE_HZ}, module::*, patterntext::{self, TextEvent}, pitch::Nominal, playback::Player, script, synth::{KeyOrigin, Patch, DEFAULT_PRESSURE}, timespan::Timespan};
 
 use super::*;
 
@@ -11,6 +13,63 @@ const PATTERN_MARGIN: f32 = 2.0;
 
 const CTRL_COLUMN_TEXT_ID: &str = "ctrl_column";
 
+const MINIMAP_ID: &str = "minimap";
+
+/// Width of each track's lane in the minimap.
+const MINIMAP_TRACK_WIDTH: f32 = 4.0;
+
+const TEMPO_LANE_ID: &str = "tempo_lane";
+
+/// Tempo ratios always offered by the rational tempo picker.
+const COMMON_TEMPO_RATIOS: [(u8, u8); 8] =
+    [(2, 1), (1, 2), (3, 2), (2, 3), (4, 3), (3, 4), (5, 4), (4, 5)];
+
+const TEMPO_LANE_WIDTH: f32 = 40.0;
+
+const PIANO_ROLL_ID: &str = "piano_roll";
+
+/// Height of the piano roll, in text lines.
+const PIANO_ROLL_LINES: f32 = 10.0;
+
+/// Scale degrees of padding above and below the notes in the piano roll.
+const PIANO_ROLL_PADDING: i32 = 2;
+
+/// Label colors that can be assigned to tracks. Track data stores an index
+/// into this list.
+const TRACK_COLORS: [(&str, Color); 6] = [
+    ("Red", Color::new(0.9, 0.2, 0.2, 1.0)),
+    ("Orange", Color::new(0.9, 0.55, 0.1, 1.0)),
+    ("Yellow", Color::new(0.85, 0.8, 0.1, 1.0)),
+    ("Green", Color::new(0.2, 0.75, 0.3, 1.0)),
+    ("Blue", Color::new(0.2, 0.45, 0.9, 1.0)),
+    ("Purple", Color::new(0.65, 0.3, 0.85, 1.0)),
+];
+
+/// Opacity of track color tints in the pattern background.
+const TRACK_TINT_ALPHA: f32 = 0.08;
+
+/// Opacity of ghost notes from other tracks.
+const GHOST_ALPHA: f32 = 0.2;
+
+/// Maximum track pressure and modulation scale.
+const MAX_TRACK_SCALE: f32 = 2.0;
+
+/// Maximum magnitude of a track playback offset, in milliseconds.
+const MAX_TRACK_OFFSET_MS: f32 = 500.0;
+
+/// Maximum length of a track auto-pan cycle, in beats.
+const MAX_AUTO_PAN_PERIOD: f32 = 64.0;
+
+/// Time after the first note of a step record chord that further notes are
+/// added to the chord, in seconds.
+const STEP_CHORD_WINDOW: f32 = 0.05;
+
+/// Row height limits, as multiples of the text line height. Below 1, events
+/// are drawn as bars instead of text.
+const MIN_ROW_ZOOM: f32 = 0.25;
+const MAX_ROW_ZOOM: f32 = 4.0;
+const ROW_ZOOM_STEP: f32 = 1.25;
+
 /// These actions are valid ways to exit pattern text entry.
 /// Defining what's on this list is a little hairy since there are pattern
 /// navigation actions that are bound to useful text editing keys by default,
@@ -43,6 +102,328 @@ pub struct PatternEditor {
     /// Highest visible tick. Lowest is `beat_scroll`.
     screen_tick_max: Timespan,
     text_position: Option<Position>,
+    /// Cursor position as of the last note preview check.
+    preview_pos: Position,
+    minimap: Minimap,
+    /// Tracks with their pressure and modulation columns hidden.
+    collapsed: HashSet<usize>,
+    /// Tracks whose notes are shown faintly in other tracks, by the index
+    /// of the track they're shown in.
+    ghost_tracks: HashMap<usize, usize>,
+    /// Row height as a multiple of the text line height.
+    pub row_zoom: f32,
+    mouse_drag: Option<MouseDrag>,
+    /// Steps of the macro being recorded, if recording.
+    macro_recording: Option<Vec<MacroStep>>,
+    last_macro: Vec<MacroStep>,
+    /// Shift state to use instead of the keyboard's while replaying a macro.
+    macro_shift: Option<bool>,
+    roll_drag: Option<RollDrag>,
+    /// If true, MIDI notes are written at the cursor, which then advances.
+    step_record: bool,
+    step_chord: Option<StepChord>,
+    /// Tick range that recording is limited to, if punching in.
+    punch_range: Option<(Timespan, Timespan)>,
+    /// Tick that replace recording has erased up to.
+    record_erased_to: Timespan,
+    /// Positions of events written in the current recording pass.
+    recorded: Vec<Position>,
+}
+
+/// Notes written at the current step in step record mode.
+struct StepChord {
+    /// Number of notes written, one per channel.
+    notes: usize,
+    /// Seconds since the first note.
+    time: f32,
+    /// Position of the last note.
+    last: Position,
+}
+
+/// A recorded editor macro step.
+#[derive(Clone)]
+enum MacroStep {
+    /// Action, and whether shift was held.
+    Action(Action, bool),
+    /// Event typed at the cursor, and whether it was entered in all channels.
+    Insert(EventData, bool),
+}
+
+/// Cursor and scroll state of the pattern editor, saved between sessions.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PatternView {
+    pub tick: Timespan,
+    pub track: usize,
+    pub channel: usize,
+    pub column: u8,
+    pub beat_scroll: Timespan,
+    pub h_scroll: f32,
+    pub row_zoom: f32,
+}
+
+impl Default for PatternView {
+    fn default() -> Self {
+        Self {
+            tick: Timespan::ZERO,
+            track: 0,
+            channel: 0,
+            column: 0,
+            beat_scroll: Timespan::ZERO,
+            h_scroll: 0.0,
+            row_zoom: 1.0,
+        }
+    }
+}
+
+/// Parameters for scaling pressure and modulation values.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ValueScaling {
+    /// Percentage to multiply values by.
+    pub percent: f32,
+    /// Amount to add to values after scaling.
+    pub offset: i8,
+}
+
+impl Default for ValueScaling {
+    fn default() -> Self {
+        Self {
+            percent: 100.0,
+            offset: 0,
+        }
+    }
+}
+
+impl ValueScaling {
+    /// Returns a scaled digit value.
+    fn apply(&self, value: u8) -> u8 {
+        let scaled = (value as f32 * self.percent / 100.0).round() + self.offset as f32;
+        scaled.clamp(0.0, EventData::DIGIT_MAX as f32) as u8
+    }
+}
+
+/// How live recording treats existing events.
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum RecordMode {
+    /// Recorded events are merged with existing events.
+    #[default]
+    Overdub,
+    /// Existing events in the cursor channel are erased as recording passes
+    /// over them.
+    Replace,
+}
+
+impl RecordMode {
+    pub const VARIANTS: [RecordMode; 2] = [Self::Overdub, Self::Replace];
+
+    /// Returns the UI string for this record mode.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Overdub => "Overdub",
+            Self::Replace => "Replace",
+        }
+    }
+}
+
+/// Cached event density for the song overview.
+#[derive(Default)]
+struct Minimap {
+    /// Module version that `density` was computed for.
+    version: Option<u64>,
+    /// Event counts per beat, per track.
+    density: Vec<Vec<u32>>,
+}
+
+impl Minimap {
+    /// Recompute event density if the module has changed.
+    fn update(&mut self, module: &Module) {
+        if self.version == Some(module.version()) {
+            return
+        }
+        self.version = Some(module.version());
+        self.density = module.tracks.iter().map(|track| {
+            let mut counts = Vec::new();
+            for evt in track.channels.iter().flat_map(|c| &c.events) {
+                let beat = evt.tick.as_f64() as usize;
+                if counts.len() <= beat {
+                    counts.resize(beat + 1, 0);
+                }
+                counts[beat] += 1;
+            }
+            counts
+        }).collect();
+    }
+}
+
+/// Pattern edit in progress with the mouse. Nothing is written to the module
+/// until the mouse button is released.
+enum MouseDrag {
+    /// Moving the event at `from` to tick `to`.
+    Move {
+        from: Position,
+        to: Timespan,
+    },
+    /// Writing values to rows of a pressure or modulation column.
+    Paint {
+        track: usize,
+        channel: usize,
+        column: u8,
+        last_tick: Timespan,
+        values: BTreeMap<Timespan, u8>,
+    },
+}
+
+impl MouseDrag {
+    /// Update the drag for the mouse being at `tick`. `value` is the value to
+    /// paint, and `row` is the distance between rows.
+    fn update(&mut self, tick: Timespan, value: u8, row: Timespan) {
+        match self {
+            Self::Move { to, .. } => *to = tick,
+            Self::Paint { last_tick, values, .. } => {
+                // fill in rows skipped by fast mouse movement
+                let (mut t, end) = if tick < *last_tick {
+                    (tick, *last_tick)
+                } else {
+                    (*last_tick, tick)
+                };
+                while t <= end {
+                    values.insert(t, value);
+                    t += row;
+                }
+                *last_tick = tick;
+            }
+        }
+    }
+
+    /// Returns the edit that applies the drag, if it changes anything.
+    fn edit(&self, module: &Module) -> Option<Edit> {
+        match self {
+            Self::Move { from, to } => {
+                if *to == from.tick {
+                    return None
+                }
+                let event = module.tracks.get(from.track)?
+                    .channels.get(from.channel)?
+                    .events.iter()
+                    .find(|e| e.tick == from.tick && e.data.logical_column() == from.column)?;
+                let to = Position { tick: *to, ..*from };
+                Some(Edit::PatternData {
+                    remove: vec![*from, to],
+                    add: vec![LocatedEvent {
+                        track: from.track,
+                        channel: from.channel,
+                        event: Event { tick: to.tick, data: event.data.clone() },
+                    }],
+                })
+            }
+            Self::Paint { track, channel, column, values, .. } => {
+                let add: Vec<_> = values.iter().map(|(tick, value)| LocatedEvent {
+                    track: *track,
+                    channel: *channel,
+                    event: Event {
+                        tick: *tick,
+                        data: if *column == MOD_COLUMN {
+                            EventData::Modulation(*value)
+                        } else {
+                            EventData::Pressure(*value)
+                        },
+                    },
+                }).collect();
+                (!add.is_empty()).then(|| Edit::PatternData {
+                    remove: add.iter().map(|e| e.position()).collect(),
+                    add,
+                })
+            }
+        }
+    }
+}
+
+/// Span of a note in the piano roll. Glides slope from the start degree to
+/// the end degree.
+#[derive(Debug, PartialEq)]
+struct RollSegment {
+    /// Position of the pitch event that starts the note.
+    pos: Position,
+    end: Timespan,
+    /// Scale degrees at the start and end of the span.
+    degrees: (i32, i32),
+}
+
+/// Note being dragged to a new pitch in the piano roll.
+struct RollDrag {
+    pos: Position,
+    /// Mouse y position at the start of the drag.
+    start_y: f32,
+    /// Degree range and row height of the roll at the start of the drag,
+    /// kept fixed while dragging.
+    range: (i32, i32),
+    row_h: f32,
+    /// Scale steps to transpose the note by.
+    steps: isize,
+}
+
+impl RollDrag {
+    /// Returns the edit that applies the drag, if it changes anything.
+    fn edit(&self, module: &Module) -> Option<Edit> {
+        if self.steps == 0 {
+            return None
+        }
+        let event = module.tracks.get(self.pos.track)?
+            .channels.get(self.pos.channel)?
+            .events.iter()
+            .find(|e| e.tick == self.pos.tick && e.data.logical_column() == self.pos.column)?;
+        let EventData::Pitch(note) = event.data else {
+            return None
+        };
+        Some(Edit::PatternData {
+            remove: vec![self.pos],
+            add: vec![LocatedEvent {
+                track: self.pos.track,
+                channel: self.pos.channel,
+                event: Event {
+                    tick: self.pos.tick,
+                    data: EventData::Pitch(note.step_shift(self.steps, &module.tuning)),
+                },
+            }],
+        })
+    }
+}
+
+/// Returns piano roll spans for the notes of a track. Notes last until the
+/// next note or note off in their channel, or until `open_end`.
+fn roll_segments(module: &Module, track: usize, open_end: Timespan) -> Vec<RollSegment> {
+    let tuning = &module.tuning;
+    let degree = |note: &Note| {
+        let (index, equave) = tuning.scale_index(note);
+        equave as i32 * tuning.size() as i32 + index as i32
+    };
+
+    let mut segments = Vec::new();
+    let channels = module.tracks.get(track).map(|t| t.channels.as_slice()).unwrap_or_default();
+    for (channel_i, channel) in channels.iter().enumerate() {
+        let notes: Vec<_> = channel.events.iter()
+            .filter(|e| matches!(e.data, EventData::Pitch(_) | EventData::NoteOff))
+            .collect();
+        for (i, event) in notes.iter().enumerate() {
+            let EventData::Pitch(note) = &event.data else {
+                continue
+            };
+            let next = notes.get(i + 1);
+            let start = degree(note);
+            let end = next.and_then(|next| match &next.data {
+                EventData::Pitch(n) if channel.is_interpolated(NOTE_COLUMN, next.tick) =>
+                    Some(degree(n)),
+                _ => None,
+            }).unwrap_or(start);
+            segments.push(RollSegment {
+                pos: Position::new(event.tick, track, channel_i, NOTE_COLUMN),
+                end: next.map_or(open_end.max(event.tick), |e| e.tick),
+                degrees: (start, end),
+            });
+        }
+    }
+    segments
 }
 
 /// Pattern data clipboard.
@@ -89,11 +470,46 @@ impl Default for PatternEditor {
             record: false,
             screen_tick_max: Timespan::ZERO,
             text_position: None,
+            preview_pos: edit_cursor,
+            minimap: Default::default(),
+            collapsed: HashSet::new(),
+            ghost_tracks: HashMap::new(),
+            row_zoom: 1.0,
+            mouse_drag: None,
+            macro_recording: None,
+            last_macro: Vec::new(),
+            macro_shift: None,
+            roll_drag: None,
+            step_record: false,
+            step_chord: None,
+            punch_range: None,
+            record_erased_to: Timespan::ZERO,
+            recorded: Vec::new(),
         }
     }
 }
 
 impl PatternEditor {
+    /// Multiply row height by `ROW_ZOOM_STEP` to the power of `steps`.
+    pub fn zoom_rows(&mut self, steps: i32) {
+        self.set_row_zoom(self.row_zoom * ROW_ZOOM_STEP.powi(steps));
+    }
+
+    /// Set row height as a multiple of the text line height.
+    pub fn set_row_zoom(&mut self, zoom: f32) {
+        // snap to 1 so that the default zoom can be returned to exactly
+        self.row_zoom = if (zoom - 1.0).abs() < 0.01 {
+            1.0
+        } else {
+            zoom.clamp(MIN_ROW_ZOOM, MAX_ROW_ZOOM)
+        };
+    }
+
+    /// Returns true if rows are too short to fit event text.
+    fn condensed(&self) -> bool {
+        self.row_zoom < 1.0
+    }
+
     /// Increment division.
     pub fn inc_division(&mut self) {
         self.set_division(self.beat_division.saturating_add(1));
@@ -150,21 +566,114 @@ impl PatternEditor {
         self.edit_start.tick
     }
 
+    /// Move the cursor to the row nearest `tick`, clearing the selection.
+    pub fn go_to(&mut self, tick: Timespan) {
+        let tick = self.round_tick(tick).max(Timespan::ZERO);
+        self.edit_start.tick = tick;
+        self.edit_end = self.edit_start;
+        self.scroll_to_cursor();
+    }
+
+    /// Take the clipboard contents of `other`, if any.
+    pub fn take_clipboard(&mut self, other: &mut PatternEditor) {
+        if other.clipboard.is_some() {
+            self.clipboard = other.clipboard.take();
+        }
+    }
+
+    /// Move the cursor to the first column of `track`, clearing the
+    /// selection.
+    pub fn go_to_track(&mut self, track: usize) {
+        self.edit_start.track = track;
+        self.edit_start.channel = 0;
+        self.edit_start.column = 0;
+        self.edit_end = self.edit_start;
+    }
+
+    /// Returns a description of the cursor location and the event under it,
+    /// for screen readers.
+    fn describe_cursor(&self, module: &Module) -> String {
+        let pos = &self.edit_start;
+        let Some(track) = module.tracks.get(pos.track) else {
+            return String::new()
+        };
+        let name = track_display_name(track, &module.patches);
+        let column = match pos.column {
+            _ if pos.track == 0 => "global",
+            NOTE_COLUMN => "note",
+            VEL_COLUMN => "pressure",
+            _ => "modulation",
+        };
+        let mut s = format!("Beat {}, {name}, channel {}, {column} column",
+            patterntext::format_tick(pos.tick), pos.channel + 1);
+
+        let data = track.channels.get(pos.channel)
+            .and_then(|c| c.events.iter().find(|e|
+                e.tick == pos.tick && e.data.spatial_column() == pos.column))
+            .and_then(|e| match e.data {
+                EventData::Pitch(note) if matches!(track.target, TrackTarget::Kit) =>
+                    module.kit_name(note).map(|s| s.to_owned())
+                        .or_else(|| patterntext::format_data(&e.data)),
+                _ => patterntext::format_data(&e.data),
+            });
+        if let Some(data) = data {
+            s.push_str(&format!(", {data}"));
+        }
+        s
+    }
+
     /// Check whether the cursor is in the digit column.
     pub fn in_digit_column(&self, ui: &Ui) -> bool {
         ui.tabs.get(MAIN_TAB_ID) == Some(&TAB_PATTERN)
             && self.edit_start.column != NOTE_COLUMN
     }
 
+    /// Check whether a track's pressure and modulation columns are hidden.
+    fn is_collapsed(&self, track: usize) -> bool {
+        self.collapsed.contains(&track)
+    }
+
+    /// Toggle hiding a track's pressure and modulation columns.
+    fn toggle_collapse(&mut self, track: usize) {
+        if track == 0 {
+            return
+        }
+        if !self.collapsed.remove(&track) {
+            self.collapsed.insert(track);
+        }
+        self.fix_collapsed_cursors();
+    }
+
+    /// Move cursors out of hidden columns.
+    fn fix_collapsed_cursors(&mut self) {
+        for cursor in [&mut self.edit_start, &mut self.edit_end] {
+            if self.collapsed.contains(&cursor.track) {
+                cursor.column = NOTE_COLUMN;
+            }
+        }
+    }
+
+    /// Check whether the cursor is in a note column.
+    pub fn in_note_column(&self, ui: &Ui) -> bool {
+        ui.tabs.get(MAIN_TAB_ID) == Some(&TAB_PATTERN)
+            && self.edit_start.track != 0
+            && self.edit_start.column == NOTE_COLUMN
+    }
+
     /// Check whether the cursor is in the global track.
     pub fn in_global_track(&self, ui: &Ui) -> bool {
         ui.tabs.get(MAIN_TAB_ID) == Some(&TAB_PATTERN)
             && self.edit_start.track == 0
     }
 
+    /// Return the current height of a row, in pixels.
+    fn row_height(&self, ui: &Ui) -> f32 {
+        line_height(&ui.style.atlas) * self.row_zoom
+    }
+
     /// Return the current height of a beat, in pixels.
     fn beat_height(&self, ui: &Ui) -> f32 {
-        line_height(&ui.style.atlas) * self.beat_division as f32
+        self.row_height(ui) * self.beat_division as f32
     }
 
     /// Convert mouse coordinates to a Position.
@@ -179,12 +688,14 @@ impl PatternEditor {
         // skip last track_x since it's not the start of a track
         for (i, tx) in track_xs.split_last().unwrap().1.iter().enumerate() {
             if x >= *tx {
-                let chan_width = channel_width(i, &ui.style);
+                let chan_width = channel_width(i, self.is_collapsed(i), &ui.style);
                 pos.track = i;
                 pos.channel = (tracks[i].channels.len() - 1)
                     .min(((x - tx) / chan_width) as usize);
                 pos.column = if i == 0 {
                     GLOBAL_COLUMN
+                } else if self.is_collapsed(i) {
+                    NOTE_COLUMN
                 } else {
                     let x = x - tx - pos.channel as f32 * chan_width;
                     if column_x(2, &ui.style) < x {
@@ -204,7 +715,7 @@ impl PatternEditor {
     /// Returns the beat position of a vertical screen position.
     fn y_tick(&self, y: f32, ui: &Ui) -> Timespan {
         let beat_height = self.beat_height(ui);
-        let f = (y - ui.cursor_y - line_height(&ui.style.atlas) * 0.5) / beat_height;
+        let f = (y - ui.cursor_y - self.row_height(ui) * 0.5) / beat_height;
         Timespan::approximate(f.into())
     }
 
@@ -250,12 +761,20 @@ impl PatternEditor {
         (start, end)
     }
 
+    /// Returns the start and end ticks of the selection, including the tail.
+    pub fn selection_ticks(&self) -> (Timespan, Timespan) {
+        let (start, end) = self.selection_corners_with_tail();
+        (start.tick, end.tick)
+    }
+
     /// Draws the cursor/selection.
     fn draw_cursor(&self, ui: &mut Ui, track_xs: &[f32]) {
         let (tl, br) = self.selection_corners();
-        let beat_height = self.beat_height(ui);
-        let start = position_coords(tl, &ui.style, track_xs, false, beat_height);
-        let end = position_coords(br, &ui.style, track_xs, true, beat_height);
+        let (beat_height, row_height) = (self.beat_height(ui), self.row_height(ui));
+        let start = position_coords(tl, &ui.style, track_xs, &self.collapsed,
+            false, beat_height, row_height);
+        let end = position_coords(br, &ui.style, track_xs, &self.collapsed,
+            true, beat_height, row_height);
 
         let selection_rect = Rect {
             x: ui.style.margin + start.x,
@@ -271,6 +790,10 @@ impl PatternEditor {
     pub fn action(&mut self, action: Action, module: &mut Module, cfg: &Config,
         player: &mut Player
     ) {
+        if let Some(steps) = &mut self.macro_recording {
+            steps.push(MacroStep::Action(action, is_shift_down()));
+        }
+
         match action {
             Action::Cut => self.cut(module),
             Action::Copy => self.copy(module),
@@ -284,23 +807,23 @@ impl PatternEditor {
             Action::StretchPaste => self.paste(module, PasteMode::Stretch),
             Action::PrevRow => self.translate_cursor(-self.row_timespan()),
             Action::NextRow => self.translate_cursor(self.row_timespan()),
-            Action::PrevColumn => shift_column_left(
-                &mut self.edit_start, &mut self.edit_end, &module.tracks),
-            Action::NextColumn => shift_column_right(
-                &mut self.edit_start, &mut self.edit_end, &module.tracks),
+            Action::PrevColumn => shift_column_left(&mut self.edit_start,
+                &mut self.edit_end, &module.tracks, &self.collapsed, self.shift_down()),
+            Action::NextColumn => shift_column_right(&mut self.edit_start,
+                &mut self.edit_end, &module.tracks, &self.collapsed, self.shift_down()),
             Action::NextChannel => shift_channel_right(
                 &mut self.edit_start, &mut self.edit_end, &module.tracks),
             Action::PrevChannel => shift_channel_left(
                 &mut self.edit_start, &mut self.edit_end, &module.tracks),
             Action::Delete => {
                 let (start, end) = self.selection_corners_with_tail();
-                if start.x_tuple() == end.x_tuple() && is_shift_down() {
+                if start.x_tuple() == end.x_tuple() && self.shift_down() {
                     self.multi_channel_delete(module);
                 } else {
                     module.delete_events(start, end);
                 }
             },
-            Action::NoteOff => self.input_note_off(module, is_shift_down()),
+            Action::NoteOff => self.input_note_off(module, self.shift_down()),
             Action::End =>
                 insert_event_at_cursor(module, &self.edit_start, EventData::End, false),
             Action::Loop =>
@@ -314,17 +837,44 @@ impl PatternEditor {
                 | Action::NudgeEnharmonic =>
                     nudge_notes(module, self.selection_corners_with_tail(), cfg),
             Action::ToggleFollow => self.follow = !self.follow,
-            // TODO: re-enable this if & when recording is implemented
-            // Action::ToggleRecord => if self.record {
-            //     player.stop();
-            //     self.record = false;
-            // } else {
-            //     player.record_from(self.cursor_tick(), module);
-            //     self.record = true;
-            // },
+            Action::ToggleRecord => if self.record {
+                player.stop();
+                self.record = false;
+            } else {
+                let bar = cfg.bar_beats as f64;
+                let pre_roll = if cfg.record_pre_roll { bar } else { 0.0 };
+                player.record_from(self.cursor_tick(), module,
+                    cfg.record_count_in as f64 * bar, pre_roll);
+                // punch in and out at the selection, if it spans rows
+                let (start, end) = self.selection_corners();
+                self.punch_range = (cfg.record_punch && end.tick > start.tick)
+                    .then(|| (start.tick, end.tick + self.row_timespan()));
+                self.record_erased_to = self.cursor_tick();
+                self.recorded.clear();
+                self.record = true;
+            },
             Action::SelectAllChannels => self.select_all_channels(module),
             Action::SelectAllRows => self.select_all_rows(module),
             Action::PlaceEvenly => self.place_events_evenly(module),
+            Action::TuningChange => self.insert_tuning_change(module),
+            Action::RationalTempo => {
+                let (n, d) = cfg.tempo_ratio;
+                insert_event_at_cursor(module, &self.edit_start,
+                    EventData::RationalTempo(n, d), false);
+            }
+            Action::InsertVibrato => self.insert_lfo(module, EventData::Vibrato(cfg.vibrato)),
+            Action::InsertTremolo => self.insert_lfo(module, EventData::Tremolo(cfg.tremolo)),
+            Action::InsertBendRamp =>
+                self.insert_lfo(module, EventData::BendRamp(cfg.bend_ramp)),
+            Action::InsertRetrigger => self.insert_lfo(module, EventData::Retrigger(Retrigger {
+                hits: cfg.retrigger_hits,
+                length: self.row_timespan(),
+                end_pressure: cfg.retrigger_pressure,
+            })),
+            Action::InsertNoteDelay => self.insert_lfo(module,
+                EventData::NoteDelay(self.row_timespan() * cfg.note_timing)),
+            Action::InsertNoteCut => self.insert_lfo(module,
+                EventData::NoteCut(self.row_timespan() * cfg.note_timing)),
             Action::NextBeat => self.translate_cursor(Timespan::new(1, 1)),
             Action::PrevBeat => self.translate_cursor(Timespan::new(-1, 1)),
             Action::NextEvent => self.next_event(module),
@@ -341,6 +891,7 @@ impl PatternEditor {
             Action::UnmuteAllTracks => player.unmute_all(module),
             Action::CycleNotation => self.cycle_notation(module),
             Action::UseLastNote => self.use_last_note(module),
+            Action::CollapseTrack => self.toggle_collapse(self.cursor_track()),
             _ => (),
         }
 
@@ -349,6 +900,112 @@ impl PatternEditor {
         }
     }
 
+    /// Returns true if shift is down, or was down when the macro step being
+    /// replayed was recorded.
+    fn shift_down(&self) -> bool {
+        self.macro_shift.unwrap_or_else(is_shift_down)
+    }
+
+    /// Toggle step record mode. Returns true if step record is now on.
+    pub fn toggle_step_record(&mut self) -> bool {
+        self.step_record = !self.step_record;
+        self.step_chord = None;
+        self.step_record
+    }
+
+    /// Write MIDI input at the cursor in step record mode. Notes that arrive
+    /// within `STEP_CHORD_WINDOW` of the first note of a step are written in
+    /// the following channels of the track.
+    fn step_record_event(&mut self, data: EventData, module: &mut Module) {
+        match data {
+            EventData::Pitch(_) => {
+                let n = self.step_chord.as_ref().map_or(0, |c| c.notes);
+                let cursor = Position { column: NOTE_COLUMN, ..self.edit_start };
+                let Some(pos) = cursor.add_channels(n, &module.tracks)
+                    .filter(|p| p.track == cursor.track) else {
+                    return
+                };
+                insert_event_at_cursor(module, &pos, data, false);
+                let chord = self.step_chord.get_or_insert(StepChord {
+                    notes: 0,
+                    time: 0.0,
+                    last: pos,
+                });
+                chord.notes += 1;
+                chord.last = pos;
+            }
+            EventData::Pressure(_) => if let Some(chord) = &self.step_chord {
+                let pos = Position { column: VEL_COLUMN, ..chord.last };
+                insert_event_at_cursor(module, &pos, data, false);
+            },
+            _ => (),
+        }
+    }
+
+    /// Advance the cursor by `rows` once the chord window of a step has
+    /// passed.
+    fn update_step_chord(&mut self, rows: u8) {
+        if let Some(chord) = &mut self.step_chord {
+            chord.time += get_frame_time();
+            if chord.time > STEP_CHORD_WINDOW {
+                self.step_chord = None;
+                self.translate_cursor(Timespan::new(rows as i32, self.beat_division));
+            }
+        }
+    }
+
+    /// Start or stop recording a macro. Returns the number of recorded steps
+    /// if recording stopped.
+    pub fn toggle_macro_recording(&mut self) -> Option<usize> {
+        match self.macro_recording.take() {
+            Some(steps) => {
+                let n = steps.len();
+                self.last_macro = steps;
+                Some(n)
+            }
+            None => {
+                self.macro_recording = Some(Vec::new());
+                None
+            }
+        }
+    }
+
+    /// Replay the last recorded macro the configured number of times.
+    pub fn play_macro(&mut self, module: &mut Module, cfg: &Config, player: &mut Player
+    ) -> Result<(), &'static str> {
+        if self.macro_recording.is_some() {
+            return Err("Can't play a macro while recording")
+        }
+        if self.last_macro.is_empty() {
+            return Err("No macro has been recorded")
+        }
+
+        let steps = self.last_macro.clone();
+        for _ in 0..cfg.macro_repeats {
+            for step in &steps {
+                match step {
+                    MacroStep::Action(action, shift) => {
+                        self.macro_shift = Some(*shift);
+                        self.action(*action, module, cfg, player);
+                    }
+                    MacroStep::Insert(data, all_channels) =>
+                        insert_event_at_cursor(module, &self.edit_start,
+                            data.clone(), *all_channels),
+                }
+            }
+        }
+        self.macro_shift = None;
+        Ok(())
+    }
+
+    /// Insert an event typed at the cursor, recording it if recording a macro.
+    fn insert_typed(&mut self, module: &mut Module, data: EventData, all_channels: bool) {
+        if let Some(steps) = &mut self.macro_recording {
+            steps.push(MacroStep::Insert(data.clone(), all_channels));
+        }
+        insert_event_at_cursor(module, &self.edit_start, data, all_channels);
+    }
+
     fn clear_tap_tempo_state(&mut self) {
         self.tap_tempo_intervals.clear();
         self.pending_interval = None;
@@ -437,6 +1094,8 @@ impl PatternEditor {
                         | (Some(EventData::EndGlide(_)), Some(EventData::StartGlide(_)))) {
                         remove.push(start_interp_pos);
                         remove.push(end_interp_pos);
+                        remove.push(curve_position(start_interp_pos));
+                        remove.push(curve_position(end_interp_pos));
                     } else {
                         match interp_event_at_start {
                             None => add.push(LocatedEvent::from_position(start,
@@ -457,7 +1116,10 @@ impl PatternEditor {
                                     EventData::EndGlide(start.column)));
                                 remove.push(end_interp_pos);
                             }
-                            Some(EventData::StartGlide(_)) => remove.push(end_interp_pos),
+                            Some(EventData::StartGlide(_)) => {
+                                remove.push(end_interp_pos);
+                                remove.push(curve_position(end_interp_pos));
+                            }
                             _ => (),
                         }
                     }
@@ -510,7 +1172,7 @@ impl PatternEditor {
                     *v = v.saturating_add_signed(offset).min(EventData::DIGIT_MAX);
                     Some(evt)
                 }
-                EventData::Modulation(v) => {
+                EventData::Modulation(v) | EventData::GlobalModulation(v) => {
                     *v = v.saturating_add_signed(offset).min(EventData::DIGIT_MAX);
                     Some(evt)
                 }
@@ -568,7 +1230,7 @@ impl PatternEditor {
             .min_by_key(|t| (*t - cursor.tick).abs());
 
         if let Some(tick) = tick {
-            if !is_shift_down() {
+            if !self.shift_down() {
                 self.edit_start.tick = tick;
             }
             self.edit_end.tick = tick;
@@ -627,6 +1289,226 @@ impl PatternEditor {
         self.edit_end.tick = module.last_event_tick().unwrap_or_default();
     }
 
+    /// Returns the selection as pattern text. If the selection is a single
+    /// cell, returns the whole pattern instead.
+    pub fn selection_text(&self, module: &Module) -> String {
+        let (start, end) = self.text_range(module);
+        let events = module.scan_events(start, end);
+        patterntext::format_events(&events, start.tick,
+            |e| module.channels_between(start, e.position()))
+    }
+
+    /// Returns the range exported by `selection_text`.
+    fn text_range(&self, module: &Module) -> (Position, Position) {
+        let (start, end) = self.selection_corners_with_tail();
+        if start != end {
+            return (start, end)
+        }
+        (Position::default(), Position {
+            tick: module.last_event_tick().unwrap_or_default() + Timespan::new(1, 1),
+            track: module.tracks.len() - 1,
+            channel: module.tracks.last().unwrap().channels.len() - 1,
+            column: MOD_COLUMN,
+        })
+    }
+
+    /// Returns the selection as a clip, in the same way as `selection_text`.
+    pub fn selection_clip(&self, module: &Module) -> Clip {
+        let start = self.text_range(module).0;
+        let (global, tracks) = module.tunings_before(start.tick);
+        Clip {
+            text: self.selection_text(module),
+            tuning: module.tuning_at(tracks[start.track].unwrap_or(global)).clone(),
+        }
+    }
+
+    /// Inserts a clip at the cursor, remapping notes to the tunings in effect
+    /// there.
+    pub fn paste_clip(&self, module: &mut Module, clip: &Clip) -> Result<(), String> {
+        let start = self.selection_corners().0;
+        let (global, tracks) = module.tunings_before(start.tick);
+        let events = clip.events(|offset| {
+            let index = start.add_channels(offset, &module.tracks)
+                .and_then(|pos| tracks[pos.track])
+                .unwrap_or(global);
+            module.tuning_at(index)
+        })?;
+        self.insert_text_events(module, events)
+    }
+
+    /// Inserts events parsed from pattern text at the cursor.
+    pub fn paste_text(&self, module: &mut Module, text: &str) -> Result<(), String> {
+        let events = patterntext::parse_events(text)?;
+        self.insert_text_events(module, events)
+    }
+
+    /// Inserts pattern text events at the cursor.
+    fn insert_text_events(&self, module: &mut Module, events: Vec<TextEvent>
+    ) -> Result<(), String> {
+        let start = self.selection_corners().0;
+        let add: Vec<_> = events.into_iter().filter_map(|e| {
+            let pos = Position { tick: start.tick + e.tick, ..start }
+                .add_channels(e.channel_offset, &module.tracks)?;
+            e.data.goes_in_track(pos.track).then(|| LocatedEvent {
+                track: pos.track,
+                channel: pos.channel,
+                event: Event {
+                    tick: pos.tick,
+                    data: e.data,
+                },
+            })
+        }).collect();
+
+        if add.is_empty() {
+            return Err(String::from("no events fit at the cursor"))
+        }
+        module.push_edit(Edit::PatternData {
+            remove: add.iter().map(|e| e.position()).collect(),
+            add,
+        });
+        Ok(())
+    }
+
+    /// Runs a script over the selection, pushing its changes as a single
+    /// edit. Returns messages printed by the script.
+    pub fn run_script(&self, module: &mut Module, src: &str)
+    -> Result<Vec<String>, Box<dyn Error>> {
+        let (start, end) = self.selection_corners_with_tail();
+        let output = script::run(src, module, start, end)?;
+        if let Some(edit) = output.edit {
+            module.push_edit(edit);
+        }
+        Ok(output.messages)
+    }
+
+    /// Returns the cursor and scroll state.
+    pub fn view(&self) -> PatternView {
+        PatternView {
+            tick: self.edit_start.tick,
+            track: self.edit_start.track,
+            channel: self.edit_start.channel,
+            column: self.edit_start.column,
+            beat_scroll: self.beat_scroll,
+            h_scroll: self.h_scroll,
+            row_zoom: self.row_zoom,
+        }
+    }
+
+    /// Restores cursor and scroll state, if the cursor is valid for `module`.
+    pub fn restore_view(&mut self, view: &PatternView, module: &Module) {
+        let Some(track) = module.tracks.get(view.track) else { return };
+        let max_column = if view.track == 0 { GLOBAL_COLUMN } else { MOD_COLUMN };
+        if view.channel >= track.channels.len() || view.column > max_column
+            || view.tick < Timespan::ZERO {
+            return
+        }
+
+        self.edit_start = Position::new(view.tick, view.track, view.channel, view.column);
+        self.edit_end = self.edit_start;
+        self.preview_pos = self.edit_start;
+        self.beat_scroll = view.beat_scroll.max(Timespan::ZERO);
+        self.h_scroll = view.h_scroll.max(0.0);
+    }
+
+    /// Returns the most recent note at or above the cursor in its channel.
+    pub fn cursor_note(&self, module: &Module) -> Option<Note> {
+        let cursor = self.edit_start;
+        module.tracks.get(cursor.track)?.channels.get(cursor.channel)?
+            .events.iter()
+            .filter(|e| e.tick <= cursor.tick)
+            .filter_map(|e| match e.data {
+                EventData::Pitch(note) => Some(note),
+                _ => None,
+            })
+            .last()
+    }
+
+    /// Cycle the curve type of the glides containing the start of the
+    /// selection. Returns the new curve type.
+    pub fn cycle_glide_curve(&self, module: &mut Module
+    ) -> Result<GlideCurve, &'static str> {
+        let (mut start, end) = self.selection_corners();
+        let mut remove = Vec::new();
+        let mut add = Vec::new();
+        let mut new_curve = None;
+
+        // iterate over columns
+        while start.x_tuple() <= end.x_tuple() {
+            let channel = &module.tracks[start.track].channels[start.channel];
+            let glide_start = channel.interp_by_col(start.column)
+                .take_while(|e| e.tick <= start.tick)
+                .fold(None, |glide, e| match e.data {
+                    EventData::StartGlide(_) => glide.or(Some(e.tick)),
+                    EventData::EndGlide(_) if e.tick < start.tick => None,
+                    _ => glide,
+                });
+
+            if let Some(tick) = glide_start {
+                let pos = curve_position(Position {
+                    tick,
+                    column: start.column | EventData::INTERP_COL_FLAG,
+                    ..start
+                });
+                let curve = *new_curve
+                    .get_or_insert(channel.glide_curve(start.column, tick).next());
+                remove.push(pos);
+                if curve != GlideCurve::Linear {
+                    add.push(LocatedEvent::from_position(pos,
+                        EventData::GlideCurve(start.column, curve)));
+                }
+            }
+
+            if let Some(pos) = start.add_channels(1, &module.tracks) {
+                start = pos
+            } else {
+                break
+            }
+        }
+
+        let curve = new_curve.ok_or("No glide at cursor")?;
+        module.push_edit(Edit::PatternData { remove, add });
+        Ok(curve)
+    }
+
+    /// Scale pressure and modulation values in the selection.
+    pub fn scale_values(&self, module: &mut Module, scaling: &ValueScaling
+    ) -> Result<(), &'static str> {
+        let (start, end) = self.selection_corners_with_tail();
+        let mut found = false;
+        let replacements: Vec<_> = module.scan_events(start, end).into_iter()
+            .filter_map(|mut evt| {
+                let (EventData::Pressure(v) | EventData::Modulation(v)) =
+                    &mut evt.event.data else {
+                    return None
+                };
+                found = true;
+                let scaled = scaling.apply(*v);
+                if scaled == *v {
+                    return None
+                }
+                *v = scaled;
+                Some(evt)
+            })
+            .collect();
+
+        if !found {
+            return Err("No pressure or modulation values selected")
+        }
+        if !replacements.is_empty() {
+            module.push_edit(Edit::ReplaceEvents(replacements));
+        }
+        Ok(())
+    }
+
+    /// Fill the selection with random notes.
+    pub fn generate_melody(&self, module: &mut Module, settings: &MelodySettings
+    ) -> Result<(), &'static str> {
+        let (start, end) = self.selection_corners_with_tail();
+        let edit = melody::generate(module, start, end, self.row_timespan(), settings)?;
+        module.push_edit(edit);
+        Ok(())
+    }
+
     /// Return the current timespan of a single row.
     fn row_timespan(&self) -> Timespan {
         Timespan::new(1, self.beat_division)
@@ -662,9 +1544,39 @@ impl PatternEditor {
         })
     }
 
+    /// Handle the "insert tuning change" key command. Cycles through the
+    /// module tunings if the cursor is already on a tuning change.
+    fn insert_tuning_change(&self, module: &mut Module) {
+        let cursor = self.edit_start;
+        if cursor.column != NOTE_COLUMN {
+            return
+        }
+
+        let index = match module.event_at(&cursor).map(|e| &e.data) {
+            Some(EventData::TuningChange(i)) => *i as usize + 1,
+            _ => 1,
+        } % module.tuning_count();
+        insert_event_at_cursor(module, &cursor, EventData::TuningChange(index as u8), false);
+    }
+
+    /// Handle the "insert vibrato/tremolo/bend ramp/retrigger/note timing" key
+    /// commands.
+    fn insert_lfo(&self, module: &mut Module, data: EventData) {
+        if self.edit_start.column == NOTE_COLUMN {
+            insert_event_at_cursor(module, &self.edit_start, data, false);
+        }
+    }
+
     /// Handle raw keys for digit input.
     fn handle_key(&mut self, key: KeyCode, module: &mut Module, ui: &mut Ui) {
         if !(is_ctrl_down() || is_alt_down()) {
+            // global modulation is entered as text in the control column
+            if key == KeyCode::M && self.edit_start.track == 0
+                && self.edit_start.column == GLOBAL_COLUMN {
+                self.text_position = Some(self.edit_start);
+                ui.focus_text(CTRL_COLUMN_TEXT_ID.into(), String::from("m"));
+                return
+            }
             let value = match key {
                 KeyCode::Key0 => 0,
                 KeyCode::Key1 => 1,
@@ -686,10 +1598,10 @@ impl PatternEditor {
             };
 
             match self.edit_start.column {
-                VEL_COLUMN => insert_event_at_cursor(module, &self.edit_start,
-                    EventData::Pressure(value), is_shift_down()),
-                MOD_COLUMN => insert_event_at_cursor(module, &self.edit_start,
-                    EventData::Modulation(value), is_shift_down()),
+                VEL_COLUMN =>
+                    self.insert_typed(module, EventData::Pressure(value), is_shift_down()),
+                MOD_COLUMN =>
+                    self.insert_typed(module, EventData::Modulation(value), is_shift_down()),
                 GLOBAL_COLUMN => if self.edit_start.track == 0 && value < 10 {
                     self.text_position = Some(self.edit_start);
                     ui.focus_text(CTRL_COLUMN_TEXT_ID.into(), value.to_string());
@@ -803,12 +1715,80 @@ impl PatternEditor {
         }
     }
 
-    fn draw_channel(&self, ui: &mut Ui, channel: &Channel, muted: bool, index: usize) {
+    /// Draw a channel's events. If `kit` is given, named kit notes are shown
+    /// by name.
+    fn draw_channel(&self, ui: &mut Ui, channel: &Channel, muted: bool, index: usize,
+        collapsed: bool, kit: Option<&Module>
+    ) {
         self.draw_channel_line(ui, index == 0);
-        self.draw_interpolation(ui, channel);
+        self.draw_interpolation(ui, channel, collapsed);
         let beat_height = self.beat_height(ui);
+        self.draw_note_timing(ui, channel, beat_height, muted);
         for event in &channel.events {
-            self.draw_event(ui, event, beat_height, muted);
+            if !collapsed || event.data.spatial_column() == NOTE_COLUMN {
+                self.draw_event(ui, event, beat_height, muted, kit);
+            }
+        }
+    }
+
+    /// Draw the note column events of a channel from another track, faintly.
+    fn draw_ghost_notes(&self, ui: &mut Ui, channel: &Channel, beat_height: f32) {
+        let color = Color { a: GHOST_ALPHA, ..ui.style.theme.fg() };
+        let x = ui.cursor_x + column_x(NOTE_COLUMN, &ui.style);
+        if x < 0.0 || x > ui.bounds.x + ui.bounds.w {
+            return
+        }
+
+        for evt in &channel.events {
+            let y = ui.cursor_y + evt.tick.as_f32() * beat_height;
+            if y < 0.0 || y > ui.bounds.y + ui.bounds.h {
+                continue
+            }
+
+            if self.condensed() {
+                if matches!(evt.data, EventData::Pitch(_)) {
+                    let w = column_x(NOTE_COLUMN + 1, &ui.style)
+                        - column_x(NOTE_COLUMN, &ui.style);
+                    let rect = Rect {
+                        x: x + ui.style.margin,
+                        y,
+                        w: w - ui.style.margin * 2.0,
+                        h: (self.row_height(ui) - 1.0).max(1.0),
+                    };
+                    ui.push_rect(rect, color, None);
+                }
+                continue
+            }
+
+            let y = y - ui.style.margin + PATTERN_MARGIN;
+            match evt.data {
+                EventData::Pitch(note) => ui.push_note_text(x, y, &note, color),
+                EventData::NoteOff => ui.push_text(x, y, String::from(" ---"), color),
+                _ => (),
+            }
+        }
+    }
+
+    /// Draw note delays and cuts as lines at the time they take effect.
+    fn draw_note_timing(&self, ui: &mut Ui, channel: &Channel, beat_height: f32,
+        muted: bool
+    ) {
+        let x1 = ui.cursor_x + ui.style.margin;
+        let x2 = ui.cursor_x + column_x(VEL_COLUMN, &ui.style) - ui.style.margin;
+
+        for evt in &channel.events {
+            let (offset, color) = match evt.data {
+                EventData::NoteDelay(t) => (t, ui.style.theme.accent1_fg()),
+                EventData::NoteCut(t) => (t, ui.style.theme.accent2_fg()),
+                _ => continue,
+            };
+            let y = (ui.cursor_y + (evt.tick + offset).as_f32() * beat_height).round()
+                + LINE_THICKNESS * 0.5;
+            if y < 0.0 || y > ui.bounds.y + ui.bounds.h {
+                continue
+            }
+            let color = if muted { Color { a: 0.25, ..color } } else { color };
+            ui.push_line(x1, y, x2, y, color);
         }
     }
 
@@ -828,7 +1808,7 @@ impl PatternEditor {
     }
 
     /// Draw all interpolation lines for a channel.
-    fn draw_interpolation(&self, ui: &mut Ui, channel: &Channel) {
+    fn draw_interpolation(&self, ui: &mut Ui, channel: &Channel, collapsed: bool) {
         const NUM_COLS: usize = 3;
 
         ui.cursor_z -= 1;
@@ -849,7 +1829,8 @@ impl PatternEditor {
             }
         }
 
-        for col in 0..NUM_COLS {
+        let visible_cols = if collapsed { 1 } else { NUM_COLS };
+        for col in 0..visible_cols {
             let mut start_tick = None;
             let x = ui.cursor_x + ui.style.margin - 1.0 - LINE_THICKNESS * 0.5
                 + column_x(col as u8, &ui.style);
@@ -859,12 +1840,24 @@ impl PatternEditor {
             let mut lines = Vec::new();
             let mut marks = Vec::new();
 
-            let mut draw_line = |start: Timespan, end: Timespan| {
+            let mut draw_line = |start: Timespan, end: Timespan, curve: GlideCurve| {
                 let y1 = ui.cursor_y
                     + (start + tpr * Timespan::new(1, 4)).as_f32() * beat_height;
                 let y2 = ui.cursor_y
                     + (end + tpr * Timespan::new(3, 4)).as_f32() * beat_height;
-                lines.push(Graphic::Line(x, y1, x, y2, colors[col as usize]));
+                if curve == GlideCurve::Linear {
+                    lines.push(Graphic::Line(x, y1, x, y2, colors[col as usize]));
+                } else {
+                    // dash non-linear glides, skipping dashes above the screen
+                    let period = ui.style.margin * 2.0;
+                    let mut y = y1 + ((ui.bounds.y - y1) / period).floor().max(0.0) * period;
+                    let y2 = y2.min(ui.bounds.y + ui.bounds.h);
+                    while y < y2 {
+                        lines.push(Graphic::Line(x, y, x, (y + period * 0.5).min(y2),
+                            colors[col as usize]));
+                        y += period;
+                    }
+                }
             };
 
             let mut draw_dup = |tick: Timespan| {
@@ -880,27 +1873,28 @@ impl PatternEditor {
                 match event.data {
                     EventData::StartGlide(_) => {
                         if start_tick.is_none() {
-                            start_tick = Some(event.tick);
+                            start_tick = Some((event.tick,
+                                channel.glide_curve(col as u8, event.tick)));
                         } else {
                             draw_dup(event.tick);
                         }
                     }
                     EventData::EndGlide(_) => {
-                        if let Some(start_tick) = start_tick.take() {
-                            draw_line(start_tick, event.tick);
+                        if let Some((start_tick, curve)) = start_tick.take() {
+                            draw_line(start_tick, event.tick, curve);
                         } else {
                             draw_dup(event.tick);
                         }
                     }
                     EventData::TickGlide(_) => if start_tick.is_none() {
-                        draw_line(event.tick, event.tick);
+                        draw_line(event.tick, event.tick, GlideCurve::Linear);
                     }
                     _ => panic!("expected glide event"),
                 }
             }
 
-            if let Some(start_tick) = start_tick {
-                draw_line(start_tick, self.screen_tick_max);
+            if let Some((start_tick, curve)) = start_tick {
+                draw_line(start_tick, self.screen_tick_max, curve);
             }
 
             ui.push_graphics(lines);
@@ -975,28 +1969,98 @@ impl PatternEditor {
         }
     }
 
-    /// Handle event input in record mode.
-    fn record_event(&mut self, data: EventData, module: &mut Module) {
-        let cursor = self.edit_start;
+    /// Handle event input in record mode. Events routed to a `track` other
+    /// than the cursor's are recorded in its first channel.
+    fn record_event(&mut self, data: EventData, track: Option<usize>, module: &mut Module) {
+        let mut cursor = self.edit_start;
+        if let Some(track) = track.filter(|t| *t != cursor.track) {
+            cursor.track = track;
+            cursor.channel = 0;
+        }
         if !data.goes_in_track(cursor.track) {
             return
         }
 
-        // skip to next open row
         let mut pos = Position {
             track: cursor.track,
             tick: cursor.tick,
             channel: cursor.channel,
             column: data.logical_column(),
         };
-        if module.event_at(&pos).is_some_and(|e| e.data != EventData::NoteOff) {
+        if let EventData::Modulation(_) = data {
+            // controller moves replace the row's value, so that the last
+            // value in each row is kept
+            if module.event_at(&pos).is_some_and(|e| e.data == data) {
+                return
+            }
+        } else if module.event_at(&pos).is_some_and(|e| e.data != EventData::NoteOff) {
+            // skip to next open row
             pos.tick += self.row_timespan();
         }
+        if self.punch_range.is_some_and(|(start, end)| pos.tick < start || pos.tick >= end) {
+            return
+        }
 
         module.insert_event(cursor.track, cursor.channel, Event {
             tick: pos.tick,
             data,
         });
+        self.recorded.push(pos);
+    }
+
+    /// Move the cursor to the recording position, erasing the events it
+    /// passes in replace mode.
+    fn follow_recording(&mut self, module: &mut Module, player: &Player, conf: &Config) {
+        // offset recorded input by output latency, since the player is
+        // reacting to what they hear
+        let tick = self.round_tick(if conf.latency_compensation {
+            player.latency_compensated_tick()
+        } else {
+            player.get_tick()
+        });
+        self.edit_start.tick = tick;
+        self.edit_end.tick = tick;
+        if conf.record_mode == RecordMode::Replace && player.recording_armed() {
+            self.erase_for_replace(module, tick + self.row_timespan());
+        }
+    }
+
+    /// Erase events in the cursor channel up to `end`, for replace
+    /// recording. Events written in the current recording pass are kept.
+    fn erase_for_replace(&mut self, module: &mut Module, end: Timespan) {
+        let mut start = self.record_erased_to;
+        self.record_erased_to = end.max(start);
+        let mut end = end;
+        if let Some((punch_in, punch_out)) = self.punch_range {
+            start = start.max(punch_in);
+            end = end.min(punch_out);
+        }
+        if end <= start {
+            return
+        }
+
+        let (track, channel) = (self.edit_start.track, self.edit_start.channel);
+        let remove: Vec<_> = module.tracks[track].channels[channel].events.iter()
+            .filter(|e| e.tick >= start && e.tick < end)
+            .map(|e| Position { track, channel, tick: e.tick, column: e.data.logical_column() })
+            .filter(|pos| !self.recorded.contains(pos))
+            .collect();
+        if !remove.is_empty() {
+            module.push_edit(Edit::PatternData { remove, add: Vec::new() });
+        }
+    }
+
+    /// Record an event generated at `tick` in `track`, such as an
+    /// arpeggiated note. Generated events aren't latency compensated.
+    pub fn record_generated(&mut self, data: EventData, track: usize, tick: Timespan,
+        module: &mut Module
+    ) {
+        if self.record {
+            let cursor_tick = self.edit_start.tick;
+            self.edit_start.tick = self.round_tick(tick);
+            self.record_event(data, Some(track), module);
+            self.edit_start.tick = cursor_tick;
+        }
     }
 
     /// Move the cursor by `offset`.
@@ -1004,7 +2068,7 @@ impl PatternEditor {
         self.edit_end.tick = self.round_tick(self.edit_end.tick + offset)
             .max(Timespan::ZERO);
 
-        if !is_shift_down() {
+        if !self.shift_down() {
             self.edit_start.tick = self.edit_end.tick;
         }
 
@@ -1025,7 +2089,9 @@ impl PatternEditor {
     }
 
     /// Draw a single pattern event.
-    fn draw_event(&self, ui: &mut Ui, evt: &Event, beat_height: f32, muted: bool) {
+    fn draw_event(&self, ui: &mut Ui, evt: &Event, beat_height: f32, muted: bool,
+        kit: Option<&Module>
+    ) {
         let y = ui.cursor_y + evt.tick.as_f32() * beat_height;
         if y < 0.0 || y > ui.bounds.y + ui.bounds.h {
             return
@@ -1041,7 +2107,7 @@ impl PatternEditor {
                 a: 0.5 + x as f32 / (EventData::DIGIT_MAX as f32 * 2.0),
                 ..ui.style.theme.accent1_fg()
             },
-            EventData::Modulation(x) => Color {
+            EventData::Modulation(x) | EventData::GlobalModulation(x) => Color {
                 a: 0.5 + x as f32 / (EventData::DIGIT_MAX as f32 * 2.0),
                 ..ui.style.theme.accent2_fg()
             },
@@ -1051,18 +2117,39 @@ impl PatternEditor {
             color = Color { a: 0.25, ..color };
         }
 
+        if self.condensed() {
+            if !matches!(evt.data, EventData::StartGlide(_) | EventData::EndGlide(_)
+                | EventData::TickGlide(_) | EventData::GlideCurve(..)
+                | EventData::NoteDelay(_) | EventData::NoteCut(_)) {
+                let rect = Rect {
+                    x: x + ui.style.margin,
+                    y,
+                    w: column_x(col + 1, &ui.style) - column_x(col, &ui.style)
+                        - ui.style.margin * 2.0,
+                    h: (self.row_height(ui) - 1.0).max(1.0),
+                };
+                ui.push_rect(rect, color, None);
+            }
+            return
+        }
+
         let y = y - ui.style.margin + PATTERN_MARGIN;
         let text = match evt.data {
-            EventData::Pitch(note) => {
-                ui.push_note_text(x, y, &note, color);
-                return
+            EventData::Pitch(note) => match kit.and_then(|m| m.kit_name(note)) {
+                Some(name) => name.chars().take(4).collect(),
+                None => {
+                    ui.push_note_text(x, y, &note, color);
+                    return
+                }
             },
             EventData::NoteOff => String::from(" ---"),
             EventData::Pressure(v) => format!("{:X}", v),
             EventData::Modulation(v) => format!("{:X}", v),
+            EventData::GlobalModulation(v) => format!("Mod{:X}", v),
             EventData::End => String::from("End"),
             EventData::Loop => String::from("Loop"),
             EventData::Section => String::from("Sect"),
+            EventData::TuningChange(i) => format!("Tun{}", i),
             EventData::Tempo(t) => t.round().to_string(),
             EventData::RationalTempo(n, d) => format!("{}:{}", n, d),
             EventData::InterpolatedPitch(_)
@@ -1071,8 +2158,15 @@ impl PatternEditor {
                 => panic!("interpolated event in pattern"),
             EventData::StartGlide(_)
                 | EventData::EndGlide(_)
-                | EventData::TickGlide(_) => return,
+                | EventData::TickGlide(_)
+                | EventData::GlideCurve(..)
+                | EventData::NoteDelay(_)
+                | EventData::NoteCut(_) => return,
             EventData::Bend(c) => format!("{:+}", c),
+            EventData::Vibrato(lfo) => format!("Vib{:X}", lfo.depth),
+            EventData::Tremolo(lfo) => format!("Trm{:X}", lfo.depth),
+            EventData::BendRamp(ramp) => format!("{:+}~", ramp.cents),
+            EventData::Retrigger(r) => format!("Rt{}", r.hits),
         };
         ui.push_text(x, y, text, color);
     }
@@ -1096,6 +2190,169 @@ impl PatternEditor {
         }
     }
 
+    /// Start a mouse edit at `pos`. Clicking a value column starts painting
+    /// `value`, clicking an event starts moving it, and clicking an empty
+    /// note column places the last note in the channel.
+    fn start_mouse_edit(&mut self, pos: Position, value: u8, module: &mut Module) {
+        if pos.track != 0 && pos.column != NOTE_COLUMN {
+            self.mouse_drag = Some(MouseDrag::Paint {
+                track: pos.track,
+                channel: pos.channel,
+                column: pos.column,
+                last_tick: pos.tick,
+                values: BTreeMap::from([(pos.tick, value)]),
+            });
+        } else if module.event_at(&pos).is_some() {
+            self.mouse_drag = Some(MouseDrag::Move { from: pos, to: pos.tick });
+        } else if pos.track != 0 {
+            let note = module.tracks[pos.track].channels[pos.channel].events.iter()
+                .filter(|e| e.tick < pos.tick)
+                .filter_map(|e| match e.data {
+                    EventData::Pitch(note) => Some(note),
+                    _ => None,
+                })
+                .last()
+                .unwrap_or(Note::new(0, Nominal::C, 0, 4));
+            module.insert_event(pos.track, pos.channel, Event {
+                tick: pos.tick,
+                data: EventData::Pitch(note),
+            });
+        }
+    }
+
+    /// Update a mouse edit for the mouse being at `pos`.
+    fn update_mouse_edit(&mut self, pos: Position, value: u8) {
+        let row = self.row_timespan();
+        if let Some(drag) = &mut self.mouse_drag {
+            drag.update(pos.tick, value, row);
+            self.edit_start.tick = pos.tick;
+            self.edit_end = self.edit_start;
+        }
+    }
+
+    /// Apply the mouse edit in progress, if any.
+    fn finish_mouse_edit(&mut self, module: &mut Module) {
+        if let Some(edit) = self.mouse_drag.take().and_then(|d| d.edit(module)) {
+            module.push_edit(edit);
+        }
+    }
+
+    /// Draws the notes of the cursor track in `rect`, with time horizontal
+    /// over the visible pattern range and scale degrees vertical. Notes can
+    /// be dragged vertically to transpose them.
+    fn draw_piano_roll(&mut self, ui: &mut Ui, module: &mut Module, rect: Rect) {
+        ui.cursor_z += PANEL_Z_OFFSET;
+        ui.push_rect(rect, ui.style.theme.content_bg(), None);
+        ui.push_line(rect.x, rect.y + LINE_THICKNESS * 0.5,
+            rect.x + rect.w, rect.y + LINE_THICKNESS * 0.5,
+            ui.style.theme.border_unfocused());
+
+        let (start, end) = (self.beat_scroll, self.screen_tick_max);
+        let track = self.edit_start.track;
+        let mut segments = roll_segments(module, track, end);
+        segments.retain(|s| s.end >= start && s.pos.tick <= end);
+
+        // apply any drag in progress
+        if let Some(drag) = &self.roll_drag {
+            if let Some(s) = segments.iter_mut().find(|s| s.pos == drag.pos) {
+                let steps = drag.steps as i32;
+                s.degrees = (s.degrees.0 + steps, s.degrees.1 + steps);
+            }
+        }
+
+        let margin = ui.style.margin;
+        let (min, max) = match &self.roll_drag {
+            Some(drag) => drag.range,
+            None => segments.iter()
+                .flat_map(|s| [s.degrees.0, s.degrees.1])
+                .fold(None, |acc: Option<(i32, i32)>, d| Some(match acc {
+                    Some((min, max)) => (min.min(d), max.max(d)),
+                    None => (d, d),
+                }))
+                .map_or((0, 0), |(min, max)|
+                    (min - PIANO_ROLL_PADDING, max + PIANO_ROLL_PADDING)),
+        };
+        let row_h = match &self.roll_drag {
+            Some(drag) => drag.row_h,
+            None => (rect.h - margin * 2.0) / (max - min + 1) as f32,
+        };
+        let span = (end - start).as_f32().max(f32::EPSILON);
+        let x_of = |tick: Timespan| rect.x + ((tick - start).as_f32() / span * rect.w)
+            .clamp(0.0, rect.w);
+        let y_of = |degree: i32| rect.y + rect.h - margin - (degree - min + 1) as f32 * row_h;
+
+        // equave lines
+        let size = module.tuning.size() as i32;
+        for degree in min..=max {
+            if size > 0 && degree.rem_euclid(size) == 0 {
+                let y = y_of(degree) + row_h;
+                ui.push_line(rect.x, y, rect.x + rect.w, y, ui.style.theme.border_unfocused());
+            }
+        }
+
+        // notes and glides
+        let bar_h = (row_h - LINE_THICKNESS).max(LINE_THICKNESS);
+        for s in &segments {
+            let color = if s.pos.channel == self.edit_start.channel {
+                ui.style.theme.accent1_fg()
+            } else {
+                ui.style.theme.fg()
+            };
+            let (x1, x2) = (x_of(s.pos.tick), x_of(s.end));
+            let (y1, y2) = (y_of(s.degrees.0), y_of(s.degrees.1));
+            if s.degrees.0 == s.degrees.1 {
+                ui.push_rect(Rect { x: x1, y: y1, w: (x2 - x1).max(LINE_THICKNESS), h: bar_h },
+                    color, None);
+            } else {
+                ui.push_line(x1, y1 + bar_h * 0.5, x2, y2 + bar_h * 0.5, color);
+            }
+        }
+
+        // cursor
+        if (start..=end).contains(&self.edit_start.tick) {
+            let x = x_of(self.edit_start.tick);
+            ui.push_line(x, rect.y, x, rect.y + rect.h, ui.style.theme.accent2_fg());
+        }
+
+        // mouse input
+        let (mouse_x, mouse_y) = mouse_position();
+        if ui.mouse_hits(rect, PIANO_ROLL_ID) {
+            ui.info = Info::PianoRoll;
+            if is_mouse_button_pressed(MouseButton::Left) {
+                let hit = segments.iter().find(|s| s.degrees.0 == s.degrees.1
+                    && (x_of(s.pos.tick)..=x_of(s.end).max(x_of(s.pos.tick) + 1.0))
+                        .contains(&mouse_x)
+                    && (y_of(s.degrees.0)..y_of(s.degrees.0) + row_h).contains(&mouse_y));
+                if let Some(s) = hit {
+                    self.edit_start = s.pos;
+                    self.edit_end = s.pos;
+                    self.roll_drag = Some(RollDrag {
+                        pos: s.pos,
+                        start_y: mouse_y,
+                        range: (min, max),
+                        row_h,
+                        steps: 0,
+                    });
+                    ui.mouse_consumed = Some(PIANO_ROLL_ID.to_owned());
+                }
+            }
+        }
+        if let Some(drag) = &mut self.roll_drag {
+            drag.steps = ((drag.start_y - mouse_y) / drag.row_h).round() as isize;
+            if !is_mouse_button_down(MouseButton::Left) {
+                if let Some(edit) = self.roll_drag.take().and_then(|d| d.edit(module)) {
+                    module.push_edit(edit);
+                }
+            }
+        }
+
+        if segments.is_empty() {
+            ui.push_text(rect.x + margin, rect.y + margin, String::from("(no notes)"),
+                ui.style.theme.border_unfocused());
+        }
+        ui.cursor_z -= PANEL_Z_OFFSET;
+    }
+
     /// Handle entered control column text.
     fn enter_ctrl_text(&mut self, s: String, module: &mut Module, ui: &mut Ui) {
         if let Some(pos) = self.text_position.take() {
@@ -1118,17 +2375,48 @@ fn parse_ctrl_text(s: &str) -> Option<EventData> {
         if f > 0.0 {
             return Some(EventData::Tempo(f))
         }
-    } else if let Some((n, d)) = s.split_once(['/', ':']) {
-        let n = n.parse::<u8>().ok()?;
-        let d = d.parse::<u8>().ok()?;
-        if n > 0 && d > 0 {
-            return Some(EventData::RationalTempo(n, d))
-        }
+    } else if let Some((n, d)) = parse_tempo_ratio(s) {
+        return Some(EventData::RationalTempo(n, d))
+    } else if let Some(v) = s.strip_prefix(['m', 'M']) {
+        return u8::from_str_radix(v, 16).ok()
+            .filter(|v| *v <= EventData::DIGIT_MAX)
+            .map(EventData::GlobalModulation)
     }
 
     None
 }
 
+/// Parse an "n:d" or "n/d" tempo ratio.
+pub fn parse_tempo_ratio(s: &str) -> Option<(u8, u8)> {
+    let (n, d) = s.split_once(['/', ':'])?;
+    let n = n.trim().parse::<u8>().ok()?;
+    let d = d.trim().parse::<u8>().ok()?;
+    (n > 0 && d > 0).then_some((n, d))
+}
+
+/// Returns tempo ratios for the rational tempo picker. Ratios based on the
+/// beat division come first; each makes a whole number of rows take as long
+/// as one beat did. Common ratios not already listed follow.
+pub fn tempo_ratio_options(division: u8) -> Vec<(u8, u8)> {
+    let d = division.max(1) as u16;
+    let mut options: Vec<(u8, u8)> = (1..=d * 2)
+        .filter(|k| *k != d)
+        .map(|k| {
+            let g = k.gcd(d);
+            ((k / g) as u8, (d / g) as u8)
+        })
+        .collect();
+    options.extend(COMMON_TEMPO_RATIOS);
+
+    let mut unique = Vec::new();
+    for ratio in options {
+        if !unique.contains(&ratio) {
+            unique.push(ratio);
+        }
+    }
+    unique
+}
+
 pub fn draw(ui: &mut Ui, module: &mut Module, player: &mut Player, pe: &mut PatternEditor,
     conf: &Config
 ) {
@@ -1148,24 +2436,86 @@ pub fn draw(ui: &mut Ui, module: &mut Module, player: &mut Player, pe: &mut Patt
 
     // note input
     let cursor = pe.edit_start;
+    let mut typed_pitch = false;
     if pe.record {
-        while let Some((_, data)) = ui.note_queue.pop() {
-            pe.record_event(data, module);
-        }
-    } else if !ui.accepting_note_input() && cursor.column == NOTE_COLUMN {
-        while let Some((_, data)) = ui.note_queue.pop() {
-            match data {
-                EventData::NoteOff => (),
-                _ => insert_event_at_cursor(module, &cursor, data, false),
+        while let Some((key, data)) = ui.note_queue.pop() {
+            // channel pressure and bend have no key, so they follow the
+            // channel's routing
+            let keyed = match data {
+                EventData::Pitch(_) | EventData::NoteOff => true,
+                EventData::Pressure(_) => key.key != 0,
+                _ => false,
+            };
+            let track = (key.origin == KeyOrigin::Midi)
+                .then(|| if keyed {
+                    conf.midi_key_track(key.channel, key.key, module.tracks.len())
+                } else {
+                    conf.midi_channel_track(key.channel, module.tracks.len())
+                })
+                .flatten();
+            // input during count-in and pre-roll is discarded, and held
+            // notes are replaced by arpeggiator output when capturing it
+            let captured = conf.arp.enabled && conf.arp.capture && keyed;
+            if player.recording_armed() && !captured {
+                pe.record_event(data, track, module);
+            }
+        }
+    } else if !ui.accepting_note_input() {
+        // MIDI notes are step recorded; other input is typed as usual
+        if pe.step_record {
+            let (midi, other) = ui.note_queue.drain(..)
+                .partition(|(key, _)| key.origin == KeyOrigin::Midi);
+            ui.note_queue = other;
+            for (_, data) in midi {
+                pe.step_record_event(data, module);
+            }
+        }
+        if cursor.column == NOTE_COLUMN {
+            while let Some((_, data)) = ui.note_queue.pop() {
+                match data {
+                    // mod wheel input is only written when recording
+                    EventData::NoteOff | EventData::Modulation(_) => (),
+                    _ => {
+                        typed_pitch |= matches!(data, EventData::Pitch(_));
+                        pe.insert_typed(module, data, false);
+                    }
+                }
             }
         }
     }
+    if pe.step_record {
+        pe.update_step_chord(conf.step_record_rows);
+    }
+
+    // audition typed notes and notes the cursor moves onto
+    if conf.preview_notes && !player.is_playing() {
+        if typed_pitch {
+            preview_note_at(module, player, &cursor);
+        } else if pe.preview_pos != pe.edit_start {
+            preview_note_at(module, player, &pe.edit_start);
+        }
+    }
+    if pe.preview_pos != pe.edit_start {
+        ui.announce(pe.describe_cursor(module));
+    }
+    pe.preview_pos = pe.edit_start;
+
+    pe.collapsed.retain(|i| *i < module.tracks.len());
+    pe.ghost_tracks.retain(|i, j| *i < module.tracks.len() && *j < module.tracks.len());
+    pe.fix_collapsed_cursors();
+
+    // reserve space for the piano roll below the pattern
+    let roll_rect = conf.show_piano_roll.then(|| {
+        let h = (ui.style.line_height() * PIANO_ROLL_LINES).round();
+        ui.bounds.h -= h;
+        Rect { y: ui.bounds.y + ui.bounds.h, h, ..ui.bounds }
+    });
 
     // draw track headers
     ui.start_group();
     ui.cursor_x -= pe.h_scroll;
     let left_x = ui.cursor_x;
-    let track_xs = draw_track_headers(ui, module, player, pe);
+    let track_xs = draw_track_headers(ui, module, player, pe, conf.show_profiler);
     let rect = Rect {
         w: ui.bounds.w - left_x.min(0.0),
         ..ui.end_group().unwrap()
@@ -1191,9 +2541,7 @@ pub fn draw(ui: &mut Ui, module: &mut Module, player: &mut Player, pe: &mut Patt
         pe.scroll_to(playhead_tick);
     }
     if pe.record {
-        let tick = pe.round_tick(player.get_tick());
-        pe.edit_start.tick = tick;
-        pe.edit_end.tick = tick;
+        pe.follow_recording(module, player, conf);
     }
     let mut scroll = pe.scroll(ui);
     if !(pe.follow || pe.record) || !player.is_playing() {
@@ -1201,6 +2549,19 @@ pub fn draw(ui: &mut Ui, module: &mut Module, player: &mut Player, pe: &mut Patt
         ui.vertical_scrollbar(&mut scroll, end_y, viewport_h, false);
         pe.set_scroll(scroll, ui);
     }
+    if conf.show_minimap {
+        pe.minimap.update(module);
+        let view = (pe.beat_scroll, pe.screen_tick_max);
+        let playhead = player.is_playing().then_some(playhead_tick);
+        if let Some(tick) = draw_minimap(ui, &pe.minimap, view, playhead) {
+            pe.scroll_to(tick);
+            scroll = pe.scroll(ui);
+        }
+    }
+    let tempo_lane_x = conf.show_tempo_lane.then(|| {
+        ui.bounds.w -= TEMPO_LANE_WIDTH;
+        ui.bounds.x + ui.bounds.w
+    });
     {
         let max_x = track_xs.last().unwrap() - left_x
             + ui.style.margin * 4.0 + ui.style.atlas.char_width();
@@ -1227,8 +2588,21 @@ pub fn draw(ui: &mut Ui, module: &mut Module, player: &mut Player, pe: &mut Patt
                 pe.edit_start = pe.edit_end;
             }
             pe.clear_tap_tempo_state();
+            if is_ctrl_down() {
+                let value = paint_value(mouse_position().0, pos, &track_xs, &ui.style);
+                pe.start_mouse_edit(pos, value, module);
+            }
         } else if is_mouse_button_down(MouseButton::Left) && !ui.grabbed() {
-            pe.edit_end = pos;
+            match &pe.mouse_drag {
+                Some(MouseDrag::Paint { track, channel, .. }) => {
+                    let origin = Position { track: *track, channel: *channel, ..pos };
+                    let value = paint_value(mouse_position().0, origin, &track_xs,
+                        &ui.style);
+                    pe.update_mouse_edit(pos, value);
+                }
+                Some(MouseDrag::Move { .. }) => pe.update_mouse_edit(pos, 0),
+                None => pe.edit_end = pos,
+            }
         }
 
         if (track_xs[0]..*track_xs.last().unwrap()).contains(&mouse_position().0) {
@@ -1242,29 +2616,61 @@ pub fn draw(ui: &mut Ui, module: &mut Module, player: &mut Player, pe: &mut Patt
         }
     }
 
+    if pe.mouse_drag.is_some() && !is_mouse_button_down(MouseButton::Left) {
+        pe.finish_mouse_edit(module);
+    }
+
     // draw background visuals
     ui.cursor_z -= 1;
     ui.push_rect(viewport, ui.style.theme.content_bg(), None);
-    draw_beats(ui, left_x, beat_height);
+    draw_beats(ui, left_x, beat_height, pe.row_height(ui));
+    draw_track_tints(ui, &module.tracks, &track_xs, viewport);
     ui.cursor_z += 1;
+    if let Some((start, end)) = player.loop_range() {
+        draw_loop_range(ui, start, end, player.loop_enabled(),
+            left_x + pe.h_scroll, beat_height);
+    }
     if player.is_playing() {
-        draw_playhead(ui, playhead_tick, left_x + pe.h_scroll, beat_height);
+        draw_playhead(ui, playhead_tick, left_x + pe.h_scroll, beat_height,
+            pe.row_height(ui));
     }
     pe.draw_cursor(ui, &track_xs);
+    if let Some(x) = tempo_lane_x {
+        draw_tempo_lane(ui, &module.tempo_map(), x, viewport, beat_height);
+    }
 
-    // draw channel data
+    // draw channel data, with any mouse edit in progress applied
+    let drag_edit = pe.mouse_drag.as_ref().and_then(|d| d.edit(module));
     for (track_i, track) in module.tracks.iter().enumerate() {
-        let chan_width = channel_width(track_i, &ui.style);
+        let collapsed = pe.is_collapsed(track_i);
+        let chan_width = channel_width(track_i, collapsed, &ui.style);
+
+        // ghost notes are drawn first, so that the track's own notes cover them
+        let ghost = pe.ghost_tracks.get(&track_i).and_then(|&j| module.tracks.get(j));
+        if let Some(ghost) = ghost {
+            let last_channel = track.channels.len() - 1;
+            for (channel_i, channel) in ghost.channels.iter().enumerate() {
+                ui.cursor_x = track_xs[track_i]
+                    + chan_width * channel_i.min(last_channel) as f32;
+                pe.draw_ghost_notes(ui, channel, beat_height);
+            }
+        }
+
         for (channel_i, channel) in track.channels.iter().enumerate() {
             ui.cursor_x = track_xs[track_i] + chan_width * channel_i as f32;
-            pe.draw_channel(ui, channel, player.track_muted(track_i), channel_i);
+            let preview = drag_edit.as_ref()
+                .and_then(|edit| preview_channel(channel, track_i, channel_i, edit));
+            pe.draw_channel(ui, preview.as_ref().unwrap_or(channel),
+                player.track_muted(track_i), channel_i, collapsed,
+                matches!(track.target, TrackTarget::Kit).then_some(&*module));
         }
     }
 
     // handle text entry
     if let Some(pos) = pe.text_position {
         let max_width = 4;
-        let coords = position_coords(pos, &ui.style, &track_xs, false, beat_height);
+        let coords = position_coords(pos, &ui.style, &track_xs, &pe.collapsed,
+            false, beat_height, pe.row_height(ui));
         let rect = Rect {
             x: coords.x + ui.style.margin,
             y: coords.y + ui.cursor_y,
@@ -1282,35 +2688,54 @@ pub fn draw(ui: &mut Ui, module: &mut Module, player: &mut Player, pe: &mut Patt
         }
     }
 
-    ui.cursor_x += channel_width(1, &ui.style);
+    ui.cursor_x += channel_width(1, false, &ui.style);
     pe.draw_channel_line(ui, true);
+
+    if let Some(rect) = roll_rect {
+        pe.draw_piano_roll(ui, module, rect);
+    }
 }
 
-/// Draws beat numbers and lines.
-fn draw_beats(ui: &mut Ui, x: f32, beat_height: f32) {
+/// Draws beat numbers and lines. Numbers are skipped if beats are too short
+/// to fit them.
+fn draw_beats(ui: &mut Ui, x: f32, beat_height: f32, row_height: f32) {
     let mut beat = 1;
     let mut y = ui.cursor_y;
-    let line_height = line_height(&ui.style.atlas);
+    let label_interval = (line_height(&ui.style.atlas) / beat_height).ceil().max(1.0) as usize;
     while y < ui.bounds.y + ui.bounds.h {
         if y >= 0.0 {
             ui.push_rect(Rect {
                 x: ui.bounds.x,
                 y,
                 w: ui.bounds.w,
-                h: line_height,
+                h: row_height,
             }, ui.style.theme.panel_bg(), None);
-            ui.push_text(x, y - ui.style.margin + PATTERN_MARGIN, beat.to_string(),
-                ui.style.theme.fg());
+            if (beat - 1) % label_interval == 0 {
+                ui.push_text(x, y - ui.style.margin + PATTERN_MARGIN, beat.to_string(),
+                    ui.style.theme.fg());
+            }
         }
         beat += 1;
         y += beat_height;
     }
 }
 
+/// Tints the background of tracks that have a label color.
+fn draw_track_tints(ui: &mut Ui, tracks: &[Track], track_xs: &[f32], viewport: Rect) {
+    for (i, track) in tracks.iter().enumerate() {
+        let label = track.color.and_then(|c| TRACK_COLORS.get(c as usize));
+        if let (Some((_, color)), Some(x), Some(next_x)) =
+            (label, track_xs.get(i), track_xs.get(i + 1)) {
+            let color = Color { a: TRACK_TINT_ALPHA, ..*color };
+            ui.push_rect(Rect { x: *x, w: next_x - x, ..viewport }, color, None);
+        }
+    }
+}
+
 /// Returns x positions of each track, plus the position of the last track's
 /// right edge.
 fn draw_track_headers(ui: &mut Ui, module: &mut Module, player: &mut Player,
-    pe: &mut PatternEditor
+    pe: &mut PatternEditor, show_voices: bool
 ) -> Vec<f32> {
     let mut edit = None;
     ui.layout = Layout::Horizontal;
@@ -1318,6 +2743,12 @@ fn draw_track_headers(ui: &mut Ui, module: &mut Module, player: &mut Player,
     // offset for beat width
     ui.cursor_x += ui.style.atlas.char_width() * 4.0 + ui.style.margin * 2.0;
 
+    // options for the ghost track combo boxes
+    let ghost_options: Vec<_> = std::iter::once(String::from("-"))
+        .chain(module.tracks.iter().enumerate().skip(1)
+            .map(|(i, track)| format!("{i}: {}", track_display_name(track, &module.patches))))
+        .collect();
+
     let mut xs = vec![ui.cursor_x];
     xs.extend(module.tracks.iter_mut().enumerate().map(|(i, track)| {
         ui.start_group();
@@ -1343,6 +2774,117 @@ fn draw_track_headers(ui: &mut Ui, module: &mut Module, player: &mut Player,
             TrackTarget::Kit => ui.offset_label(name, Info::KitTrack),
         }
 
+        // name & color label
+        ui.start_group();
+        if let Some(s) = ui.id_edit_box(&format!("track_{}_name", i), "", 10,
+            track.name.clone(), Info::TrackName) {
+            track.name = s;
+        }
+        let color_name = track.color
+            .and_then(|c| TRACK_COLORS.get(c as usize))
+            .map_or("-", |(name, _)| name);
+        if let Some(j) = ui.combo_box(&format!("track_{}_color", i), "", color_name,
+            Info::TrackColor, || std::iter::once("-")
+                .chain(TRACK_COLORS.iter().map(|(name, _)| *name))
+                .map(|s| s.to_owned())
+                .collect()) {
+            track.color = j.checked_sub(1).map(|j| j as u8);
+        }
+        ui.end_group();
+        if i != 0 {
+            ui.checkbox("Audition only", &mut track.audition_only, true, Info::AuditionOnly);
+
+            let ghost = pe.ghost_tracks.get(&i).copied().unwrap_or_default();
+            if let Some(j) = ui.combo_box(&format!("track_{i}_ghost"), "Ghost",
+                ghost_options.get(ghost).map_or("-", |s| s.as_str()), Info::GhostTrack,
+                || ghost_options.clone()) {
+                if j == 0 || j == i {
+                    pe.ghost_tracks.remove(&i);
+                } else {
+                    pe.ghost_tracks.insert(i, j);
+                }
+            }
+
+            // playback scaling
+            ui.start_group();
+            for (id, label, scale) in [
+                ("pressure_scale", "Vel %", &mut track.pressure_scale),
+                ("mod_scale", "Mod %", &mut track.mod_scale),
+            ] {
+                if let Some(s) = ui.id_edit_box(&format!("track_{i}_{id}"), label, 3,
+                    (*scale * 100.0).round().to_string(), Info::TrackScale
+                ) {
+                    match s.trim().trim_end_matches('%').parse::<f32>() {
+                        Ok(pct) => *scale = (pct / 100.0).clamp(0.0, MAX_TRACK_SCALE),
+                        Err(_) => ui.report(format!("Invalid percentage: {s}")),
+                    }
+                }
+            }
+            if let Some(s) = ui.id_edit_box(&format!("track_{i}_offset"), "ms", 4,
+                track.offset.to_string(), Info::TrackOffset
+            ) {
+                match s.trim().trim_end_matches("ms").trim().parse::<f32>() {
+                    Ok(ms) => track.offset = ms.clamp(-MAX_TRACK_OFFSET_MS, MAX_TRACK_OFFSET_MS),
+                    Err(_) => ui.report(format!("Invalid offset: {s}")),
+                }
+            }
+            ui.end_group();
+
+            // auto-pan
+            let mut auto_pan = track.auto_pan.is_some();
+            if ui.checkbox("Auto-pan", &mut auto_pan, true, Info::AutoPan) {
+                track.auto_pan = auto_pan.then(AutoPan::default);
+            }
+            if let Some(auto_pan) = &mut track.auto_pan {
+                ui.start_group();
+                if let Some(s) = ui.id_edit_box(&format!("track_{i}_pan_period"), "Beats", 3,
+                    auto_pan.period.to_string(), Info::AutoPan
+                ) {
+                    match s.trim().parse::<f32>() {
+                        Ok(f) if f > 0.0 => auto_pan.period = f.min(MAX_AUTO_PAN_PERIOD),
+                        _ => ui.report(format!("Invalid period: {s}")),
+                    }
+                }
+                for (id, label, value) in [
+                    ("pan_depth", "Depth %", &mut auto_pan.depth),
+                    ("pan_phase", "Phase %", &mut auto_pan.phase),
+                ] {
+                    if let Some(s) = ui.id_edit_box(&format!("track_{i}_{id}"), label, 3,
+                        (*value * 100.0).round().to_string(), Info::AutoPan
+                    ) {
+                        match s.trim().trim_end_matches('%').parse::<f32>() {
+                            Ok(pct) => *value = (pct / 100.0).clamp(0.0, 1.0),
+                            Err(_) => ui.report(format!("Invalid percentage: {s}")),
+                        }
+                    }
+                }
+                ui.end_group();
+            }
+
+            // tone filters
+            ui.start_group();
+            for (id, label, cutoff) in [
+                ("highpass", "HP Hz", &mut track.highpass),
+                ("lowpass", "LP Hz", &mut track.lowpass),
+            ] {
+                if let Some(s) = ui.id_edit_box(&format!("track_{i}_{id}"), label, 5,
+                    cutoff.map_or(String::from("-"), |f| f.round().to_string()),
+                    Info::TrackTone
+                ) {
+                    let s = s.trim().trim_end_matches("Hz").trim();
+                    if s.is_empty() || s == "-" {
+                        *cutoff = None;
+                    } else {
+                        match s.parse::<f32>() {
+                            Ok(f) => *cutoff = Some(f.clamp(MIN_TONE_HZ, MAX_TONE_HZ)),
+                            Err(_) => ui.report(format!("Invalid frequency: {s}")),
+                        }
+                    }
+                }
+            }
+            ui.end_group();
+        }
+
         // chanel add/remove buttons
         ui.start_group();
         if ui.button("-", track.channels.len() > 1, Info::Remove("the last channel")) {
@@ -1353,12 +2895,22 @@ fn draw_track_headers(ui: &mut Ui, module: &mut Module, player: &mut Player,
         }
         ui.end_group();
 
+        if show_voices {
+            ui.label(&format!("Voices: {}", player.voice_count(i)), Info::VoiceCount);
+        }
+
         // column labels
         ui.start_group();
         for _ in 0..track.channels.len() {
-            let color = ui.style.theme.border_unfocused();
+            let color = if player.track_frozen(i) {
+                ui.style.theme.accent1_fg()
+            } else {
+                ui.style.theme.border_unfocused()
+            };
             if i == 0 {
                 ui.colored_label("Ctrl", Info::ControlColumn, color)
+            } else if pe.is_collapsed(i) {
+                ui.colored_label("Note", Info::NoteColumn, color);
             } else {
                 ui.colored_label("Note", Info::NoteColumn, color);
                 ui.cursor_x -= ui.style.margin;
@@ -1375,13 +2927,13 @@ fn draw_track_headers(ui: &mut Ui, module: &mut Module, player: &mut Player,
 
     if let Some(edit) = edit {
         module.push_edit(edit);
-        player.update_synths(module.drain_track_history());
+        player.update_synths(module.drain_track_history(), module);
         fix_cursors(&mut pe.edit_start, &mut pe.edit_end, &module.tracks);
     }
 
     if ui.button("+", !module.patches.is_empty(), Info::Add("a new track")) {
         module.add_track();
-        player.update_synths(module.drain_track_history());
+        player.update_synths(module.drain_track_history(), module);
     }
 
     xs
@@ -1400,6 +2952,35 @@ fn nudge_notes(module: &mut Module, (start, end): (Position, Position), cfg: &Co
     module.push_edit(Edit::ReplaceEvents(replacements));
 }
 
+/// Play a preview of the pitch event at `pos`, if there is one, using the
+/// pressure in effect at that point.
+fn preview_note_at(module: &Module, player: &mut Player, pos: &Position) {
+    if pos.column != NOTE_COLUMN {
+        return
+    }
+
+    let Some(channel) = module.tracks.get(pos.track)
+        .and_then(|t| t.channels.get(pos.channel)) else {
+        return
+    };
+    let note = channel.events.iter().find_map(|e| match e.data {
+        EventData::Pitch(note) if e.tick == pos.tick => Some(note),
+        _ => None,
+    });
+
+    if let Some((patch, note)) = note.and_then(|n| module.map_note(n, pos.track)) {
+        let pressure = channel.events.iter()
+            .filter_map(|e| match e.data {
+                EventData::Pressure(v) if e.tick <= pos.tick => Some(v),
+                _ => None,
+            })
+            .last()
+            .map_or(DEFAULT_PRESSURE, |v| v as f32 / EventData::DIGIT_MAX as f32);
+        let pitch = module.play_pitch(&module.tuning, &note, module.is_kit_track(pos.track));
+        player.preview_note(pos.track, pitch, pressure, patch);
+    }
+}
+
 fn insert_event_at_cursor(module: &mut Module, cursor: &Position, data: EventData,
     all_channels: bool
 ) {
@@ -1408,10 +2989,11 @@ fn insert_event_at_cursor(module: &mut Module, cursor: &Position, data: EventDat
         return
     }
 
-    // midi pitch bend can only overwrite midi pitch bend
-    if matches!(data, EventData::Bend(_))
-        && !matches!(module.event_at(cursor).map(|e| &e.data),
-            Some(EventData::Bend(_)) | None) {
+    // pitch bends, vibrato, and tremolo can only overwrite their own kind
+    if matches!(data, EventData::Bend(_) | EventData::BendRamp(_)
+        | EventData::Vibrato(_) | EventData::Tremolo(_) | EventData::Retrigger(_))
+        && module.event_at(cursor).is_some_and(|e|
+            std::mem::discriminant(&e.data) != std::mem::discriminant(&data)) {
         return
     }
 
@@ -1437,6 +3019,49 @@ fn insert_event_at_cursor(module: &mut Module, cursor: &Position, data: EventDat
     }
 }
 
+/// Returns a copy of a channel with the events in `edit` applied, or `None` if
+/// the edit doesn't affect the channel.
+fn preview_channel(channel: &Channel, track: usize, index: usize, edit: &Edit
+) -> Option<Channel> {
+    let Edit::PatternData { remove, add } = edit else {
+        return None
+    };
+    let remove: Vec<_> = remove.iter()
+        .filter(|p| p.track == track && p.channel == index)
+        .collect();
+    let add: Vec<_> = add.iter()
+        .filter(|e| e.track == track && e.channel == index)
+        .collect();
+    if remove.is_empty() && add.is_empty() {
+        return None
+    }
+
+    let mut channel = channel.clone();
+    channel.events.retain(|e| !remove.iter()
+        .any(|p| p.tick == e.tick && p.column == e.data.logical_column()));
+    channel.events.extend(add.into_iter().map(|e| e.event.clone()));
+    channel.sort_events();
+    Some(channel)
+}
+
+/// Returns the position of the glide curve for an interpolation event
+/// position.
+fn curve_position(pos: Position) -> Position {
+    Position {
+        column: pos.column | EventData::CURVE_COL_FLAG,
+        ..pos
+    }
+}
+
+/// Returns the value painted by the mouse at `x`, increasing from the left
+/// edge of the channel at `pos` to the right edge.
+fn paint_value(x: f32, pos: Position, track_xs: &[f32], style: &Style) -> u8 {
+    let width = channel_width(pos.track, false, style);
+    let left = track_xs[pos.track] + width * pos.channel as f32;
+    let max = EventData::DIGIT_MAX as f32;
+    ((x - left) / width * (max + 1.0)).floor().clamp(0.0, max) as u8
+}
+
 /// Returns the UI display string for a track.
 fn track_name(target: TrackTarget, patches: &[Patch]) -> &str {
     match target {
@@ -1449,6 +3074,16 @@ fn track_name(target: TrackTarget, patches: &[Patch]) -> &str {
     }
 }
 
+/// Returns the user-assigned name of a track, or the name of its target if
+/// unnamed.
+pub fn track_display_name<'a>(track: &'a Track, patches: &'a [Patch]) -> &'a str {
+    if track.name.is_empty() {
+        track_name(track.target, patches)
+    } else {
+        track.name.as_str()
+    }
+}
+
 /// Returns UI display strings for each patch.
 fn track_targets(patches: &[Patch]) -> Vec<String> {
     let mut v = vec![track_name(TrackTarget::None, patches).to_owned()];
@@ -1456,19 +3091,163 @@ fn track_targets(patches: &[Patch]) -> Vec<String> {
     v
 }
 
-fn draw_playhead(ui: &mut Ui, tick: Timespan, x: f32, beat_height: f32) {
+fn draw_playhead(ui: &mut Ui, tick: Timespan, x: f32, beat_height: f32, row_height: f32) {
     let rect = Rect {
         x,
         y: ui.cursor_y + tick.as_f32() * beat_height,
         w: ui.bounds.w,
-        h: line_height(&ui.style.atlas),
+        h: row_height,
     };
     let color = Color { a: 0.1, ..ui.style.theme.fg() };
     ui.push_rect(rect, color, None);
 }
 
+/// Draws the song overview at the right edge of the pattern viewport, and
+/// returns the tick to scroll to if it's clicked.
+fn draw_minimap(ui: &mut Ui, minimap: &Minimap, view: (Timespan, Timespan),
+    playhead: Option<Timespan>
+) -> Option<Timespan> {
+    let w = MINIMAP_TRACK_WIDTH * minimap.density.len() as f32;
+    ui.bounds.w -= w;
+    let rect = Rect {
+        x: ui.bounds.x + ui.bounds.w,
+        y: ui.cursor_y,
+        w,
+        h: ui.bounds.h + ui.bounds.y - ui.cursor_y,
+    };
+    ui.push_rect(rect, ui.style.theme.panel_bg(), None);
+
+    let beats = minimap.density.iter().map(|v| v.len()).max().unwrap_or(0)
+        .max(view.1.as_f64().ceil() as usize)
+        .max(1);
+    let beat_h = rect.h / beats as f32;
+
+    // merge beats so that each row is at least a pixel tall
+    let beats_per_row = (1.0 / beat_h).ceil().max(1.0) as usize;
+    let row_h = beat_h * beats_per_row as f32;
+    let rows: Vec<Vec<u32>> = minimap.density.iter()
+        .map(|v| v.chunks(beats_per_row).map(|c| c.iter().sum()).collect())
+        .collect();
+    let max_count = rows.iter().flatten().copied().max().unwrap_or(0).max(1);
+
+    let fg = ui.style.theme.fg();
+    for (track_i, counts) in rows.iter().enumerate() {
+        let x = rect.x + track_i as f32 * MINIMAP_TRACK_WIDTH;
+        for (row_i, n) in counts.iter().enumerate().filter(|(_, n)| **n > 0) {
+            let row = Rect {
+                x: x + 1.0,
+                y: rect.y + row_i as f32 * row_h,
+                w: MINIMAP_TRACK_WIDTH - 1.0,
+                h: row_h,
+            };
+            let a = 0.2 + 0.8 * *n as f32 / max_count as f32;
+            ui.push_rect(row, Color { a, ..fg }, None);
+        }
+    }
+
+    let tick_y = |tick: Timespan| rect.y + tick.as_f32() * beat_h;
+    let view_rect = Rect {
+        y: tick_y(view.0),
+        h: tick_y(view.1) - tick_y(view.0),
+        ..rect
+    };
+    let accent = ui.style.theme.accent1_fg();
+    ui.push_rect(view_rect, Color { a: 0.1, ..accent }, Some(Color { a: 0.5, ..accent }));
+    if let Some(tick) = playhead {
+        let y = tick_y(tick);
+        ui.push_line(rect.x, y, rect.x + rect.w, y, fg);
+    }
+
+    if ui.mouse_hits(rect, MINIMAP_ID) {
+        ui.info = Info::Minimap;
+        if is_mouse_button_pressed(MouseButton::Left) {
+            ui.mouse_consumed = Some(MINIMAP_ID.to_string());
+        }
+    }
+    if ui.mouse_consumed.as_deref() == Some(MINIMAP_ID)
+        && is_mouse_button_down(MouseButton::Left) {
+        let beat = ((mouse_position().1 - rect.y) / beat_h).clamp(0.0, beats as f32);
+        return Some(Timespan::approximate(beat as f64))
+    }
+
+    None
+}
+
+/// Draws the tempo map as a line graph in a lane at `x`, scrolled with the
+/// pattern.
+fn draw_tempo_lane(ui: &mut Ui, points: &[(Timespan, f32, bool)], x: f32,
+    viewport: Rect, beat_height: f32
+) {
+    let rect = Rect { x, w: TEMPO_LANE_WIDTH, ..viewport };
+    ui.push_rect(rect, ui.style.theme.panel_bg(), None);
+    if ui.mouse_hits(rect, TEMPO_LANE_ID) {
+        ui.info = Info::TempoLane;
+    }
+
+    let (min, max) = points.iter()
+        .fold((f32::MAX, f32::MIN), |(min, max), p| (min.min(p.1), max.max(p.1)));
+    let margin = ui.style.margin;
+    let tempo_x = |tempo: f32| if max > min {
+        rect.x + margin + (tempo - min) / (max - min) * (rect.w - margin * 2.0)
+    } else {
+        rect.x + rect.w * 0.5
+    };
+    let origin_y = ui.cursor_y;
+    let tick_y = |tick: Timespan| origin_y + tick.as_f32() * beat_height;
+    let bottom = rect.y + rect.h;
+    let color = ui.style.theme.accent1_fg();
+
+    for (i, &(tick, tempo, glide)) in points.iter().enumerate() {
+        let next = points.get(i + 1);
+        let (y1, y2) = (tick_y(tick), next.map_or(bottom, |p| tick_y(p.0)));
+        let x1 = tempo_x(tempo);
+        let x2 = match next {
+            Some(p) if glide => tempo_x(p.1),
+            _ => x1,
+        };
+
+        // segment up to the next change, clipped to the lane
+        let (top, end) = (y1.max(rect.y), y2.min(bottom));
+        if top < end {
+            let x_at = |y: f32| x1 + (x2 - x1) * (y - y1) / (y2 - y1);
+            ui.push_line(x_at(top), top, x_at(end), end, color);
+        }
+
+        // step to the next tempo
+        if let Some(p) = next {
+            if (rect.y..bottom).contains(&y2) {
+                ui.push_line(x2, y2, tempo_x(p.1), y2, color);
+            }
+        }
+    }
+}
+
+/// Draws the editor loop range as a shaded span with marker lines.
+fn draw_loop_range(ui: &mut Ui, start: Timespan, end: Timespan, enabled: bool,
+    x: f32, beat_height: f32
+) {
+    let color = ui.style.theme.accent2_fg();
+    let start_y = ui.cursor_y + start.as_f32() * beat_height;
+    let end_y = ui.cursor_y + end.as_f32() * beat_height;
+    if enabled {
+        let rect = Rect {
+            x,
+            y: start_y,
+            w: ui.bounds.w,
+            h: end_y - start_y,
+        };
+        ui.push_rect(rect, Color { a: 0.05, ..color }, None);
+    }
+    let line_color = Color { a: if enabled { 0.5 } else { 0.2 }, ..color };
+    for y in [start_y, end_y] {
+        ui.push_line(x, y, x + ui.bounds.w, y, line_color);
+    }
+}
+
 /// Handle the "previous column" key command.
-fn shift_column_left(start: &mut Position, end: &mut Position, tracks: &[Track]) {
+fn shift_column_left(start: &mut Position, end: &mut Position, tracks: &[Track],
+    collapsed: &HashSet<usize>, extend: bool
+) {
     let column = end.column as i8 - 1;
     if column >= 0 {
         end.column = column as u8;
@@ -1482,11 +3261,13 @@ fn shift_column_left(start: &mut Position, end: &mut Position, tracks: &[Track])
 
         if end.track == 0 {
             end.column = GLOBAL_COLUMN;
+        } else if collapsed.contains(&end.track) {
+            end.column = NOTE_COLUMN;
         } else {
             end.column = MOD_COLUMN;
         }
     }
-    if !is_shift_down() {
+    if !extend {
         start.track = end.track;
         start.channel = end.channel;
         start.column = end.column;
@@ -1494,19 +3275,21 @@ fn shift_column_left(start: &mut Position, end: &mut Position, tracks: &[Track])
 }
 
 /// Handle the "next column" key command.
-fn shift_column_right(start: &mut Position, end: &mut Position, tracks: &[Track]) {
-    *end = next_column(*end, tracks);
+fn shift_column_right(start: &mut Position, end: &mut Position, tracks: &[Track],
+    collapsed: &HashSet<usize>, extend: bool
+) {
+    *end = next_column(*end, tracks, collapsed);
 
-    if !is_shift_down() {
+    if !extend {
         start.track = end.track;
         start.channel = end.channel;
         start.column = end.column;
     }
 }
 
-fn next_column(pos: Position, tracks: &[Track]) -> Position {
+fn next_column(pos: Position, tracks: &[Track], collapsed: &HashSet<usize>) -> Position {
     let column = pos.column + 1;
-    let n_columns = if pos.track == 0 { 1 } else { 3 };
+    let n_columns = if pos.track == 0 || collapsed.contains(&pos.track) { 1 } else { 3 };
     let mut pos = pos;
 
     if column < n_columns {
@@ -1569,25 +3352,27 @@ fn fix_cursors(start: &mut Position, end: &mut Position, tracks: &[Track]) {
 /// Returns the visual coordinates of a Position. Uses the top-left corner of
 /// the cell by default.
 fn position_coords(pos: Position, style: &Style, track_xs: &[f32],
-    bottom_left: bool, beat_height: f32
+    collapsed: &HashSet<usize>, bottom_left: bool, beat_height: f32, row_height: f32
 ) -> Vec2 {
-    let x = track_xs[pos.track] + channel_width(pos.track, style) * pos.channel as f32
+    let chan_width = channel_width(pos.track, collapsed.contains(&pos.track), style);
+    let x = track_xs[pos.track] + chan_width * pos.channel as f32
         + if bottom_left {
             column_x(pos.column + 1, style) - style.margin
         } else {
             column_x(pos.column, style)
         };
     let y = pos.beat() * beat_height + if bottom_left {
-        line_height(&style.atlas)
+        row_height
     } else {
         0.0
     };
     Vec2 { x, y }
 }
 
-/// Returns the minimum visual width of a channel.
-fn channel_width(track_index: usize, style: &Style) -> f32 {
-    if track_index == 0 {
+/// Returns the minimum visual width of a channel. Collapsed channels only
+/// show the note column.
+fn channel_width(track_index: usize, collapsed: bool, style: &Style) -> f32 {
+    if track_index == 0 || collapsed {
         column_x(1, style) + style.margin
     } else {
         column_x(3, style) + style.margin
@@ -1616,6 +3401,8 @@ fn line_height(atlas: &GlyphAtlas) -> f32 {
 
 #[cfg(test)]
 mod tests {
+    use crate::mixer::Mixer;
+
     use super::*;
 
     #[test]
@@ -1628,5 +3415,204 @@ mod tests {
         assert_eq!(parse_ctrl_text("60.5"), Some(EventData::Tempo(60.5)));
         assert_eq!(parse_ctrl_text("1/2"), Some(EventData::RationalTempo(1, 2)));
         assert_eq!(parse_ctrl_text("4:3"), Some(EventData::RationalTempo(4, 3)));
+        assert_eq!(parse_ctrl_text("0:3"), None);
+        assert_eq!(parse_ctrl_text("m8"), Some(EventData::GlobalModulation(8)));
+        assert_eq!(parse_ctrl_text("MF"), Some(EventData::GlobalModulation(0xf)));
+        assert_eq!(parse_ctrl_text("m10"), None);
+    }
+
+    #[test]
+    fn test_tempo_ratio_options() {
+        let options = tempo_ratio_options(4);
+        assert_eq!(&options[..7], &[(1, 4), (1, 2), (3, 4), (5, 4), (3, 2), (7, 4), (2, 1)]);
+        assert!(options.contains(&(2, 3)));
+        assert_eq!(options.iter().filter(|r| **r == (3, 2)).count(), 1);
+    }
+
+    #[test]
+    fn test_value_scaling() {
+        let scaling = ValueScaling { percent: 80.0, offset: 0 };
+        assert_eq!(scaling.apply(10), 8);
+        assert_eq!(scaling.apply(0), 0);
+        let scaling = ValueScaling { percent: 200.0, offset: -1 };
+        assert_eq!(scaling.apply(4), 7);
+        assert_eq!(scaling.apply(12), EventData::DIGIT_MAX);
+        let scaling = ValueScaling { percent: 100.0, offset: -5 };
+        assert_eq!(scaling.apply(3), 0);
+    }
+
+    #[test]
+    fn test_piano_roll() {
+        let mut module = Module::new(Default::default());
+        let c4 = Note::new(0, Nominal::C, 0, 4);
+        let d4 = Note::new(0, Nominal::D, 0, 4);
+        let event = |beat, data| Event { tick: Timespan::new(beat, 1), data };
+        module.insert_event(2, 0, event(0, EventData::Pitch(c4)));
+        module.insert_event(2, 0, event(0, EventData::StartGlide(NOTE_COLUMN)));
+        module.insert_event(2, 0, event(1, EventData::Pitch(d4)));
+        module.insert_event(2, 0, event(1, EventData::EndGlide(NOTE_COLUMN)));
+        module.insert_event(2, 0, event(2, EventData::NoteOff));
+        module.insert_event(2, 0, event(3, EventData::Pitch(c4)));
+
+        let end = Timespan::new(4, 1);
+        let segments = roll_segments(&module, 2, end);
+        let c = segments[0].degrees.0;
+        let spans: Vec<_> = segments.iter().map(|s| (s.pos.tick.num(), s.end.num(), s.degrees))
+            .collect();
+        assert_eq!(spans, vec![(0, 1, (c, c + 2)), (1, 2, (c + 2, c + 2)), (3, 4, (c, c))]);
+        assert!(roll_segments(&module, 0, end).is_empty());
+
+        let mut drag = RollDrag {
+            pos: segments[2].pos,
+            start_y: 0.0,
+            range: (c, c),
+            row_h: 1.0,
+            steps: 0,
+        };
+        assert!(drag.edit(&module).is_none());
+        drag.steps = 2;
+        module.push_edit(drag.edit(&module).unwrap());
+        let segments = roll_segments(&module, 2, end);
+        assert_eq!(segments[2].degrees, (c + 2, c + 2));
+    }
+
+    #[test]
+    fn test_mouse_drag() {
+        let mut module = Module::new(Default::default());
+        let row = Timespan::new(1, 4);
+        module.insert_event(1, 0, Event { tick: Timespan::ZERO, data: EventData::NoteOff });
+
+        let from = Position::new(Timespan::ZERO, 1, 0, NOTE_COLUMN);
+        let mut drag = MouseDrag::Move { from, to: Timespan::ZERO };
+        assert!(drag.edit(&module).is_none());
+        drag.update(Timespan::new(1, 1), 0, row);
+        let edit = drag.edit(&module).unwrap();
+        let channel = &module.tracks[1].channels[0];
+        let preview = preview_channel(channel, 1, 0, &edit).unwrap();
+        assert!(preview_channel(channel, 1, 1, &edit).is_none());
+        module.push_edit(edit);
+        let events = &module.tracks[1].channels[0].events;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].tick, Timespan::new(1, 1));
+        assert_eq!(preview.events.len(), 1);
+        assert_eq!(preview.events[0].tick, Timespan::new(1, 1));
+
+        let mut drag = MouseDrag::Paint {
+            track: 1,
+            channel: 0,
+            column: VEL_COLUMN,
+            last_tick: Timespan::ZERO,
+            values: BTreeMap::from([(Timespan::ZERO, 3)]),
+        };
+        drag.update(Timespan::new(1, 2), 8, row);
+        drag.update(Timespan::new(1, 4), 5, row);
+        let Some(Edit::PatternData { add, .. }) = drag.edit(&module) else {
+            panic!("expected pattern data edit")
+        };
+        let values: Vec<_> = add.iter().map(|e| (e.event.tick, e.event.data.clone()))
+            .collect();
+        assert_eq!(values, vec![
+            (Timespan::ZERO, EventData::Pressure(8)),
+            (Timespan::new(1, 4), EventData::Pressure(5)),
+            (Timespan::new(1, 2), EventData::Pressure(5)),
+        ]);
+    }
+
+    #[test]
+    fn test_record_generated() {
+        let mut module = Module::new(Default::default());
+        let mut pe = PatternEditor::default();
+        let note = EventData::Pitch(Note::new(0, Nominal::C, 0, 4));
+
+        // nothing is written unless recording
+        pe.record_generated(note.clone(), 1, Timespan::new(1, 3), &mut module);
+        assert!(module.tracks[1].channels[0].events.is_empty());
+
+        pe.record = true;
+        pe.record_generated(note.clone(), 1, Timespan::new(1, 3), &mut module);
+        pe.record_generated(EventData::NoteOff, 1, Timespan::new(2, 3), &mut module);
+        let events: Vec<_> = module.tracks[1].channels[0].events.iter()
+            .map(|e| (e.tick, e.data.clone()))
+            .collect();
+        assert_eq!(events, [
+            (Timespan::new(1, 4), note),
+            (Timespan::new(3, 4), EventData::NoteOff),
+        ]);
+        assert_eq!(pe.edit_start.tick, Timespan::ZERO);
+    }
+
+    #[test]
+    fn test_replace_record() {
+        let mut module = Module::new(Default::default());
+        let event = |beat, data| Event { tick: Timespan::new(beat, 1), data };
+        for beat in 0..4 {
+            module.insert_event(1, 0, event(beat, EventData::NoteOff));
+        }
+        let mut pe = PatternEditor::default();
+        pe.edit_start.track = 1;
+        pe.edit_end.track = 1;
+        pe.punch_range = Some((Timespan::new(1, 1), Timespan::new(3, 1)));
+
+        // events outside the punch range aren't recorded
+        pe.record_event(EventData::Pressure(5), None, &mut module);
+        assert!(pe.recorded.is_empty());
+
+        pe.edit_start.tick = Timespan::new(1, 1);
+        pe.record_event(EventData::Pressure(5), None, &mut module);
+        pe.erase_for_replace(&mut module, Timespan::new(4, 1));
+        let ticks: Vec<_> = module.tracks[1].channels[0].events.iter()
+            .map(|e| e.tick.num())
+            .collect();
+        assert_eq!(ticks, [0, 1, 3]);
+        assert_eq!(pe.record_erased_to, Timespan::new(4, 1));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_record_modulation() {
+        let mut module = Module::new(Default::default());
+        let mut pe = PatternEditor::default();
+        pe.edit_start.track = 1;
+
+        // controller moves within a row keep the last value
+        for v in [3, 4, 4, 6] {
+            pe.record_event(EventData::Modulation(v), None, &mut module);
+        }
+        pe.edit_start.tick = pe.row_timespan();
+        pe.record_event(EventData::Modulation(2), None, &mut module);
+        let events: Vec<_> = module.tracks[1].channels[0].events.iter()
+            .map(|e| (e.tick, e.data.clone()))
+            .collect();
+        assert_eq!(events, [
+            (Timespan::ZERO, EventData::Modulation(6)),
+            (pe.row_timespan(), EventData::Modulation(2)),
+        ]);
+    }
+
+    #[test]
+    fn test_record_latency_compensation() {
+        let mut module = Module::new(Default::default());
+        let mut player = Player::new(Mixer::new(module.tracks.len(), 44100.0), 44100.0);
+        player.output_latency = 0.25;
+        let mut conf = Config::default();
+        let mut pe = PatternEditor::default();
+        pe.edit_start.track = 1;
+        pe.edit_end.track = 1;
+
+        // at 120 BPM, the latency is half a beat
+        player.record_from(Timespan::ZERO, &module, 0.0, 0.0);
+        player.frame(&module, 1.0);
+        pe.follow_recording(&mut module, &player, &conf);
+        pe.record_event(EventData::Pressure(5), None, &mut module);
+        conf.latency_compensation = false;
+        pe.follow_recording(&mut module, &player, &conf);
+        pe.record_event(EventData::Pressure(6), None, &mut module);
+
+        let events: Vec<_> = module.tracks[1].channels[0].events.iter()
+            .map(|e| (e.tick, e.data.clone()))
+            .collect();
+        assert_eq!(events, [
+            (Timespan::new(3, 2), EventData::Pressure(5)),
+            (Timespan::new(2, 1), EventData::Pressure(6)),
+        ]);
+    }
+}
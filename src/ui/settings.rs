@@ -1,13 +1,18 @@
+use midir::MidiIO;
 use palette::Lchuv;
 
-use crate::{config::{self, Config}, playback::Player, Midi};
+use crate::{config::{self, Config}, dsp, input::{self, CcMode}, pitch::PitchDisplay, playback::Player, Midi};
 
-use super::{info::Info, text::{self, GlyphAtlas}, theme::Theme, Layout, Ui};
+use super::{info::Info, pattern::RecordMode, text::{self, GlyphAtlas}, theme::Theme, Layout, Ui};
 
 /// State for the settings tab UI.
 pub struct SettingsState {
-    scroll: f32,
+    pub scroll: f32,
     sample_rate: u32,
+    /// MIDI channel whose routing is being edited.
+    route_channel: usize,
+    /// Index of the macro controller whose mode is being edited.
+    macro_cc: usize,
 }
 
 impl SettingsState {
@@ -15,6 +20,8 @@ impl SettingsState {
         Self {
             scroll: 0.0,
             sample_rate,
+            route_channel: 0,
+            macro_cc: 0,
         }
     }
 }
@@ -30,7 +37,9 @@ pub fn draw(ui: &mut Ui, cfg: &mut Config, state: &mut SettingsState,
 
     general_controls(ui, cfg);
     ui.vertical_space();
-    io_controls(ui, cfg, state.sample_rate, midi, player);
+    io_controls(ui, cfg, state, midi, player);
+    ui.vertical_space();
+    record_controls(ui, cfg);
     ui.vertical_space();
     appearance_controls(ui, cfg, player);
     ui.vertical_space();
@@ -55,10 +64,35 @@ fn general_controls(ui: &mut Ui, cfg: &mut Config) {
     }
     ui.checkbox("Smooth playhead", &mut cfg.smooth_playhead, true, Info::SmoothPlayhead);
     ui.checkbox("Display info text", &mut cfg.display_info, true, Info::DisplayInfo);
+    ui.checkbox("Show profiler", &mut cfg.show_profiler, true, Info::ShowProfiler);
+    ui.checkbox("Preview notes", &mut cfg.preview_notes, true, Info::PreviewNotes);
+    ui.checkbox("Latch keyjazz notes", &mut cfg.keyjazz_latch, true, Info::KeyjazzLatch);
+    ui.checkbox("Mute audition tracks", &mut cfg.mute_audition_tracks, true,
+        Info::MuteAuditionTracks);
+    ui.checkbox("Show minimap", &mut cfg.show_minimap, true, Info::ShowMinimap);
+    ui.checkbox("Show tempo lane", &mut cfg.show_tempo_lane, true, Info::ShowTempoLane);
+    ui.checkbox("Show piano roll", &mut cfg.show_piano_roll, true, Info::ShowPianoRoll);
+    ui.checkbox("Restore session", &mut cfg.restore_session, true, Info::RestoreSession);
+    ui.checkbox("Write status messages", &mut cfg.announce_status, true,
+        Info::AnnounceStatus);
+    ui.checkbox("Show errors in dialogs", &mut cfg.error_alerts, true, Info::ErrorAlerts);
+    if let Some(s) = ui.edit_box("Quick export name", 20, cfg.quick_export_name.clone(),
+        Info::QuickExportName
+    ) {
+        cfg.quick_export_name = s;
+    }
+    if let Some(i) = ui.combo_box("pitch_display", "Pitch readout",
+        cfg.pitch_display.name(), Info::PitchDisplay,
+        || PitchDisplay::VARIANTS.map(|x| x.name().to_owned()).to_vec()) {
+        cfg.pitch_display = PitchDisplay::VARIANTS[i];
+    }
+    ui.slider("param_smooth_time", "Parameter smoothing", &mut cfg.param_smooth_time,
+        0.0..=0.1, Some("s"), 2, true, Info::ParamSmoothTime);
+    dsp::set_param_smooth_time(cfg.param_smooth_time);
 }
 
-fn io_controls(ui: &mut Ui, cfg: &mut Config, sample_rate: u32, midi: &mut Midi,
-    player: &mut Player
+fn io_controls(ui: &mut Ui, cfg: &mut Config, state: &mut SettingsState,
+    midi: &mut Midi, player: &mut Player
 ) {
     ui.header("I/O", Info::None);
 
@@ -70,10 +104,28 @@ fn io_controls(ui: &mut Ui, cfg: &mut Config, sample_rate: u32, midi: &mut Midi,
             Err(e) => ui.report(e),
         }
     }
-    if sample_rate != cfg.desired_sample_rate {
-        ui.label(&format!("Actual sample rate: {} Hz", sample_rate), Info::None);
+    if state.sample_rate != cfg.desired_sample_rate {
+        ui.label(&format!("Actual sample rate: {} Hz", state.sample_rate), Info::None);
     }
 
+    if let Some(s) = ui.edit_box("Buffer size", 6,
+        cfg.audio_buffer_size.map(|n| n.to_string()).unwrap_or_default(),
+        Info::BufferSize
+    ) {
+        if s.is_empty() {
+            cfg.audio_buffer_size = None;
+        } else {
+            match s.parse::<u32>() {
+                Ok(n) => cfg.audio_buffer_size = Some(n),
+                Err(e) => ui.report(e),
+            }
+        }
+    }
+    ui.label(&format!("Output latency: {:.1} ms", player.output_latency * 1000.0),
+        Info::OutputLatency);
+    ui.checkbox("Compensate output latency", &mut cfg.latency_compensation, true,
+        Info::LatencyCompensation);
+
     if midi.input.is_some() {
         ui.start_group();
 
@@ -84,11 +136,11 @@ fn io_controls(ui: &mut Ui, cfg: &mut Config, sample_rate: u32, midi: &mut Midi,
             "(none)"
         };
         if let Some(i) = ui.combo_box("midi_input", "MIDI input", s,
-            Info::MidiInput, || input_names(midi.input.as_ref().unwrap())) {
+            Info::MidiInput, || port_names(midi.input.as_ref().unwrap())) {
             midi.port_selection = if i == 0 {
                 None
             } else {
-                input_names(midi.input.as_ref().unwrap()).get(i).cloned()
+                port_names(midi.input.as_ref().unwrap()).get(i).cloned()
             };
         }
 
@@ -103,17 +155,127 @@ fn io_controls(ui: &mut Ui, cfg: &mut Config, sample_rate: u32, midi: &mut Midi,
             player.reset_memory();
         }
 
+        ui.checkbox("Program change remaps track", &mut cfg.midi_program_remaps_track,
+            midi.port_name.is_some(), Info::ProgramRemapsTrack);
+
+        ui.slider("mod_smooth_time", "Mod wheel smoothing", &mut cfg.mod_smooth_time,
+            0.0..=0.1, Some("s"), 2, midi.port_name.is_some(), Info::ModSmoothTime);
+        dsp::set_mod_smooth_time(cfg.mod_smooth_time);
+        ui.slider("pressure_smooth_time", "Aftertouch smoothing",
+            &mut cfg.pressure_smooth_time, 0.0..=0.1, Some("s"), 2,
+            midi.port_name.is_some(), Info::PressureSmoothTime);
+        dsp::set_pressure_smooth_time(cfg.pressure_smooth_time);
+
+        ui.end_group();
+
+        // channel routing
+        ui.start_group();
+        if let Some(i) = ui.combo_box("midi_route_channel", "Route MIDI channel",
+            &(state.route_channel + 1).to_string(), Info::MidiChannelRouting,
+            || (1..=16).map(|c| c.to_string()).collect()) {
+            state.route_channel = i;
+        }
+        let track = &mut cfg.midi_channel_tracks[state.route_channel];
+        if let Some(s) = ui.edit_box("to track", 3, track.to_string(),
+            Info::MidiChannelRouting
+        ) {
+            match s.parse() {
+                Ok(n) => *track = n,
+                Err(e) => ui.report(e),
+            }
+        }
+        let key = &mut cfg.midi_split_keys[state.route_channel];
+        if let Some(s) = ui.edit_box("split below key", 3, key.to_string(),
+            Info::MidiKeySplit
+        ) {
+            match s.parse::<u8>() {
+                Ok(n) => *key = n.min(127),
+                Err(e) => ui.report(e),
+            }
+        }
+        let track = &mut cfg.midi_split_tracks[state.route_channel];
+        if let Some(s) = ui.id_edit_box("midi_split_track", "to track", 3,
+            track.to_string(), Info::MidiKeySplit
+        ) {
+            match s.parse() {
+                Ok(n) => *track = n,
+                Err(e) => ui.report(e),
+            }
+        }
+        ui.end_group();
+
+        // macro controller modes
+        ui.start_group();
+        let cc_name = |i: usize| format!("CC {}", input::CC_MACRO_MIN as usize + i);
+        if let Some(i) = ui.combo_box("midi_macro_cc", "Macro controller",
+            &cc_name(state.macro_cc), Info::MacroCcMode,
+            || (0..cfg.midi_macro_cc_modes.len()).map(cc_name).collect()) {
+            state.macro_cc = i;
+        }
+        let mode = &mut cfg.midi_macro_cc_modes[state.macro_cc];
+        if let Some(i) = ui.combo_box("midi_macro_cc_mode", "mode", mode.name(),
+            Info::MacroCcMode,
+            || CcMode::VARIANTS.map(|x| x.name().to_owned()).to_vec()) {
+            *mode = CcMode::VARIANTS[i];
+        }
         ui.end_group();
     } else {
         ui.label("No MIDI device", Info::None);
     }
 
+    if midi.output.is_some() {
+        ui.start_group();
+
+        let s = midi.output_name.as_deref().unwrap_or("(none)");
+        if let Some(i) = ui.combo_box("midi_output", "MIDI output", s,
+            Info::MidiOutput, || port_names(midi.output.as_ref().unwrap())) {
+            midi.output_selection = if i == 0 {
+                None
+            } else {
+                port_names(midi.output.as_ref().unwrap()).get(i).cloned()
+            };
+        }
+
+        if ui.checkbox("Send tuning", &mut cfg.midi_send_tuning,
+            midi.output_name.is_some(), Info::MidiSendTuning) {
+            midi.sent_tuning = None;
+        }
+
+        ui.end_group();
+    }
+
     if let Some(d) = ui.combo_box("render_bit_depth", "Render bit depth", &format!("{} bits", cfg.render_bit_depth.unwrap_or(16)),
         Info::None, || vec!["16 bits".to_string(), "32 bits".to_string()]) {
             cfg.render_bit_depth = Some(16 + 16*(d as u8));
     }
 }
 
+fn record_controls(ui: &mut Ui, cfg: &mut Config) {
+    ui.header("RECORDING", Info::Action(input::Action::ToggleRecord));
+
+    let mut count_in = cfg.record_count_in > 0;
+    if ui.checkbox("Count-in", &mut count_in, true, Info::RecordCountIn) {
+        cfg.record_count_in = count_in as u8;
+    }
+    if count_in {
+        if let Some(s) = ui.edit_box("Count-in bars", 1,
+            cfg.record_count_in.to_string(), Info::RecordCountIn
+        ) {
+            match s.parse::<u8>() {
+                Ok(n) => cfg.record_count_in = n.clamp(1, 2),
+                Err(e) => ui.report(e),
+            }
+        }
+    }
+    ui.checkbox("Pre-roll", &mut cfg.record_pre_roll, true, Info::RecordPreRoll);
+    if let Some(i) = ui.combo_box("record_mode", "Record mode", cfg.record_mode.name(),
+        Info::RecordMode, || RecordMode::VARIANTS.map(|x| x.name().to_owned()).to_vec()) {
+        cfg.record_mode = RecordMode::VARIANTS[i];
+    }
+    ui.checkbox("Punch in/out at selection", &mut cfg.record_punch, true,
+        Info::RecordPunch);
+}
+
 fn appearance_controls(ui: &mut Ui, cfg: &mut Config, player: &mut Player) {
     ui.header("APPEARANCE", Info::None);
 
@@ -283,10 +445,10 @@ fn load_theme(ui: &mut Ui, cfg: &mut Config, player: &mut Player) {
     }
 }
 
-/// Return the names of MIDI input options.
-fn input_names(input: &midir::MidiInput) -> Vec<String> {
+/// Return the names of MIDI port options.
+fn port_names(io: &impl MidiIO) -> Vec<String> {
     let mut v = vec![String::from("(none)")];
-    v.extend(input.ports().into_iter()
-        .map(|p| input.port_name(&p).unwrap_or(String::from("(unknown)"))));
+    v.extend(io.ports().into_iter()
+        .map(|p| io.port_name(&p).unwrap_or(String::from("(unknown)"))));
     v
 }
\ No newline at end of file
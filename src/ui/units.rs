@@ -0,0 +1,101 @@
+//! Parsing of values typed into sliders, with optional units.
+
+/// Physical quantity of a unit, for checking that units are compatible.
+#[derive(Clone, Copy, PartialEq)]
+enum Quantity {
+    Frequency,
+    Time,
+    Pitch,
+}
+
+/// Units that can be converted to other units of the same quantity, with
+/// their scale relative to the smallest unit.
+const UNITS: [(&str, Quantity, f32); 8] = [
+    ("hz", Quantity::Frequency, 1.0),
+    ("khz", Quantity::Frequency, 1000.0),
+    ("ms", Quantity::Time, 1.0),
+    ("s", Quantity::Time, 1000.0),
+    ("cents", Quantity::Pitch, 1.0),
+    ("ct", Quantity::Pitch, 1.0),
+    ("st", Quantity::Pitch, 100.0),
+    ("semitones", Quantity::Pitch, 100.0),
+];
+
+/// Returns the text that slider text entry starts with, given the slider's
+/// display text.
+pub fn entry_text(display: &str) -> String {
+    let token = display.trim_start_matches('x')
+        .split([' ', ':']).next()
+        .expect("at least 1 token should be present");
+    match split_unit(display) {
+        Some((start, end, _)) if token.parse::<f32>().is_err() =>
+            display[start..end].to_owned(),
+        _ => token.to_owned(),
+    }
+}
+
+/// Parses a typed slider value. If the value has a unit, it's converted to
+/// the unit shown in `display`, the slider's display text.
+pub fn parse_value(text: &str, display: &str) -> Result<f32, String> {
+    let text = text.trim();
+    if let Ok(f) = text.parse() {
+        return Ok(f)
+    }
+
+    let (start, end, unit) = split_unit(text)
+        .filter(|(start, _, _)| *start == 0)
+        .ok_or_else(|| format!("invalid number: {text}"))?;
+    let value: f32 = text[start..end].parse()
+        .map_err(|_| format!("invalid number: {text}"))?;
+    let display_unit = split_unit(display).map(|(_, _, u)| u).unwrap_or_default();
+    if unit == display_unit {
+        return Ok(value)
+    }
+
+    let scale = |unit: &str| UNITS.iter()
+        .find(|(name, _, _)| *name == unit)
+        .map(|(_, quantity, scale)| (*quantity, *scale));
+    match (scale(&unit), scale(&display_unit)) {
+        (Some((q1, s1)), Some((q2, s2))) if q1 == q2 => Ok(value * s1 / s2),
+        (Some(_), _) => Err(format!("{unit} doesn't apply to this value")),
+        (None, _) => Err(format!("unknown unit: {unit}")),
+    }
+}
+
+/// Finds the first number in `s`. Returns its byte range and the lowercase
+/// unit following it.
+fn split_unit(s: &str) -> Option<(usize, usize, String)> {
+    let start = s.find(|c: char| c.is_ascii_digit() || "+-.".contains(c))?;
+    let end = s[start + 1..].find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .map_or(s.len(), |i| start + 1 + i);
+    s[start..end].contains(|c: char| c.is_ascii_digit())
+        .then(|| (start, end, s[end..].trim().to_lowercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_text() {
+        assert_eq!(entry_text("0.250 s"), "0.250");
+        assert_eq!(entry_text("+3.0 dB"), "+3.0");
+        assert_eq!(entry_text("x1.5"), "1.5");
+        assert_eq!(entry_text("Q 0.70"), "0.70");
+        assert_eq!(entry_text("4.0:1"), "4.0");
+        assert_eq!(entry_text("inf:1"), "inf");
+    }
+
+    #[test]
+    fn test_parse_value() {
+        assert_eq!(parse_value("0.5", "0.250 s"), Ok(0.5));
+        assert_eq!(parse_value("250 ms", "0.100 s"), Ok(0.25));
+        assert_eq!(parse_value("2kHz", "1000 Hz"), Ok(2000.0));
+        assert_eq!(parse_value("-6 dB", "+0.0 dB"), Ok(-6.0));
+        assert_eq!(parse_value("1 st", "+0.0 cents"), Ok(100.0));
+        assert_eq!(parse_value("inf", "4.0:1"), Ok(f32::INFINITY));
+        assert!(parse_value("3 Hz", "0.250 s").is_err());
+        assert!(parse_value("3 furlongs", "0.250 s").is_err());
+        assert!(parse_value("abc", "0.250 s").is_err());
+    }
+}
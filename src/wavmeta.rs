@@ -0,0 +1,183 @@
+//! Metadata chunks for exported WAV files. INFO and BWF chunks carry the
+//! song title and author, and a sampler chunk carries the song loop.
+
+use std::{fs, io, path::Path};
+
+use fundsp::wave::Wave;
+
+use crate::{module::Module, timespan::Timespan, APP_NAME};
+
+/// Size of a BWF broadcast extension chunk without coding history.
+const BEXT_SIZE: usize = 602;
+
+/// Metadata written to exported WAV files.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WavMetadata {
+    pub title: String,
+    pub author: String,
+    /// Tempo at the start of the render, in BPM.
+    pub tempo: f32,
+    /// Loop start and end, in sample frames. The end is exclusive.
+    pub loop_points: Option<(u32, u32)>,
+}
+
+impl WavMetadata {
+    /// Returns metadata for a render of `module` starting at `start`, without
+    /// loop points.
+    pub fn new(module: &Module, start: Timespan) -> Self {
+        Self {
+            title: module.title.clone(),
+            author: module.author.clone(),
+            tempo: module.tempo_at(start),
+            loop_points: None,
+        }
+    }
+
+    /// Returns metadata for a render of `module` from the start of the
+    /// song, with loop points from its Loop and End events.
+    pub fn for_song(module: &Module, sample_rate: f64) -> Self {
+        let frame = |tick| (module.time_at(tick) * sample_rate).round() as u32;
+        Self {
+            loop_points: module.loop_range().map(|(start, end)| (frame(start), frame(end))),
+            ..Self::new(module, Timespan::ZERO)
+        }
+    }
+}
+
+/// Write `wave` to a WAV file at `path` with the given bit depth (16 if
+/// unset), followed by metadata chunks.
+pub fn save(wave: &Wave, path: &Path, bit_depth: Option<u8>, meta: &WavMetadata
+) -> io::Result<()> {
+    if bit_depth == Some(32) {
+        wave.save_wav32(path)?;
+    } else {
+        wave.save_wav16(path)?;
+    }
+    append_metadata(path, meta, wave.sample_rate() as u32)
+}
+
+/// Append metadata chunks to the WAV file at `path`.
+fn append_metadata(path: &Path, meta: &WavMetadata, sample_rate: u32) -> io::Result<()> {
+    let mut data = fs::read(path)?;
+    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a WAV file"))
+    }
+
+    // chunks must start on even offsets
+    if data.len() % 2 == 1 {
+        data.push(0);
+    }
+    data.extend(metadata_chunks(meta, sample_rate));
+    let riff_size = (data.len() - 8) as u32;
+    data[4..8].copy_from_slice(&riff_size.to_le_bytes());
+    fs::write(path, data)
+}
+
+/// Returns the encoded metadata chunks.
+fn metadata_chunks(meta: &WavMetadata, sample_rate: u32) -> Vec<u8> {
+    let mut info = b"INFO".to_vec();
+    let comment = format!("{} BPM", meta.tempo);
+    for (id, value) in [
+        (b"INAM", meta.title.as_str()),
+        (b"IART", meta.author.as_str()),
+        (b"ICMT", comment.as_str()),
+        (b"ISFT", APP_NAME),
+    ] {
+        if !value.is_empty() {
+            let mut s = value.as_bytes().to_vec();
+            s.push(0);
+            push_chunk(&mut info, id, &s);
+        }
+    }
+
+    let mut bext = vec![0; BEXT_SIZE];
+    copy_truncated(&mut bext[0..256], &meta.title);
+    copy_truncated(&mut bext[256..288], &meta.author);
+
+    let mut chunks = Vec::new();
+    push_chunk(&mut chunks, b"LIST", &info);
+    push_chunk(&mut chunks, b"bext", &bext);
+    if let Some((start, end)) = meta.loop_points {
+        push_chunk(&mut chunks, b"smpl", &smpl_data(start, end, sample_rate));
+    }
+    chunks
+}
+
+/// Returns the data of a sampler chunk with one forward loop.
+fn smpl_data(start: u32, end: u32, sample_rate: u32) -> Vec<u8> {
+    let fields = [
+        0, // manufacturer
+        0, // product
+        1_000_000_000 / sample_rate.max(1), // sample period in ns
+        60, // MIDI unity note
+        0, // pitch fraction
+        0, // SMPTE format
+        0, // SMPTE offset
+        1, // number of loops
+        0, // sampler data size
+        0, // cue point ID
+        0, // loop type (forward)
+        start,
+        end.saturating_sub(1).max(start), // last frame of loop
+        0, // fraction
+        0, // play count (infinite)
+    ];
+    fields.iter().flat_map(|x| x.to_le_bytes()).collect()
+}
+
+/// Append a chunk with ID `id` to `buf`, padded to an even length.
+fn push_chunk(buf: &mut Vec<u8>, id: &[u8; 4], data: &[u8]) {
+    buf.extend(id);
+    buf.extend((data.len() as u32).to_le_bytes());
+    buf.extend(data);
+    if data.len() % 2 == 1 {
+        buf.push(0);
+    }
+}
+
+/// Copy as much of `s` into `buf` as fits.
+fn copy_truncated(buf: &mut [u8], s: &str) {
+    let n = s.len().min(buf.len());
+    buf[..n].copy_from_slice(&s.as_bytes()[..n]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the ID and data of each chunk in `buf`.
+    fn parse_chunks(mut buf: &[u8]) -> Vec<([u8; 4], &[u8])> {
+        let mut chunks = Vec::new();
+        while buf.len() >= 8 {
+            let id = buf[0..4].try_into().unwrap();
+            let size = u32::from_le_bytes(buf[4..8].try_into().unwrap()) as usize;
+            chunks.push((id, &buf[8..8 + size]));
+            buf = &buf[(8 + size + size % 2).min(buf.len())..];
+        }
+        chunks
+    }
+
+    #[test]
+    fn test_metadata_chunks() {
+        let meta = WavMetadata {
+            title: String::from("Song"),
+            author: String::new(),
+            tempo: 120.0,
+            loop_points: Some((100, 200)),
+        };
+        let buf = metadata_chunks(&meta, 44100);
+        let chunks = parse_chunks(&buf);
+        let ids: Vec<_> = chunks.iter().map(|(id, _)| id).collect();
+        assert_eq!(ids, [b"LIST", b"bext", b"smpl"]);
+
+        let info = parse_chunks(&chunks[0].1[4..]);
+        assert_eq!(info[0], (*b"INAM", &b"Song\0"[..]));
+        assert!(info.iter().all(|(id, _)| id != b"IART"));
+        assert_eq!(chunks[1].1.len(), BEXT_SIZE);
+
+        let smpl = chunks[2].1;
+        let field = |i: usize| u32::from_le_bytes(smpl[i * 4..i * 4 + 4].try_into().unwrap());
+        assert_eq!(field(7), 1);
+        assert_eq!((field(11), field(12)), (100, 199));
+    }
+}